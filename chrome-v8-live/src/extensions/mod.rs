@@ -52,6 +52,31 @@ impl Extensions {
     }
 }
 
+impl Extensions {
+    /// 把 callback 注册成 globalThis[object][name]，globalThis[object] 不存在时会创建
+    pub fn install_function(
+        scope: &mut HandleScope,
+        object: &str,
+        name: &str,
+        callback: impl MapFnTo<v8::FunctionCallback>,
+    ) {
+        let global = scope.get_current_context().global(scope);
+        let key = v8::String::new(scope, object).unwrap();
+        let target = match global.get(scope, key.into()) {
+            Some(value) if value.is_object() => v8::Local::<v8::Object>::try_from(value).unwrap(),
+            _ => {
+                let target = v8::Object::new(scope);
+                global.set(scope, key.into(), target.into()).unwrap();
+                target
+            }
+        };
+
+        let func = v8::Function::new(scope, callback).unwrap();
+        let name = v8::String::new(scope, name).unwrap();
+        target.set(scope, name.into(), func.into()).unwrap();
+    }
+}
+
 fn print(scope: &mut HandleScope, args: FunctionCallbackArguments, mut rv: ReturnValue) {
     let result: serde_json::Value = serde_v8::from_v8(scope, args.get(0)).unwrap();
     println!("rust says {result:#?}");
@@ -2,11 +2,25 @@ mod extensions;
 mod state;
 mod utils;
 
-use extensions::{Extensions, EXTERNAL_REFERENCES};
+pub use extensions::Extensions;
+// 方便使用方用同样版本的 v8/serde_v8 编写扩展函数
+pub use serde_v8;
+pub use v8;
+
+use extensions::EXTERNAL_REFERENCES;
 use once_cell::sync::OnceCell;
 use state::JsRuntimeState;
+use std::{
+    ffi::c_void,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
 use utils::execute_script;
-use v8::{CreateParams, HandleScope, Isolate, OwnedIsolate, V8};
+use v8::{CreateParams, HandleScope, Isolate, IsolateHandle, MapFnTo, OwnedIsolate, V8};
 
 type LocalValue<'a> = v8::Local<'a, v8::Value>;
 
@@ -15,6 +29,12 @@ pub struct JsRuntime {
     pub isolate: OwnedIsolate,
 }
 
+/// 堆内存快用完时 V8 会调用 near_heap_limit，这里保存终止脚本需要的 handle
+struct HeapLimit {
+    handle: IsolateHandle,
+    exceeded: AtomicBool,
+}
+
 #[derive(Debug, Default)]
 pub struct JsRuntimeParams(CreateParams);
 
@@ -58,6 +78,86 @@ impl JsRuntime {
         Self::init_isolate(isolate, initialized)
     }
 
+    /// 限制 isolate 最多使用 max_bytes 字节的堆内存，超出时终止正在执行的脚本，而不是让整个进程崩溃
+    pub fn with_heap_limit(max_bytes: usize) -> Self {
+        let params = CreateParams::default()
+            .external_references(&**EXTERNAL_REFERENCES)
+            .heap_limits(0, max_bytes);
+        let mut isolate = v8::Isolate::new(params);
+
+        // HeapLimit 放在 slot 里，随着 isolate 一起释放，Box 保证了指针地址不变
+        let limit = Box::new(HeapLimit {
+            handle: isolate.thread_safe_handle(),
+            exceeded: AtomicBool::new(false),
+        });
+        let data = &*limit as *const HeapLimit as *mut c_void;
+        isolate.set_slot(limit);
+        isolate.add_near_heap_limit_callback(near_heap_limit, data);
+
+        Self::init_isolate(isolate, false)
+    }
+
+    /// 在全局对象 object 上注册一个 Rust 实现的函数，比如 register("kv", "get", kv_get) 之后，
+    /// 脚本里就可以调用 kv.get(...)
+    pub fn register(
+        &mut self,
+        object: &str,
+        name: &str,
+        callback: impl MapFnTo<v8::FunctionCallback>,
+    ) {
+        let context = JsRuntimeState::get_context(&mut self.isolate);
+        let handle_scope = &mut HandleScope::with_context(&mut self.isolate, context);
+        Extensions::install_function(handle_scope, object, name, callback);
+    }
+
+    /// 执行脚本，超过 timeout 或者超出堆内存限制时终止执行；出错时返回错误的字符串描述
+    pub fn execute_script_with_timeout(
+        &mut self,
+        code: impl AsRef<str>,
+        timeout: Duration,
+    ) -> Result<serde_json::Value, serde_json::Value> {
+        let handle = self.isolate.thread_safe_handle();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let (done, wait) = mpsc::channel::<()>();
+        let watchdog = {
+            let timed_out = timed_out.clone();
+            thread::spawn(move || {
+                if let Err(mpsc::RecvTimeoutError::Timeout) = wait.recv_timeout(timeout) {
+                    timed_out.store(true, Ordering::SeqCst);
+                    handle.terminate_execution();
+                }
+            })
+        };
+
+        let result = {
+            let context = JsRuntimeState::get_context(&mut self.isolate);
+            let handle_scope = &mut HandleScope::with_context(&mut self.isolate, context);
+            match execute_script(handle_scope, code, false) {
+                Ok(value) => serde_v8::from_v8(handle_scope, value)
+                    .map_err(|e| serde_json::Value::String(e.to_string())),
+                Err(err) => Err(err.to_rust_string_lossy(handle_scope).into()),
+            }
+        };
+        let _ = done.send(());
+        let _ = watchdog.join();
+
+        let heap_exceeded = self
+            .isolate
+            .get_slot::<Box<HeapLimit>>()
+            .map_or(false, |l| l.exceeded.load(Ordering::SeqCst));
+        if timed_out.load(Ordering::SeqCst) || heap_exceeded {
+            // 清掉终止状态，isolate 才能继续使用
+            self.isolate.cancel_terminate_execution();
+            let reason = if heap_exceeded {
+                "heap limit exceeded"
+            } else {
+                "execution timed out"
+            };
+            return Err(reason.into());
+        }
+        result
+    }
+
     pub fn execute_script(
         &mut self,
         code: impl AsRef<str>,
@@ -99,8 +199,7 @@ impl JsRuntime {
     }
 
     pub fn create_snapshot() -> Vec<u8> {
-        let sc = Isolate::snapshot_creator(None);
-        let mut Isolate = unsafe { sc.get_ownd_isolate() };
+        Self::create_snapshot_temp()
     }
 
     fn init_isolate(mut isolate: OwnedIsolate, initialized: bool) -> Self {
@@ -116,3 +215,12 @@ impl JsRuntime {
         Self { isolate }
     }
 }
+
+/// 堆内存快用完时终止脚本，并临时放宽限制，让 V8 有足够的内存退出正在执行的脚本
+extern "C" fn near_heap_limit(data: *mut c_void, current: usize, _initial: usize) -> usize {
+    // SAFETY: data 指向 isolate slot 中的 Box<HeapLimit>，它和 isolate 的生命周期一样长
+    let limit = unsafe { &*(data as *const HeapLimit) };
+    limit.exceeded.store(true, Ordering::SeqCst);
+    limit.handle.terminate_execution();
+    current * 2
+}
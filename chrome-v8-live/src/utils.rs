@@ -36,7 +36,15 @@ pub fn execute_script<'a>(
         .and_then(|script| script.run(scope))
         // 返回运行结果
         // 返回Ok是简写了，完整代码是`|value| Ok(value)`
-        .map_or_else(|| Err(scope.stack_trace()).unwrap(), Ok)
+        // 出错时返回异常；脚本被终止时没有异常，返回 undefined
+        .map_or_else(
+            || {
+                Err(scope
+                    .exception()
+                    .unwrap_or_else(|| v8::undefined(scope).into()))
+            },
+            Ok,
+        )
     // }
 }
 
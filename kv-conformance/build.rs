@@ -0,0 +1,13 @@
+fn main() {
+    let mut config = prost_build::Config::new();
+
+    config.bytes(["."]);
+    config.type_attribute(".", "#[derive(PartialOrd)]");
+    config
+        .out_dir("src/pb")
+        .compile_protos(&["abi.proto"], &["."])
+        .unwrap();
+
+    println!("cargo:rerun-if-changed=abi.proto");
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,162 @@
+use std::io::{Read, Write};
+
+use anyhow::{bail, ensure, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use prost::Message;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// 长度占用 4 字节
+pub const LEN_LEN: usize = 4;
+/// 长度的最高位代表 payload 是否使用了 gzip 压缩
+pub const COMPRESSION_BIT: usize = 1 << 31;
+/// 次高位保留：kv-db 用它和最高位一起表示 zstd/lz4，共同协议中必须为 0
+pub const RESERVED_BIT: usize = 1 << 30;
+/// payload 达到 1436 字节就做压缩；1500MTU - IP头20 - TCP头20 - TCP option 20 - 长度4 = 1436
+pub const COMPRESSION_LIMIT: usize = 1436;
+/// 长度只能使用低 30 位
+const MAX_FRAME: usize = RESERVED_BIT - 1;
+
+/// 参考实现：把 Message 封装成 frame，各个实现封包的结果必须和它一致
+pub fn encode_frame(msg: &impl Message) -> Result<Vec<u8>> {
+    let payload = msg.encode_to_vec();
+    ensure!(
+        payload.len() <= MAX_FRAME,
+        "frame too large: {}",
+        payload.len()
+    );
+
+    if payload.len() < COMPRESSION_LIMIT {
+        let mut frame = Vec::with_capacity(LEN_LEN + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&payload);
+        return Ok(frame);
+    }
+
+    let mut encoder = GzEncoder::new(vec![0; LEN_LEN], Compression::default());
+    encoder.write_all(&payload)?;
+    let mut frame = encoder.finish()?;
+    let header = ((frame.len() - LEN_LEN) | COMPRESSION_BIT) as u32;
+    frame[..LEN_LEN].copy_from_slice(&header.to_be_bytes());
+    Ok(frame)
+}
+
+/// 参考实现：把一个完整的 frame 解包成 Message
+pub fn decode_frame<M: Message + Default>(frame: &[u8]) -> Result<M> {
+    Ok(M::decode(&payload(frame)?[..])?)
+}
+
+/// 检查 frame 头部的长度，返回（解压后的）protobuf 数据
+pub fn payload(frame: &[u8]) -> Result<Vec<u8>> {
+    let (len, compressed) = decode_header(frame)?;
+    ensure!(
+        frame.len() == LEN_LEN + len,
+        "frame length mismatch: header says {}, got {}",
+        len,
+        frame.len() - LEN_LEN
+    );
+
+    let data = &frame[LEN_LEN..];
+    if !compressed {
+        return Ok(data.to_vec());
+    }
+    let mut payload = Vec::with_capacity(len * 2);
+    GzDecoder::new(data).read_to_end(&mut payload)?;
+    Ok(payload)
+}
+
+/// frame 是否设置了压缩位
+pub fn is_compressed(frame: &[u8]) -> bool {
+    matches!(decode_header(frame), Ok((_, true)))
+}
+
+/// 从 stream 中读取一个完整的 frame（包含 4 字节的头部）
+pub async fn read_frame<S>(stream: &mut S) -> Result<Vec<u8>>
+where
+    S: AsyncRead + Unpin,
+{
+    let header = stream.read_u32().await?;
+    let (len, _) = decode_header(&header.to_be_bytes())?;
+    let mut frame = vec![0; LEN_LEN + len];
+    frame[..LEN_LEN].copy_from_slice(&header.to_be_bytes());
+    stream.read_exact(&mut frame[LEN_LEN..]).await?;
+    Ok(frame)
+}
+
+fn decode_header(frame: &[u8]) -> Result<(usize, bool)> {
+    let Some(header) = frame.get(..LEN_LEN) else {
+        bail!("frame shorter than its header: {} bytes", frame.len());
+    };
+    let header = u32::from_be_bytes(header.try_into()?) as usize;
+    ensure!(
+        header & RESERVED_BIT == 0,
+        "reserved bit set in frame header: {:#010x}",
+        header
+    );
+    Ok((header & MAX_FRAME, header & COMPRESSION_BIT != 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::{value, CommandResponse, Value};
+
+    fn response_with(len: usize) -> CommandResponse {
+        let value = Value {
+            value: Some(value::Value::Binary(Bytes::from(vec![0u8; len]))),
+        };
+        CommandResponse {
+            status: 200,
+            values: vec![value],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn frame_encode_decode_should_work() -> Result<()> {
+        let res = response_with(16);
+        let frame = encode_frame(&res)?;
+        assert!(!is_compressed(&frame));
+        assert_eq!(frame.len(), LEN_LEN + res.encoded_len());
+        assert_eq!(decode_frame::<CommandResponse>(&frame)?, res);
+        Ok(())
+    }
+
+    #[test]
+    fn frame_should_be_compressed_from_limit() -> Result<()> {
+        // values 字段的 tag + 长度占 3 字节，Value 中 binary 的 tag + 长度占 3 字节，status 占 3 字节
+        let res = response_with(COMPRESSION_LIMIT - 10);
+        assert_eq!(res.encoded_len(), COMPRESSION_LIMIT - 1);
+        assert!(!is_compressed(&encode_frame(&res)?));
+
+        let res = response_with(COMPRESSION_LIMIT - 9);
+        assert_eq!(res.encoded_len(), COMPRESSION_LIMIT);
+        let frame = encode_frame(&res)?;
+        assert!(is_compressed(&frame));
+        assert_eq!(decode_frame::<CommandResponse>(&frame)?, res);
+        Ok(())
+    }
+
+    #[test]
+    fn bad_frame_should_be_rejected() {
+        assert!(payload(&[0, 0]).is_err());
+        // 长度和实际数据不一致
+        assert!(payload(&[0, 0, 0, 2, 8]).is_err());
+        // 保留位被设置
+        assert!(payload(&[0x40, 0, 0, 0]).is_err());
+    }
+
+    #[tokio::test]
+    async fn read_frame_should_work() -> Result<()> {
+        let frame = encode_frame(&response_with(4096))?;
+        let mut data = frame.clone();
+        data.extend_from_slice(&frame);
+
+        let mut stream = &data[..];
+        assert_eq!(read_frame(&mut stream).await?, frame);
+        assert_eq!(read_frame(&mut stream).await?, frame);
+        assert!(read_frame(&mut stream).await.is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{bail, Context, Result};
+use prost::Message;
+
+use crate::{
+    frame::{self, is_compressed},
+    script::{brief, decode_hex, parse_request, parse_response},
+    CommandRequest, CommandResponse, Failure, Target,
+};
+
+/// golden frame 对应的消息
+#[derive(Debug, Clone, PartialEq)]
+pub enum GoldenMessage {
+    Request(CommandRequest),
+    Response(CommandResponse),
+}
+
+/// fixtures/frames.txt 中的一个 frame：`>`/`<` 开头的一行描述消息，之后的几行是 frame 的十六进制
+#[derive(Debug, Clone)]
+pub struct Golden {
+    /// 消息描述所在的行号
+    pub line: usize,
+    pub message: GoldenMessage,
+    pub frame: Vec<u8>,
+}
+
+impl Golden {
+    fn payload(&self) -> Vec<u8> {
+        match &self.message {
+            GoldenMessage::Request(msg) => msg.encode_to_vec(),
+            GoldenMessage::Response(msg) => msg.encode_to_vec(),
+        }
+    }
+
+    fn failure(&self, reason: impl Into<String>) -> Failure {
+        Failure {
+            fixture: "frames".into(),
+            line: self.line,
+            reason: reason.into(),
+        }
+    }
+
+    /// 用实现的 FrameCoder 解包 golden frame，结果需要和描述的消息一致
+    fn check_decode<T: Target>(&self, target: &T) -> Result<(), String> {
+        let decoded = match &self.message {
+            GoldenMessage::Request(msg) => target
+                .decode_request(&self.frame)
+                .and_then(|data| Ok(CommandRequest::decode(&data[..])?))
+                .map(|decoded| (decoded != *msg).then(|| brief(&decoded))),
+            GoldenMessage::Response(msg) => target
+                .decode_response(&self.frame)
+                .and_then(|data| Ok(CommandResponse::decode(&data[..])?))
+                .map(|decoded| (decoded != *msg).then(|| brief(&decoded))),
+        };
+        match decoded {
+            Ok(None) => Ok(()),
+            Ok(Some(decoded)) => Err(format!("decode: got {}", decoded)),
+            Err(e) => Err(format!("decode: {:#}", e)),
+        }
+    }
+
+    /// 用实现的 FrameCoder 封包消息：不压缩的 frame 必须逐字节一致；
+    /// 不同版本的 gzip 输出可能不同，压缩的 frame 只要求设置了压缩位，并且解压后的内容一致
+    fn check_encode<T: Target>(&self, target: &T) -> Result<(), String> {
+        let payload = self.payload();
+        let encoded = match self.message {
+            GoldenMessage::Request(_) => target.encode_request(&payload),
+            GoldenMessage::Response(_) => target.encode_response(&payload),
+        }
+        .map_err(|e| format!("encode: {:#}", e))?;
+
+        if !is_compressed(&self.frame) {
+            if encoded != self.frame {
+                return Err(format!(
+                    "encode: expected {}, got {}",
+                    hex(&self.frame),
+                    hex(&encoded)
+                ));
+            }
+            return Ok(());
+        }
+
+        if !is_compressed(&encoded) {
+            return Err("encode: frame should be compressed".into());
+        }
+        match frame::payload(&encoded) {
+            Ok(data) if data == payload => Ok(()),
+            Ok(_) => Err("encode: decompressed payload differs".into()),
+            Err(e) => Err(format!("encode: {:#}", e)),
+        }
+    }
+}
+
+/// 解析 fixtures/frames.txt
+pub fn parse(src: &str) -> Result<Vec<Golden>> {
+    let mut goldens: Vec<Golden> = vec![];
+    let mut hex = String::new();
+    for (i, line) in src.lines().enumerate() {
+        let line = line.trim();
+        let message = if let Some(s) = line.strip_prefix('>') {
+            GoldenMessage::Request(parse_request(s).with_context(|| format!("line {}", i + 1))?)
+        } else if let Some(s) = line.strip_prefix('<') {
+            let res = parse_response(s).with_context(|| format!("line {}", i + 1))?;
+            GoldenMessage::Response(res.into())
+        } else {
+            if !line.is_empty() && !line.starts_with('#') {
+                if goldens.is_empty() {
+                    bail!("line {}: frame without message", i + 1);
+                }
+                hex.push_str(line);
+            }
+            continue;
+        };
+
+        finish(&mut goldens, &mut hex)?;
+        goldens.push(Golden {
+            line: i + 1,
+            message,
+            frame: vec![],
+        });
+    }
+    finish(&mut goldens, &mut hex)?;
+
+    Ok(goldens)
+}
+
+fn finish(goldens: &mut [Golden], hex: &mut String) -> Result<()> {
+    if let Some(golden) = goldens.last_mut() {
+        golden.frame = decode_hex(hex).with_context(|| format!("line {}", golden.line))?;
+        if golden.frame.is_empty() {
+            bail!("line {}: missing frame", golden.line);
+        }
+    }
+    hex.clear();
+    Ok(())
+}
+
+/// 检查实现的 FrameCoder 能否正确地封包/解包所有的 golden frame
+pub fn check<T: Target>(target: &T, goldens: &[Golden]) -> Vec<Failure> {
+    goldens
+        .iter()
+        .flat_map(|golden| {
+            [golden.check_decode(target), golden.check_encode(target)]
+                .into_iter()
+                .filter_map(|r| r.err())
+                .map(|reason| golden.failure(reason))
+        })
+        .collect()
+}
+
+/// 按 frames.txt 的格式输出 frame，每行 32 字节
+pub fn hex(frame: &[u8]) -> String {
+    frame
+        .chunks(32)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 参考实现生成 golden frame，新增 golden frame 时用它生成十六进制
+pub fn reference_frame(message: &GoldenMessage) -> Result<Vec<u8>> {
+    match message {
+        GoldenMessage::Request(msg) => frame::encode_frame(msg),
+        GoldenMessage::Response(msg) => frame::encode_frame(msg),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{frame::decode_frame, FRAMES};
+
+    #[test]
+    fn golden_frames_should_match_reference() -> Result<()> {
+        let goldens = parse(FRAMES)?;
+        for golden in goldens.iter() {
+            match &golden.message {
+                GoldenMessage::Request(msg) => {
+                    assert_eq!(&decode_frame::<CommandRequest>(&golden.frame)?, msg)
+                }
+                GoldenMessage::Response(msg) => {
+                    assert_eq!(&decode_frame::<CommandResponse>(&golden.frame)?, msg)
+                }
+            }
+            let frame = reference_frame(&golden.message)?;
+            assert_eq!(
+                frame,
+                golden.frame,
+                "line {}: reference frame is\n{}",
+                golden.line,
+                hex(&frame)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn golden_frames_should_cover_compression() -> Result<()> {
+        let goldens = parse(FRAMES)?;
+        let compressed = |msg: fn(&GoldenMessage) -> bool| {
+            goldens
+                .iter()
+                .filter(|g| msg(&g.message))
+                .map(|g| is_compressed(&g.frame))
+                .collect::<Vec<_>>()
+        };
+        let requests = compressed(|m| matches!(m, GoldenMessage::Request(_)));
+        let responses = compressed(|m| matches!(m, GoldenMessage::Response(_)));
+        for flags in [requests, responses] {
+            assert!(flags.contains(&true) && flags.contains(&false));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn frame_without_message_should_fail() {
+        assert!(parse("0000").is_err());
+        assert!(parse("> hget t1 k1\n").is_err());
+        assert!(parse("> hget t1 k1\n0z").is_err());
+    }
+}
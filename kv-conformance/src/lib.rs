@@ -0,0 +1,189 @@
+//! kv-server、kv-db 和 rust-frist-lesson-exercise/kv 实现的是同一个协议：
+//! 4 字节长度（最高位表示 gzip 压缩）+ protobuf 编码的 `abi.CommandRequest`/`abi.CommandResponse`。
+//! 这个 crate 用 golden frame 和请求/响应的 transcript 描述这个协议，
+//! 每个实现在自己的 `tests/conformance.rs` 中实现 [`Target`]（一般用 [`frame_target!`] 生成），然后调用 [`run`]。
+//!
+//! 实现之间只通过 protobuf 编码后的字节交互，不需要共用生成的类型，也不需要同样版本的 prost。
+
+pub mod frame;
+pub mod golden;
+mod pb;
+#[cfg(test)]
+mod reference;
+pub mod script;
+pub mod transcript;
+
+use std::fmt;
+
+use anyhow::{bail, Result};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+pub use pb::*;
+use script::Expected;
+
+/// 请求和响应的 golden frame
+pub const FRAMES: &str = include_str!("../fixtures/frames.txt");
+
+/// 所有的 transcript，每个 transcript 在一个新的服务器上执行
+pub const TRANSCRIPTS: &[(&str, &str)] = &[
+    ("basic", include_str!("../fixtures/transcripts/basic.txt")),
+    ("values", include_str!("../fixtures/transcripts/values.txt")),
+    ("multi", include_str!("../fixtures/transcripts/multi.txt")),
+    ("exist", include_str!("../fixtures/transcripts/exist.txt")),
+    ("errors", include_str!("../fixtures/transcripts/errors.txt")),
+    (
+        "compression",
+        include_str!("../fixtures/transcripts/compression.txt"),
+    ),
+];
+
+/// 被测试的实现。消息都是 protobuf 编码后的字节，实现需要先用自己的类型 decode，再 encode 回去，
+/// 这样 proto 定义的差异（比如字段的 tag 不同）也能被发现
+pub trait Target {
+    /// 连接到服务器的 stream，一般是 `tokio::io::DuplexStream`
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send;
+
+    /// 用实现的 FrameCoder 把 CommandRequest 封包成 frame
+    fn encode_request(&self, msg: &[u8]) -> Result<Vec<u8>>;
+    /// 用实现的 FrameCoder 把 CommandResponse 封包成 frame
+    fn encode_response(&self, msg: &[u8]) -> Result<Vec<u8>>;
+    /// 用实现的 FrameCoder 把 frame 解包成 CommandRequest
+    fn decode_request(&self, frame: &[u8]) -> Result<Vec<u8>>;
+    /// 用实现的 FrameCoder 把 frame 解包成 CommandResponse
+    fn decode_response(&self, frame: &[u8]) -> Result<Vec<u8>>;
+
+    /// 在进程内启动一个使用空的内存存储的服务器，返回连接到它的 stream；在 tokio runtime 中调用
+    fn connect(&self) -> Self::Stream;
+
+    /// transcript 中 `request` 期望的响应。实现为了兼容已有的客户端保留了和共同协议不同的响应时
+    /// （比如 kv-server 的 hget/hset），在这里把 `expected` 改写成实现自己的响应
+    fn expected(&self, request: &CommandRequest, expected: Expected) -> Expected {
+        let _ = request;
+        expected
+    }
+}
+
+/// 为用 FrameCoder 封包、用 `tokio::io::duplex` 连接服务器的实现生成一个 [`Target`]。
+///
+/// 三个实现的 FrameCoder 只有方法名不同，`encode`/`decode` 给出封包和解包的方法名，
+/// `connect` 拿到服务器一端的 stream，在上面启动服务器，可选的 `expected` 对应
+/// [`Target::expected`]。展开后的代码使用调用方的
+/// `anyhow`、`bytes`、`prost` 和 `tokio`，这样 protobuf 的类型和实现用的是同一个版本的 prost。
+///
+/// ```ignore
+/// kv_conformance::frame_target! {
+///     /// 用 kv 的 FrameCoder 和 ProstServerStream 跑共同协议的兼容性测试
+///     struct Kv {
+///         request: CommandRequest,
+///         response: CommandResponse,
+///         encode: encode_frame,
+///         decode: decode_frame,
+///         connect: |server| {
+///             tokio::spawn(ProstServerStream::new(server, Service::new(MemTable::new())).process());
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! frame_target {
+    (
+        $(#[$meta:meta])*
+        struct $name:ident {
+            request: $req:ty,
+            response: $res:ty,
+            encode: $encode:ident,
+            decode: $decode:ident,
+            connect: |$server:ident| $connect:block
+            $(, expected: |$request:ident, $expected:ident| $rewrite:block)? $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        struct $name;
+
+        impl $crate::Target for $name {
+            type Stream = ::tokio::io::DuplexStream;
+
+            fn encode_request(&self, msg: &[u8]) -> ::anyhow::Result<Vec<u8>> {
+                let mut buf = ::bytes::BytesMut::new();
+                <$req as ::prost::Message>::decode(msg)?.$encode(&mut buf)?;
+                Ok(buf.to_vec())
+            }
+
+            fn encode_response(&self, msg: &[u8]) -> ::anyhow::Result<Vec<u8>> {
+                let mut buf = ::bytes::BytesMut::new();
+                <$res as ::prost::Message>::decode(msg)?.$encode(&mut buf)?;
+                Ok(buf.to_vec())
+            }
+
+            fn decode_request(&self, frame: &[u8]) -> ::anyhow::Result<Vec<u8>> {
+                let msg = <$req>::$decode(&mut ::bytes::BytesMut::from(frame))?;
+                Ok(::prost::Message::encode_to_vec(&msg))
+            }
+
+            fn decode_response(&self, frame: &[u8]) -> ::anyhow::Result<Vec<u8>> {
+                let msg = <$res>::$decode(&mut ::bytes::BytesMut::from(frame))?;
+                Ok(::prost::Message::encode_to_vec(&msg))
+            }
+
+            fn connect(&self) -> ::tokio::io::DuplexStream {
+                let (client, $server) = ::tokio::io::duplex(64 * 1024);
+                $connect
+                client
+            }
+
+            $(
+                fn expected(
+                    &self,
+                    $request: &$crate::CommandRequest,
+                    $expected: $crate::script::Expected,
+                ) -> $crate::script::Expected $rewrite
+            )?
+        }
+    };
+}
+
+/// 一条不符合协议的记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// fixtures 下的文件
+    pub fixture: String,
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.fixture, self.line, self.reason)
+    }
+}
+
+/// 检查所有的 golden frame
+pub fn check_frames<T: Target>(target: &T) -> Result<Vec<Failure>> {
+    Ok(golden::check(target, &golden::parse(FRAMES)?))
+}
+
+/// 执行所有的 transcript
+pub async fn run_transcripts<T: Target>(target: &T) -> Result<Vec<Failure>> {
+    let mut failures = vec![];
+    for (name, src) in TRANSCRIPTS {
+        let transcript = transcript::parse(name, src)?;
+        failures.extend(transcript.run(target).await);
+    }
+    Ok(failures)
+}
+
+/// 检查所有的 golden frame，执行所有的 transcript，有不符合的地方就返回包含所有失败记录的错误
+pub async fn run<T: Target>(target: &T) -> Result<()> {
+    let mut failures = check_frames(target)?;
+    failures.extend(run_transcripts(target).await?);
+    if failures.is_empty() {
+        return Ok(());
+    }
+
+    let report: Vec<_> = failures.iter().map(|f| f.to_string()).collect();
+    bail!(
+        "{} conformance failure(s):\n{}",
+        failures.len(),
+        report.join("\n")
+    )
+}
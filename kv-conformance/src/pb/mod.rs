@@ -0,0 +1,3 @@
+mod abi;
+
+pub use abi::*;
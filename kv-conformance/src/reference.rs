@@ -0,0 +1,205 @@
+//! 按共同协议实现的最小服务器，用来验证 fixtures 本身是自洽的
+
+use std::collections::{BTreeMap, HashMap};
+
+use anyhow::Result;
+use prost::Message;
+use tokio::io::{duplex, AsyncWriteExt, DuplexStream};
+
+use crate::{
+    command_request::RequestData,
+    frame::{decode_frame, encode_frame, read_frame},
+    CommandRequest, CommandResponse, Kvpair, Target, Value,
+};
+
+/// 参考实现；`compress` 为 false 时封包从不压缩，用来确认兼容性测试能发现问题
+pub struct Reference {
+    pub compress: bool,
+}
+
+impl Reference {
+    fn encode(&self, msg: &impl Message) -> Result<Vec<u8>> {
+        if self.compress {
+            return encode_frame(msg);
+        }
+        let mut frame = (msg.encoded_len() as u32).to_be_bytes().to_vec();
+        msg.encode(&mut frame)?;
+        Ok(frame)
+    }
+}
+
+impl Target for Reference {
+    type Stream = DuplexStream;
+
+    fn encode_request(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        self.encode(&CommandRequest::decode(msg)?)
+    }
+
+    fn encode_response(&self, msg: &[u8]) -> Result<Vec<u8>> {
+        self.encode(&CommandResponse::decode(msg)?)
+    }
+
+    fn decode_request(&self, frame: &[u8]) -> Result<Vec<u8>> {
+        Ok(decode_frame::<CommandRequest>(frame)?.encode_to_vec())
+    }
+
+    fn decode_response(&self, frame: &[u8]) -> Result<Vec<u8>> {
+        Ok(decode_frame::<CommandResponse>(frame)?.encode_to_vec())
+    }
+
+    fn connect(&self) -> DuplexStream {
+        let (client, mut server) = duplex(64 * 1024);
+        let compress = self.compress;
+        tokio::spawn(async move {
+            let this = Reference { compress };
+            let mut store = Store::default();
+            while let Ok(frame) = read_frame(&mut server).await {
+                let res = store.execute(decode_frame(&frame).unwrap());
+                let frame = this.encode(&res).unwrap();
+                server.write_all(&frame).await.unwrap();
+            }
+        });
+        client
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    tables: HashMap<String, BTreeMap<String, Value>>,
+}
+
+fn values(values: Vec<Value>) -> CommandResponse {
+    CommandResponse {
+        status: 200,
+        values,
+        ..Default::default()
+    }
+}
+
+fn status(status: u32, message: impl Into<String>) -> CommandResponse {
+    CommandResponse {
+        status,
+        message: message.into(),
+        ..Default::default()
+    }
+}
+
+impl Store {
+    fn get(&self, table: &str, key: &str) -> Option<Value> {
+        self.tables.get(table)?.get(key).cloned()
+    }
+
+    fn set(&mut self, table: String, pair: Kvpair) -> Option<Value> {
+        let value = pair.value.unwrap_or_default();
+        self.tables
+            .entry(table)
+            .or_default()
+            .insert(pair.key, value)
+    }
+
+    fn del(&mut self, table: &str, key: &str) -> Option<Value> {
+        self.tables.get_mut(table)?.remove(key)
+    }
+
+    fn execute(&mut self, cmd: CommandRequest) -> CommandResponse {
+        let not_found = |table: &str, key: &str| status(404, format!("Not found: {table}:{key}"));
+        match cmd.request_data {
+            Some(RequestData::Hget(cmd)) => match self.get(&cmd.table, &cmd.key) {
+                Some(v) => values(vec![v]),
+                None => not_found(&cmd.table, &cmd.key),
+            },
+            Some(RequestData::Hgetall(cmd)) => CommandResponse {
+                status: 200,
+                pairs: self
+                    .tables
+                    .get(&cmd.table)
+                    .into_iter()
+                    .flatten()
+                    .map(|(k, v)| Kvpair {
+                        key: k.clone(),
+                        value: Some(v.clone()),
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            Some(RequestData::Hmget(cmd)) => {
+                let mut result = vec![];
+                for key in cmd.keys.iter() {
+                    match self.get(&cmd.table, key) {
+                        Some(v) => result.push(v),
+                        None => return not_found(&cmd.table, key),
+                    }
+                }
+                values(result)
+            }
+            Some(RequestData::Hset(cmd)) => {
+                let old = cmd.pair.and_then(|pair| self.set(cmd.table, pair));
+                values(vec![old.unwrap_or_default()])
+            }
+            Some(RequestData::Hmset(cmd)) => values(
+                cmd.pairs
+                    .into_iter()
+                    .filter_map(|pair| self.set(cmd.table.clone(), pair))
+                    .collect(),
+            ),
+            Some(RequestData::Hdel(cmd)) => {
+                values(vec![self.del(&cmd.table, &cmd.key).unwrap_or_default()])
+            }
+            Some(RequestData::Hmdel(cmd)) => values(
+                cmd.keys
+                    .iter()
+                    .filter_map(|key| self.del(&cmd.table, key))
+                    .collect(),
+            ),
+            Some(RequestData::Hexist(cmd)) => match self.get(&cmd.table, &cmd.key) {
+                Some(_) => status(200, ""),
+                None => status(204, ""),
+            },
+            Some(RequestData::Hmexist(cmd)) => {
+                let flags: String = cmd
+                    .keys
+                    .iter()
+                    .map(|key| match self.get(&cmd.table, key) {
+                        Some(_) => '1',
+                        None => '0',
+                    })
+                    .collect();
+                match flags.contains('0') {
+                    true => status(204, flags),
+                    false => status(200, ""),
+                }
+            }
+            None => status(400, "Request has no data"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{check_frames, run, run_transcripts};
+
+    #[tokio::test]
+    async fn reference_should_conform() {
+        if let Err(e) = run(&Reference { compress: true }).await {
+            panic!("{}", e);
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_compression_should_be_reported() -> Result<()> {
+        let target = Reference { compress: false };
+        let failures = check_frames(&target)?;
+        assert!(!failures.is_empty());
+        assert!(failures
+            .iter()
+            .all(|f| f.reason == "encode: frame should be compressed"));
+
+        let failures = run_transcripts(&target).await?;
+        assert!(!failures.is_empty());
+        assert!(failures
+            .iter()
+            .all(|f| f.fixture == "transcripts/compression"));
+        Ok(())
+    }
+}
@@ -0,0 +1,451 @@
+//! fixtures 中描述请求和响应的文本格式：
+//!
+//! ```text
+//! > hset t1 k1 "v1"            请求：命令名 + 参数，hmset 的参数是 key value 交替出现
+//! < 200 [null]                 响应：状态码 [values] {pairs} "message"，后三项都可以省略
+//! < 200 {k1: "v1", k2: 2}
+//! < 204 "01"
+//! ```
+//!
+//! value 的写法：`"string"`、`0x00ff`（binary）、`42`、`1.5`、`true`、`null`（空的 Value）；
+//! string 和 binary 后面可以跟 `*N` 表示重复 N 次，用来构造大的 payload
+
+use std::fmt::Debug;
+
+use anyhow::{anyhow, bail, ensure, Result};
+use bytes::Bytes;
+
+use crate::{
+    command_request::RequestData, value, CommandRequest, CommandResponse, Hdel, Hexist, Hget,
+    Hgetall, Hmdel, Hmexist, Hmget, Hmset, Hset, Kvpair, Value,
+};
+
+/// 期望的响应：没有写 message 时不比较 message，它是给人看的，各个实现可以不同
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expected {
+    pub status: u32,
+    pub values: Vec<Value>,
+    pub pairs: Vec<Kvpair>,
+    pub message: Option<String>,
+}
+
+impl Expected {
+    /// 检查实际的响应，pairs 的顺序不做要求
+    pub fn check(&self, res: &CommandResponse) -> Result<(), String> {
+        if res.status != self.status {
+            return Err(format!(
+                "status: expected {}, got {} (message: {:?})",
+                self.status, res.status, res.message
+            ));
+        }
+        if res.values != self.values {
+            return Err(format!(
+                "values: expected {}, got {}",
+                brief(&self.values),
+                brief(&res.values)
+            ));
+        }
+        let mut pairs = res.pairs.clone();
+        pairs.sort_by(|a, b| a.key.cmp(&b.key));
+        let mut expected = self.pairs.clone();
+        expected.sort_by(|a, b| a.key.cmp(&b.key));
+        if pairs != expected {
+            return Err(format!(
+                "pairs: expected {}, got {}",
+                brief(&expected),
+                brief(&pairs)
+            ));
+        }
+        match &self.message {
+            Some(message) if message != &res.message => Err(format!(
+                "message: expected {:?}, got {:?}",
+                message, res.message
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl From<Expected> for CommandResponse {
+    fn from(e: Expected) -> Self {
+        Self {
+            status: e.status,
+            message: e.message.unwrap_or_default(),
+            values: e.values,
+            pairs: e.pairs,
+        }
+    }
+}
+
+/// 解析 `>` 之后的请求
+pub fn parse_request(line: &str) -> Result<CommandRequest> {
+    let mut p = Parser::new(line)?;
+    let cmd = p.name()?;
+    let request_data = match cmd.as_str() {
+        // 没有 request_data 的请求
+        "empty" => None,
+        "hget" => Some(RequestData::Hget(Hget {
+            table: p.name()?,
+            key: p.name()?,
+        })),
+        "hgetall" => Some(RequestData::Hgetall(Hgetall { table: p.name()? })),
+        "hmget" => Some(RequestData::Hmget(Hmget {
+            table: p.name()?,
+            keys: p.names()?,
+        })),
+        "hset" => Some(RequestData::Hset(Hset {
+            table: p.name()?,
+            pair: Some(p.pair()?),
+        })),
+        "hmset" => {
+            let table = p.name()?;
+            let mut pairs = vec![];
+            while !p.is_end() {
+                pairs.push(p.pair()?);
+            }
+            Some(RequestData::Hmset(Hmset { table, pairs }))
+        }
+        "hdel" => Some(RequestData::Hdel(Hdel {
+            table: p.name()?,
+            key: p.name()?,
+        })),
+        "hmdel" => Some(RequestData::Hmdel(Hmdel {
+            table: p.name()?,
+            keys: p.names()?,
+        })),
+        "hexist" => Some(RequestData::Hexist(Hexist {
+            table: p.name()?,
+            key: p.name()?,
+        })),
+        "hmexist" => Some(RequestData::Hmexist(Hmexist {
+            table: p.name()?,
+            keys: p.names()?,
+        })),
+        _ => bail!("unknown command: {}", cmd),
+    };
+    p.end()?;
+
+    Ok(CommandRequest { request_data })
+}
+
+/// 解析 `<` 之后的响应
+pub fn parse_response(line: &str) -> Result<Expected> {
+    let mut p = Parser::new(line)?;
+    let status = p.name()?;
+    let status = status
+        .parse()
+        .map_err(|_| anyhow!("invalid status: {}", status))?;
+
+    let mut values = vec![];
+    if p.eat('[') {
+        while !p.eat(']') {
+            values.push(p.value()?);
+            p.eat(',');
+        }
+    }
+
+    let mut pairs = vec![];
+    if p.eat('{') {
+        while !p.eat('}') {
+            let key = p.name()?;
+            p.expect(':')?;
+            pairs.push(Kvpair {
+                key,
+                value: Some(p.value()?),
+            });
+            p.eat(',');
+        }
+    }
+
+    let message = match p.peek() {
+        Some(Token::Str(_)) => Some(p.name()?),
+        _ => None,
+    };
+    p.end()?;
+
+    Ok(Expected {
+        status,
+        values,
+        pairs,
+        message,
+    })
+}
+
+/// 失败信息里的值可能很大（压缩测试用的 payload），只保留前面一部分
+pub(crate) fn brief(v: &impl Debug) -> String {
+    const MAX: usize = 160;
+    let s = format!("{:?}", v);
+    match s.char_indices().nth(MAX) {
+        Some((i, _)) => format!("{}...({} chars)", &s[..i], s.chars().count()),
+        None => s,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Punct(char),
+}
+
+const PUNCTS: &str = "[]{}:,*";
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(line: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(line)?,
+            pos: 0,
+        })
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| anyhow!("unexpected end of line"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn is_end(&self) -> bool {
+        self.pos == self.tokens.len()
+    }
+
+    fn end(&self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            Some(token) => bail!("unexpected {:?}", token),
+        }
+    }
+
+    /// 如果下一个 token 是 c 就跳过它
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        ensure!(self.eat(c), "expected `{}`, got {:?}", c, self.peek());
+        Ok(())
+    }
+
+    /// 命令名、table、key：不带引号的单词或者带引号的字符串
+    fn name(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Word(s) | Token::Str(s) => Ok(s),
+            token => bail!("expected a name, got {:?}", token),
+        }
+    }
+
+    /// 剩下的所有参数
+    fn names(&mut self) -> Result<Vec<String>> {
+        let mut names = vec![];
+        while !self.is_end() {
+            names.push(self.name()?);
+        }
+        Ok(names)
+    }
+
+    fn pair(&mut self) -> Result<Kvpair> {
+        Ok(Kvpair {
+            key: self.name()?,
+            value: Some(self.value()?),
+        })
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        let value = match self.next()? {
+            Token::Str(s) => Some(value::Value::String(s.repeat(self.repeat()?))),
+            Token::Word(w) => match w.as_str() {
+                "null" => None,
+                "true" => Some(value::Value::Bool(true)),
+                "false" => Some(value::Value::Bool(false)),
+                w if w.starts_with("0x") => {
+                    let data = decode_hex(&w[2..])?.repeat(self.repeat()?);
+                    Some(value::Value::Binary(Bytes::from(data)))
+                }
+                w if w.contains('.') => Some(value::Value::Float(w.parse()?)),
+                w => Some(value::Value::Integer(w.parse().map_err(|_| {
+                    anyhow!("invalid value `{}`, strings need to be quoted", w)
+                })?)),
+            },
+            token => bail!("expected a value, got {:?}", token),
+        };
+        Ok(Value { value })
+    }
+
+    /// 可选的 `*N`
+    fn repeat(&mut self) -> Result<usize> {
+        if !self.eat('*') {
+            return Ok(1);
+        }
+        let n = self.name()?;
+        n.parse()
+            .map_err(|_| anyhow!("invalid repeat count: {}", n))
+    }
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if PUNCTS.contains(c) {
+            chars.next();
+            tokens.push(Token::Punct(c));
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some('n') => s.push('\n'),
+                        Some('t') => s.push('\t'),
+                        Some(c) => s.push(c),
+                        None => bail!("unterminated string"),
+                    },
+                    Some(c) => s.push(c),
+                    None => bail!("unterminated string"),
+                }
+            }
+            tokens.push(Token::Str(s));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' || PUNCTS.contains(c) {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+pub(crate) fn decode_hex(s: &str) -> Result<Vec<u8>> {
+    let s: Vec<u8> = s.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    if s.len() % 2 == 1 {
+        bail!("odd number of hex digits");
+    }
+    s.chunks(2)
+        .map(|c| {
+            let c = std::str::from_utf8(c)?;
+            u8::from_str_radix(c, 16).map_err(|_| anyhow!("invalid hex: {}", c))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v(value: value::Value) -> Value {
+        Value { value: Some(value) }
+    }
+
+    #[test]
+    fn parse_request_should_work() -> Result<()> {
+        let cmd = parse_request(r#"hmset t1 k1 "v 1" k2 -2 k3 1.5 k4 true k5 0x00ff k6 null"#)?;
+        let pairs = match cmd.request_data {
+            Some(RequestData::Hmset(Hmset { table, pairs })) if table == "t1" => pairs,
+            data => panic!("unexpected request: {:?}", data),
+        };
+        let values: Vec<_> = pairs.into_iter().map(|p| p.value.unwrap()).collect();
+        assert_eq!(
+            values,
+            [
+                v(value::Value::String("v 1".into())),
+                v(value::Value::Integer(-2)),
+                v(value::Value::Float(1.5)),
+                v(value::Value::Bool(true)),
+                v(value::Value::Binary(Bytes::from_static(&[0, 0xff]))),
+                Value::default(),
+            ]
+        );
+
+        let cmd = parse_request("hmget t1 k1 \"hello world\"")?;
+        assert_eq!(
+            cmd.request_data,
+            Some(RequestData::Hmget(Hmget {
+                table: "t1".into(),
+                keys: vec!["k1".into(), "hello world".into()],
+            }))
+        );
+        assert_eq!(parse_request("empty")?, CommandRequest::default());
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_value_should_work() -> Result<()> {
+        let cmd = parse_request(r#"hset t1 k1 "ab"*3"#)?;
+        let value = match cmd.request_data {
+            Some(RequestData::Hset(Hset { pair, .. })) => pair.unwrap().value.unwrap(),
+            data => panic!("unexpected request: {:?}", data),
+        };
+        assert_eq!(value, v(value::Value::String("ababab".into())));
+
+        let cmd = parse_request("hset t1 k1 0x00*4")?;
+        let value = match cmd.request_data {
+            Some(RequestData::Hset(Hset { pair, .. })) => pair.unwrap().value.unwrap(),
+            data => panic!("unexpected request: {:?}", data),
+        };
+        assert_eq!(value, v(value::Value::Binary(Bytes::from(vec![0; 4]))));
+        Ok(())
+    }
+
+    #[test]
+    fn parse_response_should_work() -> Result<()> {
+        let res = parse_response(r#"200 ["v1", null] {k1: "v1", k2: 2}"#)?;
+        assert_eq!(res.status, 200);
+        assert_eq!(
+            res.values,
+            [v(value::Value::String("v1".into())), Value::default()]
+        );
+        assert_eq!(res.pairs.len(), 2);
+        assert_eq!(res.message, None);
+
+        let res = parse_response(r#"204 "01""#)?;
+        assert_eq!(res.status, 204);
+        assert_eq!(res.message.as_deref(), Some("01"));
+        Ok(())
+    }
+
+    #[test]
+    fn bad_script_should_fail() {
+        assert!(parse_request("hget t1").is_err());
+        assert!(parse_request("hget t1 k1 k2").is_err());
+        assert!(parse_request("hset t1 k1 v1").is_err());
+        assert!(parse_request("unknown t1").is_err());
+        assert!(parse_response("ok").is_err());
+        assert!(parse_response("200 [\"v1\"").is_err());
+    }
+
+    #[test]
+    fn check_should_ignore_pairs_order_and_missing_message() -> Result<()> {
+        let expected = parse_response(r#"200 {k1: 1, k2: 2}"#)?;
+        let mut res: CommandResponse = expected.clone().into();
+        res.pairs.reverse();
+        res.message = "ok".into();
+        assert_eq!(expected.check(&res), Ok(()));
+
+        res.status = 404;
+        assert!(expected.check(&res).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context, Result};
+use prost::Message;
+use tokio::{io::AsyncWriteExt, time};
+
+use crate::{
+    frame::{decode_frame, encode_frame, is_compressed, read_frame, COMPRESSION_LIMIT},
+    script::{parse_request, parse_response, Expected},
+    CommandRequest, CommandResponse, Failure, Target,
+};
+
+/// 每个请求等待响应的时间
+const STEP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 一个请求和期望的响应
+#[derive(Debug, Clone)]
+pub struct Step {
+    /// 请求所在的行号
+    pub line: usize,
+    pub request: CommandRequest,
+    pub expected: Expected,
+}
+
+/// fixtures/transcripts 下的一个文件：`>` 开头的请求之后紧跟 `<` 开头的期望响应
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    pub name: String,
+    pub steps: Vec<Step>,
+}
+
+/// 解析一个 transcript
+pub fn parse(name: &str, src: &str) -> Result<Transcript> {
+    let mut steps = vec![];
+    let mut request = None;
+    for (i, line) in src.lines().enumerate() {
+        let line = line.trim();
+        let ctx = || format!("{} line {}", name, i + 1);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match (
+            line.strip_prefix('>'),
+            line.strip_prefix('<'),
+            request.take(),
+        ) {
+            (Some(s), _, None) => request = Some((i + 1, parse_request(s).with_context(ctx)?)),
+            (_, Some(s), Some((line, request))) => steps.push(Step {
+                line,
+                request,
+                expected: parse_response(s).with_context(ctx)?,
+            }),
+            (Some(_), _, Some(_)) => bail!("{}: expected a response", ctx()),
+            _ => bail!("{}: expected a request", ctx()),
+        }
+    }
+    if let Some((line, _)) = request {
+        bail!("{} line {}: request without response", name, line);
+    }
+
+    Ok(Transcript {
+        name: name.into(),
+        steps,
+    })
+}
+
+impl Transcript {
+    /// 在一个新的连接上依次执行所有的请求；某一步失败后，后面的状态已经不可信，直接结束
+    pub async fn run<T: Target>(&self, target: &T) -> Option<Failure> {
+        let mut stream = target.connect();
+        for step in self.steps.iter() {
+            let expected = target.expected(&step.request, step.expected.clone());
+            let result = time::timeout(STEP_TIMEOUT, execute(&mut stream, &step.request)).await;
+            let reason = match result {
+                Ok(Ok(res)) => match expected.check(&res) {
+                    Ok(()) => continue,
+                    Err(reason) => reason,
+                },
+                Ok(Err(e)) => format!("{:#}", e),
+                Err(_) => format!("no response in {:?}", STEP_TIMEOUT),
+            };
+            return Some(Failure {
+                fixture: format!("transcripts/{}", self.name),
+                line: step.line,
+                reason,
+            });
+        }
+        None
+    }
+}
+
+/// 按参考实现封包发送请求，读取并解包响应
+async fn execute<S>(stream: &mut S, request: &CommandRequest) -> Result<CommandResponse>
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    stream.write_all(&encode_frame(request)?).await?;
+    let frame = read_frame(stream)
+        .await
+        .context("read response (server closed the connection?)")?;
+    let res: CommandResponse = decode_frame(&frame).context("decode response")?;
+
+    // 服务器发出的 frame 也要遵守压缩的约定
+    let size = res.encoded_len();
+    ensure!(
+        is_compressed(&frame) == (size >= COMPRESSION_LIMIT),
+        "response of {} bytes should {}be compressed",
+        size,
+        if size >= COMPRESSION_LIMIT {
+            ""
+        } else {
+            "not "
+        }
+    );
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_transcript_should_work() -> Result<()> {
+        let t = parse(
+            "basic",
+            "# comment\n> hset t1 k1 \"v1\"\n< 200 [null]\n\n> hget t1 k1\n< 200 [\"v1\"]\n",
+        )?;
+        assert_eq!(t.steps.len(), 2);
+        assert_eq!(t.steps[0].line, 2);
+        assert_eq!(t.steps[1].line, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn unpaired_lines_should_fail() {
+        assert!(parse("t", "> hget t1 k1").is_err());
+        assert!(parse("t", "< 200").is_err());
+        assert!(parse("t", "> hget t1 k1\n> hget t1 k2\n< 200").is_err());
+        assert!(parse("t", "hget t1 k1").is_err());
+    }
+}
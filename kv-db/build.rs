@@ -1,7 +1,13 @@
 fn main() {
     prost_build::Config::new()
         .bytes(&["."])
-        .type_attribute(".", "#[derive(PartialOrd)]")
+        // prost 生成的 enum 已经 derive 了 PartialOrd，这里只给 message 和 oneof 加上
+        .message_attribute(".", "#[derive(PartialOrd)]")
+        .enum_attribute(".abi.CommandRequest.request_data", "#[derive(PartialOrd)]")
+        .enum_attribute(".abi.Value.value", "#[derive(PartialOrd)]")
+        // HTTP 网关需要 CommandRequest/CommandResponse 的 JSON 形式
+        .type_attribute(".", "#[derive(serde::Serialize, serde::Deserialize)]")
+        .message_attribute(".", "#[serde(default)]")
         .out_dir("src/pb")
         .compile_protos(&["abi.proto"], &["src/pb"])
         .unwrap();
@@ -0,0 +1,465 @@
+use crate::{
+    error::KvError,
+    pb::abi::{value, Hset, Kvpair, Value},
+    Storage,
+};
+use bytes::Bytes;
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    str::FromStr,
+};
+
+/// 快照文件的 magic 和版本
+pub const DUMP_MAGIC: &[u8; 6] = b"KVDUMP";
+pub const DUMP_VERSION: u8 = 1;
+
+/// 快照在网络上分块传输（Dump 的响应、Restore 的请求）时每块的大小
+pub const DUMP_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 快照中单条记录的最大长度，长度来自不可信的输入，超过的直接报错
+pub const MAX_RECORD_SIZE: u64 = 64 * 1024 * 1024;
+
+/// 快照文件格式：
+///
+/// ```text
+/// | KVDUMP | version: u8 | Hset（length delimited）... | 0u8 | count: u64 | crc32: u32 |
+/// ```
+///
+/// 每条记录都是一个 length delimited 的 Hset（table + Kvpair），恢复时直接回放即可；
+/// 长度为 0 的记录表示结束，后面跟着记录数和所有记录数据的 crc32 校验和
+pub struct DumpWriter<W> {
+    inner: W,
+    hasher: crc32fast::Hasher,
+    count: u64,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> DumpWriter<W> {
+    pub fn new(mut inner: W) -> Result<Self, KvError> {
+        inner.write_all(DUMP_MAGIC)?;
+        inner.write_all(&[DUMP_VERSION])?;
+        Ok(Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            count: 0,
+            buf: Vec::new(),
+        })
+    }
+
+    /// 写入一条记录
+    pub fn write(&mut self, table: impl Into<String>, pair: Kvpair) -> Result<(), KvError> {
+        let record = Hset {
+            table: table.into(),
+            pair: Some(pair),
+        };
+        self.buf.clear();
+        record.encode_length_delimited(&mut self.buf)?;
+        self.hasher.update(&self.buf);
+        self.inner.write_all(&self.buf)?;
+        self.count += 1;
+        Ok(())
+    }
+
+    /// 写入结束标记和校验和，返回底层的 writer
+    pub fn finish(mut self) -> Result<W, KvError> {
+        self.inner.write_all(&[0])?;
+        self.inner.write_all(&self.count.to_be_bytes())?;
+        self.inner
+            .write_all(&self.hasher.finalize().to_be_bytes())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+/// 逐条读取快照文件里的记录，读到结束标记后校验记录数和校验和
+pub struct DumpReader<R> {
+    inner: BufReader<R>,
+    hasher: crc32fast::Hasher,
+    count: u64,
+    done: bool,
+}
+
+impl<R: Read> DumpReader<R> {
+    pub fn new(inner: R) -> Result<Self, KvError> {
+        let mut inner = BufReader::new(inner);
+        let mut header = [0u8; 7];
+        inner.read_exact(&mut header)?;
+        if &header[..6] != DUMP_MAGIC {
+            return Err(KvError::DumpError("invalid dump file".into()));
+        }
+        if header[6] != DUMP_VERSION {
+            return Err(KvError::DumpError(format!(
+                "unsupported dump version {}",
+                header[6]
+            )));
+        }
+
+        Ok(Self {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+            count: 0,
+            done: false,
+        })
+    }
+
+    fn read_record(&mut self) -> Result<Option<Hset>, KvError> {
+        let len = read_varint(&mut self.inner)?;
+        if len == 0 {
+            self.verify()?;
+            return Ok(None);
+        }
+
+        if len > MAX_RECORD_SIZE {
+            return Err(KvError::DumpError(format!(
+                "dump record of {} bytes exceeds {} bytes",
+                len, MAX_RECORD_SIZE
+            )));
+        }
+        // 按实际读到的数据分配内存，伪造的长度不会一次分配很大的 buffer
+        let mut buf = Vec::new();
+        (&mut self.inner).take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(truncated(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let mut prefix = Vec::with_capacity(10);
+        prost::encoding::encode_varint(len, &mut prefix);
+        self.hasher.update(&prefix);
+        self.hasher.update(&buf);
+        self.count += 1;
+
+        Ok(Some(Hset::decode(&buf[..])?))
+    }
+
+    fn verify(&mut self) -> Result<(), KvError> {
+        let mut count = [0u8; 8];
+        let mut crc = [0u8; 4];
+        self.inner.read_exact(&mut count).map_err(truncated)?;
+        self.inner.read_exact(&mut crc).map_err(truncated)?;
+        if u64::from_be_bytes(count) != self.count {
+            return Err(KvError::DumpError(format!(
+                "record count mismatch: expect {}, got {}",
+                u64::from_be_bytes(count),
+                self.count
+            )));
+        }
+        if u32::from_be_bytes(crc) != self.hasher.clone().finalize() {
+            return Err(KvError::DumpError("checksum mismatch".into()));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for DumpReader<R> {
+    type Item = Result<Hset, KvError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.read_record() {
+            Ok(Some(record)) => Some(Ok(record)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+fn truncated(e: io::Error) -> KvError {
+    KvError::DumpError(format!("truncated dump: {}", e))
+}
+
+fn read_varint(reader: &mut impl Read) -> Result<u64, KvError> {
+    let mut value = 0u64;
+    for i in 0..10 {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte).map_err(truncated)?;
+        value |= ((byte[0] & 0x7f) as u64) << (i * 7);
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(KvError::DumpError("invalid varint".into()))
+}
+
+/// 把 store 里的 table（table 为 None 则是所有 table）导出成快照，返回记录数
+pub fn dump(store: &impl Storage, table: Option<&str>, writer: impl Write) -> Result<u64, KvError> {
+    let tables = match table {
+        Some(t) => vec![t.to_string()],
+        None => store.get_tables()?,
+    };
+
+    let mut writer = DumpWriter::new(writer)?;
+    for table in tables {
+        for pair in store.get_iter(&table)? {
+            writer.write(&table, pair)?;
+        }
+    }
+    let count = writer.count;
+    writer.finish()?;
+    Ok(count)
+}
+
+/// 从快照中恢复数据到 store；先校验整个快照，校验通过才写入，返回记录数
+pub fn restore(store: &impl Storage, reader: impl Read) -> Result<u64, KvError> {
+    let records = DumpReader::new(reader)?.collect::<Result<Vec<_>, _>>()?;
+    let count = records.len() as u64;
+    for record in records {
+        let pair = record.pair.unwrap_or_default();
+        store.set(record.table, pair.key, pair.value.unwrap_or_default())?;
+    }
+    Ok(count)
+}
+
+/// table 导入导出的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// 每行一个 JSON 形式的 Kvpair
+    Jsonl,
+    /// key,type,value 三列，binary 以 hex 表示
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "jsonl" | "json" => Ok(Self::Jsonl),
+            "csv" => Ok(Self::Csv),
+            _ => Err(format!("unsupported format: {}", s)),
+        }
+    }
+}
+
+/// 把一个 table 的 kvpair 导出成 JSON lines 或 CSV，返回记录数
+pub fn export(
+    pairs: impl IntoIterator<Item = Kvpair>,
+    format: ExportFormat,
+    writer: impl Write,
+) -> Result<u64, KvError> {
+    let mut count = 0;
+    match format {
+        ExportFormat::Jsonl => {
+            let mut writer = writer;
+            for pair in pairs {
+                serde_json::to_writer(&mut writer, &pair).map_err(io::Error::from)?;
+                writer.write_all(b"\n")?;
+                count += 1;
+            }
+            writer.flush()?;
+        }
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(writer);
+            writer
+                .write_record(["key", "type", "value"])
+                .map_err(csv_error)?;
+            for pair in pairs {
+                let (ty, value) = value_to_csv(pair.value.unwrap_or_default());
+                writer
+                    .write_record([pair.key.as_str(), ty, value.as_str()])
+                    .map_err(csv_error)?;
+                count += 1;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(count)
+}
+
+/// 读取 JSON lines 或 CSV 格式的 kvpair
+pub fn import(reader: impl Read, format: ExportFormat) -> Result<Vec<Kvpair>, KvError> {
+    match format {
+        ExportFormat::Jsonl => {
+            let mut pairs = vec![];
+            for line in BufReader::new(reader).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let pair = serde_json::from_str(&line).map_err(io::Error::from)?;
+                pairs.push(pair);
+            }
+            Ok(pairs)
+        }
+        ExportFormat::Csv => {
+            let mut reader = csv::Reader::from_reader(reader);
+            let mut pairs = vec![];
+            for record in reader.records() {
+                let record = record.map_err(csv_error)?;
+                let (key, ty, value) = match (record.get(0), record.get(1), record.get(2)) {
+                    (Some(k), Some(t), Some(v)) => (k, t, v),
+                    _ => return Err(KvError::DumpError(format!("invalid record {:?}", record))),
+                };
+                pairs.push(Kvpair::new(key, value_from_csv(ty, value)?));
+            }
+            Ok(pairs)
+        }
+    }
+}
+
+fn value_to_csv(value: Value) -> (&'static str, String) {
+    match value.value {
+        Some(value::Value::String(s)) => ("string", s),
+        Some(value::Value::Binary(b)) => {
+            ("binary", b.iter().map(|b| format!("{:02x}", b)).collect())
+        }
+        Some(value::Value::Integer(i)) => ("integer", i.to_string()),
+        Some(value::Value::Float(f)) => ("float", f.to_string()),
+        Some(value::Value::Bool(b)) => ("bool", b.to_string()),
+        None => ("", String::new()),
+    }
+}
+
+fn value_from_csv(ty: &str, value: &str) -> Result<Value, KvError> {
+    let invalid = || KvError::DumpError(format!("invalid {} value: {}", ty, value));
+    let v = match ty {
+        "string" => value::Value::String(value.into()),
+        "binary" => {
+            let bytes = (0..value.len())
+                .step_by(2)
+                .map(|i| {
+                    value
+                        .get(i..i + 2)
+                        .and_then(|b| u8::from_str_radix(b, 16).ok())
+                })
+                .collect::<Option<Vec<u8>>>()
+                .ok_or_else(invalid)?;
+            value::Value::Binary(Bytes::from(bytes))
+        }
+        "integer" => value::Value::Integer(value.parse().map_err(|_| invalid())?),
+        "float" => value::Value::Float(value.parse().map_err(|_| invalid())?),
+        "bool" => value::Value::Bool(value.parse().map_err(|_| invalid())?),
+        "" => return Ok(Value::default()),
+        _ => return Err(KvError::DumpError(format!("unknown value type {}", ty))),
+    };
+    Ok(Value { value: Some(v) })
+}
+
+fn csv_error(e: csv::Error) -> KvError {
+    KvError::DumpError(format!("csv: {}", e))
+}
+
+#[cfg(test)]
+mod backup_tests {
+    use super::*;
+    use crate::{memory::MemTable, sled_db::SledDB};
+    use tempfile::tempdir;
+
+    fn prepare() -> MemTable {
+        let store = MemTable::new();
+        store.set("t1", "k1", "v1".into()).unwrap();
+        store.set("t1", "k2", 2.into()).unwrap();
+        store.set("t2", "k1", b"bin".into()).unwrap();
+        store
+    }
+
+    fn sorted(mut pairs: Vec<Kvpair>) -> Vec<Kvpair> {
+        pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        pairs
+    }
+
+    #[test]
+    fn dump_restore_should_work() {
+        let store = prepare();
+        let mut buf = vec![];
+        assert_eq!(dump(&store, None, &mut buf).unwrap(), 3);
+
+        let restored = MemTable::new();
+        assert_eq!(restore(&restored, &buf[..]).unwrap(), 3);
+        for table in ["t1", "t2"] {
+            assert_eq!(
+                sorted(restored.get_all(table).unwrap()),
+                sorted(store.get_all(table).unwrap())
+            );
+        }
+    }
+
+    #[test]
+    fn dump_single_table_should_work() {
+        let store = prepare();
+        let mut buf = vec![];
+        assert_eq!(dump(&store, Some("t1"), &mut buf).unwrap(), 2);
+    }
+
+    #[test]
+    fn migrate_memtable_to_sleddb_should_work() {
+        let store = prepare();
+        let mut buf = vec![];
+        dump(&store, None, &mut buf).unwrap();
+
+        let dir = tempdir().unwrap();
+        let sled = SledDB::new(dir.path());
+        restore(&sled, &buf[..]).unwrap();
+        assert_eq!(
+            sorted(sled.get_all("t1").unwrap()),
+            sorted(store.get_all("t1").unwrap())
+        );
+        assert_eq!(sled.get("t2", "k1").unwrap(), Some(b"bin".into()));
+    }
+
+    #[test]
+    fn corrupted_dump_should_not_be_restored() {
+        let store = prepare();
+        let mut buf = vec![];
+        dump(&store, None, &mut buf).unwrap();
+
+        // 改掉最后一个字节（校验和）
+        let last = buf.len() - 1;
+        buf[last] ^= 0xff;
+        let restored = MemTable::new();
+        assert!(matches!(
+            restore(&restored, &buf[..]),
+            Err(KvError::DumpError(_))
+        ));
+        assert!(restored.get_tables().unwrap().is_empty());
+
+        // 截断的快照同样不能恢复
+        assert!(restore(&restored, &buf[..buf.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn forged_record_length_should_not_be_allocated() {
+        let mut buf = DUMP_MAGIC.to_vec();
+        buf.push(DUMP_VERSION);
+        let restored = MemTable::new();
+
+        // 记录长度超过上限
+        let mut forged = buf.clone();
+        prost::encoding::encode_varint(u64::MAX, &mut forged);
+        let err = restore(&restored, &forged[..]).unwrap_err();
+        assert!(err.to_string().contains("exceeds"));
+
+        // 长度在上限之内，但是后面没有这么多数据
+        prost::encoding::encode_varint(MAX_RECORD_SIZE, &mut buf);
+        buf.extend_from_slice(b"short");
+        let err = restore(&restored, &buf[..]).unwrap_err();
+        assert!(err.to_string().contains("truncated"));
+        assert!(restored.get_tables().unwrap().is_empty());
+    }
+
+    #[test]
+    fn export_import_should_work() {
+        let store = prepare();
+        for format in [ExportFormat::Jsonl, ExportFormat::Csv] {
+            for table in ["t1", "t2"] {
+                let pairs = store.get_all(table).unwrap();
+                let mut buf = vec![];
+                assert_eq!(
+                    export(pairs.clone(), format, &mut buf).unwrap(),
+                    pairs.len() as u64
+                );
+                let imported = import(&buf[..], format).unwrap();
+                assert_eq!(sorted(imported), sorted(pairs));
+            }
+        }
+    }
+}
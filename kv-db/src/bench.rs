@@ -0,0 +1,599 @@
+use crate::{
+    config::{ClientConfig, ServerConfig, StorageConfig},
+    error::KvError,
+    pb::abi::{CommandRequest, Value},
+    start_server_with_listener, ProstClientStream,
+};
+use bytes::Bytes;
+use hyper::StatusCode;
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::TcpListener,
+};
+
+/// 压测使用的 table 和 topic
+pub const BENCH_TABLE: &str = "bench";
+pub const BENCH_TOPIC: &str = "bench";
+
+/// 压测支持的命令
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    Get,
+    Set,
+    Hgetall,
+    Publish,
+}
+
+impl Op {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::Get => "get",
+            Op::Set => "set",
+            Op::Hgetall => "hgetall",
+            Op::Publish => "publish",
+        }
+    }
+}
+
+impl FromStr for Op {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "get" | "hget" => Ok(Self::Get),
+            "set" | "hset" => Ok(Self::Set),
+            "hgetall" => Ok(Self::Hgetall),
+            "publish" | "pub" => Ok(Self::Publish),
+            _ => Err(format!("unsupported command: {}", s)),
+        }
+    }
+}
+
+/// 命令的比例，如 `get=80,set=15,hgetall=1,publish=4`，省略权重时为 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mix(Vec<(Op, u32)>);
+
+impl Mix {
+    /// 按照权重随机选出一个命令
+    pub fn choose(&self, rng: &mut impl Rng) -> Op {
+        let total: u32 = self.0.iter().map(|(_, w)| w).sum();
+        let mut n = rng.gen_range(0..total);
+        for (op, weight) in &self.0 {
+            if n < *weight {
+                return *op;
+            }
+            n -= weight;
+        }
+        unreachable!("weights of mix are not empty")
+    }
+
+    pub fn contains(&self, op: Op) -> bool {
+        self.0.iter().any(|(o, _)| *o == op)
+    }
+}
+
+impl FromStr for Mix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut mix = vec![];
+        for item in s.split(',').filter(|item| !item.trim().is_empty()) {
+            let (op, weight) = match item.split_once('=') {
+                Some((op, weight)) => {
+                    let weight = weight
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("invalid weight: {}", item))?;
+                    (op.parse()?, weight)
+                }
+                None => (item.parse()?, 1),
+            };
+            if weight > 0 {
+                mix.push((op, weight));
+            }
+        }
+        if mix.is_empty() {
+            return Err(format!("empty command mix: {}", s));
+        }
+        Ok(Self(mix))
+    }
+}
+
+/// 压测的负载：命令的比例、key 的数量和 value 的大小。
+/// value 的内容由 seed 生成，同样的 seed 会生成同样的请求序列
+#[derive(Debug, Clone)]
+pub struct Workload {
+    mix: Mix,
+    keyspace: u64,
+    /// 每种大小的 value 各生成一个
+    values: Vec<Value>,
+}
+
+impl Workload {
+    pub fn new(mix: Mix, keyspace: u64, value_sizes: &[usize], seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let values = value_sizes
+            .iter()
+            .map(|size| {
+                let mut buf = vec![0u8; *size];
+                rng.fill_bytes(&mut buf);
+                Bytes::from(buf).into()
+            })
+            .collect::<Vec<Value>>();
+        Self {
+            mix,
+            keyspace: keyspace.max(1),
+            values: if values.is_empty() {
+                vec![Bytes::new().into()]
+            } else {
+                values
+            },
+        }
+    }
+
+    pub fn key(i: u64) -> String {
+        format!("key:{:012}", i)
+    }
+
+    /// 压测前写入所有 key，这样 get 和 hgetall 才有数据可读
+    pub fn prefill(&self) -> impl Iterator<Item = CommandRequest> + '_ {
+        (0..self.keyspace).map(|i| {
+            let value = self.values[i as usize % self.values.len()].clone();
+            CommandRequest::new_hset(BENCH_TABLE, Self::key(i), value)
+        })
+    }
+
+    /// 生成下一个请求
+    pub fn next(&self, rng: &mut impl Rng) -> (Op, CommandRequest) {
+        let op = self.mix.choose(rng);
+        let key = Self::key(rng.gen_range(0..self.keyspace));
+        let value = self.values[rng.gen_range(0..self.values.len())].clone();
+        let cmd = match op {
+            Op::Get => CommandRequest::new_hget(BENCH_TABLE, key),
+            Op::Set => CommandRequest::new_hset(BENCH_TABLE, key, value),
+            Op::Hgetall => CommandRequest::new_hgetall(BENCH_TABLE),
+            Op::Publish => CommandRequest::new_publish(BENCH_TOPIC, vec![value]),
+        };
+        (op, cmd)
+    }
+
+    /// 是否需要预先写入数据
+    pub fn needs_prefill(&self) -> bool {
+        self.mix.contains(Op::Get) || self.mix.contains(Op::Hgetall)
+    }
+}
+
+/// 以微秒为单位的延迟直方图，内存占用固定：
+/// 小于 64us 的值每个微秒一个桶，更大的值每个 2 的幂区间再分成 32 个桶，误差在 3% 以内
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+const SUB_BITS: u32 = 5;
+const SUB_BUCKETS: usize = 1 << SUB_BITS;
+const LINEAR_BUCKETS: usize = SUB_BUCKETS * 2;
+const BUCKETS: usize = LINEAR_BUCKETS + (64 - SUB_BITS as usize - 1) * SUB_BUCKETS;
+
+fn bucket_index(v: u64) -> usize {
+    if v < LINEAR_BUCKETS as u64 {
+        return v as usize;
+    }
+    let exp = 63 - v.leading_zeros();
+    let sub = (v >> (exp - SUB_BITS)) as usize & (SUB_BUCKETS - 1);
+    LINEAR_BUCKETS + (exp - SUB_BITS - 1) as usize * SUB_BUCKETS + sub
+}
+
+/// 桶的下界
+fn bucket_lower(i: usize) -> u64 {
+    if i < LINEAR_BUCKETS {
+        return i as u64;
+    }
+    let i = i - LINEAR_BUCKETS;
+    let exp = (i / SUB_BUCKETS) as u32 + SUB_BITS + 1;
+    ((SUB_BUCKETS + i % SUB_BUCKETS) as u64) << (exp - SUB_BITS)
+}
+
+/// 桶的上界（包含）
+fn bucket_upper(i: usize) -> u64 {
+    if i + 1 == BUCKETS {
+        u64::MAX
+    } else {
+        bucket_lower(i + 1) - 1
+    }
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn record(&mut self, latency: Duration) {
+        let us = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.counts[bucket_index(us)] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(us);
+        self.min = self.min.min(us);
+        self.max = self.max.max(us);
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (a, b) in self.counts.iter_mut().zip(&other.counts) {
+            *a += b;
+        }
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// 百分位数（0.0 ~ 1.0），返回所在桶的上界，不会超过记录到的最大值
+    pub fn percentile(&self, p: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let target = ((p * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+        for (i, n) in self.counts.iter().enumerate() {
+            seen += n;
+            if seen >= target {
+                return bucket_upper(i).min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// 按 2 的幂合并后的直方图，用于输出
+    pub fn buckets(&self) -> Vec<Bucket> {
+        let mut buckets: Vec<Bucket> = vec![];
+        for (i, n) in self.counts.iter().enumerate().filter(|(_, n)| **n > 0) {
+            let le_us = bucket_upper(i).saturating_add(1).next_power_of_two();
+            match buckets.last_mut() {
+                Some(last) if last.le_us == le_us => last.count += n,
+                _ => buckets.push(Bucket { le_us, count: *n }),
+            }
+        }
+        buckets
+    }
+
+    pub fn summary(&self) -> LatencySummary {
+        LatencySummary {
+            min_us: if self.count == 0 { 0 } else { self.min },
+            mean_us: if self.count == 0 {
+                0.0
+            } else {
+                self.sum as f64 / self.count as f64
+            },
+            p50_us: self.percentile(0.5),
+            p99_us: self.percentile(0.99),
+            p999_us: self.percentile(0.999),
+            max_us: self.max,
+            histogram: self.buckets(),
+        }
+    }
+}
+
+/// 延迟小于 le_us 微秒的请求数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Bucket {
+    pub le_us: u64,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LatencySummary {
+    pub min_us: u64,
+    pub mean_us: f64,
+    pub p50_us: u64,
+    pub p99_us: u64,
+    pub p999_us: u64,
+    pub max_us: u64,
+    pub histogram: Vec<Bucket>,
+}
+
+/// 每个命令的延迟和错误数
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    ops: BTreeMap<Op, OpStats>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct OpStats {
+    latency: Histogram,
+    errors: u64,
+}
+
+impl Stats {
+    pub fn record(&mut self, op: Op, latency: Duration, ok: bool) {
+        let stats = self.ops.entry(op).or_default();
+        stats.latency.record(latency);
+        if !ok {
+            stats.errors += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &Stats) {
+        for (op, s) in &other.ops {
+            let stats = self.ops.entry(*op).or_default();
+            stats.latency.merge(&s.latency);
+            stats.errors += s.errors;
+        }
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.ops.values().map(|s| s.latency.count()).sum()
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.ops.values().map(|s| s.errors).sum()
+    }
+
+    pub fn report(&self, elapsed: Duration) -> Report {
+        let secs = elapsed.as_secs_f64();
+        let throughput = |n: u64| if secs > 0.0 { n as f64 / secs } else { 0.0 };
+
+        let mut total = Histogram::default();
+        let mut commands = BTreeMap::new();
+        for (op, s) in &self.ops {
+            total.merge(&s.latency);
+            commands.insert(
+                op.name().to_string(),
+                CommandReport {
+                    requests: s.latency.count(),
+                    errors: s.errors,
+                    throughput: throughput(s.latency.count()),
+                    latency: s.latency.summary(),
+                },
+            );
+        }
+
+        Report {
+            duration_secs: secs,
+            requests: self.requests(),
+            errors: self.errors(),
+            throughput: throughput(self.requests()),
+            latency: total.summary(),
+            commands,
+        }
+    }
+}
+
+/// 压测结果，可以输出成文本或者 JSON
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub duration_secs: f64,
+    pub requests: u64,
+    pub errors: u64,
+    /// 每秒请求数
+    pub throughput: f64,
+    pub latency: LatencySummary,
+    pub commands: BTreeMap<String, CommandReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandReport {
+    pub requests: u64,
+    pub errors: u64,
+    pub throughput: f64,
+    pub latency: LatencySummary,
+}
+
+impl fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "min {}us, mean {:.1}us, p50 {}us, p99 {}us, p999 {}us, max {}us",
+            self.min_us, self.mean_us, self.p50_us, self.p99_us, self.p999_us, self.max_us
+        )
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} requests in {:.2}s, {} errors, {:.2} requests/s",
+            self.requests, self.duration_secs, self.errors, self.throughput
+        )?;
+        writeln!(f, "latency: {}", self.latency)?;
+        for (name, cmd) in &self.commands {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "== {}: {} requests, {} errors, {:.2} requests/s",
+                name, cmd.requests, cmd.errors, cmd.throughput
+            )?;
+            writeln!(f, "latency: {}", cmd.latency)?;
+            for bucket in &cmd.latency.histogram {
+                let percent = bucket.count as f64 * 100.0 / cmd.requests.max(1) as f64;
+                writeln!(
+                    f,
+                    "  <= {:>9}us {:>10} {:>6.2}% {}",
+                    bucket.le_us,
+                    bucket.count,
+                    percent,
+                    "#".repeat((percent / 2.0).ceil() as usize)
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 在一个 stream 上不停地发送请求直到 deadline，每个 worker 用自己的 seed 生成请求
+pub async fn run_worker<S>(
+    client: &mut ProstClientStream<S>,
+    workload: &Workload,
+    seed: u64,
+    deadline: Instant,
+) -> Result<Stats, KvError>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut stats = Stats::default();
+    while Instant::now() < deadline {
+        let (op, cmd) = workload.next(&mut rng);
+        let start = Instant::now();
+        let res = client.execute(&cmd).await?;
+        // 没有写入过的 key 返回 404，不算错误
+        let ok = res.status == StatusCode::OK.as_u16() as u32
+            || (op == Op::Get && res.status == StatusCode::NOT_FOUND.as_u16() as u32);
+        stats.record(op, start.elapsed(), ok);
+    }
+    Ok(stats)
+}
+
+/// 没有指定服务器地址时，在本进程中启动一个使用 MemTable 的服务器，返回连接它的客户端配置
+pub async fn start_local_server() -> anyhow::Result<ClientConfig> {
+    // 绑定 0 端口拿到一个空闲的端口，listener 直接交给服务器，返回之前就已经在监听了
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?.to_string();
+
+    let mut config: ServerConfig = toml::from_str(include_str!("../fixtures/server.conf"))?;
+    config.general.addr = addr.clone();
+    config.storage = StorageConfig::MemTable;
+    tokio::spawn(async move {
+        if let Err(e) = start_server_with_listener(listener, config).await {
+            tracing::error!("Local server exited: {:?}", e);
+        }
+    });
+
+    let mut config: ClientConfig = toml::from_str(include_str!("../fixtures/client.conf"))?;
+    config.general.addr = addr;
+    Ok(config)
+}
+
+#[cfg(test)]
+mod bench_tests {
+    use super::*;
+    use crate::{start_client_with_config, COMPRESSION_LIMIT};
+
+    #[test]
+    fn mix_should_parse() {
+        let mix: Mix = "get=80, set=20,hgetall".parse().unwrap();
+        assert_eq!(
+            mix,
+            Mix(vec![(Op::Get, 80), (Op::Set, 20), (Op::Hgetall, 1)])
+        );
+        // 权重为 0 的命令会被忽略
+        let mix: Mix = "get=0,publish=1".parse().unwrap();
+        assert_eq!(mix, Mix(vec![(Op::Publish, 1)]));
+
+        assert!("".parse::<Mix>().is_err());
+        assert!("get=0".parse::<Mix>().is_err());
+        assert!("del=1".parse::<Mix>().is_err());
+        assert!("get=x".parse::<Mix>().is_err());
+    }
+
+    #[test]
+    fn workload_should_be_deterministic() {
+        let mix: Mix = "get=1,set=1,hgetall=1,publish=1".parse().unwrap();
+        let sizes = [16, COMPRESSION_LIMIT + 1];
+        let w1 = Workload::new(mix.clone(), 100, &sizes, 42);
+        let w2 = Workload::new(mix, 100, &sizes, 42);
+        let mut r1 = StdRng::seed_from_u64(1);
+        let mut r2 = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            assert_eq!(w1.next(&mut r1), w2.next(&mut r2));
+        }
+        assert_eq!(w1.prefill().count(), 100);
+        assert!(w1.prefill().eq(w2.prefill()));
+    }
+
+    #[test]
+    fn histogram_buckets_should_be_contiguous() {
+        for i in 0..BUCKETS - 1 {
+            assert_eq!(bucket_upper(i) + 1, bucket_lower(i + 1));
+            assert_eq!(bucket_index(bucket_lower(i)), i);
+            assert_eq!(bucket_index(bucket_upper(i)), i);
+        }
+        assert_eq!(bucket_index(u64::MAX), BUCKETS - 1);
+    }
+
+    #[test]
+    fn histogram_percentile_should_work() {
+        let mut h = Histogram::default();
+        for us in 1..=1000 {
+            h.record(Duration::from_micros(us));
+        }
+        assert_eq!(h.count(), 1000);
+        let within = |v: u64, expected: u64| v >= expected && v <= expected + expected * 3 / 100;
+        assert!(within(h.percentile(0.5), 500));
+        assert!(within(h.percentile(0.99), 990));
+        assert_eq!(h.percentile(0.999), 1000);
+        assert_eq!(h.percentile(1.0), 1000);
+
+        let summary = h.summary();
+        assert_eq!(summary.min_us, 1);
+        assert_eq!(summary.max_us, 1000);
+        assert_eq!(summary.histogram.iter().map(|b| b.count).sum::<u64>(), 1000);
+        assert_eq!(summary.histogram.last().unwrap().le_us, 1024);
+    }
+
+    #[test]
+    fn report_should_merge_stats() {
+        let mut s1 = Stats::default();
+        s1.record(Op::Get, Duration::from_micros(10), true);
+        s1.record(Op::Set, Duration::from_micros(20), false);
+        let mut s2 = Stats::default();
+        s2.record(Op::Get, Duration::from_micros(30), true);
+        s1.merge(&s2);
+
+        let report = s1.report(Duration::from_secs(2));
+        assert_eq!(report.requests, 3);
+        assert_eq!(report.errors, 1);
+        assert_eq!(report.throughput, 1.5);
+        assert_eq!(report.commands["get"].requests, 2);
+        assert_eq!(report.commands["set"].errors, 1);
+        assert_eq!(report.latency.max_us, 30);
+
+        let text = report.to_string();
+        assert!(text.contains("== get: 2 requests"));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["commands"]["get"]["latency"]["p50_us"], 10);
+    }
+
+    #[tokio::test]
+    async fn bench_against_local_server_should_work() -> anyhow::Result<()> {
+        let config = start_local_server().await?;
+        let mut ctrl = start_client_with_config(config).await?;
+        let mut client = ctrl.open_stream().await?;
+
+        let mix = "get=1,set=1,hgetall=1,publish=1".parse().unwrap();
+        let workload = Workload::new(mix, 10, &[16, COMPRESSION_LIMIT + 1], 0);
+        for cmd in workload.prefill() {
+            client.execute(&cmd).await?;
+        }
+        let deadline = Instant::now() + Duration::from_millis(200);
+        let stats = run_worker(&mut client, &workload, 0, deadline).await?;
+        assert!(stats.requests() > 0);
+        assert_eq!(stats.errors(), 0);
+        Ok(())
+    }
+}
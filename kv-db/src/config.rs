@@ -1,18 +1,28 @@
-use crate::error::KvError;
+use crate::{error::KvError, network::COMPRESSION_LIMIT};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{collections::HashMap, fs};
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ServerConfig {
     pub general: GeneralConfig,
     pub storage: StorageConfig,
     pub tls: ServerTlsConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    /// 可选的 HTTP/JSON、WebSocket 网关，不配置则不启动
+    pub http: Option<HttpConfig>,
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    #[serde(default)]
+    pub scripting: ScriptConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct ClientConfig {
     pub general: GeneralConfig,
     pub tls: ClientTlsConfig,
+    #[serde(default)]
+    pub compression: CompressionConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -20,6 +30,15 @@ pub struct GeneralConfig {
     pub addr: String,
 }
 
+/// HTTP 网关和 TCP 监听使用同一份 [tls] 配置，配置了 ca 时客户端同样需要证书
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct HttpConfig {
+    pub addr: String,
+    /// <Bearer token, 认证主体>，不为空时每个请求都要带 `Authorization: Bearer <token>`
+    #[serde(default)]
+    pub tokens: HashMap<String, String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(tag = "type", content = "args")]
 pub enum StorageConfig {
@@ -41,6 +60,116 @@ pub struct ClientTlsConfig {
     pub ca: Option<String>,
 }
 
+/// frame 的压缩算法
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Codec {
+    None,
+    #[default]
+    Gzip,
+    Zstd,
+    Lz4,
+}
+
+/// frame 的压缩配置；服务器会跟随客户端实际使用的压缩算法来回复
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct CompressionConfig {
+    /// 发送 frame 时使用的压缩算法
+    pub codec: Codec,
+    /// payload 达到多少字节才压缩
+    pub threshold: usize,
+    /// 压缩等级，不设置则使用各算法的默认等级（lz4 忽略）
+    pub level: Option<i32>,
+    /// 读取时允许的最大 frame 长度
+    pub max_frame: usize,
+    /// 解压后允许的最大长度，防止解压炸弹
+    pub max_decompressed: usize,
+    /// 服务器接收分块 Restore 时最多缓存的快照长度
+    pub max_restore: usize,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            codec: Codec::default(),
+            threshold: COMPRESSION_LIMIT,
+            level: None,
+            max_frame: 64 * 1024 * 1024,
+            max_decompressed: 64 * 1024 * 1024,
+            max_restore: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// 限流、配额以及内存上限，默认都不限制
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct LimitsConfig {
+    /// 每个连接的限流
+    pub connection: Option<RateLimitConfig>,
+    /// 每个认证主体（客户端证书的 CN）的限流，同一个主体的所有连接共享
+    pub principal: Option<RateLimitConfig>,
+    /// <table, 配额>
+    pub quotas: HashMap<String, QuotaConfig>,
+    /// MemTable 最多使用的内存（字节）
+    pub maxmemory: Option<usize>,
+    /// 达到 maxmemory 之后的淘汰策略
+    pub eviction: EvictionPolicy,
+    /// 每次淘汰时随机采样的 key 的数量，不设置则为 5
+    pub maxmemory_samples: Option<usize>,
+}
+
+/// 令牌桶：每秒补充 rate 个令牌，最多攒 burst 个
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RateLimitConfig {
+    pub rate: f64,
+    pub burst: u32,
+}
+
+/// table 的配额，不设置则不限
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct QuotaConfig {
+    pub max_keys: Option<usize>,
+    pub max_bytes: Option<usize>,
+}
+
+/// 内存淘汰策略
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvictionPolicy {
+    /// 不淘汰，写入直接报错
+    #[default]
+    #[serde(rename = "noeviction")]
+    NoEviction,
+    /// 淘汰最久没有访问的 key
+    Lru,
+    /// 淘汰访问次数最少的 key
+    Lfu,
+    /// 在设置了过期时间的 key 中淘汰最快过期的
+    VolatileTtl,
+}
+
+/// Eval 脚本的资源限制，需要开启 scripting feature
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct ScriptConfig {
+    /// 每个脚本最多使用的堆内存（字节）
+    pub heap_limit: usize,
+    /// 每个脚本最长的执行时间（毫秒）
+    pub timeout: u64,
+}
+
+impl Default for ScriptConfig {
+    fn default() -> Self {
+        Self {
+            heap_limit: 16 * 1024 * 1024,
+            timeout: 1000,
+        }
+    }
+}
+
 impl ServerConfig {
     pub fn load(path: &str) -> Result<Self, KvError> {
         let config = fs::read_to_string(path)?;
@@ -49,6 +178,14 @@ impl ServerConfig {
     }
 }
 
+impl ClientConfig {
+    pub fn load(path: &str) -> Result<Self, KvError> {
+        let config = fs::read_to_string(path)?;
+        let config = toml::from_str(&config)?;
+        Ok(config)
+    }
+}
+
 #[cfg(test)]
 mod config_tests {
     use crate::config::*;
@@ -60,6 +197,63 @@ mod config_tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn server_config_with_http_should_be_loaded() {
+        let config = format!(
+            "{}\n[http]\naddr = '127.0.0.1:8080'\n",
+            include_str!("../fixtures/server.conf")
+        );
+        let config: ServerConfig = toml::from_str(&config).unwrap();
+        assert_eq!(
+            config.http,
+            Some(HttpConfig {
+                addr: "127.0.0.1:8080".into(),
+                ..Default::default()
+            })
+        );
+
+        let config = format!(
+            "{}\n[http]\naddr = '127.0.0.1:8080'\ntokens = {{ secret = 'alice' }}\n",
+            include_str!("../fixtures/server.conf")
+        );
+        let config: ServerConfig = toml::from_str(&config).unwrap();
+        assert_eq!(config.http.unwrap().tokens["secret"], "alice");
+    }
+
+    #[test]
+    fn compression_config_should_be_loaded() {
+        let config = format!(
+            "{}\n[compression]\ncodec = 'zstd'\nlevel = 3\n",
+            include_str!("../fixtures/client.conf")
+        );
+        let config: ClientConfig = toml::from_str(&config).unwrap();
+        assert_eq!(config.compression.codec, Codec::Zstd);
+        assert_eq!(config.compression.level, Some(3));
+        assert_eq!(config.compression.threshold, COMPRESSION_LIMIT);
+    }
+
+    #[test]
+    fn limits_config_should_be_loaded() {
+        let config = format!(
+            "{}\n[limits]\nmaxmemory = 1024\neviction = 'volatile-ttl'\nmaxmemory_samples = 10\n\
+             connection = {{ rate = 10.0, burst = 20 }}\n\
+             [limits.quotas.t1]\nmax_keys = 100\n",
+            include_str!("../fixtures/server.conf")
+        );
+        let config: ServerConfig = toml::from_str(&config).unwrap();
+        let limits = config.limits;
+        assert_eq!(limits.maxmemory, Some(1024));
+        assert_eq!(limits.eviction, EvictionPolicy::VolatileTtl);
+        assert_eq!(limits.maxmemory_samples, Some(10));
+        assert_eq!(limits.connection.unwrap().burst, 20);
+        assert_eq!(limits.principal, None);
+        assert_eq!(limits.quotas["t1"].max_keys, Some(100));
+        assert_eq!(limits.quotas["t1"].max_bytes, None);
+
+        let config: ServerConfig = toml::from_str(include_str!("../fixtures/server.conf")).unwrap();
+        assert_eq!(config.limits, LimitsConfig::default());
+    }
+
     #[test]
     fn client_config_should_be_loaded() {
         let result: Result<ClientConfig, toml::de::Error> =
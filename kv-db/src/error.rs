@@ -22,6 +22,20 @@ pub enum KvError {
     IOError(String),
     #[error("I/O error")]
     IoError(#[from] std::io::Error),
+    #[error("Dump error: {0}")]
+    DumpError(String),
+    #[error("Frame error: {0}")]
+    FrameError(String),
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+    #[error("Out of memory: {0}")]
+    OutOfMemory(String),
+    #[error("Script error: {0}")]
+    ScriptError(String),
     #[error("Internal error: {0}")]
     Internal(String),
 
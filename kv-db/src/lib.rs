@@ -1,3 +1,5 @@
+pub mod backup;
+pub mod bench;
 pub mod config;
 pub mod error;
 pub mod network;
@@ -5,7 +7,7 @@ pub mod pb;
 pub mod service;
 pub mod storage;
 
-use std::time::Duration;
+use std::sync::Arc;
 
 pub use network::*;
 pub use service::*;
@@ -13,14 +15,11 @@ pub use storage::*;
 
 use anyhow::Result;
 use config::{ClientConfig, ServerConfig};
-use network::tls::{TlsClientConnector, TlsServerAcceptor};
-use storage::{memory::MemTable, sled_db::SledDB};
-use tokio::{
-    net::{TcpListener, TcpStream},
-    time,
-};
+use network::tls::{peer_principal, TlsClientConnector, TlsServerAcceptor};
+use storage::{eviction::DEFAULT_SAMPLES, memory::MemTable, quota::QuotaStore, sled_db::SledDB};
+use tokio::net::{TcpListener, TcpStream};
 use tokio_util::compat::FuturesAsyncReadCompatExt as _;
-use tracing::info;
+use tracing::{error, info, warn};
 
 use crate::{multiplex::YamuxCtrl, service_builder::ServiceBuilder};
 
@@ -35,26 +34,64 @@ pub async fn start_client_with_config(
         .map(|(cert, key)| (key.as_str(), cert.as_str()));
     let connector = TlsClientConnector::new(&tls.domain, identity, tls.ca.as_deref())?;
     let stream = TcpStream::connect(addr).await?;
+    // 请求和响应都是小包，关掉 Nagle 算法，否则和 delayed ACK 叠加会让每个请求多出几十毫秒
+    stream.set_nodelay(true)?;
     let stream = connector.connect(stream).await?;
-    Ok(YamuxCtrl::new_client(stream, None))
+    Ok(YamuxCtrl::new_client(stream, None).compression(config.compression.clone()))
 }
 
 /// 通过配置文件创建KV Service
 pub async fn start_server_with_config(config: ServerConfig) -> Result<()> {
+    let listener = TcpListener::bind(&config.general.addr).await?;
+    start_server_with_listener(listener, config).await
+}
+
+/// 在已经绑定好的 listener 上启动 KV Service，忽略配置中的 general.addr
+pub async fn start_server_with_listener(listener: TcpListener, config: ServerConfig) -> Result<()> {
+    let limits = &config.limits;
+    let quotas = limits.quotas.clone();
     match &config.storage {
-        config::StorageConfig::MemTable => start_server(MemTable::default(), config).await?,
-        config::StorageConfig::SledDB(path) => start_server(SledDB::new(path), config).await?,
+        config::StorageConfig::MemTable => {
+            let store = match limits.maxmemory {
+                Some(maxmemory) => MemTable::with_maxmemory(maxmemory, limits.eviction)
+                    .maxmemory_samples(limits.maxmemory_samples.unwrap_or(DEFAULT_SAMPLES)),
+                None => MemTable::default(),
+            };
+            start_server(QuotaStore::new(store, quotas), listener, config).await?
+        }
+        config::StorageConfig::SledDB(path) => {
+            let store = QuotaStore::new(SledDB::new(path), quotas);
+            start_server(store, listener, config).await?
+        }
     };
 
     Ok(())
 }
 
-async fn start_server<Store: Storage>(store: Store, config: ServerConfig) -> Result<()> {
-    let service = ServiceBuilder::new(store).finish();
+async fn start_server<Store: Storage>(
+    store: Store,
+    listener: TcpListener,
+    config: ServerConfig,
+) -> Result<()> {
+    let service = ServiceBuilder::new(store)
+        .rate_limit(&config.limits)
+        .scripting(config.scripting.clone())
+        .finish();
     let tls = &config.tls;
     let tls = TlsServerAcceptor::new(&tls.cert, &tls.key, tls.ca.as_deref())?;
-    let addr = &config.general.addr;
-    let listener = TcpListener::bind(addr).await?;
+    // 配置了 http 就同时启动 HTTP/JSON 网关，和 TCP 共享同一个 Service 和 TLS 配置
+    if let Some(http) = &config.http {
+        let addr = http.addr.clone();
+        let tokens = Arc::new(http.tokens.clone());
+        let (tls, service) = (tls.clone(), service.clone());
+        tokio::spawn(async move {
+            if let Err(e) = http::start_http_gateway(&addr, tls, tokens, service).await {
+                error!("Http gateway on {} exited: {:?}", addr, e);
+            }
+        });
+    }
+    let addr = listener.local_addr()?;
+    let compression = config.compression.clone();
     info!("Start listening on{}", addr);
 
     // loop {
@@ -77,16 +114,37 @@ async fn start_server<Store: Storage>(store: Store, config: ServerConfig) -> Res
     loop {
         let (tcp_stream, addr) = listener.accept().await?;
         info!("Clietn {:?} connected", addr);
+        if let Err(e) = tcp_stream.set_nodelay(true) {
+            warn!("Failed to set TCP_NODELAY for {:?}: {:?}", addr, e);
+        }
         let tls = tls.clone();
         // 使用TLS协议包装TCP
         let service = service.clone();
+        let compression = compression.clone();
         tokio::spawn(async move {
-            let tls_stream = tls.accept(tcp_stream).await.unwrap();
+            // 握手失败只影响这一个连接，记录下来即可
+            let tls_stream = match tls.accept(tcp_stream).await {
+                Ok(tls_stream) => tls_stream,
+                Err(e) => {
+                    warn!("TLS handshake with {:?} failed: {:?}", addr, e);
+                    return;
+                }
+            };
+            let principal = peer_principal(&tls_stream);
+            let bucket = service.limiter.connection_bucket();
             YamuxCtrl::new_server(tls_stream, None, move |stream| {
                 let service = service.clone();
+                let compression = compression.clone();
+                let principal = principal.clone();
+                let bucket = bucket.clone();
                 async move {
-                    let stream = ProstServerStream::new(stream.compat(), service.clone());
-                    stream.process().await.unwrap();
+                    let stream = ProstServerStream::new(stream.compat(), service.clone())
+                        .compression(compression)
+                        .rate_limit(bucket)
+                        .principal(principal);
+                    if let Err(e) = stream.process().await {
+                        error!("Stream of {:?} exited: {:?}", addr, e);
+                    }
                     Ok(())
                 }
             });
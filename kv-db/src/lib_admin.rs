@@ -0,0 +1,158 @@
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use kv_db::{
+    backup::{self, ExportFormat, DUMP_CHUNK_SIZE},
+    config::ClientConfig,
+    multiplex::YamuxCtrl,
+    pb::abi::CommandRequest,
+    sled_db::SledDB,
+    start_client_with_config, ProstClientStream,
+};
+use std::fs::File;
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_util::compat::Compat;
+use tracing::info;
+
+/// kv-db 的备份、恢复、导入导出以及迁移工具
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// 客户端配置文件，默认使用 fixtures/client.conf
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// 覆盖配置文件中的服务器地址
+    #[arg(short, long)]
+    addr: Option<String>,
+
+    #[command(subcommand)]
+    action: Action,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+    /// 导出快照到文件
+    Dump {
+        #[arg(short, long)]
+        out: String,
+        /// 只导出某个 table，默认导出所有 table
+        #[arg(short, long)]
+        table: Option<String>,
+    },
+    /// 从快照文件恢复
+    Restore {
+        #[arg(short, long)]
+        input: String,
+    },
+    /// 把一个 table 导出成 jsonl 或 csv
+    Export {
+        #[arg(short, long)]
+        table: String,
+        #[arg(short, long, default_value = "jsonl")]
+        format: ExportFormat,
+        #[arg(short, long)]
+        out: String,
+    },
+    /// 从 jsonl 或 csv 导入到一个 table
+    Import {
+        #[arg(short, long)]
+        table: String,
+        #[arg(short, long, default_value = "jsonl")]
+        format: ExportFormat,
+        #[arg(short, long)]
+        input: String,
+    },
+    /// 把服务器上的数据迁移到本地 SledDB（sled:<path>）或者另一个服务器（<addr>）
+    Migrate {
+        #[arg(long)]
+        to: String,
+        #[arg(short, long)]
+        table: Option<String>,
+    },
+}
+
+type Client = ProstClientStream<Compat<yamux::Stream>>;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+    let args = Args::parse();
+
+    let mut config = match &args.config {
+        Some(path) => ClientConfig::load(path)?,
+        None => toml::from_str(include_str!("../fixtures/client.conf"))?,
+    };
+    if let Some(addr) = args.addr {
+        config.general.addr = addr;
+    }
+
+    // yamux ctrl 需要一直持有，drop 之后连接就断开了
+    let (_ctrl, mut client) = connect(config.clone()).await?;
+
+    match args.action {
+        Action::Dump { out, table } => {
+            let mut file = tokio::fs::File::create(&out).await?;
+            let cmd = CommandRequest::new_dump(table.unwrap_or_default());
+            let n = client.execute_dump(&cmd, &mut file).await?;
+            info!("Dumped {} bytes to {}", n, out);
+        }
+        Action::Restore { input } => {
+            let mut file = tokio::fs::File::open(&input).await?;
+            let res = client.execute_restore(&mut file).await?;
+            info!("Restored from {}: {:?}", input, res);
+        }
+        Action::Export { table, format, out } => {
+            let res = client.execute(&CommandRequest::new_hgetall(&table)).await?;
+            let n = backup::export(res.pairs, format, File::create(&out)?)?;
+            info!("Exported {} pairs of {} to {}", n, table, out);
+        }
+        Action::Import {
+            table,
+            format,
+            input,
+        } => {
+            let pairs = backup::import(File::open(&input)?, format)?;
+            let n = pairs.len();
+            for pair in pairs {
+                let value = pair.value.unwrap_or_default();
+                let cmd = CommandRequest::new_hset(&table, pair.key, value);
+                client.execute(&cmd).await?;
+            }
+            info!("Imported {} pairs from {} to {}", n, input, table);
+        }
+        Action::Migrate { to, table } => {
+            let cmd = CommandRequest::new_dump(table.unwrap_or_default());
+            match to.strip_prefix("sled:") {
+                Some(path) => {
+                    let mut data = vec![];
+                    client.execute_dump(&cmd, &mut data).await?;
+                    let n = backup::restore(&SledDB::new(path), &data[..])?;
+                    info!("Migrated {} pairs to sled db {}", n, path);
+                }
+                None => {
+                    let mut config = config;
+                    config.general.addr = to.clone();
+                    let (_ctrl, mut target) = connect(config).await?;
+                    // 一边 dump 一边 restore，中间只经过一个管道
+                    let (mut writer, mut reader) = tokio::io::duplex(DUMP_CHUNK_SIZE);
+                    let dump = async {
+                        let n = client.execute_dump(&cmd, &mut writer).await;
+                        drop(writer);
+                        n
+                    };
+                    let (_, res) = tokio::try_join!(dump, target.execute_restore(&mut reader))?;
+                    info!("Migrated to server {}: {:?}", to, res);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn connect(config: ClientConfig) -> Result<(YamuxCtrl<TlsStream<TcpStream>>, Client)> {
+    let mut ctrl = start_client_with_config(config).await?;
+    let client = ctrl.open_stream().await?;
+    Ok((ctrl, client))
+}
@@ -0,0 +1,125 @@
+use anyhow::Result;
+use clap::Parser;
+use kv_db::{
+    bench::{self, Mix, Stats, Workload},
+    config::ClientConfig,
+    start_client_with_config,
+};
+use std::time::{Duration, Instant};
+
+/// kv-db 的压测工具，类似 redis-benchmark；不指定服务器地址时压测本进程中启动的服务器
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// 客户端配置文件，默认使用 fixtures/client.conf
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// 服务器地址，不指定时在本进程中启动一个使用 MemTable 的服务器
+    #[arg(short, long)]
+    addr: Option<String>,
+
+    /// 命令的比例，支持 get / set / hgetall / publish
+    #[arg(short, long, default_value = "get=50,set=50")]
+    mix: Mix,
+
+    /// key 的数量
+    #[arg(short, long, default_value_t = 10000)]
+    keyspace: u64,
+
+    /// value 的大小（字节），可以用逗号分隔多个，超过 COMPRESSION_LIMIT 的 value 会被压缩
+    #[arg(short, long, value_delimiter = ',', default_value = "64")]
+    value_sizes: Vec<usize>,
+
+    /// TCP 连接数
+    #[arg(long, default_value_t = 1)]
+    connections: usize,
+
+    /// 每个连接上并发的 yamux stream 数
+    #[arg(short, long, default_value_t = 16)]
+    streams: usize,
+
+    /// 压测时长（秒）
+    #[arg(short, long, default_value_t = 10)]
+    duration: u64,
+
+    /// 生成请求的随机数种子，同样的种子生成同样的请求序列
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// 不预先写入 keyspace 中的 key
+    #[arg(long)]
+    no_prefill: bool,
+
+    /// 以 JSON 格式输出结果
+    #[arg(long)]
+    json: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // 日志输出到 stderr，stdout 只输出压测结果；本进程中的服务器每个请求都会打 info 日志，这里只保留 warn 以上
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(tracing::Level::WARN)
+        .init();
+    let args = Args::parse();
+
+    let config = match (&args.addr, &args.config) {
+        (None, _) => bench::start_local_server().await?,
+        (Some(addr), path) => {
+            let mut config = match path {
+                Some(path) => ClientConfig::load(path)?,
+                None => toml::from_str(include_str!("../fixtures/client.conf"))?,
+            };
+            config.general.addr = addr.clone();
+            config
+        }
+    };
+    eprintln!("Benchmarking {}", config.general.addr);
+
+    let workload = Workload::new(args.mix, args.keyspace, &args.value_sizes, args.seed);
+
+    // yamux ctrl 需要一直持有，drop 之后连接就断开了
+    let mut ctrls = vec![];
+    for _ in 0..args.connections.max(1) {
+        ctrls.push(start_client_with_config(config.clone()).await?);
+    }
+
+    if workload.needs_prefill() && !args.no_prefill {
+        let mut client = ctrls[0].open_stream().await?;
+        for cmd in workload.prefill() {
+            client.execute(&cmd).await?;
+        }
+        eprintln!("Prefilled {} keys", args.keyspace);
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(args.duration);
+    let start = Instant::now();
+    let mut handles = vec![];
+    for ctrl in ctrls.iter_mut() {
+        for _ in 0..args.streams.max(1) {
+            let mut client = ctrl.open_stream().await?;
+            let workload = workload.clone();
+            // 每个 worker 使用不同的种子
+            let seed = args.seed.wrapping_add(handles.len() as u64 + 1);
+            handles.push(tokio::spawn(async move {
+                bench::run_worker(&mut client, &workload, seed, deadline).await
+            }));
+        }
+    }
+
+    let mut stats = Stats::default();
+    for handle in handles {
+        stats.merge(&handle.await??);
+    }
+    let report = stats.report(start.elapsed());
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report);
+    }
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use crate::{
+    config::{Codec, CompressionConfig},
     error::{IOError, KvError},
     pb::abi::{CommandRequest, CommandResponse},
 };
@@ -11,13 +12,13 @@ use tracing::debug;
 
 // 帧头4字节，Length Prefix Message
 pub const Len_Len: usize = 4;
-// 1bit压缩位
-const COMPRESSIONS_BIT: usize = 1 << 31;
-// 长度31bit，所以最大能支持2G大小的frame
-// const MAX_FRAME: usize = 2 * 1024 * 1024 * 1024;
-const MAX_FRAME: usize = 2 << 31 >> 1;
+// 帧头最高 2bit 存放压缩算法：00 不压缩，10 gzip（兼容之前只有 1bit 压缩位的帧），01 zstd，11 lz4
+const CODEC_SHIFT: usize = 30;
+const CODEC_MASK: usize = 0b11 << CODEC_SHIFT;
+// 长度30bit，所以最大能支持1G大小的frame
+const MAX_FRAME: usize = (1 << CODEC_SHIFT) - 1;
 // 如果payload达到1436字节就做压缩; MTU:1500 - Len_Len:4 - TCP:20 - IP:20 - reserved:20
-const COMPRESSION_LIMIT: usize = 1436;
+pub const COMPRESSION_LIMIT: usize = 1436;
 
 /// 实际应用产品开发的时候，可以直接使用`tokio_util::codec::LengthDelimitedCodec`，功能几乎一样的
 /// [LengthDelimitedCodec](https://docs.rs/tokio-util/0.6.8/tokio_util/codec/length_delimited/index.html)
@@ -25,60 +26,80 @@ pub trait FrameCoder
 where
     Self: Sized + Message + Default,
 {
-    // 将Message封包(encode)成Frame
+    // 将Message封包(encode)成Frame，使用默认的压缩配置
     fn encode_frame(&self, buf: &mut BytesMut) -> Result<(), KvError> {
+        self.encode_frame_with(buf, &CompressionConfig::default())
+    }
+
+    // 将Message封包(encode)成Frame
+    fn encode_frame_with(
+        &self,
+        buf: &mut BytesMut,
+        config: &CompressionConfig,
+    ) -> Result<(), KvError> {
         // return Length of message
         let size = self.encoded_len();
-        buf.put_u32(size as _);
-        // 达到限制，压缩它
-        if size >= COMPRESSION_LIMIT {
-            // cache area
-            let mut msg_cache = Vec::with_capacity(size);
-            self.encode(&mut msg_cache)?;
-
-            // clear LPM, later compressed and merge into it
-            let payload = buf.split_off(Len_Len);
-            buf.clear();
-
-            // process compression for gzip
-            let mut encoder = GzEncoder::new(payload.writer(), Compression::default());
-            encoder.write_all(&msg_cache).to_error()?;
-
-            // done compression.
-            let payload = encoder.finish().to_error()?.into_inner();
-            debug!("Encode a frame: size {}({})", size, payload.len());
-
-            // pushed the compression length
-            buf.put_u32((payload.len() | COMPRESSIONS_BIT) as _);
-
-            // merge BytesMut into buf
-            buf.unsplit(payload);
-        } else {
+        if size > MAX_FRAME {
+            return Err(KvError::FrameError(format!("frame too large: {}", size)));
+        }
+
+        // 没达到限制，不压缩
+        if config.codec == Codec::None || size < config.threshold {
+            buf.put_u32(size as _);
             self.encode(buf)?;
+            return Ok(());
         }
 
+        // cache area
+        let mut msg_cache = Vec::with_capacity(size);
+        self.encode(&mut msg_cache)?;
+
+        // process compression
+        let payload = compress(config.codec, config.level, &msg_cache)?;
+        debug!(
+            "Encode a frame: size {}({}), codec {:?}",
+            size,
+            payload.len(),
+            config.codec
+        );
+
+        // pushed the compression length and codec
+        buf.put_u32((payload.len() | codec_to_bits(config.codec)) as _);
+        buf.put_slice(&payload);
+
         Ok(())
     }
 
-    // 将一个Frame解包(decode)成Message
+    // 将一个Frame解包(decode)成Message，使用默认的压缩配置
     fn decode_frame(buf: &mut BytesMut) -> Result<Self, KvError> {
-        // get 4 byte,And get compressed bit from first bit of it
-        let header = buf.get_u32() as usize;
-        let (len, compressed) = decode_header(header);
-        debug!("Got a frame: msg len {}, compressed {}", len, compressed);
+        Self::decode_frame_with(buf, &CompressionConfig::default())
+    }
 
-        if compressed {
-            // uncompressed
-            let mut decoder = GzDecoder::new(&buf[..len]);
-            let mut msg_buf = Vec::with_capacity(len * 2);
-            decoder.read_to_end(&mut msg_buf).to_error()?;
-            buf.advance(len);
+    // 将一个Frame解包(decode)成Message；解压后的数据超过 max_decompressed 则报错，防止解压炸弹
+    fn decode_frame_with(buf: &mut BytesMut, config: &CompressionConfig) -> Result<Self, KvError> {
+        // get 4 byte,And get codec from first 2 bits of it
+        let header = buf.get_u32() as usize;
+        let (len, codec) = decode_header(header);
+        debug!("Got a frame: msg len {}, codec {:?}", len, codec);
+
+        if len > buf.len() {
+            return Err(KvError::FrameError(format!(
+                "incomplete frame: expect {}, got {}",
+                len,
+                buf.len()
+            )));
+        }
 
-            Ok(Self::decode(&msg_buf[..msg_buf.len()])?)
-        } else {
+        if codec == Codec::None {
             let msg = Self::decode(&buf[..len])?;
             buf.advance(len);
             Ok(msg)
+        } else {
+            // uncompressed
+            let msg_buf = decompress(codec, &buf[..len], config.max_decompressed)?;
+            buf.advance(len);
+
+            Ok(Self::decode(&msg_buf[..])?)
         }
     }
 }
@@ -86,31 +107,116 @@ where
 impl FrameCoder for CommandRequest {}
 impl FrameCoder for CommandResponse {}
 
-/// reading complete frame from stream
-pub async fn read_fame<S>(stream: &mut S, buf: &mut BytesMut) -> Result<(), KvError>
+/// reading complete frame from stream，帧长度超过 max_frame 则报错
+pub async fn read_fame<S>(
+    stream: &mut S,
+    buf: &mut BytesMut,
+    max_frame: usize,
+) -> Result<(), KvError>
 where
     S: AsyncRead + Unpin + Send,
 {
     let header = stream.read_u32().await.to_error()? as usize;
-    let (len, _compressed) = decode_header(header);
+    let (len, _codec) = decode_header(header);
+    if len > max_frame {
+        return Err(KvError::FrameError(format!(
+            "frame length {} exceeds limit {}",
+            len, max_frame
+        )));
+    }
 
     // ensure buffer sufficient of capacity
     buf.reserve(Len_Len + len);
     buf.put_u32(header as _);
-    unsafe {
-        buf.advance_mut(len);
-    }
+    buf.resize(Len_Len + len, 0);
     // read all
     stream.read_exact(&mut buf[Len_Len..]).await.to_error()?;
 
     Ok(())
 }
 
+/// 查看 buf 中 frame 使用的压缩算法
+pub fn frame_codec(buf: &[u8]) -> Option<Codec> {
+    let header: [u8; Len_Len] = buf.get(..Len_Len)?.try_into().ok()?;
+    let (_, codec) = decode_header(u32::from_be_bytes(header) as usize);
+    Some(codec)
+}
+
+#[inline]
+fn decode_header(header: usize) -> (usize, Codec) {
+    let codec = codec_from_bits(header & CODEC_MASK);
+    let len = header & !CODEC_MASK;
+    (len, codec)
+}
+
+#[inline]
+fn codec_to_bits(codec: Codec) -> usize {
+    let id = match codec {
+        Codec::None => 0b00,
+        Codec::Gzip => 0b10,
+        Codec::Zstd => 0b01,
+        Codec::Lz4 => 0b11,
+    };
+    id << CODEC_SHIFT
+}
+
 #[inline]
-fn decode_header(header: usize) -> (usize, bool) {
-    let compressed = header & COMPRESSIONS_BIT == COMPRESSIONS_BIT;
-    let len = header & !COMPRESSIONS_BIT;
-    (len, compressed)
+fn codec_from_bits(bits: usize) -> Codec {
+    match bits >> CODEC_SHIFT {
+        0b10 => Codec::Gzip,
+        0b01 => Codec::Zstd,
+        0b11 => Codec::Lz4,
+        _ => Codec::None,
+    }
+}
+
+fn compress(codec: Codec, level: Option<i32>, data: &[u8]) -> Result<Vec<u8>, KvError> {
+    match codec {
+        Codec::None => Ok(data.to_vec()),
+        Codec::Gzip => {
+            let level = level.map_or_else(Compression::default, |l| {
+                Compression::new(l.clamp(0, 9) as _)
+            });
+            let mut encoder = GzEncoder::new(Vec::with_capacity(data.len()), level);
+            encoder.write_all(data).to_error()?;
+            encoder.finish().to_error()
+        }
+        Codec::Zstd => {
+            let level = level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL);
+            zstd::stream::encode_all(data, level).to_error()
+        }
+        Codec::Lz4 => {
+            let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::with_capacity(data.len()));
+            encoder.write_all(data).to_error()?;
+            encoder
+                .finish()
+                .map_err(|e| KvError::FrameError(format!("lz4: {}", e)))
+        }
+    }
+}
+
+fn decompress(codec: Codec, data: &[u8], max: usize) -> Result<Vec<u8>, KvError> {
+    let decoder: Box<dyn Read + '_> = match codec {
+        Codec::None => Box::new(data),
+        Codec::Gzip => Box::new(GzDecoder::new(data)),
+        Codec::Zstd => Box::new(zstd::stream::read::Decoder::new(data).to_error()?),
+        Codec::Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(data)),
+    };
+
+    // 最多只读 max + 1 字节，读满了说明解压后的数据超出限制
+    let mut msg_buf = Vec::with_capacity((data.len() * 2).min(max));
+    decoder
+        .take(max as u64 + 1)
+        .read_to_end(&mut msg_buf)
+        .to_error()?;
+    if msg_buf.len() > max {
+        return Err(KvError::FrameError(format!(
+            "decompressed frame exceeds limit {}",
+            max
+        )));
+    }
+
+    Ok(msg_buf)
 }
 
 #[cfg(test)]
@@ -120,11 +226,13 @@ mod frame_tests {
     use bytes::{Bytes, BytesMut};
 
     use crate::{
+        config::{Codec, CompressionConfig},
+        error::KvError,
         network::frame::COMPRESSION_LIMIT,
         pb::abi::{CommandRequest, CommandResponse, Value},
     };
 
-    use super::FrameCoder;
+    use super::{frame_codec, FrameCoder};
 
     #[test]
     fn command_request_encode_decode_should_work() -> Result<()> {
@@ -155,6 +263,68 @@ mod frame_tests {
         Ok(())
     }
 
+    #[test]
+    fn all_codecs_encode_decode_should_work() -> Result<()> {
+        let value: Value = Bytes::from(vec![0u8; COMPRESSION_LIMIT + 1]).into();
+        let cmd: CommandResponse = value.into();
+
+        for codec in [Codec::None, Codec::Gzip, Codec::Zstd, Codec::Lz4] {
+            let config = CompressionConfig {
+                codec,
+                ..Default::default()
+            };
+            let mut buf = BytesMut::new();
+            cmd.encode_frame_with(&mut buf, &config)?;
+            assert_eq!(frame_codec(&buf), Some(codec));
+            assert_eq!(
+                is_compressed(&buf),
+                codec == Codec::Gzip || codec == Codec::Lz4
+            );
+
+            let cmd1 = CommandResponse::decode_frame(&mut buf)?;
+            assert_eq!(cmd1, cmd);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn threshold_should_be_configurable() -> Result<()> {
+        let config = CompressionConfig {
+            codec: Codec::Zstd,
+            threshold: 16,
+            ..Default::default()
+        };
+        let mut buf = BytesMut::new();
+        let cmd = CommandRequest::new_hset("t3", "key3", "test frame");
+        cmd.encode_frame_with(&mut buf, &config)?;
+        assert_eq!(frame_codec(&buf), Some(Codec::Zstd));
+
+        let cmd1 = CommandRequest::decode_frame(&mut buf)?;
+        assert_eq!(cmd1, cmd);
+
+        Ok(())
+    }
+
+    #[test]
+    fn decompression_bomb_should_be_rejected() -> Result<()> {
+        let value: Value = Bytes::from(vec![0u8; 1024 * 1024]).into();
+        let cmd: CommandResponse = value.into();
+        let mut buf = BytesMut::new();
+        cmd.encode_frame(&mut buf)?;
+        // 1M 的 0 压缩后只有 1k 左右
+        assert!(buf.len() < 4096);
+
+        let config = CompressionConfig {
+            max_decompressed: 64 * 1024,
+            ..Default::default()
+        };
+        let result = CommandResponse::decode_frame_with(&mut buf, &config);
+        assert!(matches!(result, Err(KvError::FrameError(_))));
+
+        Ok(())
+    }
+
     fn is_compressed(data: &[u8]) -> bool {
         if let &[v] = &data[..1] {
             v >> 7 == 1
@@ -167,11 +337,10 @@ mod frame_tests {
 #[cfg(test)]
 mod fn_test_of_read_fame {
     use bytes::BytesMut;
-    use tokio::io::AsyncRead;
 
-    use crate::{pb::abi::CommandRequest, test_utils::DummyStream};
+    use crate::{error::KvError, pb::abi::CommandRequest, test_utils::DummyStream};
 
-    use super::{read_fame, FrameCoder};
+    use super::{read_fame, FrameCoder, MAX_FRAME};
 
     #[tokio::test]
     async fn read_frame_should_work() {
@@ -183,10 +352,24 @@ mod fn_test_of_read_fame {
 
         // get request frame form stream
         let mut data = BytesMut::new();
-        read_fame(&mut stream, &mut data).await.unwrap();
+        read_fame(&mut stream, &mut data, MAX_FRAME).await.unwrap();
 
         let cmd1 = CommandRequest::decode_frame(&mut data).unwrap();
 
         assert_eq!(cmd, cmd1)
     }
+
+    #[tokio::test]
+    async fn read_frame_too_large_should_fail() {
+        let mut buf = BytesMut::new();
+        let cmd = CommandRequest::new_hset("t1", "k2", "a long enough value");
+        cmd.encode_frame(&mut buf).unwrap();
+        let mut stream = DummyStream { buf };
+
+        // 长度头超过限制，不应该再去读取数据
+        let mut data = BytesMut::new();
+        let result = read_fame(&mut stream, &mut data, 8).await;
+        assert!(matches!(result, Err(KvError::FrameError(_))));
+        assert!(data.is_empty());
+    }
 }
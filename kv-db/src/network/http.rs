@@ -0,0 +1,466 @@
+use crate::{
+    error::KvError,
+    limit::ConnectionBucket,
+    pb::abi::{CommandRequest, CommandResponse, Value},
+    tls::{peer_principal, TlsServerAcceptor},
+    Service, Storage,
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Request, State,
+    },
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use futures::{Stream, StreamExt};
+use hyper::server::conn::http1;
+use hyper_util::{rt::TokioIo, service::TowerToHyperService};
+use std::{collections::HashMap, convert::Infallible, sync::Arc};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+/// <Bearer token, 认证主体>
+pub type Tokens = Arc<HashMap<String, String>>;
+
+/// 网关上一个连接的认证主体和令牌桶，和 TCP 监听上的 ProstServerStream 一样，
+/// 同一个连接上的请求共享令牌桶，同一个主体的所有连接共享主体的令牌桶
+#[derive(Debug, Clone, Default)]
+pub struct HttpConnection {
+    /// 来自客户端证书
+    pub principal: Option<String>,
+    pub bucket: Option<ConnectionBucket>,
+}
+
+/// HTTP 网关：把 JSON 形式的 CommandRequest 转交给同一个 Service 处理，
+/// 这样 Service 上注册的各种事件（on_received/on_executed/on_before_send）也同样生效
+///
+/// - `POST /cmd`：body 为 JSON 形式的 CommandRequest，返回 CommandResponse
+/// - `GET /tables/:t`：HGETALL
+/// - `GET|PUT|DELETE /tables/:t/keys/:k`：HGET / HSET（body 为 JSON 形式的 Value）/ HDEL
+/// - `POST /topics/:topic`：PUBLISH，body 为 JSON 形式的 Value 数组
+/// - `GET /topics/:topic/ws`、`GET /topics/:topic/sse`：SUBSCRIBE，持续推送 CommandResponse
+///
+/// tokens 不为空时要求 Bearer token 认证，所有请求都经过 Service 的限流
+pub fn router<Store: Storage>(service: Service<Store>, tokens: Tokens) -> Router {
+    Router::new()
+        .route("/cmd", post(cmd_handler::<Store>))
+        .route("/tables/:table", get(hgetall_handler::<Store>))
+        .route(
+            "/tables/:table/keys/:key",
+            get(hget_handler::<Store>)
+                .put(hset_handler::<Store>)
+                .delete(hdel_handler::<Store>),
+        )
+        .route("/topics/:topic", post(publish_handler::<Store>))
+        .route("/topics/:topic/ws", get(ws_handler::<Store>))
+        .route("/topics/:topic/sse", get(sse_handler::<Store>))
+        .layer(middleware::from_fn_with_state(
+            (service.clone(), tokens),
+            guard::<Store>,
+        ))
+        .with_state(service)
+}
+
+/// 在 addr 上启动 HTTPS 网关，tls 和 TCP 监听是同一份配置
+pub async fn start_http_gateway<Store: Storage>(
+    addr: &str,
+    tls: TlsServerAcceptor,
+    tokens: Tokens,
+    service: Service<Store>,
+) -> Result<(), KvError> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Start http gateway on {}", addr);
+    serve(listener, tls.with_alpn(&["http/1.1"]), tokens, service).await
+}
+
+async fn serve<Store: Storage>(
+    listener: TcpListener,
+    tls: TlsServerAcceptor,
+    tokens: Tokens,
+    service: Service<Store>,
+) -> Result<(), KvError> {
+    let app = router(service.clone(), tokens);
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        let (tls, app, service) = (tls.clone(), app.clone(), service.clone());
+        tokio::spawn(async move {
+            let stream = match tls.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("Http client {:?} failed to handshake: {:?}", addr, e);
+                    return;
+                }
+            };
+            let connection = HttpConnection {
+                principal: peer_principal(&stream),
+                bucket: service.limiter.connection_bucket(),
+            };
+            let app = TowerToHyperService::new(app.layer(Extension(connection)));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), app)
+                .with_upgrades()
+                .await
+            {
+                info!("Http client {:?} disconnected: {:?}", addr, e);
+            }
+        });
+    }
+}
+
+/// 认证并限流，和 ProstServerStream::process 处理每个命令前做的一样
+async fn guard<Store: Storage>(
+    State((service, tokens)): State<(Service<Store>, Tokens)>,
+    connection: Option<Extension<HttpConnection>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let connection = connection.map(|Extension(c)| c).unwrap_or_default();
+    let mut principal = connection.principal;
+    if !tokens.is_empty() {
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match token.and_then(|token| tokens.get(token)) {
+            Some(name) => principal = Some(name.clone()),
+            None => {
+                let e = KvError::Unauthorized("missing or invalid bearer token".into());
+                return CommandResponse::from(e).into_response();
+            }
+        }
+    }
+
+    let limiter = &service.limiter;
+    if let Err(e) = limiter.acquire(connection.bucket.as_ref(), principal.as_deref()) {
+        return CommandResponse::from(e).into_response();
+    }
+    next.run(req).await
+}
+
+/// 把 CommandResponse 转换成 HTTP 响应，HTTP 状态码复用 CommandResponse 的 status
+impl IntoResponse for CommandResponse {
+    fn into_response(self) -> Response {
+        let status =
+            StatusCode::from_u16(self.status as _).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, Json(self)).into_response()
+    }
+}
+
+/// 执行非流式的命令，只取第一个 CommandResponse
+async fn execute<Store: Storage>(service: &Service<Store>, cmd: CommandRequest) -> Response {
+    match service.execute(cmd).next().await {
+        Some(resp) => resp.as_ref().clone().into_response(),
+        None => CommandResponse::from(KvError::Internal("Didn't get any response".into()))
+            .into_response(),
+    }
+}
+
+async fn cmd_handler<Store: Storage>(
+    State(service): State<Service<Store>>,
+    Json(cmd): Json<CommandRequest>,
+) -> Response {
+    execute(&service, cmd).await
+}
+
+async fn hgetall_handler<Store: Storage>(
+    State(service): State<Service<Store>>,
+    Path(table): Path<String>,
+) -> Response {
+    execute(&service, CommandRequest::new_hgetall(table)).await
+}
+
+async fn hget_handler<Store: Storage>(
+    State(service): State<Service<Store>>,
+    Path((table, key)): Path<(String, String)>,
+) -> Response {
+    execute(&service, CommandRequest::new_hget(table, key)).await
+}
+
+async fn hset_handler<Store: Storage>(
+    State(service): State<Service<Store>>,
+    Path((table, key)): Path<(String, String)>,
+    Json(value): Json<Value>,
+) -> Response {
+    execute(&service, CommandRequest::new_hset(table, key, value)).await
+}
+
+async fn hdel_handler<Store: Storage>(
+    State(service): State<Service<Store>>,
+    Path((table, key)): Path<(String, String)>,
+) -> Response {
+    execute(&service, CommandRequest::new_hdel(table, key)).await
+}
+
+async fn publish_handler<Store: Storage>(
+    State(service): State<Service<Store>>,
+    Path(topic): Path<String>,
+    Json(data): Json<Vec<Value>>,
+) -> Response {
+    execute(&service, CommandRequest::new_publish(&topic, data)).await
+}
+
+/// 订阅主题，第一条消息是 subscription id，之后是 publish 的数据
+fn subscribe<Store: Storage>(
+    service: &Service<Store>,
+    topic: &str,
+) -> impl Stream<Item = Arc<CommandResponse>> + Send {
+    service.execute(CommandRequest::new_subscribe(topic))
+}
+
+async fn ws_handler<Store: Storage>(
+    State(service): State<Service<Store>>,
+    Path(topic): Path<String>,
+    ws: WebSocketUpgrade,
+) -> Response {
+    ws.on_upgrade(move |socket| ws_subscribe(socket, service, topic))
+}
+
+async fn ws_subscribe<Store: Storage>(
+    mut socket: WebSocket,
+    service: Service<Store>,
+    topic: String,
+) {
+    let mut stream = Box::pin(subscribe(&service, &topic));
+    loop {
+        tokio::select! {
+            data = stream.next() => {
+                let Some(data) = data else { break };
+                let text = match serde_json::to_string(data.as_ref()) {
+                    Ok(text) => text,
+                    Err(e) => {
+                        warn!("Failed to serialize response: {:?}", e);
+                        continue;
+                    }
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            // 客户端关闭连接后停止推送；drop 掉 stream 后，下一次 publish 时订阅会被清理
+            msg = socket.recv() => match msg {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                _ => {}
+            }
+        }
+    }
+    info!("WebSocket subscriber of {} disconnected", topic);
+}
+
+async fn sse_handler<Store: Storage>(
+    State(service): State<Service<Store>>,
+    Path(topic): Path<String>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let stream = subscribe(&service, &topic).map(|data| {
+        Ok(Event::default()
+            .json_data(data.as_ref())
+            .unwrap_or_else(|_| Event::default().event("error")))
+    });
+    Sse::new(stream)
+}
+
+#[cfg(test)]
+mod http_tests {
+    use super::*;
+    use crate::{
+        assert_res_ok,
+        config::{LimitsConfig, RateLimitConfig},
+        service_builder::ServiceBuilder,
+        tls::TlsClientConnector,
+    };
+    use axum::{
+        body::{to_bytes, Body},
+        http::Method,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tower::ServiceExt;
+
+    async fn call(app: &Router, method: Method, uri: &str, body: String) -> CommandResponse {
+        call_with_token(app, method, uri, body, None).await
+    }
+
+    async fn call_with_token(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        body: String,
+        token: Option<&str>,
+    ) -> CommandResponse {
+        let mut req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json");
+        if let Some(token) = token {
+            req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let req = req.body(Body::from(body)).unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        let body = to_bytes(res.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn post_cmd_should_work() {
+        let service: Service = ServiceBuilder::default().finish();
+        let app = router(service, Tokens::default());
+
+        let cmd = CommandRequest::new_hset("t1", "k1", "v1");
+        let body = serde_json::to_string(&cmd).unwrap();
+        let res = call(&app, Method::POST, "/cmd", body).await;
+        assert_res_ok(&res, &[Value::default()], &[]);
+
+        let cmd = CommandRequest::new_hget("t1", "k1");
+        let body = serde_json::to_string(&cmd).unwrap();
+        let res = call(&app, Method::POST, "/cmd", body).await;
+        assert_res_ok(&res, &["v1".into()], &[]);
+    }
+
+    #[tokio::test]
+    async fn rest_routes_should_work() {
+        let service: Service = ServiceBuilder::default().finish();
+        let app = router(service, Tokens::default());
+
+        let value: Value = 42.into();
+        let body = serde_json::to_string(&value).unwrap();
+        let res = call(&app, Method::PUT, "/tables/t1/keys/k1", body).await;
+        assert_res_ok(&res, &[Value::default()], &[]);
+
+        let res = call(&app, Method::GET, "/tables/t1/keys/k1", "".into()).await;
+        assert_res_ok(&res, std::slice::from_ref(&value), &[]);
+
+        let res = call(&app, Method::DELETE, "/tables/t1/keys/k1", "".into()).await;
+        assert_res_ok(&res, &[value], &[]);
+
+        let res = call(&app, Method::GET, "/tables/t1/keys/k1", "".into()).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND.as_u16() as u32);
+    }
+
+    #[tokio::test]
+    async fn sse_subscribe_should_work() {
+        let service: Service = ServiceBuilder::default().finish();
+        let app = router(service.clone(), Tokens::default());
+
+        let req = Request::get("/topics/lobby/sse")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.clone().oneshot(req).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let mut body = res.into_body().into_data_stream();
+
+        // 第一条是 subscription id
+        let id = body.next().await.unwrap().unwrap();
+        assert!(String::from_utf8_lossy(&id).starts_with("data:"));
+
+        let data: Value = "hello".into();
+        let body_str = serde_json::to_string(&vec![data.clone()]).unwrap();
+        call(&app, Method::POST, "/topics/lobby", body_str).await;
+
+        let event = body.next().await.unwrap().unwrap();
+        let event = String::from_utf8_lossy(&event);
+        let json = event.trim().trim_start_matches("data:");
+        let res: CommandResponse = serde_json::from_str(json).unwrap();
+        assert_res_ok(&res, &[data], &[]);
+    }
+
+    #[tokio::test]
+    async fn bearer_token_should_be_required() {
+        let service: Service = ServiceBuilder::default().finish();
+        let tokens = Arc::new(HashMap::from([("secret".to_string(), "alice".to_string())]));
+        let app = router(service, tokens);
+
+        let uri = "/tables/t1/keys/k1";
+        let res = call(&app, Method::GET, uri, "".into()).await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED.as_u16() as u32);
+        let res = call_with_token(&app, Method::GET, uri, "".into(), Some("wrong")).await;
+        assert_eq!(res.status, StatusCode::UNAUTHORIZED.as_u16() as u32);
+        let res = call_with_token(&app, Method::GET, uri, "".into(), Some("secret")).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND.as_u16() as u32);
+    }
+
+    #[tokio::test]
+    async fn rate_limit_should_apply_to_gateway() {
+        let limits = LimitsConfig {
+            principal: Some(RateLimitConfig {
+                rate: 0.0,
+                burst: 1,
+            }),
+            ..Default::default()
+        };
+        let service: Service = ServiceBuilder::default().rate_limit(&limits).finish();
+        let tokens = Arc::new(HashMap::from([
+            ("t-alice".to_string(), "alice".to_string()),
+            ("t-bob".to_string(), "bob".to_string()),
+        ]));
+        let app = router(service, tokens);
+
+        let uri = "/tables/t1/keys/k1";
+        let res = call_with_token(&app, Method::GET, uri, "".into(), Some("t-alice")).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND.as_u16() as u32);
+        let res = call_with_token(&app, Method::GET, uri, "".into(), Some("t-alice")).await;
+        assert_eq!(res.status, StatusCode::TOO_MANY_REQUESTS.as_u16() as u32);
+        let res = call_with_token(&app, Method::GET, uri, "".into(), Some("t-bob")).await;
+        assert_eq!(res.status, StatusCode::NOT_FOUND.as_u16() as u32);
+    }
+
+    const CA_CERT: &str = include_str!("../../fixtures/ca.cert");
+    const CLIENT_CERT: &str = include_str!("../../fixtures/client.cert");
+    const CLIENT_KEY: &str = include_str!("../../fixtures/client.key");
+    const SERVER_CERT: &str = include_str!("../../fixtures/server.cert");
+    const SERVER_KEY: &str = include_str!("../../fixtures/server.key");
+
+    /// 通过 TLS 发送一个 HTTP/1.1 请求，返回整个响应
+    async fn https_get(
+        addr: std::net::SocketAddr,
+        identity: Option<(&str, &str)>,
+        uri: &str,
+    ) -> Result<String, KvError> {
+        let connector = TlsClientConnector::new("kvserver.acme.inc", identity, Some(CA_CERT))?;
+        let stream = tokio::net::TcpStream::connect(addr).await?;
+        let mut stream = connector.connect(stream).await?;
+        let req = format!(
+            "GET {} HTTP/1.1\r\nHost: kvserver.acme.inc\r\nConnection: close\r\n\r\n",
+            uri
+        );
+        stream.write_all(req.as_bytes()).await?;
+        let mut res = String::new();
+        stream.read_to_string(&mut res).await?;
+        Ok(res)
+    }
+
+    #[tokio::test]
+    async fn gateway_should_require_client_cert() {
+        let limits = LimitsConfig {
+            principal: Some(RateLimitConfig {
+                rate: 0.0,
+                burst: 1,
+            }),
+            ..Default::default()
+        };
+        let service: Service = ServiceBuilder::default().rate_limit(&limits).finish();
+        let tls = TlsServerAcceptor::new(SERVER_CERT, SERVER_KEY, Some(CA_CERT)).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, tls, Tokens::default(), service));
+
+        let identity = Some((CLIENT_CERT, CLIENT_KEY));
+        let res = https_get(addr, identity, "/tables/t1/keys/k1")
+            .await
+            .unwrap();
+        assert!(res.starts_with("HTTP/1.1 404"), "{}", res);
+        // 证书的 CN 是认证主体，它的令牌桶已经用完
+        let res = https_get(addr, identity, "/tables/t1/keys/k1")
+            .await
+            .unwrap();
+        assert!(res.starts_with("HTTP/1.1 429"), "{}", res);
+
+        // 没有客户端证书
+        assert!(https_get(addr, None, "/tables/t1/keys/k1").await.is_err());
+    }
+}
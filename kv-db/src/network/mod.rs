@@ -1,23 +1,44 @@
 mod frame;
+pub mod http;
 pub mod multiplex;
 pub mod stream;
 pub mod stream_result;
 pub mod tls;
 
+pub use frame::{FrameCoder, COMPRESSION_LIMIT};
+
 use self::{stream::ProstStream, stream_result::StreamResult};
 use crate::{
+    backup::DUMP_CHUNK_SIZE,
+    config::CompressionConfig,
     error::KvError,
-    pb::abi::{CommandRequest, CommandResponse},
+    limit::ConnectionBucket,
+    pb::abi::{command_request::RequestData, value, CommandRequest, CommandResponse},
     Service, Storage,
 };
+use bytes::{Bytes, BytesMut};
 use futures::{SinkExt, StreamExt};
-use tokio::io::{AsyncRead, AsyncWrite};
+use hyper::StatusCode;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tracing::info;
 
 /// S: 各种协议。protocol: TPC UDP WS HTTP TLS and Customize
 pub struct ProstServerStream<S, DB> {
     stream: ProstStream<S, CommandRequest, CommandResponse>,
     service: Service<DB>,
+    /// 这个连接的令牌桶
+    bucket: Option<ConnectionBucket>,
+    /// 认证主体，来自客户端证书
+    principal: Option<String>,
+    /// 分块 Restore 最多接收的字节数
+    max_restore: usize,
+}
+
+/// 正在接收的分块 Restore
+enum ChunkedRestore {
+    Receiving(BytesMut),
+    /// 已经返回了错误，丢弃这次 Restore 剩下的块
+    Discarding,
 }
 
 pub struct ProstClientStream<S> {
@@ -31,14 +52,93 @@ where
 {
     pub fn new(stream: S, service: Service<D>) -> Self {
         Self {
-            stream: ProstStream::new(stream),
+            stream: ProstStream::new(stream).follow_peer_codec(),
+            bucket: service.limiter.connection_bucket(),
             service,
+            principal: None,
+            max_restore: CompressionConfig::default().max_restore,
         }
     }
 
+    /// 使用指定的令牌桶，这样同一个 TCP 连接上的所有 yamux stream 共享限流
+    pub fn rate_limit(mut self, bucket: Option<ConnectionBucket>) -> Self {
+        self.bucket = bucket;
+        self
+    }
+
+    /// 设置认证主体，同一个主体的所有连接共享限流
+    pub fn principal(mut self, principal: Option<String>) -> Self {
+        self.principal = principal;
+        self
+    }
+
+    /// 设置 frame 的压缩配置
+    pub fn compression(mut self, config: CompressionConfig) -> Self {
+        self.max_restore = config.max_restore;
+        self.stream.set_compression(config);
+        self
+    }
+
     pub async fn process(mut self) -> Result<(), KvError> {
+        let mut restore: Option<ChunkedRestore> = None;
         while let Some(Ok(cmd)) = self.stream.next().await {
             info!("Got a new command: {:?}", cmd);
+            // 每个 frame 都要限流，包括分块 Restore 中间的块
+            let limited = self
+                .service
+                .limiter
+                .acquire(self.bucket.as_ref(), self.principal.as_deref());
+            let cmd = match cmd.request_data {
+                Some(RequestData::Restore(chunk)) if chunk.more || restore.is_some() => {
+                    // 一次分块 Restore 只有一个响应：出错时立即返回，之后的块都丢弃
+                    let mut data = match restore.take() {
+                        Some(ChunkedRestore::Receiving(data)) => data,
+                        Some(ChunkedRestore::Discarding) => {
+                            if chunk.more {
+                                restore = Some(ChunkedRestore::Discarding);
+                            }
+                            continue;
+                        }
+                        None => BytesMut::new(),
+                    };
+                    let error = match limited {
+                        Err(e) => Some(e),
+                        Ok(()) if data.len() + chunk.data.len() > self.max_restore => {
+                            Some(KvError::DumpError(format!(
+                                "Restore data exceeds {} bytes",
+                                self.max_restore
+                            )))
+                        }
+                        Ok(()) => None,
+                    };
+                    if let Some(e) = error {
+                        self.stream.send(&CommandResponse::from(e)).await?;
+                        if chunk.more {
+                            restore = Some(ChunkedRestore::Discarding);
+                        }
+                        continue;
+                    }
+
+                    data.extend_from_slice(&chunk.data);
+                    if chunk.more {
+                        restore = Some(ChunkedRestore::Receiving(data));
+                        continue;
+                    }
+                    CommandRequest::new_restore(data.freeze())
+                }
+                request_data => {
+                    // 分块 Restore 还没结束就收到了别的命令，放弃这次 Restore
+                    if let Some(ChunkedRestore::Receiving(_)) = restore.take() {
+                        let e = KvError::DumpError("Restore is interrupted".into());
+                        self.stream.send(&CommandResponse::from(e)).await?;
+                    }
+                    if let Err(e) = limited {
+                        self.stream.send(&CommandResponse::from(e)).await?;
+                        continue;
+                    }
+                    CommandRequest { request_data }
+                }
+            };
             let mut res = self.service.execute(cmd);
             // let res = res.next().await.unwrap().as_ref().to_owned();
             while let Some(data) = res.next().await {
@@ -60,6 +160,12 @@ where
         }
     }
 
+    /// 设置 frame 的压缩配置
+    pub fn compression(mut self, config: CompressionConfig) -> Self {
+        self.stream.set_compression(config);
+        self
+    }
+
     pub async fn execute(&mut self, cmd: &CommandRequest) -> Result<CommandResponse, KvError> {
         self.stream.send(&cmd).await?;
 
@@ -69,6 +175,62 @@ where
         }
     }
 
+    /// 执行 Dump 命令，把分块收到的快照依次写入 writer，返回写入的字节数
+    pub async fn execute_dump<W>(
+        &mut self,
+        cmd: &CommandRequest,
+        writer: &mut W,
+    ) -> Result<u64, KvError>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        self.stream.send(cmd).await?;
+
+        let mut written = 0;
+        loop {
+            let res = match self.stream.next().await {
+                Some(v) => v?,
+                None => return Err(KvError::Internal("Dump is interrupted".into())),
+            };
+            if res.status != StatusCode::OK.as_u16() as u32 {
+                return Err(KvError::DumpError(res.message));
+            }
+            // 不带 values 的响应表示快照结束
+            match res.values.first().and_then(|v| v.value.as_ref()) {
+                Some(value::Value::Binary(data)) => {
+                    writer.write_all(data).await?;
+                    written += data.len() as u64;
+                }
+                _ => break,
+            }
+        }
+        writer.flush().await?;
+
+        Ok(written)
+    }
+
+    /// 把 reader 中的快照分块发送给服务器恢复，只有最后一块有响应
+    pub async fn execute_restore<R>(&mut self, reader: &mut R) -> Result<CommandResponse, KvError>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut buf = vec![0u8; DUMP_CHUNK_SIZE];
+        loop {
+            let n = read_full(reader, &mut buf).await?;
+            let more = n == buf.len();
+            let cmd = CommandRequest::new_restore_chunk(Bytes::copy_from_slice(&buf[..n]), more);
+            self.stream.send(&cmd).await?;
+            if !more {
+                break;
+            }
+        }
+
+        match self.stream.next().await {
+            Some(v) => v,
+            None => Err(KvError::Internal("Restore is interrupted".into())),
+        }
+    }
+
     pub async fn execute_streaming(
         self,
         cmd: &CommandRequest,
@@ -81,10 +243,27 @@ where
     }
 }
 
+/// 尽量读满 buf，返回读到的字节数，小于 buf 的长度说明已经读完
+async fn read_full<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match reader.read(&mut buf[n..]).await? {
+            0 => break,
+            len => n += len,
+        }
+    }
+    Ok(n)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{assert_res_ok, pb::abi::Value, service_builder::ServiceBuilder};
+    use crate::{
+        assert_res_ok,
+        config::{Codec, LimitsConfig, RateLimitConfig},
+        pb::abi::Value,
+        service_builder::ServiceBuilder,
+    };
     use anyhow::Result;
     use bytes::Bytes;
     use std::net::SocketAddr;
@@ -134,15 +313,222 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn client_server_negotiated_codec_should_work() -> anyhow::Result<()> {
+        let addr = start_server().await?;
+
+        let stream = TcpStream::connect(addr).await?;
+        let config = CompressionConfig {
+            codec: Codec::Lz4,
+            ..Default::default()
+        };
+        let mut client = ProstClientStream::new(stream).compression(config);
+
+        // 服务器默认使用 gzip，收到 lz4 的 frame 后改用 lz4 回复
+        let v: Value = Bytes::from(vec![0u8; 16384]).into();
+        let cmd = CommandRequest::new_hset("t3", "k3", v.clone());
+        client.execute(&cmd).await?;
+
+        let cmd = CommandRequest::new_hget("t3", "k3");
+        let res = client.execute(&cmd).await?;
+        assert_res_ok(&res, &[v], &[]);
+        assert_eq!(client.stream.peer_codec(), Some(Codec::Lz4));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_server_dump_should_work() -> anyhow::Result<()> {
+        let addr = start_server().await?;
+
+        let stream = TcpStream::connect(addr).await?;
+        let mut client = ProstClientStream::new(stream);
+        client
+            .execute(&CommandRequest::new_hset("t1", "k1", "v1"))
+            .await?;
+
+        let mut data = vec![];
+        let n = client
+            .execute_dump(&CommandRequest::new_dump("t1"), &mut data)
+            .await?;
+        assert_eq!(n as usize, data.len());
+
+        // dump 结束后 stream 还能继续使用
+        let res = client.execute(&CommandRequest::new_restore(data)).await?;
+        assert_res_ok(&res, &[1.into()], &[]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_server_chunked_restore_should_work() -> anyhow::Result<()> {
+        let addr = start_server().await?;
+        let stream = TcpStream::connect(addr).await?;
+        let mut client = ProstClientStream::new(stream);
+        let v1: Value = Bytes::from(vec![1u8; 100 * 1024]).into();
+        let v2: Value = Bytes::from(vec![2u8; 100 * 1024]).into();
+        client
+            .execute(&CommandRequest::new_hset("t1", "k1", v1.clone()))
+            .await?;
+        client
+            .execute(&CommandRequest::new_hset("t1", "k2", v2.clone()))
+            .await?;
+        let mut data = vec![];
+        client
+            .execute_dump(&CommandRequest::new_dump("t1"), &mut data)
+            .await?;
+
+        // 目标服务器的 frame 上限比整个快照小，只能分块恢复
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let target = listener.local_addr()?;
+        let service: Service = ServiceBuilder::default().finish();
+        let config = CompressionConfig {
+            max_frame: 128 * 1024,
+            ..Default::default()
+        };
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let server =
+                    ProstServerStream::new(stream, service.clone()).compression(config.clone());
+                tokio::spawn(server.process());
+            }
+        });
+
+        let stream = TcpStream::connect(target).await?;
+        let mut client = ProstClientStream::new(stream);
+        assert!(data.len() > 128 * 1024);
+        let res = client.execute_restore(&mut &data[..]).await?;
+        assert_res_ok(&res, &[2.into()], &[]);
+        let res = client
+            .execute(&CommandRequest::new_hget("t1", "k2"))
+            .await?;
+        assert_res_ok(&res, &[v2], &[]);
+
+        // 损坏的快照在最后一块时返回错误，stream 还能继续使用
+        let res = client.execute_restore(&mut &data[..data.len() - 1]).await?;
+        assert_eq!(res.status, 400);
+        let res = client
+            .execute(&CommandRequest::new_hget("t1", "k1"))
+            .await?;
+        assert_res_ok(&res, &[v1], &[]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn client_server_rate_limit_should_work() -> anyhow::Result<()> {
+        let limits = LimitsConfig {
+            connection: Some(RateLimitConfig {
+                rate: 0.0,
+                burst: 2,
+            }),
+            ..Default::default()
+        };
+        let addr = start_server_with(limits).await?;
+
+        let stream = TcpStream::connect(addr).await?;
+        let mut client = ProstClientStream::new(stream);
+        let cmd = CommandRequest::new_hget("t1", "k1");
+        for _ in 0..2 {
+            let res = client.execute(&cmd).await?;
+            assert_eq!(res.status, 404);
+        }
+        // 令牌用完之后返回 429，连接不会断开
+        let res = client.execute(&cmd).await?;
+        assert_eq!(res.status, 429);
+        let res = client.execute(&cmd).await?;
+        assert_eq!(res.status, 429);
+
+        // 新的连接有新的令牌桶
+        let stream = TcpStream::connect(addr).await?;
+        let mut client = ProstClientStream::new(stream);
+        assert_eq!(client.execute(&cmd).await?.status, 404);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chunked_restore_should_be_rate_limited() -> anyhow::Result<()> {
+        let limits = LimitsConfig {
+            connection: Some(RateLimitConfig {
+                rate: 0.0,
+                burst: 2,
+            }),
+            ..Default::default()
+        };
+        let addr = start_server_with(limits).await?;
+        let mut client = ProstClientStream::new(TcpStream::connect(addr).await?);
+
+        // 第三块被限流，返回 429，剩下的块被丢弃，没有响应
+        for more in [true, true, true, false] {
+            let cmd = CommandRequest::new_restore_chunk(Bytes::from_static(b"data"), more);
+            client.stream.send(&cmd).await?;
+        }
+        let res = client.stream.next().await.unwrap()?;
+        assert_eq!(res.status, 429);
+        let res = client
+            .execute(&CommandRequest::new_hget("t1", "k1"))
+            .await?;
+        assert_eq!(res.status, 429);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn chunked_restore_should_be_bounded() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let service: Service = ServiceBuilder::default().finish();
+        let config = CompressionConfig {
+            max_restore: 16,
+            ..Default::default()
+        };
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let server = ProstServerStream::new(stream, service).compression(config);
+            server.process().await.unwrap();
+        });
+        let mut client = ProstClientStream::new(TcpStream::connect(addr).await?);
+
+        // 超过 max_restore 时返回错误，剩下的块被丢弃
+        for more in [true, true, false] {
+            let cmd = CommandRequest::new_restore_chunk(Bytes::from(vec![0u8; 10]), more);
+            client.stream.send(&cmd).await?;
+        }
+        let res = client.stream.next().await.unwrap()?;
+        assert_eq!(res.status, 400);
+        assert!(res.message.contains("exceeds 16 bytes"));
+
+        // 分块 Restore 中间收到别的命令，放弃这次 Restore，再执行这个命令
+        let cmd = CommandRequest::new_restore_chunk(Bytes::from_static(b"data"), true);
+        client.stream.send(&cmd).await?;
+        client
+            .stream
+            .send(&CommandRequest::new_hget("t1", "k1"))
+            .await?;
+        let res = client.stream.next().await.unwrap()?;
+        assert_eq!(res.status, 400);
+        assert!(res.message.contains("Restore is interrupted"));
+        let res = client.stream.next().await.unwrap()?;
+        assert_eq!(res.status, 404);
+
+        Ok(())
+    }
+
     async fn start_server() -> Result<SocketAddr> {
+        start_server_with(LimitsConfig::default()).await
+    }
+
+    async fn start_server_with(limits: LimitsConfig) -> Result<SocketAddr> {
         let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
         let addr = listener.local_addr().unwrap();
+        let service: Service = ServiceBuilder::default().rate_limit(&limits).finish();
 
         tokio::spawn(async move {
             loop {
                 let (stream, _) = listener.accept().await.unwrap();
-                let service: Service = ServiceBuilder::default().finish();
-                let server = ProstServerStream::new(stream, service);
+                let server = ProstServerStream::new(stream, service.clone());
                 tokio::spawn(server.process());
             }
         });
@@ -4,10 +4,12 @@ use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
 use yamux::{Config, Connection, ConnectionError, Control, Mode};
 
-use crate::ProstClientStream;
+use crate::{config::CompressionConfig, ProstClientStream};
 
 pub struct YamuxCtrl<S> {
     ctrl: Control,
+    // 新打开的 stream 使用的压缩配置
+    compression: CompressionConfig,
     _conn: PhantomData<S>,
 }
 
@@ -40,10 +42,17 @@ where
 
         Self {
             ctrl,
+            compression: CompressionConfig::default(),
             _conn: PhantomData::default(),
         }
     }
 
+    /// 设置之后 open_stream 打开的 stream 所使用的压缩配置
+    pub fn compression(mut self, config: CompressionConfig) -> Self {
+        self.compression = config;
+        self
+    }
+
     pub fn new_client(stream: S, config: Option<Config>) -> Self {
         Self::new(stream, config, true, |_s| future::ready(Ok(())))
     }
@@ -61,7 +70,7 @@ where
         &mut self,
     ) -> Result<ProstClientStream<Compat<yamux::Stream>>, ConnectionError> {
         let stream = self.ctrl.open_stream().await?;
-        Ok(ProstClientStream::new(stream.compat()))
+        Ok(ProstClientStream::new(stream.compat()).compression(self.compression.clone()))
     }
 }
 
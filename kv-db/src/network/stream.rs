@@ -1,5 +1,8 @@
-use super::frame::{read_fame, FrameCoder};
-use crate::error::{IOError, KvError};
+use super::frame::{frame_codec, read_fame, FrameCoder};
+use crate::{
+    config::{Codec, CompressionConfig},
+    error::{IOError, KvError},
+};
 use bytes::BytesMut;
 use futures::{ready, FutureExt, Sink, Stream};
 use std::{
@@ -18,6 +21,12 @@ pub struct ProstStream<S, In, Out> {
     written: usize,
     // read buffer
     rbuf: BytesMut,
+    // frame 的压缩配置
+    compression: CompressionConfig,
+    // 对端最近一次使用的压缩算法
+    peer_codec: Option<Codec>,
+    // 是否跟随对端的压缩算法来发送 frame
+    follow_peer: bool,
 
     // 幽灵类型
     _in: PhantomData<In>,
@@ -34,10 +43,29 @@ where
             wbuf: BytesMut::new(),
             written: 0,
             rbuf: BytesMut::new(),
+            compression: CompressionConfig::default(),
+            peer_codec: None,
+            follow_peer: false,
             _in: PhantomData::default(),
             _out: PhantomData::default(),
         }
     }
+
+    pub fn set_compression(&mut self, config: CompressionConfig) {
+        self.compression = config;
+    }
+
+    /// 收到对端压缩过的 frame 后，改用对端的压缩算法发送，
+    /// 这样服务器就可以按每个连接和客户端协商压缩算法
+    pub fn follow_peer_codec(mut self) -> Self {
+        self.follow_peer = true;
+        self
+    }
+
+    /// 对端最近一次使用的压缩算法
+    pub fn peer_codec(&self) -> Option<Codec> {
+        self.peer_codec
+    }
 }
 
 // 读取时，返回In
@@ -49,13 +77,14 @@ where
 {
     type Item = Result<In, KvError>;
 
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
         // 上一次调用结束后，应该情况 rbuf
-        assert!(self.rbuf.len() == 0);
+        assert!(this.rbuf.len() == 0);
         // 把 rbuf 分离出来,摆脱 self 的引用
-        let mut rbuf = self.rbuf.split_off(0);
+        let mut rbuf = this.rbuf.split_off(0);
         // 使用 read_frame 来获取数据 Frame
-        let fut = read_fame(&mut self.stream, &mut rbuf);
+        let fut = read_fame(&mut this.stream, &mut rbuf, this.compression.max_frame);
         // 因为 poll_xxx() 方法已经是 async/await 的底层 API 实现，
         // 所以我们在 poll_xxx() 方法中，是不能直接使用异步函数的；
         // 需要把它看作一个 future，然后调用 future 的 poll 函数。
@@ -63,9 +92,19 @@ where
         // 这样就可以调用 FutureExt 的 poll_unpin() 方法了。Box::pin 会生成 Pin<Box>。
         ready!(Box::pin(fut).poll_unpin(cx))?;
         // 把拿到的 Frame 合并回 rbuf
-        self.rbuf.unsplit(rbuf);
+        this.rbuf.unsplit(rbuf);
+        // 记录对端使用的压缩算法
+        if let Some(codec) = frame_codec(&this.rbuf).filter(|c| *c != Codec::None) {
+            this.peer_codec = Some(codec);
+            if this.follow_peer {
+                this.compression.codec = codec;
+            }
+        }
         // 解析这个新拿到的这个Frame
-        Poll::Ready(Some(In::decode_frame(&mut self.rbuf)))
+        Poll::Ready(Some(In::decode_frame_with(
+            &mut this.rbuf,
+            &this.compression,
+        )))
     }
 }
 
@@ -85,7 +124,7 @@ where
 
     fn start_send(self: Pin<&mut Self>, item: &Out) -> Result<(), Self::Error> {
         let this = self.get_mut();
-        item.encode_frame(&mut this.wbuf)?;
+        item.encode_frame_with(&mut this.wbuf, &this.compression)?;
         Ok(())
     }
 
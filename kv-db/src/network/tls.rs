@@ -7,7 +7,7 @@ use tokio_rustls::{
     client,
     rustls::{
         internal::pemfile, AllowAnyAuthenticatedClient, Certificate, ClientConfig, NoClientAuth,
-        PrivateKey, RootCertStore, ServerConfig,
+        PrivateKey, RootCertStore, ServerConfig, Session,
     },
     server,
     webpki::DNSNameRef,
@@ -113,6 +113,16 @@ impl TlsServerAcceptor {
         })
     }
 
+    /// 使用同样的证书，但协商另外的 ALPN，例如 HTTP 网关使用 http/1.1
+    pub fn with_alpn(&self, protocols: &[&str]) -> Self {
+        let mut config = self.inner.as_ref().clone();
+        let protocols: Vec<_> = protocols.iter().map(|p| p.as_bytes().to_vec()).collect();
+        config.set_protocols(&protocols);
+        Self {
+            inner: Arc::new(config),
+        }
+    }
+
     // 触发 TLS 协议，把底层的 stream 转换成 TLS stream
     pub async fn accept<S>(&self, stream: S) -> Result<server::TlsStream<S>, KvError>
     where
@@ -123,6 +133,14 @@ impl TlsServerAcceptor {
     }
 }
 
+/// 认证主体：客户端证书 subject 中的 CN；没有客户端证书返回 None
+pub fn peer_principal<S>(stream: &server::TlsStream<S>) -> Option<String> {
+    let certs = stream.get_ref().1.get_peer_certificates()?;
+    let (_, cert) = x509_parser::parse_x509_certificate(&certs.first()?.0).ok()?;
+    let cn = cert.subject().iter_common_name().next()?;
+    cn.as_str().ok().map(|cn| cn.to_string())
+}
+
 fn load_certs(cert: &str) -> Result<Vec<Certificate>, KvError> {
     let mut cert = Cursor::new(cert);
     pemfile::certs(&mut cert).map_err(|_| KvError::ConvertError("server".into(), "cert"))
@@ -1,16 +1,20 @@
+// This file is @generated by prost-build.
 /// 来自客户端的命令请求
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CommandRequest {
     #[prost(
         oneof = "command_request::RequestData",
-        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12"
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18"
     )]
     pub request_data: ::core::option::Option<command_request::RequestData>,
 }
 /// Nested message and enum types in `CommandRequest`.
 pub mod command_request {
+    #[derive(serde::Serialize, serde::Deserialize)]
     #[derive(PartialOrd)]
     #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
@@ -40,10 +44,28 @@ pub mod command_request {
         Unsubscribe(super::Unsubscribe),
         #[prost(message, tag = "12")]
         Publish(super::Publish),
+        /// 备份/恢复
+        #[prost(message, tag = "13")]
+        Dump(super::Dump),
+        #[prost(message, tag = "14")]
+        Restore(super::Restore),
+        /// 二级索引
+        #[prost(message, tag = "15")]
+        Hfind(super::Hfind),
+        #[prost(message, tag = "16")]
+        Hindex(super::Hindex),
+        /// 服务器端脚本
+        #[prost(message, tag = "17")]
+        Eval(super::Eval),
+        /// 过期时间
+        #[prost(message, tag = "18")]
+        Hexpire(super::Hexpire),
     }
 }
 /// 服务器的响应
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CommandResponse {
@@ -61,7 +83,9 @@ pub struct CommandResponse {
     pub pairs: ::prost::alloc::vec::Vec<Kvpair>,
 }
 /// 从 table 中获取一个 key，返回 value
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hget {
@@ -71,7 +95,9 @@ pub struct Hget {
     pub key: ::prost::alloc::string::String,
 }
 /// 从 table 中获取所有的 Kvpair
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hgetall {
@@ -79,7 +105,9 @@ pub struct Hgetall {
     pub table: ::prost::alloc::string::String,
 }
 /// 从 table 中获取一组 key，返回它们的 value
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hmget {
@@ -89,7 +117,9 @@ pub struct Hmget {
     pub keys: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// 返回的值
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Value {
@@ -98,6 +128,7 @@ pub struct Value {
 }
 /// Nested message and enum types in `Value`.
 pub mod value {
+    #[derive(serde::Serialize, serde::Deserialize)]
     #[derive(PartialOrd)]
     #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
@@ -115,7 +146,9 @@ pub mod value {
     }
 }
 /// 返回的 kvpair
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Kvpair {
@@ -126,7 +159,9 @@ pub struct Kvpair {
 }
 /// 往 table 里存一个 kvpair，
 /// 如果 table 不存在就创建这个 table
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hset {
@@ -137,7 +172,9 @@ pub struct Hset {
 }
 /// 往 table 中存一组 kvpair，
 /// 如果 table 不存在就创建这个 table
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hmset {
@@ -147,7 +184,9 @@ pub struct Hmset {
     pub pairs: ::prost::alloc::vec::Vec<Kvpair>,
 }
 /// 从 table 中删除一个 key，返回它之前的值
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hdel {
@@ -157,7 +196,9 @@ pub struct Hdel {
     pub key: ::prost::alloc::string::String,
 }
 /// 从 table 中删除一组 key，返回它们之前的值
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hmdel {
@@ -167,7 +208,9 @@ pub struct Hmdel {
     pub keys: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
 /// 查看 key 是否存在
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hexist {
@@ -177,7 +220,9 @@ pub struct Hexist {
     pub key: ::prost::alloc::string::String,
 }
 /// 查看一组 key 是否存在
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hmexist {
@@ -186,8 +231,25 @@ pub struct Hmexist {
     #[prost(string, repeated, tag = "2")]
     pub keys: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
+/// 设置 key 的过期时间（毫秒），再次设置 key 的值会清除过期时间；
+/// 返回 key 是否存在，不支持过期的存储返回 400
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(PartialOrd)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Hexpire {
+    #[prost(string, tag = "1")]
+    pub table: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub key: ::prost::alloc::string::String,
+    #[prost(uint64, tag = "3")]
+    pub ttl: u64,
+}
 /// 订阅某个主题；订阅成功，第一次返回id
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Subscribe {
@@ -195,7 +257,9 @@ pub struct Subscribe {
     pub topic: ::prost::alloc::string::String,
 }
 /// 取消订阅
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Unsubscribe {
@@ -205,7 +269,9 @@ pub struct Unsubscribe {
     pub id: u32,
 }
 /// 发布数据到某个主题
+#[derive(serde::Serialize, serde::Deserialize)]
 #[derive(PartialOrd)]
+#[serde(default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Publish {
@@ -214,3 +280,106 @@ pub struct Publish {
     #[prost(message, repeated, tag = "2")]
     pub data: ::prost::alloc::vec::Vec<Value>,
 }
+/// 导出 table 的快照，table 为空则导出所有 table；
+/// 快照以多个 CommandResponse 分块返回，每块的 values\[0\] 是快照的一段数据，最后一个不带 values 的响应表示结束
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(PartialOrd)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Dump {
+    #[prost(string, tag = "1")]
+    pub table: ::prost::alloc::string::String,
+}
+/// 从 Dump 得到的快照中恢复数据，返回恢复的 kvpair 数量；
+/// 快照可以在同一个 stream 上分成多个 Restore 发送，除最后一块外 more 都为 true，
+/// 中间的块没有响应，收到最后一块后校验整个快照再写入
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(PartialOrd)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Restore {
+    #[prost(bytes = "bytes", tag = "1")]
+    pub data: ::prost::bytes::Bytes,
+    #[prost(bool, tag = "2")]
+    pub more: bool,
+}
+/// 通过 JSON path 上的二级索引查找 table 中的 kvpair；
+/// 设置了 eq 时做等值查询，否则查询 \[min, max\] 区间，min/max 不设置则不限
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(PartialOrd)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Hfind {
+    #[prost(string, tag = "1")]
+    pub table: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub path: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "3")]
+    pub eq: ::core::option::Option<Value>,
+    #[prost(message, optional, tag = "4")]
+    pub min: ::core::option::Option<Value>,
+    #[prost(message, optional, tag = "5")]
+    pub max: ::core::option::Option<Value>,
+}
+/// 管理 table 上 JSON path 的二级索引
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(PartialOrd)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Hindex {
+    #[prost(string, tag = "1")]
+    pub table: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub path: ::prost::alloc::string::String,
+    #[prost(enumeration = "IndexAction", tag = "3")]
+    pub action: i32,
+}
+/// 在服务器上原子地执行 JavaScript 脚本，脚本中通过 kv.get/kv.set/kv.del 访问数据，通过 ARGS 拿到参数；
+/// script 为空时执行之前缓存的 sha 对应的脚本。返回脚本最后一个表达式的值
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(PartialOrd)]
+#[serde(default)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Eval {
+    #[prost(string, tag = "1")]
+    pub script: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub sha: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub args: ::prost::alloc::vec::Vec<Value>,
+}
+#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum IndexAction {
+    Create = 0,
+    Drop = 1,
+    Rebuild = 2,
+}
+impl IndexAction {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            IndexAction::Create => "CREATE",
+            IndexAction::Drop => "DROP",
+            IndexAction::Rebuild => "REBUILD",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "CREATE" => Some(Self::Create),
+            "DROP" => Some(Self::Drop),
+            "REBUILD" => Some(Self::Rebuild),
+            _ => None,
+        }
+    }
+}
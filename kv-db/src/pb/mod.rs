@@ -3,6 +3,7 @@ pub mod abi;
 use abi::{command_request::RequestData, *};
 use bytes::Bytes;
 use hyper::StatusCode;
+use std::time::Duration;
 
 use crate::error::KvError;
 
@@ -42,10 +43,51 @@ impl CommandRequest {
         .into()
     }
 
-    pub fn new_hmget(table: impl Into<String>, key: impl Into<String>) -> Self {
+    pub fn new_hmget(table: impl Into<String>, keys: Vec<String>) -> Self {
         RequestData::Hmget(Hmget {
             table: table.into(),
-            keys: vec![key.into()],
+            keys,
+        })
+        .into()
+    }
+
+    pub fn new_hmset(table: impl Into<String>, pairs: Vec<Kvpair>) -> Self {
+        RequestData::Hmset(Hmset {
+            table: table.into(),
+            pairs,
+        })
+        .into()
+    }
+
+    pub fn new_hmdel(table: impl Into<String>, keys: Vec<String>) -> Self {
+        RequestData::Hmdel(Hmdel {
+            table: table.into(),
+            keys,
+        })
+        .into()
+    }
+
+    pub fn new_hexist(table: impl Into<String>, key: impl Into<String>) -> Self {
+        RequestData::Hexist(Hexist {
+            table: table.into(),
+            key: key.into(),
+        })
+        .into()
+    }
+
+    pub fn new_hmexist(table: impl Into<String>, keys: Vec<String>) -> Self {
+        RequestData::Hmexist(Hmexist {
+            table: table.into(),
+            keys,
+        })
+        .into()
+    }
+
+    pub fn new_hexpire(table: impl Into<String>, key: impl Into<String>, ttl: Duration) -> Self {
+        RequestData::Hexpire(Hexpire {
+            table: table.into(),
+            key: key.into(),
+            ttl: ttl.as_millis() as _,
         })
         .into()
     }
@@ -65,6 +107,83 @@ impl CommandRequest {
         .into()
     }
 
+    pub fn new_dump(table: impl Into<String>) -> Self {
+        RequestData::Dump(Dump {
+            table: table.into(),
+        })
+        .into()
+    }
+
+    pub fn new_restore(data: impl Into<Bytes>) -> Self {
+        Self::new_restore_chunk(data, false)
+    }
+
+    /// 分块恢复中的一块，除最后一块外 more 都为 true
+    pub fn new_restore_chunk(data: impl Into<Bytes>, more: bool) -> Self {
+        RequestData::Restore(Restore {
+            data: data.into(),
+            more,
+        })
+        .into()
+    }
+
+    pub fn new_hfind_eq(table: impl Into<String>, path: impl Into<String>, eq: Value) -> Self {
+        RequestData::Hfind(Hfind {
+            table: table.into(),
+            path: path.into(),
+            eq: Some(eq),
+            ..Default::default()
+        })
+        .into()
+    }
+
+    pub fn new_hfind_range(
+        table: impl Into<String>,
+        path: impl Into<String>,
+        min: Option<Value>,
+        max: Option<Value>,
+    ) -> Self {
+        RequestData::Hfind(Hfind {
+            table: table.into(),
+            path: path.into(),
+            eq: None,
+            min,
+            max,
+        })
+        .into()
+    }
+
+    pub fn new_hindex(
+        table: impl Into<String>,
+        path: impl Into<String>,
+        action: IndexAction,
+    ) -> Self {
+        RequestData::Hindex(Hindex {
+            table: table.into(),
+            path: path.into(),
+            action: action as _,
+        })
+        .into()
+    }
+
+    pub fn new_eval(script: impl Into<String>, args: Vec<Value>) -> Self {
+        RequestData::Eval(Eval {
+            script: script.into(),
+            sha: String::new(),
+            args,
+        })
+        .into()
+    }
+
+    pub fn new_evalsha(sha: impl Into<String>, args: Vec<Value>) -> Self {
+        RequestData::Eval(Eval {
+            script: String::new(),
+            sha: sha.into(),
+            args,
+        })
+        .into()
+    }
+
     pub fn new_unsubscribe(topic: &str, id: u32) -> Self {
         RequestData::Unsubscribe(Unsubscribe {
             topic: topic.into(),
@@ -82,6 +201,23 @@ impl CommandResponse {
     }
 }
 
+impl From<Vec<bool>> for CommandResponse {
+    /// 全部存在时返回 200；否则返回 204，message 中按 key 的顺序用 1/0 表示是否存在
+    fn from(flags: Vec<bool>) -> Self {
+        if flags.iter().all(|&exist| exist) {
+            return Self::ok();
+        }
+        Self {
+            status: StatusCode::NO_CONTENT.as_u16() as _,
+            message: flags
+                .iter()
+                .map(|&exist| if exist { '1' } else { '0' })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
 impl Kvpair {
     pub fn new(key: impl Into<String>, value: Value) -> Self {
         Self {
@@ -148,7 +284,14 @@ impl From<KvError> for CommandResponse {
 
         match value {
             KvError::NotFound(_) => result.status = StatusCode::NOT_FOUND.as_u16() as _,
-            KvError::InvalidCommand(_) => result.status = StatusCode::BAD_REQUEST.as_u16() as _,
+            KvError::InvalidCommand(_) | KvError::DumpError(_) | KvError::ScriptError(_) => {
+                result.status = StatusCode::BAD_REQUEST.as_u16() as _
+            }
+            KvError::RateLimited(_) => result.status = StatusCode::TOO_MANY_REQUESTS.as_u16() as _,
+            KvError::Unauthorized(_) => result.status = StatusCode::UNAUTHORIZED.as_u16() as _,
+            KvError::QuotaExceeded(_) | KvError::OutOfMemory(_) => {
+                result.status = StatusCode::INSUFFICIENT_STORAGE.as_u16() as _
+            }
             _ => {}
         }
 
@@ -204,6 +347,14 @@ impl TryFrom<&Value> for i64 {
     }
 }
 
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Self {
+            value: Some(value::Value::Bool(b)),
+        }
+    }
+}
+
 impl From<String> for Value {
     fn from(value: String) -> Self {
         Self {
@@ -1,8 +1,14 @@
 use crate::{
+    backup,
     error::KvError,
-    pb::abi::{CommandResponse, Hget, Hgetall, Hset, Value},
+    pb::abi::{
+        CommandResponse, Hdel, Hexist, Hexpire, Hfind, Hget, Hgetall, Hindex, Hmdel, Hmexist,
+        Hmget, Hmset, Hset, IndexAction, Restore, Value,
+    },
+    storage::index::{IndexKey, IndexQuery},
     Storage,
 };
+use std::time::Duration;
 
 pub trait CommandService {
     fn execute(self, store: &impl Storage) -> CommandResponse;
@@ -39,3 +45,147 @@ impl CommandService for Hset {
         }
     }
 }
+
+impl CommandService for Hdel {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        match store.del(&self.table, &self.key) {
+            Ok(Some(v)) => v.into(),
+            Ok(None) => Value::default().into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandService for Hmget {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        let mut values = Vec::with_capacity(self.keys.len());
+        for key in self.keys {
+            match store.get(&self.table, &key) {
+                Ok(Some(v)) => values.push(v),
+                // 和 HGET 一样，有一个 key 不存在就返回 404
+                Ok(None) => return KvError::NotFound(format!("{}:{}", self.table, key)).into(),
+                Err(e) => return e.into(),
+            }
+        }
+        values.into()
+    }
+}
+
+impl CommandService for Hmset {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        // 只返回被覆盖的旧值
+        let mut values = vec![];
+        for pair in self.pairs {
+            match store.set(&self.table, pair.key, pair.value.unwrap_or_default()) {
+                Ok(Some(v)) => values.push(v),
+                Ok(None) => {}
+                Err(e) => return e.into(),
+            }
+        }
+        values.into()
+    }
+}
+
+impl CommandService for Hmdel {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        // 只返回确实被删除的值
+        let mut values = vec![];
+        for key in self.keys {
+            match store.del(&self.table, &key) {
+                Ok(Some(v)) => values.push(v),
+                Ok(None) => {}
+                Err(e) => return e.into(),
+            }
+        }
+        values.into()
+    }
+}
+
+impl CommandService for Hexist {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        match store.contains(&self.table, &self.key) {
+            Ok(exist) => vec![exist].into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandService for Hmexist {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        let mut flags = Vec::with_capacity(self.keys.len());
+        for key in self.keys {
+            match store.contains(&self.table, &key) {
+                Ok(exist) => flags.push(exist),
+                Err(e) => return e.into(),
+            }
+        }
+        flags.into()
+    }
+}
+
+impl CommandService for Hexpire {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        match store.expire(&self.table, &self.key, Duration::from_millis(self.ttl)) {
+            Ok(exist) => vec![exist].into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandService for Restore {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        // 分块的 Restore 由 ProstServerStream 在同一个 stream 上拼起来，到这里的都应该是完整的快照
+        if self.more {
+            return KvError::InvalidCommand("chunked Restore needs a stream".into()).into();
+        }
+        match backup::restore(store, &self.data[..]) {
+            Ok(count) => (count as i64).into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandService for Hfind {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        let query = match self.query() {
+            Ok(query) => query,
+            Err(e) => return e.into(),
+        };
+        match store.find(&self.table, &self.path, &query) {
+            Ok(v) => v.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl Hfind {
+    fn query(&self) -> Result<IndexQuery, KvError> {
+        let key = |v: &Value| {
+            IndexKey::try_from(v)
+                .map_err(|_| KvError::InvalidCommand(format!("Can't query index with {:?}", v)))
+        };
+        match &self.eq {
+            Some(eq) => Ok(IndexQuery::Eq(key(eq)?)),
+            None => Ok(IndexQuery::Range {
+                min: self.min.as_ref().map(key).transpose()?,
+                max: self.max.as_ref().map(key).transpose()?,
+            }),
+        }
+    }
+}
+
+impl CommandService for Hindex {
+    fn execute(self, store: &impl crate::Storage) -> CommandResponse {
+        let result = match self.action() {
+            IndexAction::Create => store.create_index(&self.table, &self.path).map(Value::from),
+            IndexAction::Drop => store.drop_index(&self.table, &self.path).map(Value::from),
+            IndexAction::Rebuild => store
+                .rebuild_index(&self.table, &self.path)
+                .map(|_| true.into()),
+        };
+        match result {
+            Ok(v) => v.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{
+    config::{LimitsConfig, RateLimitConfig},
+    error::KvError,
+};
+use dashmap::DashMap;
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// 令牌桶
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+/// 一个连接上的所有 yamux stream 共享的令牌桶
+pub type ConnectionBucket = Arc<Mutex<TokenBucket>>;
+
+/// 服务器的限流器：每个连接一个令牌桶，由连接自己持有；
+/// 同一个认证主体的所有连接共享一个令牌桶，存放在这里
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    connection: Option<RateLimitConfig>,
+    principal: Option<RateLimitConfig>,
+    principals: DashMap<String, TokenBucket>,
+}
+
+impl TokenBucket {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let burst = config.burst.max(1) as f64;
+        Self {
+            rate: config.rate,
+            burst,
+            tokens: burst,
+            last: Instant::now(),
+        }
+    }
+
+    /// 取一个令牌，没有令牌返回 false
+    pub fn try_acquire(&mut self) -> bool {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.last = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(config: &LimitsConfig) -> Self {
+        Self {
+            connection: config.connection,
+            principal: config.principal,
+            principals: DashMap::new(),
+        }
+    }
+
+    /// 为新连接创建令牌桶，没有配置连接限流返回 None
+    pub fn connection_bucket(&self) -> Option<ConnectionBucket> {
+        self.connection
+            .as_ref()
+            .map(|config| Arc::new(Mutex::new(TokenBucket::new(config))))
+    }
+
+    /// 处理一个请求之前调用，先检查连接的令牌桶，再检查认证主体的令牌桶
+    pub fn acquire(
+        &self,
+        connection: Option<&ConnectionBucket>,
+        principal: Option<&str>,
+    ) -> Result<(), KvError> {
+        if let Some(bucket) = connection {
+            if !bucket.lock().unwrap().try_acquire() {
+                return Err(KvError::RateLimited("connection".into()));
+            }
+        }
+
+        if let (Some(config), Some(principal)) = (&self.principal, principal) {
+            let mut bucket = self
+                .principals
+                .entry(principal.into())
+                .or_insert_with(|| TokenBucket::new(config));
+            if !bucket.try_acquire() {
+                return Err(KvError::RateLimited(format!("principal {}", principal)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod limit_tests {
+    use super::*;
+    use std::{thread, time::Duration};
+
+    fn limits(connection: Option<(f64, u32)>, principal: Option<(f64, u32)>) -> LimitsConfig {
+        let config = |(rate, burst)| RateLimitConfig { rate, burst };
+        LimitsConfig {
+            connection: connection.map(config),
+            principal: principal.map(config),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn token_bucket_should_refill() {
+        let mut bucket = TokenBucket::new(&RateLimitConfig {
+            rate: 100.0,
+            burst: 2,
+        });
+        assert!(bucket.try_acquire());
+        assert!(bucket.try_acquire());
+        assert!(!bucket.try_acquire());
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(bucket.try_acquire());
+    }
+
+    #[test]
+    fn connection_limit_should_work() {
+        let limiter = RateLimiter::new(&limits(Some((0.0, 1)), None));
+        let c1 = limiter.connection_bucket();
+        let c2 = limiter.connection_bucket();
+        assert!(limiter.acquire(c1.as_ref(), None).is_ok());
+        assert!(matches!(
+            limiter.acquire(c1.as_ref(), None),
+            Err(KvError::RateLimited(_))
+        ));
+        // 每个连接有自己的令牌桶
+        assert!(limiter.acquire(c2.as_ref(), None).is_ok());
+    }
+
+    #[test]
+    fn principal_limit_should_be_shared_between_connections() {
+        let limiter = RateLimiter::new(&limits(None, Some((0.0, 2))));
+        assert!(limiter.connection_bucket().is_none());
+        assert!(limiter.acquire(None, Some("alice")).is_ok());
+        assert!(limiter.acquire(None, Some("alice")).is_ok());
+        assert!(limiter.acquire(None, Some("alice")).is_err());
+        assert!(limiter.acquire(None, Some("bob")).is_ok());
+        // 没有认证主体的连接不受限制
+        assert!(limiter.acquire(None, None).is_ok());
+    }
+}
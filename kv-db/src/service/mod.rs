@@ -1,21 +1,30 @@
 mod command_service;
+pub mod limit;
 pub mod notify;
+pub mod script;
 pub mod service_builder;
 pub mod topic;
 pub mod topic_service;
 
 use crate::{
+    backup::{self, DUMP_CHUNK_SIZE},
     error::KvError,
     memory::MemTable,
-    pb::abi::{command_request::RequestData, CommandRequest, CommandResponse},
+    pb::abi::{command_request::RequestData, CommandRequest, CommandResponse, Dump, Eval, Value},
     Storage,
 };
+use bytes::Bytes;
 use command_service::*;
-use futures::{stream, Stream};
-use std::{ops::Deref, sync::Arc};
+use futures::{stream, Stream, StreamExt};
+use std::{io, ops::Deref, sync::Arc};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use topic_service::*;
 use tracing::info;
 
+/// Dump 最多缓存多少块还没发送出去的快照
+const DUMP_CHANNEL_SIZE: usize = 4;
+
 /// 可以跨线程，可以调用 execute 来执行某个 CommandRequest 命令，返回 CommandResponse。
 pub struct Service<Store = MemTable> {
     inner: Arc<ServiceBuilder<Store>>,
@@ -26,7 +35,41 @@ impl<Store: Storage> Service<Store> {
     pub fn execute(&self, cmd: CommandRequest) -> impl Stream<Item = Arc<CommandResponse>> + Send {
         info!("God request: {:?}", &cmd);
         self.on_received.notify(&cmd);
-        let mut resp = dispatch(cmd.clone(), &self.store);
+        if let Some(RequestData::Dump(cmd)) = cmd.request_data {
+            return dispatch_dump(cmd, self.inner.clone());
+        }
+        let service = self.clone();
+        if let Some(RequestData::Eval(eval)) = &cmd.request_data {
+            let eval = eval.clone();
+            return Box::pin(
+                stream::once(async move {
+                    let resp = dispatch_eval(eval, service.inner.clone()).await;
+                    service.respond(cmd, resp)
+                })
+                .flatten(),
+            );
+        }
+
+        // 没有脚本在执行时直接处理，否则等待脚本执行完
+        let resp = match self.scripts.try_read() {
+            Ok(_guard) => dispatch(cmd.clone(), &self.store),
+            Err(_) => {
+                return Box::pin(
+                    stream::once(async move {
+                        let resp = {
+                            let _guard = service.scripts.read().await;
+                            dispatch(cmd.clone(), &service.store)
+                        };
+                        service.respond(cmd, resp)
+                    })
+                    .flatten(),
+                )
+            }
+        };
+        self.respond(cmd, resp)
+    }
+
+    fn respond(&self, cmd: CommandRequest, mut resp: CommandResponse) -> StreamingResponse {
         if resp == CommandResponse::default() {
             dispatch_stream(cmd, self.broadcaster.clone())
         } else {
@@ -59,12 +102,22 @@ impl<Store> Deref for Service<Store> {
     }
 }
 
-/// 从 Request 中得到 Response，目前处理所有 HGET/HSET/HGETALL
+/// 从 Request 中得到 Response，处理所有 HXXX 命令
 fn dispatch(cmd: CommandRequest, store: &impl Storage) -> CommandResponse {
     match cmd.request_data {
         Some(RequestData::Hget(cmd)) => cmd.execute(store),
         Some(RequestData::Hgetall(cmd)) => cmd.execute(store),
         Some(RequestData::Hset(cmd)) => cmd.execute(store),
+        Some(RequestData::Hdel(cmd)) => cmd.execute(store),
+        Some(RequestData::Hmget(cmd)) => cmd.execute(store),
+        Some(RequestData::Hmset(cmd)) => cmd.execute(store),
+        Some(RequestData::Hmdel(cmd)) => cmd.execute(store),
+        Some(RequestData::Hexist(cmd)) => cmd.execute(store),
+        Some(RequestData::Hmexist(cmd)) => cmd.execute(store),
+        Some(RequestData::Hexpire(cmd)) => cmd.execute(store),
+        Some(RequestData::Restore(cmd)) => cmd.execute(store),
+        Some(RequestData::Hfind(cmd)) => cmd.execute(store),
+        Some(RequestData::Hindex(cmd)) => cmd.execute(store),
         None => KvError::InvalidCommand("Request has no data".into()).into(),
         // 没有做任何处理，尝试让之后的 dispatch_stream 处理
         _ => CommandResponse::default(),
@@ -84,6 +137,95 @@ fn dispatch(cmd: CommandRequest, store: &impl Storage) -> CommandResponse {
 //     }
 // }
 
+/// 执行脚本：等正在执行的命令结束后持有写锁，在阻塞线程里运行 V8，不会占住 tokio 的工作线程
+async fn dispatch_eval<Store: Storage>(
+    cmd: Eval,
+    inner: Arc<ServiceBuilder<Store>>,
+) -> CommandResponse {
+    let guard = inner.scripts.write().await;
+    let result = tokio::task::spawn_blocking(move || {
+        let _guard = guard;
+        inner.scripts.eval(&cmd, inner.clone())
+    })
+    .await;
+    result.unwrap_or_else(|e| KvError::Internal(format!("script task failed: {}", e)).into())
+}
+
+/// 脚本通过 ServiceBuilder 访问 store，这样 store 不需要单独放在 Arc 里
+impl<Store: Storage> script::ScriptStore for ServiceBuilder<Store> {
+    fn get(&self, table: &str, key: &str) -> Result<Option<Value>, KvError> {
+        Storage::get(&self.store, table, key)
+    }
+
+    fn set(&self, table: &str, key: &str, value: Value) -> Result<Option<Value>, KvError> {
+        Storage::set(&self.store, table, key, value)
+    }
+
+    fn del(&self, table: &str, key: &str) -> Result<Option<Value>, KvError> {
+        Storage::del(&self.store, table, key)
+    }
+}
+
+/// 导出快照，分块返回，最后以一个不带 values 的响应表示结束；
+/// 快照在阻塞线程里边遍历边发送，通道满了就等待，不会把整个快照放在内存里
+fn dispatch_dump<Store: Storage>(
+    cmd: Dump,
+    inner: Arc<ServiceBuilder<Store>>,
+) -> StreamingResponse {
+    let (tx, rx) = mpsc::channel(DUMP_CHANNEL_SIZE);
+    tokio::task::spawn_blocking(move || {
+        let table = Some(cmd.table.as_str()).filter(|t| !t.is_empty());
+        let mut chunks = DumpChunks::new(tx.clone());
+        let resp = match backup::dump(&inner.store, table, &mut chunks) {
+            Ok(_) => CommandResponse::ok(),
+            Err(e) => e.into(),
+        };
+        // 客户端已经断开时发送失败，不需要处理
+        let _ = tx.blocking_send(Arc::new(resp));
+    });
+    Box::pin(ReceiverStream::new(rx))
+}
+
+/// 把写入的快照按 DUMP_CHUNK_SIZE 分块，作为 Dump 的响应发送出去
+struct DumpChunks {
+    tx: mpsc::Sender<Arc<CommandResponse>>,
+    buf: Vec<u8>,
+}
+
+impl DumpChunks {
+    fn new(tx: mpsc::Sender<Arc<CommandResponse>>) -> Self {
+        Self {
+            tx,
+            buf: Vec::with_capacity(DUMP_CHUNK_SIZE),
+        }
+    }
+
+    fn send(&mut self, len: usize) -> io::Result<()> {
+        let chunk = Bytes::copy_from_slice(&self.buf[..len]);
+        self.buf.drain(..len);
+        self.tx
+            .blocking_send(Arc::new(Value::from(chunk).into()))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "dump receiver is closed"))
+    }
+}
+
+impl io::Write for DumpChunks {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        while self.buf.len() >= DUMP_CHUNK_SIZE {
+            self.send(DUMP_CHUNK_SIZE)?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.send(self.buf.len())?;
+        }
+        Ok(())
+    }
+}
+
 fn dispatch_stream(cmd: CommandRequest, topic: impl Topic) -> StreamingResponse {
     match cmd.request_data {
         Some(RequestData::Subscribe(cmd)) => Box::pin(cmd.execute(topic)),
@@ -98,7 +240,10 @@ mod service_tests {
     use super::*;
     use crate::{
         memory::MemTable,
-        pb::abi::{command_request::RequestData, CommandRequest, CommandResponse, Kvpair, Value},
+        pb::abi::{
+            command_request::RequestData, CommandRequest, CommandResponse, IndexAction, Kvpair,
+            Value,
+        },
         Storage,
     };
 
@@ -137,6 +282,107 @@ mod service_tests {
         }
     }
 
+    #[test]
+    fn hdel_should_work() {
+        let store = MemTable::new();
+        let cmd = CommandRequest::new_hset("score", "u1", 10);
+        dispatch(cmd, &store);
+        let cmd = CommandRequest::new_hdel("score", "u1");
+        let res = dispatch(cmd.clone(), &store);
+        assert_res_ok(res, &[10.into()], &[]);
+
+        // 再次删除，key 已经不存在了
+        let res = dispatch(cmd, &store);
+        assert_res_ok(res, &[Value::default()], &[]);
+    }
+
+    #[test]
+    fn hmset_hmget_should_work() {
+        let store = MemTable::new();
+        dispatch(CommandRequest::new_hset("score", "u1", 10), &store);
+        let pairs = vec![Kvpair::new("u1", 11.into()), Kvpair::new("u2", 8.into())];
+        let res = dispatch(CommandRequest::new_hmset("score", pairs), &store);
+        assert_res_ok(res, &[10.into()], &[]);
+
+        let keys = vec!["u2".to_string(), "u1".to_string()];
+        let res = dispatch(CommandRequest::new_hmget("score", keys), &store);
+        assert_res_ok(res, &[8.into(), 11.into()], &[]);
+
+        let keys = vec!["u1".to_string(), "u3".to_string()];
+        let res = dispatch(CommandRequest::new_hmget("score", keys), &store);
+        assert_res_error(res, 404, "score:u3");
+    }
+
+    #[test]
+    fn hmdel_should_work() {
+        let store = MemTable::new();
+        dispatch(CommandRequest::new_hset("score", "u1", 10), &store);
+        let keys = vec!["u1".to_string(), "u2".to_string()];
+        let res = dispatch(CommandRequest::new_hmdel("score", keys), &store);
+        assert_res_ok(res, &[10.into()], &[]);
+    }
+
+    #[test]
+    fn hexist_should_work() {
+        let store = MemTable::new();
+        dispatch(CommandRequest::new_hset("score", "u1", 10), &store);
+        let res = dispatch(CommandRequest::new_hexist("score", "u1"), &store);
+        assert_res_ok(res, &[], &[]);
+        let res = dispatch(CommandRequest::new_hexist("score", "u2"), &store);
+        assert_res_error(res, 204, "");
+
+        let keys = vec!["u1".to_string(), "u2".to_string()];
+        let res = dispatch(CommandRequest::new_hmexist("score", keys), &store);
+        assert_res_error(res, 204, "10");
+    }
+
+    #[test]
+    fn hfind_should_work() {
+        let store = MemTable::new();
+        let user = |age: i64| Value::from(format!(r#"{{"age":{}}}"#, age));
+        for (k, age) in [("u1", 10), ("u2", 20), ("u3", 30)] {
+            dispatch(CommandRequest::new_hset("users", k, user(age)), &store);
+        }
+
+        // 没有索引时返回 404
+        let cmd = CommandRequest::new_hfind_eq("users", "age", 20.into());
+        let res = dispatch(cmd.clone(), &store);
+        assert_res_error(res, 404, "index users:age");
+
+        let res = dispatch(
+            CommandRequest::new_hindex("users", "$.age", IndexAction::Create),
+            &store,
+        );
+        assert_res_ok(res, &[true.into()], &[]);
+
+        let res = dispatch(cmd, &store);
+        assert_res_ok(res, &[], &[Kvpair::new("u2", user(20))]);
+
+        let cmd = CommandRequest::new_hfind_range("users", "age", Some(15.into()), None);
+        let res = dispatch(cmd, &store);
+        assert_res_ok(
+            res,
+            &[],
+            &[Kvpair::new("u2", user(20)), Kvpair::new("u3", user(30))],
+        );
+
+        // 二进制数据不能作为查询条件
+        let cmd = CommandRequest::new_hfind_eq("users", "age", b"20".into());
+        let res = dispatch(cmd, &store);
+        assert_res_error(res, 400, "Can't query index");
+
+        let res = dispatch(
+            CommandRequest::new_hindex("users", "age", IndexAction::Drop),
+            &store,
+        );
+        assert_res_ok(res, &[true.into()], &[]);
+        let res = dispatch(
+            CommandRequest::new_hindex("users", "age", IndexAction::Rebuild),
+            &store,
+        );
+        assert_res_error(res, 404, "index users:age");
+    }
+
     // 测试成功返回的结果
     fn assert_res_ok(mut res: CommandResponse, values: &[Value], pairs: &[Kvpair]) {
         res.pairs.sort_by(|a, b| a.partial_cmp(b).unwrap());
@@ -159,6 +405,16 @@ mod service_tests {
             RequestData::Hget(v) => v.execute(store),
             RequestData::Hgetall(v) => v.execute(store),
             RequestData::Hset(v) => v.execute(store),
+            RequestData::Hdel(v) => v.execute(store),
+            RequestData::Hmget(v) => v.execute(store),
+            RequestData::Hmset(v) => v.execute(store),
+            RequestData::Hmdel(v) => v.execute(store),
+            RequestData::Hexist(v) => v.execute(store),
+            RequestData::Hmexist(v) => v.execute(store),
+            RequestData::Hexpire(v) => v.execute(store),
+            RequestData::Restore(v) => v.execute(store),
+            RequestData::Hfind(v) => v.execute(store),
+            RequestData::Hindex(v) => v.execute(store),
             _ => todo!(),
         }
     }
@@ -166,13 +422,17 @@ mod service_tests {
 
 #[cfg(test)]
 mod service_tests_2 {
-    use std::thread;
+    use std::{thread, time::Duration};
 
     use futures::StreamExt;
 
+    use bytes::Bytes;
+
     use crate::{
-        assert_res_ok,
-        pb::abi::{CommandRequest, Value},
+        assert_res_error, assert_res_ok,
+        memory::MemTable,
+        pb::abi::{value, CommandRequest, Value},
+        sled_db::SledDB,
         Service,
     };
 
@@ -199,10 +459,97 @@ mod service_tests_2 {
         let res = res.next().await.unwrap();
         assert_res_ok(&res, &["v1".into()], &[]);
     }
+
+    #[tokio::test]
+    async fn dump_restore_should_work() {
+        let service: Service = ServiceBuilder::default().finish();
+        let value: Value = Bytes::from(vec![1u8; 100 * 1024]).into();
+        let res = service.execute(CommandRequest::new_hset("t1", "k1", value.clone()));
+        res.collect::<Vec<_>>().await;
+
+        // 快照大于一块，会分多次返回
+        let res: Vec<_> = service
+            .execute(CommandRequest::new_dump(""))
+            .collect()
+            .await;
+        assert_eq!(res.len(), 3);
+        assert_res_ok(res.last().unwrap(), &[], &[]);
+        let mut data = vec![];
+        for chunk in &res[..res.len() - 1] {
+            if let Some(value::Value::Binary(b)) = &chunk.values[0].value {
+                data.extend_from_slice(b);
+            }
+        }
+
+        let restored: Service = ServiceBuilder::default().finish();
+        let mut res = restored.execute(CommandRequest::new_restore(data));
+        assert_res_ok(&res.next().await.unwrap(), &[1.into()], &[]);
+        let mut res = restored.execute(CommandRequest::new_hget("t1", "k1"));
+        assert_res_ok(&res.next().await.unwrap(), &[value], &[]);
+    }
+
+    #[tokio::test]
+    async fn restore_corrupted_dump_should_fail() {
+        let service: Service = ServiceBuilder::default().finish();
+        let mut res = service.execute(CommandRequest::new_restore(&b"KVDUMP\x01\x02"[..]));
+        assert_res_error(&res.next().await.unwrap(), 400, "Dump error");
+    }
+
+    #[tokio::test]
+    async fn commands_should_wait_for_running_script() {
+        let service: Service = ServiceBuilder::default().finish();
+        let guard = service.scripts.write().await;
+
+        // 脚本持有写锁时，其它命令等待而不是阻塞线程
+        let cmd = CommandRequest::new_hset("t1", "k1", "v1");
+        let mut res = service.execute(cmd);
+        let timeout = tokio::time::timeout(Duration::from_millis(50), res.next()).await;
+        assert!(timeout.is_err());
+
+        drop(guard);
+        assert_res_ok(&res.next().await.unwrap(), &[Value::default()], &[]);
+        let cmd = CommandRequest::new_hget("t1", "k1");
+        let res = service.execute(cmd).next().await.unwrap();
+        assert_res_ok(&res, &["v1".into()], &[]);
+    }
+
+    #[tokio::test]
+    async fn hexpire_should_work() {
+        let service: Service = ServiceBuilder::new(MemTable::new()).finish();
+        let cmd = CommandRequest::new_hset("t1", "k1", "v1");
+        service.execute(cmd).next().await.unwrap();
+
+        let cmd = CommandRequest::new_hexpire("t1", "k1", Duration::from_millis(10));
+        let res = service.execute(cmd).next().await.unwrap();
+        assert_res_ok(&res, &[], &[]);
+        let cmd = CommandRequest::new_hexpire("t1", "k2", Duration::from_millis(10));
+        let res = service.execute(cmd).next().await.unwrap();
+        assert_eq!(res.status, 204);
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let cmd = CommandRequest::new_hexist("t1", "k1");
+        let res = service.execute(cmd).next().await.unwrap();
+        assert_eq!(res.status, 204);
+
+        // SledDB 不支持过期
+        let dir = tempfile::tempdir().unwrap();
+        let service: Service<SledDB> = ServiceBuilder::new(SledDB::new(&dir)).finish();
+        let cmd = CommandRequest::new_hexpire("t1", "k1", Duration::from_millis(10));
+        let res = service.execute(cmd).next().await.unwrap();
+        assert_res_error(&res, 400, "expire is not supported");
+    }
+
+    #[tokio::test]
+    async fn restore_chunk_without_stream_should_fail() {
+        let service: Service = ServiceBuilder::default().finish();
+        let cmd = CommandRequest::new_restore_chunk(&b"KVDUMP\x01"[..], true);
+        let mut res = service.execute(cmd);
+        assert_res_error(&res.next().await.unwrap(), 400, "chunked Restore");
+    }
 }
 
 #[cfg(test)]
-use crate::pb::abi::{Kvpair, Value};
+use crate::pb::abi::Kvpair;
 
 use self::{
     notify::{Notify, NotifyMut},
@@ -0,0 +1,353 @@
+use crate::{
+    config::ScriptConfig,
+    error::KvError,
+    pb::abi::{CommandResponse, Eval, Value},
+    Storage,
+};
+use dashmap::DashMap;
+use sha1::{Digest, Sha1};
+use std::sync::Arc;
+use tokio::sync::{OwnedRwLockWriteGuard, RwLock, RwLockReadGuard, TryLockError};
+
+/// 服务器端脚本：缓存执行过的脚本，以便通过 SHA 再次执行；
+/// 脚本执行时持有写锁，其它命令执行时持有读锁，所以脚本中的多个读写是原子的。
+/// 锁是异步的，等待脚本的命令不会占住 tokio 的工作线程
+#[derive(Debug, Default)]
+pub struct Scripts {
+    config: ScriptConfig,
+    /// <sha1, script>
+    cache: DashMap<String, String>,
+    lock: Arc<RwLock<()>>,
+}
+
+/// 脚本可以使用的 store 接口；Storage 不是 object safe 的，这里包装一下放进 isolate 的 slot 里
+pub trait ScriptStore: Send + Sync + 'static {
+    fn get(&self, table: &str, key: &str) -> Result<Option<Value>, KvError>;
+    fn set(&self, table: &str, key: &str, value: Value) -> Result<Option<Value>, KvError>;
+    fn del(&self, table: &str, key: &str) -> Result<Option<Value>, KvError>;
+}
+
+impl<S: Storage> ScriptStore for S {
+    fn get(&self, table: &str, key: &str) -> Result<Option<Value>, KvError> {
+        Storage::get(self, table, key)
+    }
+
+    fn set(&self, table: &str, key: &str, value: Value) -> Result<Option<Value>, KvError> {
+        Storage::set(self, table, key, value)
+    }
+
+    fn del(&self, table: &str, key: &str) -> Result<Option<Value>, KvError> {
+        Storage::del(self, table, key)
+    }
+}
+
+/// 脚本的 SHA1，客户端可以用它来执行已经缓存的脚本
+pub fn script_sha(script: &str) -> String {
+    format!("{:x}", Sha1::digest(script.as_bytes()))
+}
+
+impl Scripts {
+    pub fn new(config: ScriptConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// 执行普通命令前获取读锁，没有脚本在执行或等待时不需要等待
+    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, ()>, TryLockError> {
+        self.lock.try_read()
+    }
+
+    /// 等待正在执行的脚本结束后获取读锁
+    pub async fn read(&self) -> RwLockReadGuard<'_, ()> {
+        self.lock.read().await
+    }
+
+    /// 执行脚本前获取写锁；锁可以带到阻塞线程里，客户端断开时脚本执行完才会释放
+    pub async fn write(&self) -> OwnedRwLockWriteGuard<()> {
+        self.lock.clone().write_owned().await
+    }
+
+    /// 同步执行脚本，V8 会阻塞当前线程；调用者需要持有写锁，并在阻塞线程中调用
+    pub fn eval(&self, cmd: &Eval, store: Arc<dyn ScriptStore>) -> CommandResponse {
+        let script = if cmd.script.is_empty() {
+            match self.cache.get(&cmd.sha) {
+                Some(script) => script.clone(),
+                None => return KvError::NotFound(format!("script {}", cmd.sha)).into(),
+            }
+        } else {
+            self.cache
+                .insert(script_sha(&cmd.script), cmd.script.clone());
+            cmd.script.clone()
+        };
+
+        match run(&script, cmd, store, &self.config) {
+            Ok(v) => v.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+#[cfg(feature = "scripting")]
+use js::run;
+
+#[cfg(not(feature = "scripting"))]
+fn run(
+    _script: &str,
+    _cmd: &Eval,
+    _store: Arc<dyn ScriptStore>,
+    _config: &ScriptConfig,
+) -> Result<Value, KvError> {
+    Err(KvError::ScriptError(
+        "kv-db is built without the `scripting` feature".into(),
+    ))
+}
+
+/// 用 chrome-v8-live 的 JsRuntime 执行脚本，脚本通过全局的 kv.get/kv.set/kv.del 访问 store，
+/// 通过 ARGS 拿到参数；每次执行都使用新的 isolate，脚本之间互不影响
+#[cfg(feature = "scripting")]
+mod js {
+    use super::*;
+    use crate::pb::abi::value;
+    use chrome_v8_live::{serde_v8, v8, JsRuntime};
+    use std::time::Duration;
+
+    pub(super) fn run(
+        script: &str,
+        cmd: &Eval,
+        store: Arc<dyn ScriptStore>,
+        config: &ScriptConfig,
+    ) -> Result<Value, KvError> {
+        JsRuntime::init();
+        let mut runtime = JsRuntime::with_heap_limit(config.heap_limit);
+        runtime.register("kv", "get", kv_get);
+        runtime.register("kv", "set", kv_set);
+        runtime.register("kv", "del", kv_del);
+
+        // isolate 的 slot 只能存放 'static 的数据，所以 store 以 Arc 的形式传进来
+        runtime.isolate.set_slot(store);
+
+        let args: Vec<_> = cmd.args.iter().map(to_json).collect();
+        let args = serde_json::to_string(&args).map_err(|e| KvError::Internal(e.to_string()))?;
+        let timeout = Duration::from_millis(config.timeout);
+        runtime
+            .execute_script_with_timeout(format!("globalThis.ARGS = {};", args), timeout)
+            .map_err(script_error)?;
+        let result = runtime
+            .execute_script_with_timeout(script, timeout)
+            .map_err(script_error)?;
+        Ok(from_json(result))
+    }
+
+    fn script_error(e: serde_json::Value) -> KvError {
+        match e {
+            serde_json::Value::String(s) => KvError::ScriptError(s),
+            e => KvError::ScriptError(e.to_string()),
+        }
+    }
+
+    fn store(scope: &mut v8::HandleScope) -> Arc<dyn ScriptStore> {
+        scope.get_slot::<Arc<dyn ScriptStore>>().unwrap().clone()
+    }
+
+    fn arg<T: serde::de::DeserializeOwned>(
+        scope: &mut v8::HandleScope,
+        args: &v8::FunctionCallbackArguments,
+        i: i32,
+    ) -> Result<T, KvError> {
+        serde_v8::from_v8(scope, args.get(i))
+            .map_err(|e| KvError::ScriptError(format!("invalid argument {}: {}", i, e)))
+    }
+
+    /// 把 store 的结果返回给脚本，出错时在脚本中抛出异常
+    fn reply(
+        scope: &mut v8::HandleScope,
+        mut rv: v8::ReturnValue,
+        result: Result<Option<Value>, KvError>,
+    ) {
+        match result {
+            Ok(v) => {
+                let json = v.as_ref().map_or(serde_json::Value::Null, to_json);
+                rv.set(serde_v8::to_v8(scope, json).unwrap());
+            }
+            Err(e) => {
+                let msg = v8::String::new(scope, &e.to_string()).unwrap();
+                let exception = v8::Exception::error(scope, msg);
+                scope.throw_exception(exception);
+            }
+        }
+    }
+
+    fn kv_get(
+        scope: &mut v8::HandleScope,
+        args: v8::FunctionCallbackArguments,
+        rv: v8::ReturnValue,
+    ) {
+        let result = (|| {
+            let table: String = arg(scope, &args, 0)?;
+            let key: String = arg(scope, &args, 1)?;
+            store(scope).get(&table, &key)
+        })();
+        reply(scope, rv, result);
+    }
+
+    fn kv_set(
+        scope: &mut v8::HandleScope,
+        args: v8::FunctionCallbackArguments,
+        rv: v8::ReturnValue,
+    ) {
+        let result = (|| {
+            let table: String = arg(scope, &args, 0)?;
+            let key: String = arg(scope, &args, 1)?;
+            let value: serde_json::Value = arg(scope, &args, 2)?;
+            store(scope).set(&table, &key, from_json(value))
+        })();
+        reply(scope, rv, result);
+    }
+
+    fn kv_del(
+        scope: &mut v8::HandleScope,
+        args: v8::FunctionCallbackArguments,
+        rv: v8::ReturnValue,
+    ) {
+        let result = (|| {
+            let table: String = arg(scope, &args, 0)?;
+            let key: String = arg(scope, &args, 1)?;
+            store(scope).del(&table, &key)
+        })();
+        reply(scope, rv, result);
+    }
+
+    /// Value 转换成脚本中的值，二进制数据转换成字节数组
+    fn to_json(v: &Value) -> serde_json::Value {
+        match &v.value {
+            Some(value::Value::String(s)) => s.as_str().into(),
+            Some(value::Value::Binary(b)) => b.to_vec().into(),
+            Some(value::Value::Integer(i)) => (*i).into(),
+            Some(value::Value::Float(f)) => (*f).into(),
+            Some(value::Value::Bool(b)) => (*b).into(),
+            None => serde_json::Value::Null,
+        }
+    }
+
+    /// 脚本中的值转换成 Value，数组和对象以 JSON 字符串保存，这样也可以为它们建立二级索引
+    fn from_json(v: serde_json::Value) -> Value {
+        match v {
+            serde_json::Value::Null => Value::default(),
+            serde_json::Value::Bool(b) => b.into(),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => i.into(),
+                None => Value {
+                    value: n.as_f64().map(value::Value::Float),
+                },
+            },
+            serde_json::Value::String(s) => s.into(),
+            v => v.to_string().into(),
+        }
+    }
+
+    #[cfg(test)]
+    mod js_tests {
+        use super::*;
+        use crate::{memory::MemTable, pb::abi::CommandRequest};
+
+        fn eval(scripts: &Scripts, store: &Arc<MemTable>, script: &str, args: Vec<Value>) -> Value {
+            let res = scripts.eval(
+                &Eval {
+                    script: script.into(),
+                    sha: "".into(),
+                    args,
+                },
+                store.clone(),
+            );
+            assert_eq!(res.status, 200, "{}", res.message);
+            res.values[0].clone()
+        }
+
+        #[test]
+        fn eval_should_access_store() {
+            let scripts = Scripts::default();
+            let store = Arc::new(MemTable::new());
+            Storage::set(&*store, "t1", "counter", 41.into()).unwrap();
+            let script = r#"
+                const n = kv.get("t1", "counter") + ARGS[0];
+                kv.set("t1", "counter", n);
+                kv.set("t1", "doc", { n });
+                n
+            "#;
+            assert_eq!(eval(&scripts, &store, script, vec![1.into()]), 42.into());
+            let get = |key| Storage::get(&*store, "t1", key).unwrap();
+            assert_eq!(get("counter"), Some(42.into()));
+            assert_eq!(get("doc"), Some(r#"{"n":42}"#.into()));
+        }
+
+        #[test]
+        fn script_error_should_be_returned() {
+            let scripts = Scripts::default();
+            let res = scripts.eval(
+                &Eval {
+                    script: "throw new Error('boom')".into(),
+                    ..Default::default()
+                },
+                Arc::new(MemTable::new()),
+            );
+            assert_eq!(res.status, 400);
+            assert!(res.message.contains("boom"));
+        }
+
+        #[test]
+        fn script_timeout_should_work() {
+            let scripts = Scripts::new(ScriptConfig {
+                timeout: 50,
+                ..Default::default()
+            });
+            let cmd = CommandRequest::new_eval("while (true) {}", vec![]);
+            let Some(crate::pb::abi::command_request::RequestData::Eval(cmd)) = cmd.request_data
+            else {
+                unreachable!()
+            };
+            let res = scripts.eval(&cmd, Arc::new(MemTable::new()));
+            assert_eq!(res.status, 400);
+            assert!(res.message.contains("timed out"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod script_tests {
+    use super::*;
+    use crate::memory::MemTable;
+
+    #[test]
+    fn script_sha_should_work() {
+        assert_eq!(
+            script_sha("return 1"),
+            "e0e1f9fabfc9d4800c877a703b823ac0578ff8db"
+        );
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    #[test]
+    fn eval_without_scripting_feature_should_fail() {
+        let scripts = Scripts::default();
+        let cmd = Eval {
+            script: "return 1".into(),
+            ..Default::default()
+        };
+        let res = scripts.eval(&cmd, Arc::new(MemTable::new()));
+        assert_eq!(res.status, 400);
+        assert!(res.message.contains("scripting"));
+    }
+
+    #[test]
+    fn evalsha_with_unknown_script_should_fail() {
+        let scripts = Scripts::default();
+        let cmd = Eval {
+            sha: script_sha("return 1"),
+            ..Default::default()
+        };
+        let res = scripts.eval(&cmd, Arc::new(MemTable::new()));
+        assert_eq!(res.status, 404);
+    }
+}
@@ -1,6 +1,8 @@
 use std::sync::Arc;
 
+use super::{limit::RateLimiter, script::Scripts};
 use crate::{
+    config::{LimitsConfig, ScriptConfig},
     memory::MemTable,
     pb::abi::{CommandRequest, CommandResponse},
     Service, Storage,
@@ -16,6 +18,10 @@ pub struct ServiceBuilder<Store> {
     pub on_before_send: Vec<fn(&mut CommandResponse)>,
     /// 在服务器发送完 CommandResponse 后触发
     pub on_after_send: Vec<fn()>,
+    /// 连接和认证主体的限流
+    pub limiter: RateLimiter,
+    /// Eval 执行的脚本
+    pub scripts: Scripts,
 }
 
 impl<Store: Storage> ServiceBuilder<Store> {
@@ -26,6 +32,8 @@ impl<Store: Storage> ServiceBuilder<Store> {
             on_executed: Vec::new(),
             on_before_send: Vec::new(),
             on_after_send: Vec::new(),
+            limiter: RateLimiter::default(),
+            scripts: Scripts::default(),
         }
     }
 
@@ -46,6 +54,16 @@ impl<Store: Storage> ServiceBuilder<Store> {
         self
     }
 
+    pub fn rate_limit(mut self, config: &LimitsConfig) -> Self {
+        self.limiter = RateLimiter::new(config);
+        self
+    }
+
+    pub fn scripting(mut self, config: ScriptConfig) -> Self {
+        self.scripts = Scripts::new(config);
+        self
+    }
+
     pub fn finish(self) -> Service<Store> {
         Service {
             inner: Arc::new(self),
@@ -70,6 +88,8 @@ impl Default for ServiceBuilder<MemTable> {
             on_executed: Default::default(),
             on_before_send: Default::default(),
             on_after_send: Default::default(),
+            limiter: Default::default(),
+            scripts: Default::default(),
         }
     }
 }
@@ -111,7 +131,7 @@ mod builder_tests {
 
         let mut res = service.execute(CommandRequest::new_hset("t1", "k1", "v1"));
         let res = res.next().await.unwrap().as_ref().to_owned();
-        assert_eq!(res.status, StatusCode::CREATED.as_u16() as _);
+        assert_eq!(res.status, StatusCode::CREATED.as_u16() as u32);
         assert_eq!(res.message, "");
         assert_eq!(res.values, vec![Value::default()]);
     }
@@ -0,0 +1,338 @@
+use crate::config::EvictionPolicy;
+use dashmap::DashMap;
+use indexmap::IndexSet;
+use rand::seq::index;
+use std::{
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, MutexGuard,
+    },
+    time::Instant,
+};
+
+/// (table, key)
+pub type EntryKey = (String, String);
+
+/// 和 Redis 的 maxmemory-samples 一样，每次淘汰时随机采样的 key 的数量
+pub const DEFAULT_SAMPLES: usize = 5;
+
+/// MemTable 的内存上限：记录每个 key 占用的内存以及访问情况，超过上限时按照策略选出要淘汰的 key。
+/// 占用的内存按照 key 和 protobuf 编码后的 value 的长度估算
+#[derive(Debug)]
+pub struct MemoryLimit {
+    maxmemory: usize,
+    policy: EvictionPolicy,
+    used: AtomicUsize,
+    /// 逻辑时钟，每次访问加一，用于 LRU
+    clock: AtomicU64,
+    entries: DashMap<EntryKey, EntryMeta>,
+    /// 每次淘汰时采样的 key 的数量
+    samples: usize,
+    /// 所有的 key，用于随机采样
+    keys: KeySet,
+    /// 设置了过期时间的 key，volatile-ttl 从这里采样
+    volatile: KeySet,
+}
+
+/// 可以随机采样的 key 集合，只在 key 增删时加锁，读取不会用到
+#[derive(Debug, Default)]
+struct KeySet(Mutex<IndexSet<EntryKey>>);
+
+#[derive(Debug, Clone, Copy)]
+struct EntryMeta {
+    size: usize,
+    last_access: u64,
+    hits: u64,
+}
+
+impl MemoryLimit {
+    pub fn new(maxmemory: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            maxmemory,
+            policy,
+            used: AtomicUsize::new(0),
+            clock: AtomicU64::new(0),
+            entries: DashMap::new(),
+            samples: DEFAULT_SAMPLES,
+            keys: KeySet::default(),
+            volatile: KeySet::default(),
+        }
+    }
+
+    /// 设置每次淘汰时采样的 key 的数量，越大越接近精确的 LRU/LFU，但淘汰得越慢
+    pub fn samples(mut self, samples: usize) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    pub fn maxmemory(&self) -> usize {
+        self.maxmemory
+    }
+
+    /// 目前估算使用的内存
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed)
+    }
+
+    /// 把 (table, key) 的大小改成 size 之后，超出上限多少字节
+    pub fn overflow(&self, table: &str, key: &str, size: usize) -> usize {
+        let old = self
+            .entries
+            .get(&entry_key(table, key))
+            .map_or(0, |m| m.size);
+        (self.used() + size).saturating_sub(old + self.maxmemory)
+    }
+
+    /// 写入之后记录 key 的大小
+    pub fn insert(&self, table: &str, key: &str, size: usize) {
+        let now = self.tick();
+        let meta = EntryMeta {
+            size,
+            last_access: now,
+            hits: 1,
+        };
+        let key = entry_key(table, key);
+        match self.entries.insert(key.clone(), meta) {
+            Some(old) => {
+                self.used.fetch_sub(old.size, Ordering::Relaxed);
+            }
+            None => self.keys.insert(key),
+        };
+        self.used.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// 删除之后去掉 key 的记录
+    pub fn remove(&self, table: &str, key: &str) {
+        let key = entry_key(table, key);
+        if let Some((_, old)) = self.entries.remove(&key) {
+            self.used.fetch_sub(old.size, Ordering::Relaxed);
+            self.keys.remove(&key);
+        }
+        self.volatile.remove(&key);
+    }
+
+    /// 记录 key 设置了过期时间
+    pub fn expire(&self, table: &str, key: &str) {
+        self.volatile.insert(entry_key(table, key));
+    }
+
+    /// 记录 key 的过期时间被清除
+    pub fn persist(&self, table: &str, key: &str) {
+        self.volatile.remove(&entry_key(table, key));
+    }
+
+    /// 记录一次读取
+    pub fn touch(&self, table: &str, key: &str) {
+        let now = self.tick();
+        if let Some(mut meta) = self.entries.get_mut(&entry_key(table, key)) {
+            meta.last_access = now;
+            meta.hits += 1;
+        }
+    }
+
+    /// 按照淘汰策略选出一个 key，skip 是正在写入的 key，不能被淘汰；
+    /// 和 Redis 一样只在随机采样的 samples 个 key 中选择，所以是近似的 LRU/LFU
+    pub fn victim(
+        &self,
+        expires: &DashMap<EntryKey, Instant>,
+        skip: (&str, &str),
+    ) -> Option<EntryKey> {
+        let meta = |k: &EntryKey| self.entries.get(k).map(|m| *m);
+        match self.policy {
+            EvictionPolicy::NoEviction => None,
+            EvictionPolicy::Lru => self
+                .keys
+                .sample(self.samples, skip)
+                .into_iter()
+                .filter_map(|k| Some((meta(&k)?.last_access, k)))
+                .min()
+                .map(|(_, k)| k),
+            EvictionPolicy::Lfu => self
+                .keys
+                .sample(self.samples, skip)
+                .into_iter()
+                .filter_map(|k| meta(&k).map(|m| ((m.hits, m.last_access), k)))
+                .min()
+                .map(|(_, k)| k),
+            EvictionPolicy::VolatileTtl => self
+                .volatile
+                .sample(self.samples, skip)
+                .into_iter()
+                .filter_map(|k| Some((*expires.get(&k)?, k)))
+                .min()
+                .map(|(_, k)| k),
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+/// MemTable 可以 clone，clone 出来的 MemTable 有自己的内存记录
+impl Clone for MemoryLimit {
+    fn clone(&self) -> Self {
+        Self {
+            maxmemory: self.maxmemory,
+            policy: self.policy,
+            used: AtomicUsize::new(self.used()),
+            clock: AtomicU64::new(self.clock.load(Ordering::Relaxed)),
+            entries: self.entries.clone(),
+            samples: self.samples,
+            keys: KeySet(Mutex::new(self.keys.lock().clone())),
+            volatile: KeySet(Mutex::new(self.volatile.lock().clone())),
+        }
+    }
+}
+
+impl KeySet {
+    fn insert(&self, key: EntryKey) {
+        self.lock().insert(key);
+    }
+
+    fn remove(&self, key: &EntryKey) {
+        self.lock().swap_remove(key);
+    }
+
+    /// 随机取出最多 n 个不同的 key，不包括 skip
+    fn sample(&self, n: usize, skip: (&str, &str)) -> Vec<EntryKey> {
+        let keys = self.lock();
+        let is_skipped = |k: &EntryKey| (k.0.as_str(), k.1.as_str()) == skip;
+        if keys.len() <= n {
+            return keys.iter().filter(|k| !is_skipped(k)).cloned().collect();
+        }
+
+        let mut sampled: Vec<_> = index::sample(&mut rand::thread_rng(), keys.len(), n)
+            .into_iter()
+            .filter_map(|i| keys.get_index(i))
+            .filter(|&k| !is_skipped(k))
+            .cloned()
+            .collect();
+        // 只采样了一个 key 并且正好是 skip 时，换成它后面的 key
+        if sampled.is_empty() {
+            if let Some(i) = keys.iter().position(is_skipped) {
+                sampled.extend(keys.get_index((i + 1) % keys.len()).cloned());
+            }
+        }
+        sampled
+    }
+
+    fn lock(&self) -> MutexGuard<'_, IndexSet<EntryKey>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+pub fn entry_key(table: &str, key: &str) -> EntryKey {
+    (table.into(), key.into())
+}
+
+#[cfg(test)]
+mod eviction_tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn prepare(policy: EvictionPolicy) -> MemoryLimit {
+        let limit = MemoryLimit::new(30, policy);
+        limit.insert("t", "k1", 10);
+        limit.insert("t", "k2", 10);
+        limit.insert("t", "k3", 10);
+        limit
+    }
+
+    #[test]
+    fn overflow_should_work() {
+        let limit = prepare(EvictionPolicy::Lru);
+        assert_eq!(limit.used(), 30);
+        // 覆盖同样大小的 value 不会超出
+        assert_eq!(limit.overflow("t", "k1", 10), 0);
+        assert_eq!(limit.overflow("t", "k1", 15), 5);
+        assert_eq!(limit.overflow("t", "k4", 1), 1);
+
+        limit.remove("t", "k1");
+        assert_eq!(limit.used(), 20);
+        assert_eq!(limit.overflow("t", "k4", 10), 0);
+    }
+
+    #[test]
+    fn lru_victim_should_be_least_recently_used() {
+        let limit = prepare(EvictionPolicy::Lru);
+        limit.touch("t", "k1");
+        let expires = DashMap::new();
+        assert_eq!(
+            limit.victim(&expires, ("t", "k4")),
+            Some(entry_key("t", "k2"))
+        );
+        assert_eq!(
+            limit.victim(&expires, ("t", "k2")),
+            Some(entry_key("t", "k3"))
+        );
+    }
+
+    #[test]
+    fn lfu_victim_should_be_least_frequently_used() {
+        let limit = prepare(EvictionPolicy::Lfu);
+        limit.touch("t", "k1");
+        limit.touch("t", "k1");
+        limit.touch("t", "k2");
+        let expires = DashMap::new();
+        assert_eq!(
+            limit.victim(&expires, ("t", "k4")),
+            Some(entry_key("t", "k3"))
+        );
+    }
+
+    #[test]
+    fn volatile_ttl_victim_should_expire_first() {
+        let limit = prepare(EvictionPolicy::VolatileTtl);
+        let expires = DashMap::new();
+        // 没有设置过期时间的 key 不会被淘汰
+        assert_eq!(limit.victim(&expires, ("t", "k4")), None);
+
+        let now = Instant::now();
+        expires.insert(entry_key("t", "k1"), now + Duration::from_secs(20));
+        expires.insert(entry_key("t", "k3"), now + Duration::from_secs(10));
+        limit.expire("t", "k1");
+        limit.expire("t", "k3");
+        assert_eq!(
+            limit.victim(&expires, ("t", "k4")),
+            Some(entry_key("t", "k3"))
+        );
+    }
+
+    #[test]
+    fn victim_should_be_sampled() {
+        let limit = MemoryLimit::new(1000, EvictionPolicy::Lru).samples(1);
+        for i in 0..100 {
+            limit.insert("t", &format!("k{}", i), 10);
+        }
+        let expires = DashMap::new();
+        // 只采样一个 key，但不会选中正在写入的 key
+        for _ in 0..100 {
+            let victim = limit.victim(&expires, ("t", "k0")).unwrap();
+            assert_ne!(victim, entry_key("t", "k0"));
+        }
+        let limit = MemoryLimit::new(20, EvictionPolicy::Lru).samples(1);
+        limit.insert("t", "k1", 10);
+        limit.insert("t", "k2", 10);
+        for _ in 0..10 {
+            assert_eq!(
+                limit.victim(&expires, ("t", "k1")),
+                Some(entry_key("t", "k2"))
+            );
+        }
+
+        // 删除的 key 不会再被采样
+        limit.remove("t", "k2");
+        assert_eq!(limit.victim(&expires, ("t", "k1")), None);
+    }
+
+    #[test]
+    fn noeviction_should_not_choose_victim() {
+        let limit = prepare(EvictionPolicy::NoEviction);
+        assert_eq!(limit.victim(&DashMap::new(), ("t", "k4")), None);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::{
+    error::KvError,
+    pb::abi::{value, Kvpair, Value},
+};
+use dashmap::DashMap;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet},
+    ops::Bound,
+};
+
+/// 二级索引的 key：JSON 中的标量值
+#[derive(Debug, Clone)]
+pub enum IndexKey {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+}
+
+/// 二级索引的查询条件
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexQuery {
+    /// 等于
+    Eq(IndexKey),
+    /// 闭区间 [min, max]，不设置则不限
+    Range {
+        min: Option<IndexKey>,
+        max: Option<IndexKey>,
+    },
+}
+
+/// 一个 JSON path 上的索引：索引值 -> 所有对应的 key
+#[derive(Debug, Default, Clone)]
+struct Index {
+    entries: BTreeMap<IndexKey, BTreeSet<String>>,
+}
+
+/// 所有 table 的二级索引；Value::String 中存放的 JSON 文档会按照 JSON path 建立索引，
+/// 由 Storage 在每次 set/del 时调用 update 来保持索引和数据一致
+#[derive(Debug, Default, Clone)]
+pub struct Indexes {
+    /// <table, <JSON path, Index>>
+    tables: DashMap<String, DashMap<String, Index>>,
+}
+
+impl Indexes {
+    /// 为 table 的 JSON path 建立索引，用 pairs 初始化；索引已经存在返回 false
+    pub fn create(&self, table: &str, path: &str, pairs: impl Iterator<Item = Kvpair>) -> bool {
+        let path = normalize(path);
+        let indexes = self.tables.entry(table.into()).or_default();
+        if indexes.contains_key(path) {
+            return false;
+        }
+        indexes.insert(path.into(), Index::build(path, pairs));
+        true
+    }
+
+    /// 删除索引，索引不存在返回 false
+    pub fn remove(&self, table: &str, path: &str) -> bool {
+        let path = normalize(path);
+        match self.tables.get(table) {
+            Some(indexes) => indexes.remove(path).is_some(),
+            None => false,
+        }
+    }
+
+    /// table 上所有索引的 JSON path
+    pub fn paths(&self, table: &str) -> Vec<String> {
+        match self.tables.get(table) {
+            Some(indexes) => indexes.iter().map(|i| i.key().clone()).collect(),
+            None => vec![],
+        }
+    }
+
+    /// 用 pairs 重新建立索引
+    pub fn rebuild(
+        &self,
+        table: &str,
+        path: &str,
+        pairs: impl Iterator<Item = Kvpair>,
+    ) -> Result<(), KvError> {
+        let path = normalize(path);
+        let indexes = self.tables.get(table);
+        let result = match indexes.as_ref().and_then(|i| i.get_mut(path)) {
+            Some(mut index) => {
+                *index = Index::build(path, pairs);
+                Ok(())
+            }
+            None => Err(index_not_found(table, path)),
+        };
+        result
+    }
+
+    /// 数据从 old 变成 new 时更新 table 上的所有索引
+    pub fn update(&self, table: &str, key: &str, old: Option<&Value>, new: Option<&Value>) {
+        let Some(indexes) = self.tables.get(table) else {
+            return;
+        };
+        for mut index in indexes.iter_mut() {
+            let path = index.key().clone();
+            if let Some(old) = old {
+                index.remove(&path, key, old);
+            }
+            if let Some(new) = new {
+                index.insert(&path, key, new);
+            }
+        }
+    }
+
+    /// 查询索引，返回满足条件的 key
+    pub fn find(
+        &self,
+        table: &str,
+        path: &str,
+        query: &IndexQuery,
+    ) -> Result<Vec<String>, KvError> {
+        let path = normalize(path);
+        let indexes = self.tables.get(table);
+        let result = match indexes.as_ref().and_then(|i| i.get(path)) {
+            Some(index) => Ok(index.find(query)),
+            None => Err(index_not_found(table, path)),
+        };
+        result
+    }
+}
+
+impl Index {
+    fn build(path: &str, pairs: impl Iterator<Item = Kvpair>) -> Self {
+        let mut index = Self::default();
+        for pair in pairs {
+            if let Some(value) = &pair.value {
+                index.insert(path, &pair.key, value);
+            }
+        }
+        index
+    }
+
+    fn insert(&mut self, path: &str, key: &str, value: &Value) {
+        for k in index_keys(path, value) {
+            self.entries.entry(k).or_default().insert(key.into());
+        }
+    }
+
+    fn remove(&mut self, path: &str, key: &str, value: &Value) {
+        for k in index_keys(path, value) {
+            if let Some(keys) = self.entries.get_mut(&k) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.entries.remove(&k);
+                }
+            }
+        }
+    }
+
+    fn find(&self, query: &IndexQuery) -> Vec<String> {
+        let (min, max) = match query {
+            IndexQuery::Eq(k) => (Bound::Included(k), Bound::Included(k)),
+            IndexQuery::Range { min, max } => (
+                min.as_ref().map_or(Bound::Unbounded, Bound::Included),
+                max.as_ref().map_or(Bound::Unbounded, Bound::Included),
+            ),
+        };
+        // BTreeMap::range 在 min > max 时会 panic
+        if let (Bound::Included(min), Bound::Included(max)) = (min, max) {
+            if min > max {
+                return vec![];
+            }
+        }
+
+        let keys: BTreeSet<_> = self
+            .entries
+            .range((min, max))
+            .flat_map(|(_, keys)| keys.iter().cloned())
+            .collect();
+        keys.into_iter().collect()
+    }
+}
+
+/// 从 Value 中取出 JSON path 对应的索引值；JSON 数组中的每个标量都会被索引
+fn index_keys(path: &str, value: &Value) -> Vec<IndexKey> {
+    let Some(value::Value::String(s)) = &value.value else {
+        return vec![];
+    };
+    let Ok(doc) = serde_json::from_str::<serde_json::Value>(s) else {
+        return vec![];
+    };
+
+    match json_path_get(&doc, path) {
+        Some(serde_json::Value::Array(items)) => {
+            items.iter().filter_map(IndexKey::from_json).collect()
+        }
+        Some(v) => IndexKey::from_json(v).into_iter().collect(),
+        None => vec![],
+    }
+}
+
+/// `$.user.age` 和 `user.age` 是同一个 JSON path
+fn normalize(path: &str) -> &str {
+    path.trim_start_matches('$').trim_start_matches('.')
+}
+
+/// 支持 `$.user.name`、`user.tags.0` 这样以 `.` 分隔的简单 JSON path
+pub fn json_path_get<'a>(doc: &'a serde_json::Value, path: &str) -> Option<&'a serde_json::Value> {
+    let path = normalize(path);
+    if path.is_empty() {
+        return Some(doc);
+    }
+    path.split('.').try_fold(doc, |v, seg| match v {
+        serde_json::Value::Object(map) => map.get(seg),
+        serde_json::Value::Array(items) => items.get(seg.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn index_not_found(table: &str, path: &str) -> KvError {
+    KvError::NotFound(format!("index {}:{}", table, path))
+}
+
+impl IndexKey {
+    fn from_json(v: &serde_json::Value) -> Option<Self> {
+        match v {
+            serde_json::Value::Null => Some(Self::Null),
+            serde_json::Value::Bool(b) => Some(Self::Bool(*b)),
+            serde_json::Value::Number(n) => n.as_f64().map(Self::Number),
+            serde_json::Value::String(s) => Some(Self::String(s.clone())),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Null => 0,
+            Self::Bool(_) => 1,
+            Self::Number(_) => 2,
+            Self::String(_) => 3,
+        }
+    }
+}
+
+impl TryFrom<&Value> for IndexKey {
+    type Error = KvError;
+
+    fn try_from(v: &Value) -> Result<Self, Self::Error> {
+        match &v.value {
+            Some(value::Value::String(s)) => Ok(Self::String(s.clone())),
+            Some(value::Value::Integer(i)) => Ok(Self::Number(*i as f64)),
+            Some(value::Value::Float(f)) => Ok(Self::Number(*f)),
+            Some(value::Value::Bool(b)) => Ok(Self::Bool(*b)),
+            None => Ok(Self::Null),
+            Some(value::Value::Binary(_)) => Err(KvError::ConvertError(v.clone(), "IndexKey")),
+        }
+    }
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Bool(a), Self::Bool(b)) => a.cmp(b),
+            (Self::Number(a), Self::Number(b)) => a.total_cmp(b),
+            (Self::String(a), Self::String(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+#[cfg(test)]
+mod index_tests {
+    use super::*;
+
+    fn doc(name: &str, age: i64, tags: &[&str]) -> Value {
+        serde_json::json!({ "user": { "name": name, "age": age }, "tags": tags })
+            .to_string()
+            .into()
+    }
+
+    fn prepare() -> Indexes {
+        let indexes = Indexes::default();
+        let pairs = vec![
+            Kvpair::new("u1", doc("alice", 20, &["a"])),
+            Kvpair::new("u2", doc("bob", 30, &["a", "b"])),
+            Kvpair::new("u3", "not json".into()),
+        ];
+        assert!(indexes.create("users", "$.user.age", pairs.clone().into_iter()));
+        assert!(indexes.create("users", "tags", pairs.into_iter()));
+        indexes
+    }
+
+    fn range(min: Option<i64>, max: Option<i64>) -> IndexQuery {
+        IndexQuery::Range {
+            min: min.map(|v| IndexKey::Number(v as _)),
+            max: max.map(|v| IndexKey::Number(v as _)),
+        }
+    }
+
+    #[test]
+    fn index_find_should_work() {
+        let indexes = prepare();
+        let eq = IndexQuery::Eq(IndexKey::Number(20.0));
+        assert_eq!(indexes.find("users", "$.user.age", &eq).unwrap(), ["u1"]);
+
+        let r = range(Some(25), None);
+        assert_eq!(indexes.find("users", "$.user.age", &r).unwrap(), ["u2"]);
+        let r = range(None, Some(30));
+        assert_eq!(
+            indexes.find("users", "$.user.age", &r).unwrap(),
+            ["u1", "u2"]
+        );
+        let r = range(Some(40), Some(10));
+        assert!(indexes.find("users", "$.user.age", &r).unwrap().is_empty());
+
+        // 数组中的每个元素都会被索引
+        let eq = IndexQuery::Eq(IndexKey::String("a".into()));
+        assert_eq!(indexes.find("users", "tags", &eq).unwrap(), ["u1", "u2"]);
+
+        // 没有索引的 path 返回 NotFound
+        assert!(indexes.find("users", "user.name", &eq).is_err());
+        assert!(indexes.remove("users", "tags"));
+        assert!(indexes.find("users", "tags", &eq).is_err());
+    }
+
+    #[test]
+    fn index_update_should_work() {
+        let indexes = prepare();
+        let old = doc("alice", 20, &["a"]);
+        let new = doc("alice", 40, &["c"]);
+        indexes.update("users", "u1", Some(&old), Some(&new));
+
+        let r = range(Some(35), None);
+        assert_eq!(indexes.find("users", "user.age", &r).unwrap(), ["u1"]);
+        let eq = IndexQuery::Eq(IndexKey::String("a".into()));
+        assert_eq!(indexes.find("users", "tags", &eq).unwrap(), ["u2"]);
+
+        // 删除
+        indexes.update("users", "u2", Some(&doc("bob", 30, &["a", "b"])), None);
+        assert!(indexes.find("users", "tags", &eq).unwrap().is_empty());
+    }
+
+    #[test]
+    fn json_path_get_should_work() {
+        let doc = serde_json::json!({ "a": { "b": [1, { "c": true }] } });
+        assert_eq!(json_path_get(&doc, "a.b.0"), Some(&serde_json::json!(1)));
+        assert_eq!(
+            json_path_get(&doc, "$.a.b.1.c"),
+            Some(&serde_json::json!(true))
+        );
+        assert_eq!(json_path_get(&doc, "a.x"), None);
+    }
+}
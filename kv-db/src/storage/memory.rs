@@ -1,22 +1,144 @@
-use super::Storage;
+use super::{
+    entry_size,
+    eviction::{entry_key, EntryKey, MemoryLimit},
+    index::Indexes,
+    Storage,
+};
 use crate::{
+    config::EvictionPolicy,
     error::KvError,
     pb::abi::{Kvpair, Value},
     StorageIter,
 };
-use dashmap::{mapref::one::Ref, DashMap};
+use dashmap::{
+    mapref::{entry::Entry, one::Ref},
+    DashMap,
+};
+use std::{
+    sync::{Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
 
 /// Memory DB
 #[derive(Debug, Default, Clone)]
 pub struct MemTable {
     tables: DashMap<String, DashMap<String, Value>>,
+    indexes: Indexes,
+    /// 设置了过期时间的 key，读取时发现过期才删除
+    expires: DashMap<EntryKey, Instant>,
+    /// 内存上限，None 表示不限制
+    limit: Option<MemoryLimit>,
+    /// 淘汰或者过期删掉的 key
+    removed: RemovedKeys,
 }
 
+/// 开启记录之后保存 MemTable 自己删掉的 (table, 占用的字节数)，等待 take_removed 取走
+#[derive(Debug, Default)]
+struct RemovedKeys(Mutex<Option<Vec<(String, usize)>>>);
+
 impl MemTable {
     pub fn new() -> Self {
         Self::default()
     }
 
+    /// 最多使用 maxmemory 字节，超过之后按照 policy 淘汰 key
+    pub fn with_maxmemory(maxmemory: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            limit: Some(MemoryLimit::new(maxmemory, policy)),
+            ..Default::default()
+        }
+    }
+
+    /// 每次淘汰时随机采样的 key 的数量，默认是 5
+    pub fn maxmemory_samples(mut self, samples: usize) -> Self {
+        self.limit = self.limit.map(|l| l.samples(samples));
+        self
+    }
+
+    /// 估算的内存使用量，没有设置内存上限时返回 None
+    pub fn used_memory(&self) -> Option<usize> {
+        self.limit.as_ref().map(|l| l.used())
+    }
+
+    fn is_expired(&self, table: &str, key: &str) -> bool {
+        if self.expires.is_empty() {
+            return false;
+        }
+        let deadline = self.expires.get(&entry_key(table, key)).map(|t| *t);
+        matches!(deadline, Some(t) if t <= Instant::now())
+    }
+
+    /// 删除 table 中所有过期的 key
+    fn remove_expired(&self, table: &str) {
+        if self.expires.is_empty() {
+            return;
+        }
+        let now = Instant::now();
+        let expired: Vec<_> = self
+            .expires
+            .iter()
+            .filter(|e| e.key().0 == table && *e.value() <= now)
+            .map(|e| e.key().1.clone())
+            .collect();
+        for key in expired {
+            self.evict(table, &key);
+        }
+    }
+
+    /// 淘汰或者过期删除 key，调用者拿不到旧的值，需要记录下来
+    fn evict(&self, table: &str, key: &str) {
+        if let Some(old) = self.remove(table, key) {
+            self.removed.push(table, entry_size(key, &old));
+        }
+    }
+
+    /// 删除 key，同时更新索引、过期时间和内存记录
+    fn remove(&self, table: &str, key: &str) -> Option<Value> {
+        // 持有 key 所在分片的写锁更新索引，避免和同一个 key 上的 set 交错
+        let old = self
+            .tables
+            .get(table)
+            .and_then(|t| match t.entry(key.into()) {
+                Entry::Occupied(entry) => {
+                    self.indexes.update(table, key, Some(entry.get()), None);
+                    Some(entry.remove())
+                }
+                Entry::Vacant(_) => None,
+            });
+        if !self.expires.is_empty() {
+            self.expires.remove(&entry_key(table, key));
+        }
+        if let Some(limit) = &self.limit {
+            limit.remove(table, key);
+        }
+        old
+    }
+
+    /// 写入 size 字节之前，淘汰 key 直到内存足够；无法淘汰时返回 OutOfMemory
+    fn reserve(
+        &self,
+        limit: &MemoryLimit,
+        table: &str,
+        key: &str,
+        size: usize,
+    ) -> Result<(), KvError> {
+        while limit.overflow(table, key, size) > 0 {
+            match limit.victim(&self.expires, (table, key)) {
+                Some((t, k)) => self.evict(&t, &k),
+                None => {
+                    return Err(KvError::OutOfMemory(format!(
+                        "used {} + {} > maxmemory {} with policy {:?}",
+                        limit.used(),
+                        size,
+                        limit.maxmemory(),
+                        limit.policy()
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn get_or_create_table(&self, name: impl Into<String>) -> Ref<String, DashMap<String, Value>> {
         let names: String = name.into();
         match self.tables.get(&names) {
@@ -35,8 +157,17 @@ impl Storage for MemTable {
         table: impl Into<String>,
         key: impl Into<String>,
     ) -> Result<Option<Value>, KvError> {
-        let table = self.get_or_create_table(table);
-        Ok(table.get(&key.into()).map(|v| v.value().clone()))
+        let (name, key) = (table.into(), key.into());
+        if self.is_expired(&name, &key) {
+            self.evict(&name, &key);
+            return Ok(None);
+        }
+        let table = self.get_or_create_table(name.as_str());
+        let value = table.get(&key).map(|v| v.value().clone());
+        if let (Some(limit), Some(_)) = (&self.limit, &value) {
+            limit.touch(&name, &key);
+        }
+        Ok(value)
     }
 
     fn set(
@@ -45,13 +176,48 @@ impl Storage for MemTable {
         key: impl Into<String>,
         value: Value,
     ) -> Result<Option<Value>, KvError> {
-        let table = self.get_or_create_table(table);
-        Ok(table.insert(key.into(), value))
+        let (name, key) = (table.into(), key.into());
+        let size = entry_size(&key, &value);
+        if let Some(limit) = &self.limit {
+            self.reserve(limit, &name, &key, size)?;
+        }
+        if self.is_expired(&name, &key) {
+            self.evict(&name, &key);
+        }
+
+        let table = self.get_or_create_table(name.as_str());
+        let old = match table.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                self.indexes
+                    .update(&name, &key, Some(entry.get()), Some(&value));
+                Some(entry.insert(value))
+            }
+            Entry::Vacant(entry) => {
+                self.indexes.update(&name, &key, None, Some(&value));
+                entry.insert(value);
+                None
+            }
+        };
+        drop(table);
+        if !self.expires.is_empty() && self.expires.remove(&entry_key(&name, &key)).is_some() {
+            if let Some(limit) = &self.limit {
+                limit.persist(&name, &key);
+            }
+        }
+        if let Some(limit) = &self.limit {
+            limit.insert(&name, &key, size);
+        }
+        Ok(old)
     }
 
     fn contains(&self, table: impl Into<String>, key: impl Into<String>) -> Result<bool, KvError> {
-        let table = self.get_or_create_table(table);
-        Ok(table.contains_key(&key.into()))
+        let (name, key) = (table.into(), key.into());
+        if self.is_expired(&name, &key) {
+            self.evict(&name, &key);
+            return Ok(false);
+        }
+        let table = self.get_or_create_table(name);
+        Ok(table.contains_key(&key))
     }
 
     fn del(
@@ -59,12 +225,18 @@ impl Storage for MemTable {
         table: impl Into<String>,
         key: impl Into<String>,
     ) -> Result<Option<Value>, KvError> {
-        let table = self.get_or_create_table(table);
-        Ok(table.remove(&key.into()).map(|(_, v)| v))
+        let (name, key) = (table.into(), key.into());
+        if self.is_expired(&name, &key) {
+            self.evict(&name, &key);
+            return Ok(None);
+        }
+        Ok(self.remove(&name, &key))
     }
 
     fn get_all(&self, table: impl Into<String>) -> Result<Vec<Kvpair>, KvError> {
-        let table = self.get_or_create_table(table);
+        let name = table.into();
+        self.remove_expired(&name);
+        let table = self.get_or_create_table(name);
         let result: Vec<Kvpair> = table
             .iter()
             .map(|m| Kvpair::new(m.key(), m.value().clone()))
@@ -73,8 +245,172 @@ impl Storage for MemTable {
     }
 
     fn get_iter(&self, table: impl Into<String>) -> Result<impl Iterator<Item = Kvpair>, KvError> {
-        let table = self.get_or_create_table(table).clone();
+        let name = table.into();
+        self.remove_expired(&name);
+        let table = self.get_or_create_table(name).clone();
         let iter = StorageIter::new(table.into_iter());
         Ok(iter)
     }
+
+    fn get_tables(&self) -> Result<Vec<String>, KvError> {
+        // get 时会自动创建 table，这里忽略掉空的 table（过期的 key 这里不做检查）
+        let tables = self
+            .tables
+            .iter()
+            .filter(|t| !t.value().is_empty())
+            .map(|t| t.key().clone())
+            .collect();
+        Ok(tables)
+    }
+
+    fn indexes(&self) -> &Indexes {
+        &self.indexes
+    }
+
+    fn create_index(&self, table: &str, path: &str) -> Result<bool, KvError> {
+        self.remove_expired(table);
+        // 持有 table 的写锁建索引，set/remove 要等索引建好之后才能修改这个 table
+        let data = self.tables.entry(table.into()).or_default();
+        let pairs = data.iter().map(|e| Kvpair::new(e.key(), e.value().clone()));
+        Ok(self.indexes.create(table, path, pairs))
+    }
+
+    fn rebuild_index(&self, table: &str, path: &str) -> Result<(), KvError> {
+        self.remove_expired(table);
+        let data = self.tables.entry(table.into()).or_default();
+        let pairs = data.iter().map(|e| Kvpair::new(e.key(), e.value().clone()));
+        self.indexes.rebuild(table, path, pairs)
+    }
+
+    /// 再次 set 这个 key 会清除过期时间
+    fn expire(&self, table: &str, key: &str, ttl: Duration) -> Result<bool, KvError> {
+        let Some(t) = self.tables.get(table) else {
+            return Ok(false);
+        };
+        // 持有 key 所在分片的锁，避免 key 在这期间被删除
+        let exists = match t.get(key) {
+            Some(_) if self.is_expired(table, key) => false,
+            Some(_) => {
+                self.expires
+                    .insert(entry_key(table, key), Instant::now() + ttl);
+                if let Some(limit) = &self.limit {
+                    limit.expire(table, key);
+                }
+                true
+            }
+            None => false,
+        };
+        Ok(exists)
+    }
+
+    fn track_removed(&self) {
+        self.removed.lock().get_or_insert_with(Vec::new);
+    }
+
+    fn take_removed(&self) -> Vec<(String, usize)> {
+        self.removed
+            .lock()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
+impl RemovedKeys {
+    fn push(&self, table: &str, size: usize) {
+        if let Some(removed) = self.lock().as_mut() {
+            removed.push((table.into(), size));
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Vec<(String, usize)>>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// clone 出来的 MemTable 保持同样的记录设置，但不带上还没有取走的记录
+impl Clone for RemovedKeys {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(self.lock().as_ref().map(|_| vec![])))
+    }
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use super::*;
+    use std::thread;
+
+    fn value(n: usize) -> Value {
+        "x".repeat(n).into()
+    }
+
+    #[test]
+    fn expire_should_work() {
+        let store = MemTable::new();
+        store.set("t1", "k1", "v1".into()).unwrap();
+        store.set("t1", "k2", "v2".into()).unwrap();
+        assert!(store.expire("t1", "k1", Duration::from_millis(10)).unwrap());
+        assert!(store.expire("t1", "k2", Duration::from_millis(10)).unwrap());
+        assert!(!store.expire("t1", "k3", Duration::from_millis(10)).unwrap());
+        // 重新 set 会清除过期时间
+        store.set("t1", "k2", "v2".into()).unwrap();
+
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(store.get("t1", "k1").unwrap(), None);
+        assert!(!store.contains("t1", "k1").unwrap());
+        assert_eq!(
+            store.get_all("t1").unwrap(),
+            vec![Kvpair::new("k2", "v2".into())]
+        );
+    }
+
+    #[test]
+    fn maxmemory_noeviction_should_reject_writes() {
+        let size = entry_size("k1", &value(10));
+        let store = MemTable::with_maxmemory(size * 2, EvictionPolicy::NoEviction);
+        store.set("t1", "k1", value(10)).unwrap();
+        store.set("t1", "k2", value(10)).unwrap();
+        assert_eq!(store.used_memory(), Some(size * 2));
+
+        let res = store.set("t1", "k3", value(10));
+        assert!(matches!(res, Err(KvError::OutOfMemory(_))));
+        // 覆盖同样大小的 value 不需要更多内存
+        store.set("t1", "k1", value(10)).unwrap();
+        // 删除之后就可以再写入
+        store.del("t1", "k2").unwrap();
+        store.set("t1", "k3", value(10)).unwrap();
+    }
+
+    #[test]
+    fn maxmemory_lru_should_evict() {
+        let size = entry_size("k1", &value(10));
+        let store = MemTable::with_maxmemory(size * 2, EvictionPolicy::Lru);
+        store.set("t1", "k1", value(10)).unwrap();
+        store.set("t1", "k2", value(10)).unwrap();
+        store.get("t1", "k1").unwrap();
+
+        store.set("t1", "k3", value(10)).unwrap();
+        assert!(store.contains("t1", "k1").unwrap());
+        assert!(!store.contains("t1", "k2").unwrap());
+        assert!(store.contains("t1", "k3").unwrap());
+        assert_eq!(store.used_memory(), Some(size * 2));
+
+        // 比 maxmemory 还大的 value 无法写入
+        let res = store.set("t1", "k4", value(size * 3));
+        assert!(matches!(res, Err(KvError::OutOfMemory(_))));
+    }
+
+    #[test]
+    fn maxmemory_volatile_ttl_should_evict_keys_with_ttl() {
+        let size = entry_size("k1", &value(10));
+        let store = MemTable::with_maxmemory(size * 2, EvictionPolicy::VolatileTtl);
+        store.set("t1", "k1", value(10)).unwrap();
+        store.set("t1", "k2", value(10)).unwrap();
+        assert!(store.set("t1", "k3", value(10)).is_err());
+
+        store.expire("t1", "k2", Duration::from_secs(60)).unwrap();
+        store.set("t1", "k3", value(10)).unwrap();
+        assert!(store.contains("t1", "k1").unwrap());
+        assert!(!store.contains("t1", "k2").unwrap());
+    }
 }
@@ -1,10 +1,16 @@
+pub mod eviction;
+pub mod index;
 pub mod memory;
+pub mod quota;
 pub mod sled_db;
 
 use crate::{
     error::KvError,
     pb::abi::{Kvpair, Value},
 };
+use index::{IndexQuery, Indexes};
+use prost::Message;
+use std::time::Duration;
 
 /// 对存储的抽象，我们不关心数据存在哪儿，但需要定义外界如何和存储打交道
 pub trait Storage: Send + Sync + 'static {
@@ -38,6 +44,53 @@ pub trait Storage: Send + Sync + 'static {
 
     /// 遍历 HashTable，返回 kv pair 的 Iterator
     fn get_iter(&self, table: impl Into<String>) -> Result<impl Iterator<Item = Kvpair>, KvError>;
+
+    /// 返回所有 HashTable 的名字
+    fn get_tables(&self) -> Result<Vec<String>, KvError>;
+
+    /// 二级索引，实现者需要在 set/del 时调用 Indexes::update 保持索引最新
+    fn indexes(&self) -> &Indexes;
+
+    /// 设置 key 的过期时间，key 不存在返回 false；默认不支持过期
+    fn expire(&self, _table: &str, _key: &str, _ttl: Duration) -> Result<bool, KvError> {
+        Err(KvError::InvalidCommand(
+            "expire is not supported by this storage".into(),
+        ))
+    }
+
+    /// 开始记录存储自己删掉的 key（淘汰、过期），这些 key 不会通过 del 的返回值告诉调用者
+    fn track_removed(&self) {}
+
+    /// 取走开始记录之后存储自己删掉的 (table, 占用的字节数)，字节数按照 entry_size 计算
+    fn take_removed(&self) -> Vec<(String, usize)> {
+        vec![]
+    }
+
+    /// 为 HashTable 中 JSON 文档的 path 建立索引，索引已经存在返回 false
+    fn create_index(&self, table: &str, path: &str) -> Result<bool, KvError> {
+        Ok(self.indexes().create(table, path, self.get_iter(table)?))
+    }
+
+    /// 删除索引，索引不存在返回 false
+    fn drop_index(&self, table: &str, path: &str) -> Result<bool, KvError> {
+        Ok(self.indexes().remove(table, path))
+    }
+
+    /// 用 HashTable 中现有的数据重建索引
+    fn rebuild_index(&self, table: &str, path: &str) -> Result<(), KvError> {
+        self.indexes().rebuild(table, path, self.get_iter(table)?)
+    }
+
+    /// 通过索引查找 HashTable 中满足条件的 kv pair
+    fn find(&self, table: &str, path: &str, query: &IndexQuery) -> Result<Vec<Kvpair>, KvError> {
+        let mut pairs = vec![];
+        for key in self.indexes().find(table, path, query)? {
+            if let Some(value) = self.get(table, key.as_str())? {
+                pairs.push(Kvpair::new(key, value));
+            }
+        }
+        Ok(pairs)
+    }
 }
 
 /// 提供 Storage iterator，这样 trait 的实现者只需要
@@ -65,6 +118,11 @@ where
     }
 }
 
+/// 估算一个 kv pair 占用的字节数：key 的长度加上 protobuf 编码后 value 的长度
+pub fn entry_size(key: &str, value: &Value) -> usize {
+    key.len() + value.encoded_len()
+}
+
 trait U8toString<T> {
     fn u8_to_string(self) -> String;
 }
@@ -101,6 +159,131 @@ mod tests {
         test_get_iter(store);
     }
 
+    #[test]
+    pub fn memtable_get_tables_should_work() {
+        let store = MemTable::new();
+        test_get_tables(store);
+    }
+
+    #[test]
+    pub fn memtable_index_should_work() {
+        let store = MemTable::new();
+        test_index(store);
+    }
+
+    #[test]
+    pub fn memtable_index_concurrent_should_work() {
+        let store = MemTable::new();
+        test_index_concurrent(store);
+    }
+
+    #[test]
+    pub fn memtable_index_create_concurrent_should_work() {
+        for _ in 0..10 {
+            test_index_create_concurrent(MemTable::new());
+        }
+    }
+
+    pub fn test_index(store: impl Storage) {
+        let user = |age: i64| Value::from(format!(r#"{{"user":{{"age":{}}}}}"#, age));
+        store.set("users", "u1", user(20)).unwrap();
+        store.set("users", "u2", user(30)).unwrap();
+        assert!(store.create_index("users", "$.user.age").unwrap());
+        assert!(!store.create_index("users", "user.age").unwrap());
+
+        let query = IndexQuery::Range {
+            min: Some(index::IndexKey::Number(25.0)),
+            max: None,
+        };
+        let pairs = store.find("users", "user.age", &query).unwrap();
+        assert_eq!(pairs, vec![Kvpair::new("u2", user(30))]);
+
+        // set/del 之后索引会同步更新
+        store.set("users", "u1", user(40)).unwrap();
+        store.set("users", "u3", user(50)).unwrap();
+        store.del("users", "u2").unwrap();
+        let pairs = store.find("users", "user.age", &query).unwrap();
+        assert_eq!(
+            pairs,
+            vec![Kvpair::new("u1", user(40)), Kvpair::new("u3", user(50))]
+        );
+
+        store.rebuild_index("users", "user.age").unwrap();
+        assert_eq!(store.find("users", "user.age", &query).unwrap().len(), 2);
+
+        assert!(store.drop_index("users", "user.age").unwrap());
+        assert!(store.find("users", "user.age", &query).is_err());
+    }
+
+    pub fn test_index_concurrent(store: impl Storage) {
+        let user = |age: i64| Value::from(format!(r#"{{"user":{{"age":{}}}}}"#, age));
+        assert!(store.create_index("users", "user.age").unwrap());
+        std::thread::scope(|s| {
+            for i in 0..8 {
+                let store = &store;
+                s.spawn(move || {
+                    for n in 0..100 {
+                        store.set("users", "u1", user(i * 100 + n)).unwrap();
+                        if n % 10 == 0 {
+                            store.del("users", "u1").unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        // 并发写入之后索引中只剩下最终的值
+        let query = IndexQuery::Range {
+            min: None,
+            max: None,
+        };
+        let pairs = store.find("users", "user.age", &query).unwrap();
+        let expected: Vec<_> = store.get_all("users").unwrap();
+        assert_eq!(pairs, expected);
+    }
+
+    pub fn test_index_create_concurrent(store: impl Storage) {
+        let user = |age: i64| Value::from(format!(r#"{{"user":{{"age":{}}}}}"#, age));
+        std::thread::scope(|s| {
+            for i in 0..4 {
+                let store = &store;
+                s.spawn(move || {
+                    for n in 0..200 {
+                        let key = format!("u{}", n % 50);
+                        store
+                            .set("users", key.as_str(), user(i * 1000 + n))
+                            .unwrap();
+                        if n % 7 == 0 {
+                            store.del("users", format!("u{}", n % 11)).unwrap();
+                        }
+                    }
+                });
+            }
+            // 建索引、重建索引和写入同时进行
+            store.create_index("users", "user.age").unwrap();
+            store.rebuild_index("users", "user.age").unwrap();
+        });
+
+        let query = IndexQuery::Range {
+            min: None,
+            max: None,
+        };
+        let mut pairs = store.find("users", "user.age", &query).unwrap();
+        let mut expected = store.get_all("users").unwrap();
+        pairs.sort_by(|a, b| a.key.cmp(&b.key));
+        expected.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(pairs, expected);
+    }
+
+    pub fn test_get_tables(store: impl Storage) {
+        store.set("t1", "k1", "v1".into()).unwrap();
+        store.set("t2", "k1", 1.into()).unwrap();
+        store.set("t2", "k2", "v2".into()).unwrap();
+        let mut tables = store.get_tables().unwrap();
+        tables.sort();
+        assert_eq!(tables, vec!["t1".to_string(), "t2".to_string()]);
+    }
+
     pub fn test_get_all(store: impl Storage) {
         store.set("t2", "k1", "v1".into()).unwrap();
         store.set("t2", "k2", "v2".into()).unwrap();
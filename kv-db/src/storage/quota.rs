@@ -0,0 +1,355 @@
+use super::{
+    entry_size,
+    index::{IndexQuery, Indexes},
+    Storage,
+};
+use crate::{
+    config::QuotaConfig,
+    error::KvError,
+    pb::abi::{Kvpair, Value},
+};
+use dashmap::DashMap;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::Duration,
+};
+
+/// 给 Storage 加上 table 级别的配额：key 的数量和占用的字节数。
+/// 每个 table 第一次写入时遍历一次 table 得到当前用量，之后在 set/del 时更新；
+/// inner 自己删掉的 key（淘汰、过期）通过 take_removed 取回后扣除
+pub struct QuotaStore<S> {
+    inner: S,
+    quotas: HashMap<String, QuotaConfig>,
+    usage: DashMap<String, Arc<TableUsage>>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub keys: usize,
+    pub bytes: usize,
+}
+
+/// 一个 table 的用量；写入的时候持有 lock，这样同一个 table 的并发写入不会超出配额，
+/// 扣除 inner 删掉的 key 不需要 lock
+#[derive(Debug, Default)]
+struct TableUsage {
+    lock: Mutex<()>,
+    keys: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl<S: Storage> QuotaStore<S> {
+    pub fn new(inner: S, quotas: HashMap<String, QuotaConfig>) -> Self {
+        if !quotas.is_empty() {
+            inner.track_removed();
+        }
+        Self {
+            inner,
+            quotas,
+            usage: DashMap::new(),
+        }
+    }
+
+    /// table 目前的用量，没有配置配额的 table 返回 None
+    pub fn usage(&self, table: &str) -> Result<Option<Usage>, KvError> {
+        if !self.quotas.contains_key(table) {
+            return Ok(None);
+        }
+        self.settle();
+        Ok(Some(self.table_usage(table)?.load()))
+    }
+
+    fn table_usage(&self, table: &str) -> Result<Arc<TableUsage>, KvError> {
+        let usage = self
+            .usage
+            .entry(table.into())
+            .or_try_insert_with(|| self.scan(table))?;
+        Ok(usage.clone())
+    }
+
+    fn scan(&self, table: &str) -> Result<Arc<TableUsage>, KvError> {
+        let usage = TableUsage::default();
+        for pair in self.inner.get_iter(table)? {
+            usage.add(1, entry_size(&pair.key, &pair.value.unwrap_or_default()));
+        }
+        Ok(Arc::new(usage))
+    }
+
+    /// 扣除 inner 自己删掉的 key；还没有统计过的 table 第一次用到时会重新遍历，不需要扣除
+    fn settle(&self) {
+        for (table, size) in self.inner.take_removed() {
+            if let Some(usage) = self.usage.get(&table) {
+                usage.sub(1, size);
+            }
+        }
+    }
+}
+
+impl TableUsage {
+    fn load(&self) -> Usage {
+        Usage {
+            keys: self.keys.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add(&self, keys: usize, bytes: usize) {
+        self.keys.fetch_add(keys, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    fn sub(&self, keys: usize, bytes: usize) {
+        let sub = |n: usize| move |v: usize| Some(v.saturating_sub(n));
+        let _ = self
+            .keys
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, sub(keys));
+        let _ = self
+            .bytes
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, sub(bytes));
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ()> {
+        self.lock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl QuotaConfig {
+    fn check(&self, table: &str, usage: &Usage) -> Result<(), KvError> {
+        if matches!(self.max_keys, Some(max) if usage.keys > max) {
+            return Err(KvError::QuotaExceeded(format!(
+                "table {} has more than {} keys",
+                table,
+                self.max_keys.unwrap_or_default()
+            )));
+        }
+        if matches!(self.max_bytes, Some(max) if usage.bytes > max) {
+            return Err(KvError::QuotaExceeded(format!(
+                "table {} uses more than {} bytes",
+                table,
+                self.max_bytes.unwrap_or_default()
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl<S: Storage> Storage for QuotaStore<S> {
+    fn get(
+        &self,
+        table: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Result<Option<Value>, KvError> {
+        let value = self.inner.get(table, key);
+        self.settle();
+        value
+    }
+
+    fn set(
+        &self,
+        table: impl Into<String>,
+        key: impl Into<String>,
+        value: Value,
+    ) -> Result<Option<Value>, KvError> {
+        let (table, key) = (table.into(), key.into());
+        let Some(quota) = self.quotas.get(&table) else {
+            return self.inner.set(table, key, value);
+        };
+
+        // 持有 table 用量的锁直到写入完成，这样同一个 table 的并发写入不会超出配额
+        let usage = self.table_usage(&table)?;
+        let _lock = usage.lock();
+        // get 可能发现 key 已经过期并删掉它，先扣除再检查
+        let old = self.inner.get(table.as_str(), key.as_str())?;
+        self.settle();
+        let mut next = usage.load();
+        match &old {
+            Some(old) => next.bytes = next.bytes.saturating_sub(entry_size(&key, old)),
+            None => next.keys += 1,
+        }
+        let size = entry_size(&key, &value);
+        next.bytes += size;
+        quota.check(&table, &next)?;
+
+        // 写入时被淘汰的 key 由 settle 扣除
+        let old = self.inner.set(table, key.as_str(), value)?;
+        match &old {
+            Some(old) => usage.sub(0, entry_size(&key, old)),
+            None => usage.add(1, 0),
+        }
+        usage.add(0, size);
+        self.settle();
+        Ok(old)
+    }
+
+    fn contains(&self, table: impl Into<String>, key: impl Into<String>) -> Result<bool, KvError> {
+        let exists = self.inner.contains(table, key);
+        self.settle();
+        exists
+    }
+
+    fn del(
+        &self,
+        table: impl Into<String>,
+        key: impl Into<String>,
+    ) -> Result<Option<Value>, KvError> {
+        let (table, key) = (table.into(), key.into());
+        if !self.quotas.contains_key(&table) {
+            return self.inner.del(table, key);
+        }
+
+        let usage = self.table_usage(&table)?;
+        let _lock = usage.lock();
+        let old = self.inner.del(table, key.as_str())?;
+        if let Some(old) = &old {
+            usage.sub(1, entry_size(&key, old));
+        }
+        self.settle();
+        Ok(old)
+    }
+
+    fn get_all(&self, table: impl Into<String>) -> Result<Vec<Kvpair>, KvError> {
+        let pairs = self.inner.get_all(table);
+        self.settle();
+        pairs
+    }
+
+    fn get_iter(&self, table: impl Into<String>) -> Result<impl Iterator<Item = Kvpair>, KvError> {
+        let iter = self.inner.get_iter(table);
+        self.settle();
+        iter
+    }
+
+    fn get_tables(&self) -> Result<Vec<String>, KvError> {
+        self.inner.get_tables()
+    }
+
+    fn indexes(&self) -> &Indexes {
+        self.inner.indexes()
+    }
+
+    fn expire(&self, table: &str, key: &str, ttl: Duration) -> Result<bool, KvError> {
+        self.inner.expire(table, key, ttl)
+    }
+
+    // 索引相关的方法也交给 inner，SledDB 会持久化索引的定义
+
+    fn create_index(&self, table: &str, path: &str) -> Result<bool, KvError> {
+        self.inner.create_index(table, path)
+    }
+
+    fn drop_index(&self, table: &str, path: &str) -> Result<bool, KvError> {
+        self.inner.drop_index(table, path)
+    }
+
+    fn rebuild_index(&self, table: &str, path: &str) -> Result<(), KvError> {
+        self.inner.rebuild_index(table, path)
+    }
+
+    fn find(&self, table: &str, path: &str, query: &IndexQuery) -> Result<Vec<Kvpair>, KvError> {
+        self.inner.find(table, path, query)
+    }
+}
+
+#[cfg(test)]
+mod quota_tests {
+    use super::*;
+    use crate::{
+        config::EvictionPolicy,
+        memory::MemTable,
+        storage::tests::{test_basic_interface, test_get_iter},
+    };
+
+    fn quota(max_keys: Option<usize>, max_bytes: Option<usize>) -> HashMap<String, QuotaConfig> {
+        let config = QuotaConfig {
+            max_keys,
+            max_bytes,
+        };
+        [("t1".to_string(), config)].into()
+    }
+
+    #[test]
+    fn quota_store_basic_interface_should_work() {
+        test_basic_interface(QuotaStore::new(MemTable::new(), quota(Some(10), None)));
+        test_get_iter(QuotaStore::new(MemTable::new(), quota(Some(10), None)));
+    }
+
+    #[test]
+    fn max_keys_should_work() {
+        let store = QuotaStore::new(MemTable::new(), quota(Some(2), None));
+        store.set("t1", "k1", "v1".into()).unwrap();
+        store.set("t1", "k2", "v2".into()).unwrap();
+        let res = store.set("t1", "k3", "v3".into());
+        assert!(matches!(res, Err(KvError::QuotaExceeded(_))));
+        assert!(!store.contains("t1", "k3").unwrap());
+
+        // 覆盖已有的 key 不会增加 key 的数量
+        store.set("t1", "k1", "v11".into()).unwrap();
+        store.del("t1", "k2").unwrap();
+        store.set("t1", "k3", "v3".into()).unwrap();
+
+        // 没有配置配额的 table 不受限制
+        for i in 0..10 {
+            store.set("t2", format!("k{}", i), "v".into()).unwrap();
+        }
+    }
+
+    #[test]
+    fn max_bytes_should_work() {
+        let value: Value = "x".repeat(10).into();
+        let size = entry_size("k1", &value);
+        let store = QuotaStore::new(MemTable::new(), quota(None, Some(size * 2)));
+        store.set("t1", "k1", value.clone()).unwrap();
+        store.set("t1", "k2", value.clone()).unwrap();
+        assert!(store.set("t1", "k3", value.clone()).is_err());
+        let bigger: Value = "x".repeat(11).into();
+        assert!(store.set("t1", "k1", bigger).is_err());
+        assert_eq!(
+            store.usage("t1").unwrap(),
+            Some(Usage {
+                keys: 2,
+                bytes: size * 2
+            })
+        );
+    }
+
+    #[test]
+    fn usage_should_exclude_evicted_and_expired_keys() {
+        let value: Value = "x".repeat(10).into();
+        let size = entry_size("k1", &value);
+        let inner = MemTable::with_maxmemory(size * 2, EvictionPolicy::Lru);
+        let store = QuotaStore::new(inner, quota(Some(2), None));
+        store.set("t1", "k1", value.clone()).unwrap();
+        store.set("t1", "k2", value.clone()).unwrap();
+        // 写入 t2 时 MemTable 淘汰了 t1 的 k1，t1 的用量也要扣掉
+        store.set("t2", "k1", value.clone()).unwrap();
+        assert!(!store.contains("t1", "k1").unwrap());
+        let one = Some(Usage {
+            keys: 1,
+            bytes: size,
+        });
+        assert_eq!(store.usage("t1").unwrap(), one);
+        store.set("t1", "k3", value.clone()).unwrap();
+        assert_eq!(store.usage("t1").unwrap(), one);
+
+        // 过期的 key 同样要扣掉
+        assert!(store.expire("t1", "k3", Duration::from_millis(10)).unwrap());
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!store.contains("t1", "k3").unwrap());
+        assert_eq!(store.usage("t1").unwrap(), Some(Usage::default()));
+    }
+
+    #[test]
+    fn usage_should_include_existing_data() {
+        let inner = MemTable::new();
+        inner.set("t1", "k1", "v1".into()).unwrap();
+        inner.set("t1", "k2", "v2".into()).unwrap();
+        let store = QuotaStore::new(inner, quota(Some(2), None));
+        assert_eq!(store.usage("t1").unwrap().unwrap().keys, 2);
+        assert!(store.set("t1", "k3", "v3".into()).is_err());
+        assert_eq!(store.usage("t2").unwrap(), None);
+    }
+}
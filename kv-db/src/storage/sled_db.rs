@@ -1,18 +1,64 @@
-use super::U8toString;
+use super::{index::Indexes, U8toString};
 use crate::{
     error::KvError,
-    pb::abi::{value, Kvpair, Value},
+    pb::abi::{Kvpair, Value},
     Storage, StorageIter,
 };
+use dashmap::DashMap;
+use prost::Message;
 use sled::{Db, Error, IVec};
 use std::{fmt::Debug, ops::Deref, path::Path};
 
-pub struct SledDB(Db);
+/// 保存索引定义的 sled tree，数据本身存在默认的 tree 里
+const INDEX_TREE: &str = "__indexes__";
+
+pub struct SledDB {
+    db: Db,
+    indexes: Indexes,
+    /// 每个 table 的写锁：sled 没有 entry 锁，写数据和更新索引要在同一把锁里完成
+    locks: DashMap<String, ()>,
+}
 
 impl SledDB {
     /// 读取本都数据库文件
     pub fn new(path: impl AsRef<Path>) -> Self {
-        Self(sled::open(path).unwrap())
+        Self::from_db(sled::open(path).unwrap())
+    }
+
+    fn from_db(db: Db) -> Self {
+        let db = Self {
+            db,
+            indexes: Indexes::default(),
+            locks: DashMap::new(),
+        };
+        // 索引只保存了定义，打开数据库时用现有的数据重建
+        for (table, path) in db.index_defs().unwrap() {
+            if let Ok(pairs) = db.get_iter(table.as_str()) {
+                db.indexes.create(&table, &path, pairs);
+            }
+        }
+        db
+    }
+
+    /// 所有已经定义的索引 (table, path)
+    fn index_defs(&self) -> Result<Vec<(String, String)>, KvError> {
+        let tree = self.open_tree(INDEX_TREE).sled_error()?;
+        let mut defs = vec![];
+        for item in tree.iter().keys() {
+            let key = item.sled_error()?.u8_to_string();
+            if let Some((table, path)) = key.split_once('\0') {
+                defs.push((table.into(), path.into()));
+            }
+        }
+        Ok(defs)
+    }
+
+    fn get_index_key(table: &str, path: &str) -> String {
+        format!(
+            "{}\0{}",
+            table,
+            path.trim_start_matches('$').trim_start_matches('.')
+        )
     }
 
     /// 在 sleddb 里，因为它可以 scan_prefix，我们用 prefix
@@ -44,8 +90,13 @@ impl Storage for SledDB {
         key: impl Into<String>,
         value: Value,
     ) -> Result<Option<Value>, KvError> {
-        let key = SledDB::get_full_key(&table.into(), &key.into());
-        self.insert(key, value).flip()
+        let (table, key) = (table.into(), key.into());
+        let full_key = SledDB::get_full_key(&table, &key);
+        let _lock = self.locks.entry(table.clone()).or_default();
+        let old = self.insert(full_key, value.clone()).flip()?;
+        self.indexes
+            .update(&table, &key, old.as_ref(), Some(&value));
+        Ok(old)
     }
 
     fn contains(&self, table: impl Into<String>, key: impl Into<String>) -> Result<bool, KvError> {
@@ -58,41 +109,87 @@ impl Storage for SledDB {
         table: impl Into<String>,
         key: impl Into<String>,
     ) -> Result<Option<Value>, KvError> {
-        let key = SledDB::get_full_key(&table.into(), &key.into());
-        self.remove(key).flip()
+        let (table, key) = (table.into(), key.into());
+        let full_key = SledDB::get_full_key(&table, &key);
+        let _lock = self.locks.entry(table.clone()).or_default();
+        let old = self.remove(full_key).flip()?;
+        self.indexes.update(&table, &key, old.as_ref(), None);
+        Ok(old)
     }
 
     fn get_all(&self, table: impl Into<String>) -> Result<Vec<Kvpair>, KvError> {
-        let data = self
-            .scan_prefix(table.into())
-            .map(|v: Result<(IVec, IVec), Error>| v.into())
-            .collect();
-        Ok(data)
+        Ok(self.get_iter(table)?.collect())
     }
 
     fn get_iter(&self, table: impl Into<String>) -> Result<impl Iterator<Item = Kvpair>, KvError> {
-        let iter = self.scan_prefix(table.into()).into_iter();
+        let prefix = SledDB::get_table_prefix(&table.into());
+        let len = prefix.len();
+        // 返回的 key 去掉 table 的 prefix
+        let iter = self
+            .scan_prefix(prefix)
+            .map(move |v| v.map(|(k, v)| (k.subslice(len, k.len() - len), v)));
         let iter = StorageIter::new(iter);
         Ok(iter)
     }
+
+    fn get_tables(&self) -> Result<Vec<String>, KvError> {
+        let mut tables: Vec<String> = vec![];
+        for item in self.iter().keys() {
+            let key = item.sled_error()?.u8_to_string();
+            if let Some((table, _)) = key.split_once(':') {
+                // key 是有序的，同一个 table 的 key 都挨在一起
+                if tables.last().map(|t| t.as_str()) != Some(table) {
+                    tables.push(table.into());
+                }
+            }
+        }
+        Ok(tables)
+    }
+
+    fn indexes(&self) -> &Indexes {
+        &self.indexes
+    }
+
+    fn create_index(&self, table: &str, path: &str) -> Result<bool, KvError> {
+        let tree = self.open_tree(INDEX_TREE).sled_error()?;
+        tree.insert(SledDB::get_index_key(table, path), &[])
+            .sled_error()?;
+        let _lock = self.locks.entry(table.into()).or_default();
+        Ok(self.indexes.create(table, path, self.get_iter(table)?))
+    }
+
+    fn rebuild_index(&self, table: &str, path: &str) -> Result<(), KvError> {
+        let _lock = self.locks.entry(table.into()).or_default();
+        self.indexes.rebuild(table, path, self.get_iter(table)?)
+    }
+
+    fn drop_index(&self, table: &str, path: &str) -> Result<bool, KvError> {
+        let tree = self.open_tree(INDEX_TREE).sled_error()?;
+        tree.remove(SledDB::get_index_key(table, path))
+            .sled_error()?;
+        Ok(self.indexes.remove(table, path))
+    }
 }
 
+/// Value 以 protobuf 编码后存入 sled，这样各种类型的 Value 都能存取
 impl From<Value> for IVec {
     fn from(value: Value) -> Self {
-        match value.value {
-            Some(v) => match v {
-                value::Value::String(s) => s.as_str().into(),
-                _ => todo!(),
-            },
-            None => todo!(),
-        }
+        value.encode_to_vec().into()
+    }
+}
+
+/// 从 sled 里读出的数据解码成 Value；兼容之前直接以字符串存储的数据
+fn decode_value(data: &[u8]) -> Value {
+    match Value::decode(data) {
+        Ok(v) if v.value.is_some() => v,
+        _ => data.into(),
     }
 }
 
 impl From<Result<(IVec, IVec), Error>> for Kvpair {
     fn from(value: Result<(IVec, IVec), Error>) -> Self {
         match value {
-            Ok(v) => Kvpair::new(v.0.u8_to_string(), v.1.as_ref().into()),
+            Ok(v) => Kvpair::new(v.0.u8_to_string(), decode_value(&v.1)),
             Err(_) => Kvpair::default(),
         }
     }
@@ -102,7 +199,7 @@ impl std::ops::Deref for SledDB {
     type Target = Db;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.db
     }
 }
 
@@ -117,7 +214,7 @@ where
 {
     fn flip(self) -> Result<Option<Value>, KvError> {
         match self {
-            Ok(value) => Ok(value.map(|m| m.deref().into())),
+            Ok(value) => Ok(value.map(|m| decode_value(m.deref()))),
             Err(e) => Err(KvError::Internal(
                 format!("error flipr: {:?}", e).to_owned(),
             )),
@@ -147,7 +244,14 @@ mod sled_test {
 
     use tempfile::tempdir;
 
-    use crate::storage::tests::{test_basic_interface, test_get_all, test_get_iter};
+    use crate::storage::{
+        index::{IndexKey, IndexQuery},
+        tests::{
+            test_basic_interface, test_get_all, test_get_iter, test_get_tables, test_index,
+            test_index_concurrent, test_index_create_concurrent,
+        },
+        Storage,
+    };
 
     use super::SledDB;
 
@@ -170,4 +274,56 @@ mod sled_test {
         let store = SledDB::new(dir);
         test_get_iter(store);
     }
+
+    #[test]
+    fn sleddb_get_tables_should_work() {
+        let dir = tempdir().unwrap();
+        let store = SledDB::new(dir);
+        test_get_tables(store);
+    }
+
+    #[test]
+    fn sleddb_index_should_work() {
+        let dir = tempdir().unwrap();
+        let store = SledDB::new(&dir);
+        test_index(store);
+    }
+
+    #[test]
+    fn sleddb_index_concurrent_should_work() {
+        let dir = tempdir().unwrap();
+        let store = SledDB::new(&dir);
+        test_index_concurrent(store);
+    }
+
+    #[test]
+    fn sleddb_index_create_concurrent_should_work() {
+        let dir = tempdir().unwrap();
+        let store = SledDB::new(&dir);
+        test_index_create_concurrent(store);
+    }
+
+    #[test]
+    fn sleddb_index_should_survive_reopen() {
+        let dir = tempdir().unwrap();
+        {
+            let store = SledDB::new(&dir);
+            store.set("t1", "k1", r#"{"a":1}"#.into()).unwrap();
+            assert!(store.create_index("t1", "a").unwrap());
+        }
+
+        // sled 的后台线程退出前可能还占着文件锁
+        let db = (0..100)
+            .find_map(|_| {
+                sled::open(&dir)
+                    .inspect_err(|_| std::thread::sleep(std::time::Duration::from_millis(10)))
+                    .ok()
+            })
+            .unwrap();
+        let store = SledDB::from_db(db);
+        let query = IndexQuery::Eq(IndexKey::Number(1.0));
+        let pairs = store.find("t1", "a", &query).unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].key, "k1");
+    }
 }
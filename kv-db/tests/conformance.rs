@@ -0,0 +1,26 @@
+use anyhow::Result;
+use kv_db::{
+    memory::MemTable,
+    pb::abi::{CommandRequest, CommandResponse},
+    service_builder::ServiceBuilder,
+    FrameCoder, ProstServerStream,
+};
+
+kv_conformance::frame_target! {
+    /// 用 kv-db 的 FrameCoder 和 ProstServerStream 跑共同协议的兼容性测试
+    struct KvDb {
+        request: CommandRequest,
+        response: CommandResponse,
+        encode: encode_frame,
+        decode: decode_frame,
+        connect: |server| {
+            let service = ServiceBuilder::new(MemTable::new()).finish();
+            tokio::spawn(ProstServerStream::new(server, service).process());
+        }
+    }
+}
+
+#[tokio::test]
+async fn kv_db_should_conform_to_protocol() -> Result<()> {
+    kv_conformance::run(&KvDb).await
+}
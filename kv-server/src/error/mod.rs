@@ -22,6 +22,17 @@ pub enum KvError {
     #[error("I/O error")]
     IoError(#[from] std::io::Error),
 
+    #[error("Subscription {0} not found")]
+    SubscriptionNotFound(u32),
+    #[error("Failed to parse {0} {1}")]
+    CertificateParseError(&'static str, &'static str),
+    #[error("TLS error")]
+    TlsError(#[from] tokio_rustls::rustls::Error),
+    #[error("Invalid DNS name: {0}")]
+    InvalidDnsName(String),
+    #[error("yamux connection error")]
+    YamuxError(#[from] yamux::ConnectionError),
+
     #[error("Internal error: {0}")]
     Internal(String),
 
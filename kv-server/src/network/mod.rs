@@ -1,11 +1,16 @@
 mod frame;
+mod multiplex;
+mod stream_result;
 mod tls;
 
 use std::{marker::PhantomData, pin::Pin, task::Poll};
 
 use bytes::BytesMut;
 pub use frame::*;
-use futures::{ready, FutureExt, Sink, Stream};
+use futures::{ready, stream, FutureExt, Sink, Stream, StreamExt};
+pub use multiplex::*;
+pub use stream_result::*;
+pub use tls::*;
 use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tracing::info;
 
@@ -170,16 +175,18 @@ where
     pub async fn process(mut self) -> Result<(), KvError> {
         while let Ok(cmd) = self.recv().await {
             info!("God a CommandRequest: {:?}", cmd);
-            // 执行请求 CommandRequest
-            let resp = self.service.execute(cmd);
-            self.send(resp).await.unwrap();
+            // 执行请求 CommandRequest，Subscribe 会持续返回发布到主题的数据，直到取消订阅
+            let mut resp = self.service.execute_streaming(cmd);
+            while let Some(data) = resp.next().await {
+                self.send(&data).await?;
+            }
         }
 
         Ok(())
     }
 
     /// 封包并返回给客户端，执行完 `mgs` CommandRequest 命令的结果
-    async fn send(&mut self, msg: CommandResponse) -> Result<(), KvError> {
+    async fn send(&mut self, msg: &CommandResponse) -> Result<(), KvError> {
         let mut buf = BytesMut::new();
         // 封包
         msg.frame_encode(&mut buf)?;
@@ -237,3 +244,28 @@ where
         CommandResponse::frame_decode(&mut buf)
     }
 }
+
+impl<S> PostClientStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    /// 执行会返回多个响应的命令（Subscribe），返回的 StreamResult 中带有订阅 id；
+    /// 这个 stream 之后只能用来接收数据，取消订阅需要在另一个 stream 上执行
+    pub async fn execute_streaming(
+        mut self,
+        cmd: CommandRequest,
+    ) -> Result<StreamResult<ResponseStream>, KvError> {
+        self.send(cmd).await?;
+        // 关闭写的一端，服务器处理完订阅后读到 EOF 就会结束这个 stream
+        self.inner.shutdown().await?;
+
+        let stream: ResponseStream = Box::pin(stream::unfold(self, |mut client| async move {
+            match client.recv().await {
+                Ok(res) => Some((Ok(res), client)),
+                // 连接断开，或者服务器结束了这个 stream
+                Err(_) => None,
+            }
+        }));
+        StreamResult::new(stream).await
+    }
+}
@@ -0,0 +1,154 @@
+use std::marker::PhantomData;
+
+use futures::{future, Future, TryStreamExt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt, TokioAsyncReadCompatExt};
+use yamux::{Config, Connection, ConnectionError, Control, Mode};
+
+use crate::{KvError, PostClientStream};
+
+/// yamux 多路复用：一个 TCP/TLS 连接上可以同时打开多个 stream，
+/// 每个 stream 上跑的还是原来的 frame 协议
+pub struct YamuxCtrl<S> {
+    ctrl: Control,
+    _conn: PhantomData<S>,
+}
+
+impl<S> YamuxCtrl<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    fn new<F, Fut>(stream: S, config: Option<Config>, mode: Mode, f: F) -> Self
+    where
+        F: FnMut(yamux::Stream) -> Fut,
+        F: Send + 'static,
+        Fut: Future<Output = Result<(), ConnectionError>> + Send + 'static,
+    {
+        let mut config = config.unwrap_or_default();
+        config.set_window_update_mode(yamux::WindowUpdateMode::OnRead);
+        // yamux 使用的是 futures 的 AsyncRead/AsyncWrite，需要 compat() 转换
+        let conn = Connection::new(stream.compat(), config, mode);
+        let ctrl = conn.control();
+        // 处理这个连接上所有的 stream
+        tokio::spawn(yamux::into_stream(conn).try_for_each_concurrent(None, f));
+
+        Self {
+            ctrl,
+            _conn: PhantomData,
+        }
+    }
+
+    /// 客户端，通过 open_stream 打开新的 stream
+    pub fn new_client(stream: S, config: Option<Config>) -> Self {
+        Self::new(stream, config, Mode::Client, |_s| future::ready(Ok(())))
+    }
+
+    /// 服务器，对方每打开一个 stream 就调用一次 f
+    pub fn new_server<F, Fut>(stream: S, config: Option<Config>, f: F) -> Self
+    where
+        F: FnMut(yamux::Stream) -> Fut,
+        F: Send + 'static,
+        Fut: Future<Output = Result<(), ConnectionError>> + Send + 'static,
+    {
+        Self::new(stream, config, Mode::Server, f)
+    }
+
+    pub async fn open_stream(
+        &mut self,
+    ) -> Result<PostClientStream<Compat<yamux::Stream>>, KvError> {
+        let stream = self.ctrl.open_stream().await?;
+        Ok(PostClientStream::new(stream.compat()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use futures::StreamExt;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::compat::FuturesAsyncReadCompatExt;
+
+    use super::*;
+    use crate::{
+        tls_utils, CommandRequest, MemoryDB, PostServerStream, Service, ServiceInner,
+        TlsClientConnector, TlsServerAcceptor, Value,
+    };
+
+    /// 启动一个 TLS + yamux 的服务器，每个 stream 由 PostServerStream 处理
+    async fn start_server(acceptor: TlsServerAcceptor) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service: Service = ServiceInner::new(MemoryDB::new()).into();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let stream = acceptor.accept(stream).await.unwrap();
+                let service = service.clone();
+                YamuxCtrl::new_server(stream, None, move |stream| {
+                    let stream = PostServerStream::new(stream.compat(), service.clone());
+                    async move {
+                        stream.process().await.unwrap();
+                        Ok(())
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    async fn connect() -> YamuxCtrl<tokio_rustls::client::TlsStream<TcpStream>> {
+        let certs = tls_utils::generate();
+        let (cert, key) = &certs.server;
+        let acceptor = TlsServerAcceptor::new(cert, key, Some(&certs.ca)).unwrap();
+        let addr = start_server(acceptor).await;
+
+        let (cert, key) = &certs.client;
+        let identity = Some((cert.as_str(), key.as_str()));
+        let connector =
+            TlsClientConnector::new(tls_utils::DOMAIN, identity, Some(&certs.ca)).unwrap();
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let stream = connector.connect(stream).await.unwrap();
+        YamuxCtrl::new_client(stream, None)
+    }
+
+    #[tokio::test]
+    async fn yamux_ctrl_client_server_should_work() {
+        let mut ctrl = connect().await;
+
+        let mut s1 = ctrl.open_stream().await.unwrap();
+        let mut s2 = ctrl.open_stream().await.unwrap();
+        let cmd = CommandRequest::new_hset("t1", "k1", "v1".into());
+        assert_eq!(s1.execute(cmd).await.unwrap().status, 200);
+
+        // 同一个连接上的另一个 stream 能读到写入的数据
+        let res = s2
+            .execute(CommandRequest::new_hget("t1", "k1"))
+            .await
+            .unwrap();
+        assert_eq!(res.values, vec![Value::from("v1")]);
+    }
+
+    #[tokio::test]
+    async fn yamux_pub_sub_should_work() {
+        let mut ctrl = connect().await;
+
+        let sub = ctrl.open_stream().await.unwrap();
+        let mut sub = sub
+            .execute_streaming(CommandRequest::new_subscribe("lobby"))
+            .await
+            .unwrap();
+        let id = sub.id;
+
+        let mut publisher = ctrl.open_stream().await.unwrap();
+        let cmd = CommandRequest::new_publish("lobby", vec!["hello".into()]);
+        assert_eq!(publisher.execute(cmd).await.unwrap().status, 200);
+        let data = sub.next().await.unwrap().unwrap();
+        assert_eq!(data.values, vec![Value::from("hello")]);
+
+        // 在另一个 stream 上取消订阅，订阅的 stream 会结束
+        let cmd = CommandRequest::new_unsubscribe("lobby", id);
+        assert_eq!(publisher.execute(cmd).await.unwrap().status, 200);
+        assert!(sub.next().await.is_none());
+    }
+}
@@ -0,0 +1,51 @@
+use std::{
+    ops::{Deref, DerefMut},
+    pin::Pin,
+};
+
+use futures::{Stream, StreamExt};
+
+use crate::{CommandResponse, KvError};
+
+/// 客户端收到的响应流
+pub type ResponseStream = Pin<Box<dyn Stream<Item = Result<CommandResponse, KvError>> + Send>>;
+
+/// Subscribe 的结果：第一个响应中的订阅 id，以及之后收到的数据
+pub struct StreamResult<T> {
+    pub id: u32,
+    inner: T,
+}
+
+impl<T> StreamResult<T>
+where
+    T: Stream<Item = Result<CommandResponse, KvError>> + Send + Unpin,
+{
+    pub async fn new(mut stream: T) -> Result<Self, KvError> {
+        let id = match stream.next().await {
+            Some(Ok(CommandResponse {
+                status: 200,
+                values,
+                ..
+            })) if !values.is_empty() => i64::try_from(&values[0])? as u32,
+            Some(Ok(res)) => return Err(KvError::Internal(res.message)),
+            Some(Err(e)) => return Err(e),
+            None => return Err(KvError::Internal("Invalid stream".into())),
+        };
+
+        Ok(Self { id, inner: stream })
+    }
+}
+
+impl<T> Deref for StreamResult<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for StreamResult<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
@@ -0,0 +1,278 @@
+use std::{io::Cursor, sync::Arc};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::{
+    client,
+    rustls::{
+        server::AllowAnyAuthenticatedClient, Certificate, ClientConfig, PrivateKey, RootCertStore,
+        ServerConfig, ServerName,
+    },
+    server, TlsAcceptor, TlsConnector,
+};
+
+use crate::KvError;
+
+/// KV Server 自己的 ALPN (Application-Layer Protocol Negotiation)
+const ALPN_KV: &str = "kv";
+
+/// 存放 TLS ServerConfig，并提供 accept 方法把底层的 stream 转换成 TLS stream
+#[derive(Clone)]
+pub struct TlsServerAcceptor {
+    inner: Arc<ServerConfig>,
+}
+
+/// 存放 TLS ClientConfig，并提供 connect 方法把底层的 stream 转换成 TLS stream
+#[derive(Clone)]
+pub struct TlsClientConnector {
+    pub config: Arc<ClientConfig>,
+    pub domain: Arc<String>,
+}
+
+impl TlsClientConnector {
+    /// 加载客户端证书（cert, key）和签署服务器证书的 CA 证书，生成 ClientConfig；
+    /// 服务器开启了 mTLS 时需要提供客户端证书
+    pub fn new(
+        domain: impl Into<String>,
+        identity: Option<(&str, &str)>,
+        server_ca: Option<&str>,
+    ) -> Result<Self, KvError> {
+        let mut roots = RootCertStore::empty();
+        match server_ca {
+            // 服务器证书不在系统的根证书链中，但是这个 CA 证书能验证它
+            Some(cert) => {
+                for cert in load_certs(cert)? {
+                    roots
+                        .add(&cert)
+                        .map_err(|_| KvError::CertificateParseError("CA", "cert"))?;
+                }
+            }
+            // 加载系统信任的根证书
+            None => {
+                let certs = rustls_native_certs::load_native_certs()?;
+                roots
+                    .add_parsable_certificates(&certs.into_iter().map(|c| c.0).collect::<Vec<_>>());
+            }
+        }
+
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots);
+        let mut config = match identity {
+            Some((cert, key)) => {
+                builder.with_client_auth_cert(load_certs(cert)?, load_key(key)?)?
+            }
+            None => builder.with_no_client_auth(),
+        };
+        config.alpn_protocols = vec![ALPN_KV.as_bytes().to_vec()];
+
+        Ok(Self {
+            config: Arc::new(config),
+            domain: Arc::new(domain.into()),
+        })
+    }
+
+    /// 触发 TLS 握手，把底层的 stream 转换成 TLS stream
+    pub async fn connect<S>(&self, stream: S) -> Result<client::TlsStream<S>, KvError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let domain = ServerName::try_from(self.domain.as_str())
+            .map_err(|_| KvError::InvalidDnsName(self.domain.to_string()))?;
+
+        let stream = TlsConnector::from(self.config.clone())
+            .connect(domain, stream)
+            .await?;
+
+        Ok(stream)
+    }
+}
+
+impl TlsServerAcceptor {
+    /// 加载服务器证书和私钥，生成 ServerConfig；
+    /// 提供了 client_ca 时开启 mTLS，只接受这个 CA 签发的客户端证书
+    pub fn new(cert: &str, key: &str, client_ca: Option<&str>) -> Result<Self, KvError> {
+        let certs = load_certs(cert)?;
+        let key = load_key(key)?;
+
+        let builder = ServerConfig::builder().with_safe_defaults();
+        let mut config = match client_ca {
+            None => builder.with_no_client_auth().with_single_cert(certs, key)?,
+            Some(ca) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(ca)? {
+                    roots
+                        .add(&cert)
+                        .map_err(|_| KvError::CertificateParseError("CA", "cert"))?;
+                }
+                let verifier = AllowAnyAuthenticatedClient::new(roots).boxed();
+                builder
+                    .with_client_cert_verifier(verifier)
+                    .with_single_cert(certs, key)?
+            }
+        };
+        config.alpn_protocols = vec![ALPN_KV.as_bytes().to_vec()];
+
+        Ok(Self {
+            inner: Arc::new(config),
+        })
+    }
+
+    /// 触发 TLS 握手，把底层的 stream 转换成 TLS stream
+    pub async fn accept<S>(&self, stream: S) -> Result<server::TlsStream<S>, KvError>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let acceptor = TlsAcceptor::from(self.inner.clone());
+        Ok(acceptor.accept(stream).await?)
+    }
+}
+
+fn load_certs(cert: &str) -> Result<Vec<Certificate>, KvError> {
+    let mut cursor = Cursor::new(cert);
+    let certs = rustls_pemfile::certs(&mut cursor)
+        .map_err(|_| KvError::CertificateParseError("server", "cert"))?;
+    if certs.is_empty() {
+        return Err(KvError::CertificateParseError("server", "cert"));
+    }
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn load_key(key: &str) -> Result<PrivateKey, KvError> {
+    let mut cursor = Cursor::new(key);
+    // 依次尝试 PKCS8、RSA、EC 格式的私钥
+    loop {
+        match rustls_pemfile::read_one(&mut cursor) {
+            Ok(Some(rustls_pemfile::Item::PKCS8Key(key)))
+            | Ok(Some(rustls_pemfile::Item::RSAKey(key)))
+            | Ok(Some(rustls_pemfile::Item::ECKey(key))) => return Ok(PrivateKey(key)),
+            Ok(Some(_)) => continue,
+            _ => return Err(KvError::CertificateParseError("private", "key")),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tls_utils {
+    use certify::{generate_ca, generate_cert, load_ca};
+
+    /// 测试使用的 CA 证书，以及它签发的服务器和客户端的 (cert, key)；
+    /// fixtures 中的客户端证书已经过期了，这里每次重新生成
+    pub struct TestCerts {
+        pub ca: String,
+        pub server: (String, String),
+        pub client: (String, String),
+    }
+
+    pub const DOMAIN: &str = "kvserver.acme.inc";
+
+    pub fn generate() -> TestCerts {
+        let (ca_cert, ca_key) =
+            generate_ca(["acme.inc"], "CN", "Acme Inc.", "Acme CA", None, Some(365)).unwrap();
+        let ca = load_ca(&ca_cert, &ca_key).unwrap();
+        let server = generate_cert(
+            &ca,
+            [DOMAIN],
+            "CN",
+            "Acme Inc.",
+            "Acme KV server",
+            None,
+            false,
+            Some(365),
+        )
+        .unwrap();
+        let client = generate_cert(
+            &ca,
+            [],
+            "CN",
+            "Acme Inc.",
+            "awesome-device-id",
+            None,
+            true,
+            Some(365),
+        )
+        .unwrap();
+        TestCerts {
+            ca: ca_cert,
+            server,
+            client,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::{tls_utils::*, *};
+
+    async fn start_server(acceptor: TlsServerAcceptor) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(mut stream) = acceptor.accept(stream).await {
+                        let mut buf = [0; 5];
+                        stream.read_exact(&mut buf).await.unwrap();
+                        stream.write_all(&buf).await.unwrap();
+                    }
+                });
+            }
+        });
+        addr
+    }
+
+    async fn echo(connector: &TlsClientConnector, addr: SocketAddr) -> Result<Vec<u8>, KvError> {
+        let stream = TcpStream::connect(addr).await?;
+        let mut stream = connector.connect(stream).await?;
+        stream.write_all(b"hello").await?;
+        let mut buf = vec![0; 5];
+        stream.read_exact(&mut buf).await?;
+        Ok(buf)
+    }
+
+    #[tokio::test]
+    async fn tls_should_work() {
+        let certs = generate();
+        let (cert, key) = &certs.server;
+        let addr = start_server(TlsServerAcceptor::new(cert, key, None).unwrap()).await;
+
+        let connector = TlsClientConnector::new(DOMAIN, None, Some(&certs.ca)).unwrap();
+        assert_eq!(echo(&connector, addr).await.unwrap(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn tls_with_client_cert_should_work() {
+        let certs = generate();
+        let (cert, key) = &certs.server;
+        let acceptor = TlsServerAcceptor::new(cert, key, Some(&certs.ca)).unwrap();
+        let addr = start_server(acceptor).await;
+
+        let (cert, key) = &certs.client;
+        let identity = Some((cert.as_str(), key.as_str()));
+        let connector = TlsClientConnector::new(DOMAIN, identity, Some(&certs.ca)).unwrap();
+        assert_eq!(echo(&connector, addr).await.unwrap(), b"hello");
+
+        // 开启 mTLS 后，没有客户端证书的连接会被拒绝
+        let connector = TlsClientConnector::new(DOMAIN, None, Some(&certs.ca)).unwrap();
+        assert!(echo(&connector, addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn tls_with_bad_domain_should_not_work() {
+        let certs = generate();
+        let (cert, key) = &certs.server;
+        let addr = start_server(TlsServerAcceptor::new(cert, key, None).unwrap()).await;
+
+        let connector =
+            TlsClientConnector::new("kvserver1.acme.inc", None, Some(&certs.ca)).unwrap();
+        assert!(echo(&connector, addr).await.is_err());
+    }
+}
@@ -33,6 +33,95 @@ impl CommandRequest {
             })),
         }
     }
+
+    pub fn new_hmget(table: impl Into<String>, keys: Vec<String>) -> Self {
+        Self {
+            request_data: Some(RequestData::Hmget(Hmget {
+                table: table.into(),
+                keys,
+            })),
+        }
+    }
+
+    pub fn new_hmset(table: impl Into<String>, pairs: Vec<Kvpair>) -> Self {
+        Self {
+            request_data: Some(RequestData::Hmset(Hmset {
+                table: table.into(),
+                pairs,
+            })),
+        }
+    }
+
+    pub fn new_hdel(table: impl Into<String>, key: impl Into<String>) -> Self {
+        Self {
+            request_data: Some(RequestData::Hdel(Hdel {
+                table: table.into(),
+                key: key.into(),
+            })),
+        }
+    }
+
+    pub fn new_hmdel(table: impl Into<String>, keys: Vec<String>) -> Self {
+        Self {
+            request_data: Some(RequestData::Hmdel(Hmdel {
+                table: table.into(),
+                keys,
+            })),
+        }
+    }
+
+    pub fn new_hexist(table: impl Into<String>, key: impl Into<String>) -> Self {
+        Self {
+            request_data: Some(RequestData::Hexist(Hexist {
+                table: table.into(),
+                key: key.into(),
+            })),
+        }
+    }
+
+    pub fn new_hmexist(table: impl Into<String>, keys: Vec<String>) -> Self {
+        Self {
+            request_data: Some(RequestData::Hmexist(Hmexist {
+                table: table.into(),
+                keys,
+            })),
+        }
+    }
+
+    pub fn new_subscribe(topic: impl Into<String>) -> Self {
+        Self {
+            request_data: Some(RequestData::Subscribe(Subscribe {
+                topic: topic.into(),
+            })),
+        }
+    }
+
+    pub fn new_unsubscribe(topic: impl Into<String>, id: u32) -> Self {
+        Self {
+            request_data: Some(RequestData::Unsubscribe(Unsubscribe {
+                topic: topic.into(),
+                id,
+            })),
+        }
+    }
+
+    pub fn new_publish(topic: impl Into<String>, data: Vec<Value>) -> Self {
+        Self {
+            request_data: Some(RequestData::Publish(Publish {
+                topic: topic.into(),
+                data,
+            })),
+        }
+    }
+}
+
+impl CommandResponse {
+    pub fn ok() -> Self {
+        Self {
+            status: StatusCode::OK.as_u16() as _,
+            ..Default::default()
+        }
+    }
 }
 
 impl Kvpair {
@@ -115,6 +204,23 @@ impl From<Vec<Kvpair>> for CommandResponse {
     }
 }
 
+/// 全部存在时返回 200；否则返回 204，message 中按 key 的顺序用 1/0 表示是否存在
+impl From<Vec<bool>> for CommandResponse {
+    fn from(flags: Vec<bool>) -> Self {
+        if flags.iter().all(|&exist| exist) {
+            return Self::ok();
+        }
+        Self {
+            status: StatusCode::NO_CONTENT.as_u16() as _,
+            message: flags
+                .iter()
+                .map(|&exist| if exist { '1' } else { '0' })
+                .collect(),
+            ..Default::default()
+        }
+    }
+}
+
 impl From<KvError> for CommandResponse {
     fn from(err: KvError) -> Self {
         let mut res = Self {
@@ -125,6 +231,7 @@ impl From<KvError> for CommandResponse {
 
         match err {
             KvError::NotFound(_, _) => res.status = StatusCode::NOT_FOUND.as_u16() as _,
+            KvError::SubscriptionNotFound(_) => res.status = StatusCode::NOT_FOUND.as_u16() as _,
             KvError::InvalidCommand(_) => res.status = StatusCode::BAD_REQUEST.as_u16() as _,
             _ => {}
         }
@@ -133,6 +240,17 @@ impl From<KvError> for CommandResponse {
     }
 }
 
+impl TryFrom<&Value> for i64 {
+    type Error = KvError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value.value {
+            Some(value::Value::Integer(i)) => Ok(i),
+            _ => Err(KvError::ConvertError(value.clone(), "Integer")),
+        }
+    }
+}
+
 impl TryFrom<Value> for Vec<u8> {
     type Error = KvError;
 
@@ -1,7 +1,7 @@
 use reqwest::StatusCode;
 
 use crate::{
-    pb::{Hget, Hgetall, Hset},
+    pb::{Hdel, Hexist, Hget, Hgetall, Hmdel, Hmexist, Hmget, Hmset, Hset},
     CommandResponse, CommandServer, Kvpair, Storage, Value,
 };
 
@@ -74,3 +74,82 @@ impl CommandServer for Hgetall {
         }
     }
 }
+
+impl CommandServer for Hmget {
+    fn execute(self, store: &impl Storage) -> CommandResponse {
+        let mut res = CommandResponse::default();
+        let mut values = Vec::with_capacity(self.keys.len());
+        for key in self.keys {
+            // 和 Hget 的响应保持一致：有一个 key 取不到就返回 500
+            if let Ok(Some(v)) = store.get(&self.table, &key) {
+                values.push(v);
+            } else {
+                res.status = StatusCode::INTERNAL_SERVER_ERROR.as_u16() as _;
+                res.message = format!("Fialed go get field value for key: {}", key);
+                return res;
+            }
+        }
+        values.into()
+    }
+}
+
+impl CommandServer for Hmset {
+    fn execute(self, store: &impl Storage) -> CommandResponse {
+        // 只返回被覆盖的旧 value
+        let mut values = vec![];
+        for Kvpair { key, value } in self.pairs {
+            match store.set(&self.table, key, value.unwrap_or_default()) {
+                Ok(Some(v)) => values.push(v),
+                Ok(None) => {}
+                Err(err) => return err.into(),
+            }
+        }
+        values.into()
+    }
+}
+
+impl CommandServer for Hdel {
+    fn execute(self, store: &impl Storage) -> CommandResponse {
+        match store.del(&self.table, &self.key) {
+            Ok(old) => old.unwrap_or_default().into(),
+            Err(err) => err.into(),
+        }
+    }
+}
+
+impl CommandServer for Hmdel {
+    fn execute(self, store: &impl Storage) -> CommandResponse {
+        // 只返回确实被删除的 value
+        let mut values = vec![];
+        for key in self.keys {
+            match store.del(&self.table, &key) {
+                Ok(Some(v)) => values.push(v),
+                Ok(None) => {}
+                Err(err) => return err.into(),
+            }
+        }
+        values.into()
+    }
+}
+
+impl CommandServer for Hexist {
+    fn execute(self, store: &impl Storage) -> CommandResponse {
+        match store.contains(&self.table, &self.key) {
+            Ok(exist) => vec![exist].into(),
+            Err(err) => err.into(),
+        }
+    }
+}
+
+impl CommandServer for Hmexist {
+    fn execute(self, store: &impl Storage) -> CommandResponse {
+        let mut flags = Vec::with_capacity(self.keys.len());
+        for key in self.keys {
+            match store.contains(&self.table, &key) {
+                Ok(exist) => flags.push(exist),
+                Err(err) => return err.into(),
+            }
+        }
+        flags.into()
+    }
+}
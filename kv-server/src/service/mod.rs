@@ -1,11 +1,18 @@
 mod command_server;
+mod topic;
 
-use std::sync::Arc;
+use std::{pin::Pin, sync::Arc};
 
 pub use command_server::*;
+use futures::{stream, Stream};
+use tokio_stream::wrappers::ReceiverStream;
+pub use topic::*;
 use tracing::debug;
 
-use crate::{CommandRequest, CommandResponse, MemoryDB, RequestData, Storage};
+use crate::{CommandRequest, CommandResponse, KvError, MemoryDB, RequestData, Storage};
+
+/// Subscribe 返回的响应流：第一个响应是订阅 id，之后是发布到主题的数据
+pub type StreamingResponse = Pin<Box<dyn Stream<Item = Arc<CommandResponse>> + Send>>;
 
 pub trait CommandServer {
     fn execute(self, store: &impl Storage) -> CommandResponse;
@@ -59,6 +66,7 @@ impl<Store> ServiceInner<Store> {
 
 pub struct Service<Store = MemoryDB> {
     inner: Arc<ServiceInner<Store>>,
+    broadcaster: Arc<BroadCaster>,
 }
 
 pub trait Notify<Arg> {
@@ -87,9 +95,27 @@ impl<Arg> NotifyMut<Arg> for Vec<fn(&mut Arg)> {
 }
 
 impl<Store: Storage> Service<Store> {
+    /// 执行一个命令，返回一个响应；Subscribe 会返回多个响应，需要使用 execute_streaming
     pub fn execute(&self, cmd: CommandRequest) -> CommandResponse {
         self.inner.on_recevied.notify(&cmd);
-        let mut resp = dispatch(cmd, &self.inner.clone().store);
+        let mut resp = match cmd.request_data {
+            Some(RequestData::Subscribe(_)) => {
+                KvError::InvalidCommand("Subscribe requires execute_streaming".into()).into()
+            }
+            Some(RequestData::Unsubscribe(cmd)) => {
+                match self.broadcaster.clone().unsubscribe(cmd.topic, cmd.id) {
+                    Ok(_) => CommandResponse::ok(),
+                    Err(e) => e.into(),
+                }
+            }
+            Some(RequestData::Publish(cmd)) => {
+                self.broadcaster
+                    .clone()
+                    .publish(cmd.topic, Arc::new(cmd.data.into()));
+                CommandResponse::ok()
+            }
+            _ => dispatch(cmd, &self.inner.store),
+        };
         self.inner.on_executed.notify(&resp);
         self.inner.on_before_send.notify(&mut resp);
         if !self.inner.on_before_send.is_empty() {
@@ -98,12 +124,27 @@ impl<Store: Storage> Service<Store> {
 
         resp
     }
+
+    /// 执行一个命令，返回响应流；除了 Subscribe，其它命令的响应流里只有一个响应
+    pub fn execute_streaming(&self, cmd: CommandRequest) -> StreamingResponse {
+        match cmd.request_data {
+            Some(RequestData::Subscribe(cmd)) => {
+                let rx = self.broadcaster.clone().subscribe(cmd.topic);
+                Box::pin(ReceiverStream::new(rx))
+            }
+            _ => {
+                let resp = self.execute(cmd);
+                Box::pin(stream::once(async { Arc::new(resp) }))
+            }
+        }
+    }
 }
 
 impl<Store> From<ServiceInner<Store>> for Service<Store> {
     fn from(inner: ServiceInner<Store>) -> Self {
         Self {
             inner: Arc::new(inner),
+            broadcaster: Default::default(),
         }
     }
 }
@@ -113,6 +154,7 @@ impl<Store> Clone for Service<Store> {
     fn clone(&self) -> Self {
         Self {
             inner: Arc::clone(&self.inner),
+            broadcaster: Arc::clone(&self.broadcaster),
         }
     }
 }
@@ -123,19 +165,88 @@ pub fn dispatch(cmd: CommandRequest, storage: &impl Storage) -> CommandResponse
             RequestData::Hget(cmd) => cmd.execute(storage),
             RequestData::Hset(cmd) => cmd.execute(storage),
             RequestData::Hgetall(cmd) => cmd.execute(storage),
-            RequestData::Hmget(_) => todo!(),
-            RequestData::Hmset(_) => todo!(),
-            RequestData::Hdel(_) => todo!(),
-            RequestData::Hmdel(_) => todo!(),
-            RequestData::Hexist(_) => todo!(),
-            RequestData::Hmexist(_) => todo!(),
+            RequestData::Hmget(cmd) => cmd.execute(storage),
+            RequestData::Hmset(cmd) => cmd.execute(storage),
+            RequestData::Hdel(cmd) => cmd.execute(storage),
+            RequestData::Hmdel(cmd) => cmd.execute(storage),
+            RequestData::Hexist(cmd) => cmd.execute(storage),
+            RequestData::Hmexist(cmd) => cmd.execute(storage),
+            // pub/sub 命令由 Service 处理
+            RequestData::Subscribe(_) | RequestData::Unsubscribe(_) | RequestData::Publish(_) => {
+                KvError::InvalidCommand("pub/sub commands are handled by Service".into()).into()
+            }
         };
     }
-    CommandResponse::default()
+    KvError::InvalidCommand("Request has no data".into()).into()
 }
 
 #[cfg(test)]
 mod test {
+    use super::*;
+    use crate::{Kvpair, Value};
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn service_pub_sub_should_work() {
+        let service: Service = ServiceInner::new(MemoryDB::new()).into();
+
+        let mut sub = service.execute_streaming(CommandRequest::new_subscribe("lobby"));
+        let id: i64 = (&sub.next().await.unwrap().values[0]).try_into().unwrap();
+
+        let res = service.execute(CommandRequest::new_publish("lobby", vec!["hi".into()]));
+        assert_eq!(res.status, 200);
+        let data = sub.next().await.unwrap();
+        assert_eq!(data.values, vec![Value::from("hi")]);
+
+        let res = service.execute(CommandRequest::new_unsubscribe("lobby", id as _));
+        assert_eq!(res.status, 200);
+        assert!(sub.next().await.is_none());
+        let res = service.execute(CommandRequest::new_unsubscribe("lobby", id as _));
+        assert_eq!(res.status, 404);
+
+        // Subscribe 不能用 execute 执行
+        let res = service.execute(CommandRequest::new_subscribe("lobby"));
+        assert_eq!(res.status, 400);
+    }
+
+    #[test]
+    fn dispatch_should_work() {
+        let store = MemoryDB::new();
+        let res = dispatch(CommandRequest::new_hset("t1", "k1", "v1".into()), &store);
+        assert_eq!((res.status, res.message.as_str()), (200, "添加成功"));
+        assert!(res.values.is_empty());
+        let res = dispatch(CommandRequest::new_hset("t1", "k1", "v2".into()), &store);
+        assert_eq!((res.status, res.message.as_str()), (200, "添加成功"));
+
+        let res = dispatch(CommandRequest::new_hget("t1", "k2"), &store);
+        assert_eq!(res.status, 500);
+
+        let pairs = vec![Kvpair::new("k2", 2), Kvpair::new("k3", 3)];
+        let res = dispatch(CommandRequest::new_hmset("t1", pairs), &store);
+        assert_eq!((res.status, res.values), (200, vec![]));
+        let keys = vec!["k3".into(), "k1".into()];
+        let res = dispatch(CommandRequest::new_hmget("t1", keys), &store);
+        assert_eq!(res.values, vec![3.into(), "v2".into()]);
+        let keys = vec!["k1".into(), "k4".into()];
+        let res = dispatch(CommandRequest::new_hmget("t1", keys), &store);
+        assert_eq!(res.status, 500);
+
+        let keys = vec!["k1".into(), "k4".into()];
+        let res = dispatch(CommandRequest::new_hmexist("t1", keys), &store);
+        assert_eq!((res.status, res.message.as_str()), (204, "10"));
+        let res = dispatch(CommandRequest::new_hexist("t1", "k1"), &store);
+        assert_eq!(res.status, 200);
+
+        let keys = vec!["k1".into(), "k4".into()];
+        let res = dispatch(CommandRequest::new_hmdel("t1", keys), &store);
+        assert_eq!(res.values, vec![Value::from("v2")]);
+        let res = dispatch(CommandRequest::new_hdel("t1", "k2"), &store);
+        assert_eq!(res.values, vec![Value::from(2)]);
+
+        let res = dispatch(CommandRequest::default(), &store);
+        assert_eq!(res.status, 400);
+    }
+
     #[test]
     fn test_service() {
         fn add(a: i32, b: i32) -> i32 {
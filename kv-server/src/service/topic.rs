@@ -0,0 +1,183 @@
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use dashmap::{DashMap, DashSet};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{debug, info, warn};
+
+use crate::{CommandResponse, KvError, Value};
+
+/// 每个订阅的 channel 最多缓存的数据
+const BROADCAST_CAPACITY: usize = 128;
+
+/// 下一个订阅 id
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+fn new_subscription_id() -> u32 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+pub trait Topic: Send + Sync + 'static {
+    /// 订阅某个主题，返回接收数据的 channel，第一个数据是订阅 id
+    fn subscribe(self, name: impl Into<String>) -> mpsc::Receiver<Arc<CommandResponse>>;
+    /// 取消订阅
+    fn unsubscribe(self, name: impl Into<String>, id: u32) -> Result<u32, KvError>;
+    /// 往主题里发布一个数据
+    fn publish(self, name: impl Into<String>, value: Arc<CommandResponse>);
+}
+
+/// 主题发布订阅的数据结构
+#[derive(Default)]
+pub struct BroadCaster {
+    /// 所有的主题，<主题名字，订阅 id>
+    topics: DashMap<String, DashSet<u32>>,
+    /// 所有的订阅，<订阅 id，channel 的发送端>
+    subscriptions: DashMap<u32, mpsc::Sender<Arc<CommandResponse>>>,
+}
+
+impl BroadCaster {
+    fn remove_subscription(&self, name: String, id: u32) -> Option<u32> {
+        if let Some(v) = self.topics.get_mut(&name) {
+            v.remove(&id);
+
+            // 主题没有订阅了，也删除主题
+            if v.is_empty() {
+                info!("Topic: {:?} is deleted", &name);
+                drop(v);
+                self.topics.remove(&name);
+            }
+        }
+
+        debug!("Subscription {} is removed!", id);
+        self.subscriptions.remove(&id).map(|(id, _)| id)
+    }
+}
+
+impl Topic for Arc<BroadCaster> {
+    fn subscribe(self, name: impl Into<String>) -> mpsc::Receiver<Arc<CommandResponse>> {
+        let id = {
+            let entry = self.topics.entry(name.into()).or_default();
+            let id = new_subscription_id();
+            entry.value().insert(id);
+            id
+        };
+
+        let (tx, rx) = mpsc::channel(BROADCAST_CAPACITY);
+        // channel 是新建的，一定有空间放下订阅 id
+        let v: Value = (id as i64).into();
+        tx.try_send(Arc::new(v.into())).unwrap();
+
+        self.subscriptions.insert(id, tx);
+        debug!("Subscription {} is added", id);
+        rx
+    }
+
+    fn unsubscribe(self, name: impl Into<String>, id: u32) -> Result<u32, KvError> {
+        match self.remove_subscription(name.into(), id) {
+            Some(id) => Ok(id),
+            None => Err(KvError::SubscriptionNotFound(id)),
+        }
+    }
+
+    /// 不等待订阅者：buffer 满了说明订阅者处理不过来，和断开的订阅者一样直接删除，
+    /// 这样一个慢的订阅者不会拖慢发布者和其它订阅者
+    fn publish(self, name: impl Into<String>, value: Arc<CommandResponse>) {
+        let name = name.into();
+        let mut dropped = vec![];
+        if let Some(topic) = self.topics.get(&name) {
+            // 复制一份订阅 id，尽快释放锁
+            let subscriptions = topic.value().clone();
+            drop(topic);
+
+            for id in subscriptions.into_iter() {
+                let Some(tx) = self.subscriptions.get(&id) else {
+                    continue;
+                };
+                match tx.try_send(value.clone()) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(_)) => {
+                        warn!("Subscription {} buffer is full, dropping it", id);
+                        dropped.push(id);
+                    }
+                    // 客户端断开了连接
+                    Err(TrySendError::Closed(_)) => dropped.push(id),
+                }
+            }
+        }
+
+        for id in dropped {
+            self.remove_subscription(name.clone(), id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id_of(res: &CommandResponse) -> i64 {
+        (&res.values[0]).try_into().unwrap()
+    }
+
+    #[tokio::test]
+    async fn pub_sub_should_work() {
+        let broadcaster = Arc::new(BroadCaster::default());
+        let lobby = "lobby";
+
+        let mut rx1 = broadcaster.clone().subscribe(lobby);
+        let mut rx2 = broadcaster.clone().subscribe(lobby);
+        let id1 = id_of(&rx1.recv().await.unwrap());
+        let id2 = id_of(&rx2.recv().await.unwrap());
+        assert_ne!(id1, id2);
+
+        let value: Value = "hello".into();
+        broadcaster
+            .clone()
+            .publish(lobby, Arc::new(value.clone().into()));
+        assert_eq!(rx1.recv().await.unwrap().values, vec![value.clone()]);
+        assert_eq!(rx2.recv().await.unwrap().values, vec![value]);
+
+        // 取消订阅之后收不到新的数据
+        broadcaster.clone().unsubscribe(lobby, id1 as _).unwrap();
+        let value: Value = "world".into();
+        broadcaster
+            .clone()
+            .publish(lobby, Arc::new(value.clone().into()));
+        assert!(rx1.recv().await.is_none());
+        assert_eq!(rx2.recv().await.unwrap().values, vec![value]);
+
+        assert!(matches!(
+            broadcaster.unsubscribe(lobby, id1 as _),
+            Err(KvError::SubscriptionNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn slow_subscriber_should_be_dropped() {
+        let broadcaster = Arc::new(BroadCaster::default());
+        let lobby = "lobby";
+        let mut slow = broadcaster.clone().subscribe(lobby);
+        let mut fast = broadcaster.clone().subscribe(lobby);
+        let id = id_of(&slow.recv().await.unwrap());
+        fast.recv().await.unwrap();
+
+        for i in 0..=BROADCAST_CAPACITY {
+            let value: Value = (i as i64).into();
+            broadcaster.clone().publish(lobby, Arc::new(value.into()));
+            // 快的订阅者一直能收到
+            assert_eq!(fast.recv().await.unwrap().values, vec![(i as i64).into()]);
+        }
+
+        // 慢的订阅者收完 buffer 里的数据之后就结束了
+        for _ in 0..BROADCAST_CAPACITY {
+            assert!(slow.recv().await.is_some());
+        }
+        assert!(slow.recv().await.is_none());
+        assert!(matches!(
+            broadcaster.unsubscribe(lobby, id as _),
+            Err(KvError::SubscriptionNotFound(_))
+        ));
+    }
+}
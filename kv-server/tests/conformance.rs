@@ -0,0 +1,42 @@
+use anyhow::Result;
+use kv_conformance::{command_request::RequestData, script::Expected};
+use kv_server::{
+    CommandRequest, CommandResponse, FrameCoder, MemoryDB, PostServerStream, Service, ServiceInner,
+};
+
+kv_conformance::frame_target! {
+    /// 用 kv-server 的 FrameCoder 和 PostServerStream 跑共同协议的兼容性测试
+    struct KvServer {
+        request: CommandRequest,
+        response: CommandResponse,
+        encode: frame_encode,
+        decode: frame_decode,
+        connect: |server| {
+            let service: Service = ServiceInner::new(MemoryDB::new()).into();
+            tokio::spawn(PostServerStream::new(server, service).process());
+        },
+        expected: |request, expected| {
+            // 为了兼容已有的客户端，kv-server 保留了原来的响应：
+            // hset 只返回 "添加成功"，hget/hmget 取不到 key 时返回 500
+            match request.request_data {
+                Some(RequestData::Hset(_)) if expected.status == 200 => Expected {
+                    values: vec![],
+                    message: Some("添加成功".into()),
+                    ..expected
+                },
+                Some(RequestData::Hget(_) | RequestData::Hmget(_)) if expected.status == 404 => {
+                    Expected {
+                        status: 500,
+                        ..expected
+                    }
+                }
+                _ => expected,
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn kv_server_should_conform_to_protocol() -> Result<()> {
+    kv_conformance::run(&KvServer).await
+}
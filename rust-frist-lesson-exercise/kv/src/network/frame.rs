@@ -11,7 +11,7 @@ use crate::{CommandRequest, CommandResponse, KvError};
 pub const LEN_LEN: usize = 4;
 /// 长度占 31位bit,所以最大的 frame是2G()
 const MAX_FRAME: usize = 2 * 1024 * 1024 * 1024;
-/// 如果 payload超过了 1436字节, 就做压缩
+/// 如果 payload达到了 1436字节, 就做压缩
 const COMPRESSION_LIMIT: usize = 1436;
 /// 代表压缩的 bit, 长度4字节的最高位
 const COMPRESSION_BIT: usize = 1 << 31;
@@ -31,8 +31,8 @@ where
         // 我们先写入长度,如果需要压缩,在重写压缩后的长度
         buf.put_u32(size as _);
 
-        // 超过 1436字节, 压缩
-        if size > COMPRESSION_LIMIT {
+        // 达到 1436字节, 压缩
+        if size >= COMPRESSION_LIMIT {
             let mut buf1 = Vec::with_capacity(size);
             self.encode(&mut buf1)?;
 
@@ -1,5 +1,6 @@
 /// 来自客户端的命令请求
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CommandRequest {
     /// oneof·一个
@@ -10,6 +11,7 @@ pub struct CommandRequest {
 pub mod command_request {
     /// oneof·一个
     #[derive(PartialOrd)]
+    #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum RequestData {
         #[prost(message, tag = "1")]
@@ -34,6 +36,7 @@ pub mod command_request {
 }
 /// 服务器的响应
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct CommandResponse {
     /// 状态码；复用http 2xx/4xx/5xx状态码
@@ -51,14 +54,16 @@ pub struct CommandResponse {
 }
 /// 响应返回的值
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Value {
-    #[prost(oneof = "value::Value", tags = "1, 2, 3, 4, 6")]
+    #[prost(oneof = "value::Value", tags = "1, 2, 3, 4, 5")]
     pub value: ::core::option::Option<value::Value>,
 }
 /// Nested message and enum types in `Value`.
 pub mod value {
     #[derive(PartialOrd)]
+    #[allow(clippy::derive_partial_eq_without_eq)]
     #[derive(Clone, PartialEq, ::prost::Oneof)]
     pub enum Value {
         #[prost(string, tag = "1")]
@@ -69,12 +74,13 @@ pub mod value {
         Integer(i64),
         #[prost(double, tag = "4")]
         Float(f64),
-        #[prost(bool, tag = "6")]
+        #[prost(bool, tag = "5")]
         Bool(bool),
     }
 }
 /// 响应返回的 kv pair
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Kvpair {
     #[prost(string, tag = "1")]
@@ -84,6 +90,7 @@ pub struct Kvpair {
 }
 /// command: 从 table中获取一个 key，返回value
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hget {
     #[prost(string, tag = "1")]
@@ -93,6 +100,7 @@ pub struct Hget {
 }
 /// command: get table all kvpair
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hgetall {
     #[prost(string, tag = "1")]
@@ -101,6 +109,7 @@ pub struct Hgetall {
 /// 通过数组key获取指定表中的数据
 /// command: get table key array（ Get a set of keys from a table ）, return value
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hmget {
     #[prost(string, tag = "1")]
@@ -111,6 +120,7 @@ pub struct Hmget {
 /// save kvpair to table
 /// if `does not exist`·不存在 table, creact table
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hset {
     #[prost(string, tag = "1")]
@@ -122,6 +132,7 @@ pub struct Hset {
 /// save kvpair to table array
 /// if `does not exist`·不存在 table, creact table
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hmset {
     #[prost(string, tag = "1")]
@@ -131,6 +142,7 @@ pub struct Hmset {
 }
 /// delete one a key for table, return before value
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hdel {
     #[prost(string, tag = "1")]
@@ -140,6 +152,7 @@ pub struct Hdel {
 }
 /// delete key array for table, return before value
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hmdel {
     #[prost(string, tag = "1")]
@@ -149,6 +162,7 @@ pub struct Hmdel {
 }
 /// examine·查看 whether the key is Already·已经 existed·存在
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hexist {
     #[prost(string, tag = "1")]
@@ -158,6 +172,7 @@ pub struct Hexist {
 }
 /// examine·查看 whether the key array Already·已经 existed·存在
 #[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Hmexist {
     #[prost(string, tag = "1")]
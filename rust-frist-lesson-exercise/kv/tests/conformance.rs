@@ -0,0 +1,21 @@
+use anyhow::Result;
+use kv::{CommandRequest, CommandResponse, FrameCoder, MemTable, ProstServerStream, Service};
+
+kv_conformance::frame_target! {
+    /// 用 kv 的 FrameCoder 和 ProstServerStream 跑共同协议的兼容性测试
+    struct Kv {
+        request: CommandRequest,
+        response: CommandResponse,
+        encode: encode_frame,
+        decode: decode_frame,
+        connect: |server| {
+            let service: Service = Service::new(MemTable::new());
+            tokio::spawn(ProstServerStream::new(server, service).process());
+        }
+    }
+}
+
+#[tokio::test]
+async fn kv_should_conform_to_protocol() -> Result<()> {
+    kv_conformance::run(&Kv).await
+}
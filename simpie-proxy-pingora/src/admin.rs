@@ -0,0 +1,124 @@
+use std::{collections::BTreeMap, net::SocketAddr};
+
+use async_trait::async_trait;
+use axum::{Json, Router, extract::State, routing::get};
+use pingora::{server::ShutdownWatch, services::background::BackgroundService};
+use serde::Serialize;
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use crate::conf::ProxyConfig;
+
+/// 管理接口，GET /health 返回每个 upstream 中服务的健康状态
+pub struct Admin {
+    config: ProxyConfig,
+    addr: SocketAddr,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeerHealth {
+    pub addr: String,
+    /// 主动健康检查的结果
+    pub healthy: bool,
+    /// 是否被被动检查摘除
+    pub ejected: bool,
+    /// 当前转发中的请求数
+    pub active: usize,
+}
+
+impl Admin {
+    pub fn new(config: ProxyConfig, addr: SocketAddr) -> Self {
+        Self { config, addr }
+    }
+}
+
+pub fn router(config: ProxyConfig) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .with_state(config)
+}
+
+async fn health(State(config): State<ProxyConfig>) -> Json<BTreeMap<String, Vec<PeerHealth>>> {
+    let config = config.load();
+    let upstreams = config
+        .upstreams
+        .iter()
+        .map(|(name, upstream)| {
+            let peers = upstream
+                .servers
+                .iter()
+                .zip(&upstream.peers)
+                .map(|(addr, peer)| PeerHealth {
+                    addr: addr.clone(),
+                    healthy: peer.is_healthy(),
+                    ejected: peer.is_ejected(),
+                    active: peer.active(),
+                })
+                .collect();
+            (name.clone(), peers)
+        })
+        .collect();
+
+    Json(upstreams)
+}
+
+#[async_trait]
+impl BackgroundService for Admin {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let listener = match TcpListener::bind(self.addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("admin failed to listen on {}: {}", self.addr, e);
+                return;
+            }
+        };
+        info!("admin running on: {}", self.addr);
+
+        let shutdown = async move {
+            let _ = shutdown.changed().await;
+        };
+        if let Err(e) = axum::serve(listener, router(self.config.clone()))
+            .with_graceful_shutdown(shutdown)
+            .await
+        {
+            warn!("admin stopped: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::{ProxyConfigResolved, SimpleProxyConfig};
+    use axum::http::Request;
+
+    #[tokio::test]
+    async fn health_should_report_peers() {
+        let yaml: &'static str = include_str!("../fixtures/simple.yaml");
+        let config = SimpleProxyConfig::from_yaml_str(yaml).unwrap();
+        let config = ProxyConfig::new(ProxyConfigResolved::try_from(config).unwrap());
+
+        let upstream = &config.load().upstreams["api_servers"];
+        let req = Request::new(()).into_parts().0;
+        let chosen = upstream.choose(&req, None).unwrap();
+        for _ in 0..3 {
+            chosen.report(false);
+        }
+
+        let Json(upstreams) = health(State(config.clone())).await;
+        assert_eq!(upstreams.len(), 2);
+        let api = &upstreams["api_servers"];
+        assert_eq!(api.len(), 2);
+        let peer = api.iter().find(|p| p.addr == chosen.addr).unwrap();
+        assert!(peer.healthy && peer.ejected);
+        assert_eq!(peer.active, 1);
+        assert!(
+            upstreams["web_servers"]
+                .iter()
+                .all(|p| p.healthy && !p.ejected)
+        );
+
+        let json = serde_json::to_value(&upstreams).unwrap();
+        assert_eq!(json["web_servers"][0]["addr"], "127.0.0.1:3003");
+    }
+}
@@ -0,0 +1,677 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        Arc, LazyLock, Mutex,
+        atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Instant,
+};
+
+use anyhow::{Result, anyhow};
+use axum::http::{HeaderName, header::COOKIE, request::Parts};
+use rand::Rng;
+use tracing::warn;
+
+use crate::conf::{
+    HashKey, HealthCheckResolved, LbStrategy, PassiveCheckResolved, UpstreamConfigResolved,
+};
+
+/// 一致性哈希中每个服务的虚拟节点个数 (再乘以权重)
+const VIRTUAL_NODES: u32 = 160;
+
+/// 服务权重的上限，限制一致性哈希环的大小
+pub(crate) const MAX_WEIGHT: u32 = 100;
+
+/// 被动摘除的截止时间以进程启动后的毫秒数保存
+static EPOCH: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+#[derive(Debug, Clone)]
+pub enum Strategy {
+    Random,
+    RoundRobin,
+    WeightedRoundRobin,
+    LeastConn,
+    /// 哈希环按 hash 排序，保存 (hash, 服务下标)
+    ConsistentHash(HashKeyResolved, Arc<Vec<(u64, usize)>>),
+    PowerOfTwoChoices,
+}
+
+#[derive(Debug, Clone)]
+pub enum HashKeyResolved {
+    Header(HeaderName),
+    Cookie(String),
+    ClientIp,
+}
+
+/// upstream 级别的运行时状态，服务列表和权重不变时热更新后继续使用
+#[derive(Debug, Default)]
+pub struct UpstreamState {
+    cursor: AtomicUsize,
+    /// smooth weighted round-robin 中每个服务当前的权重
+    current_weights: Mutex<Vec<i64>>,
+}
+
+/// 单个服务的运行时状态，热更新后按地址继续使用
+#[derive(Debug, Default)]
+pub struct PeerState {
+    active: AtomicUsize,
+    /// 主动健康检查判定为不健康，默认是健康的
+    unhealthy: AtomicBool,
+    /// 主动健康检查连续成功/失败的次数
+    successes: AtomicU32,
+    failures: AtomicU32,
+    /// 被动检查中连续失败的请求数
+    fails: AtomicU32,
+    /// 被动摘除到什么时候 (EPOCH 之后的毫秒数)，0 表示没有被摘除
+    ejected_until: AtomicU64,
+}
+
+/// 选中的服务，存活期间计入这个服务的活跃连接数
+#[derive(Debug)]
+pub struct ChosenPeer {
+    pub addr: String,
+    /// https 服务的 SNI，None 表示明文连接
+    pub sni: Option<String>,
+    state: Arc<PeerState>,
+    passive_check: Option<PassiveCheckResolved>,
+}
+
+impl Strategy {
+    pub(crate) fn new(
+        strategy: LbStrategy,
+        hash_key: Option<&HashKey>,
+        servers: &[String],
+        weights: &[u32],
+    ) -> Result<Self> {
+        if hash_key.is_some() && strategy != LbStrategy::ConsistentHash {
+            return Err(anyhow!("hash_key only works with consistent_hash"));
+        }
+
+        Ok(match strategy {
+            LbStrategy::Random => Strategy::Random,
+            LbStrategy::RoundRobin => Strategy::RoundRobin,
+            LbStrategy::WeightedRoundRobin => Strategy::WeightedRoundRobin,
+            LbStrategy::LeastConn => Strategy::LeastConn,
+            LbStrategy::PowerOfTwoChoices => Strategy::PowerOfTwoChoices,
+            LbStrategy::ConsistentHash => {
+                let key = match hash_key {
+                    Some(HashKey::Header(name)) => {
+                        HashKeyResolved::Header(HeaderName::try_from(name.as_str())?)
+                    }
+                    Some(HashKey::Cookie(name)) => HashKeyResolved::Cookie(name.clone()),
+                    Some(HashKey::ClientIp) | None => HashKeyResolved::ClientIp,
+                };
+                Strategy::ConsistentHash(key, Arc::new(hash_ring(servers, weights)))
+            }
+        })
+    }
+}
+
+fn hash_ring(servers: &[String], weights: &[u32]) -> Vec<(u64, usize)> {
+    let mut ring: Vec<(u64, usize)> = servers
+        .iter()
+        .zip(weights)
+        .enumerate()
+        .flat_map(|(i, (addr, weight))| {
+            (0..VIRTUAL_NODES.saturating_mul(*weight))
+                .map(move |v| (hash(format!("{}-{}", addr, v).as_bytes()), i))
+        })
+        .collect();
+    ring.sort_unstable();
+    ring
+}
+
+/// FNV-1a 再加上 splitmix64 的 finalizer 打散，保证不同进程/版本的结果一致
+fn hash(bytes: &[u8]) -> u64 {
+    let mut h = bytes.iter().fold(0xcbf29ce484222325_u64, |h, b| {
+        (h ^ *b as u64).wrapping_mul(0x100000001b3)
+    });
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d049bb133111eb);
+    h ^ (h >> 31)
+}
+
+impl HashKeyResolved {
+    fn hash(&self, req: &Parts, client_ip: Option<IpAddr>) -> Option<u64> {
+        match self {
+            HashKeyResolved::Header(name) => req.headers.get(name).map(|v| hash(v.as_bytes())),
+            HashKeyResolved::Cookie(name) => req
+                .headers
+                .get_all(COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(';'))
+                .filter_map(|pair| pair.trim().split_once('='))
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| hash(v.as_bytes())),
+            HashKeyResolved::ClientIp => client_ip.map(|ip| hash(ip.to_string().as_bytes())),
+        }
+    }
+}
+
+impl UpstreamConfigResolved {
+    /// 按负载均衡策略从健康的服务中选出一个，没有服务时返回 None
+    ///
+    /// 所有服务都不健康时退化为在全部服务中选择，避免直接拒绝所有请求
+    pub fn choose(&self, req: &Parts, client_ip: Option<IpAddr>) -> Option<ChosenPeer> {
+        let n = self.servers.len();
+        if n == 0 {
+            return None;
+        }
+
+        let mut usable: Vec<bool> = self.peers.iter().map(|p| p.is_available()).collect();
+        if !usable.contains(&true) {
+            usable = vec![true; n];
+        }
+        let candidates: Vec<usize> = (0..n).filter(|&i| usable[i]).collect();
+        let m = candidates.len();
+
+        let index = match &self.strategy {
+            Strategy::Random => candidates[rand::rng().random_range(0..m)],
+            Strategy::RoundRobin => {
+                candidates[self.state.cursor.fetch_add(1, Ordering::Relaxed) % m]
+            }
+            Strategy::WeightedRoundRobin => self.next_weighted(&usable),
+            Strategy::LeastConn => {
+                // 从轮询的位置开始找，连接数相同时不会总是选中第一个
+                let start = self.state.cursor.fetch_add(1, Ordering::Relaxed);
+                (0..m)
+                    .map(|i| candidates[(start + i) % m])
+                    .min_by_key(|&i| self.peers[i].active())
+                    .unwrap_or_default()
+            }
+            Strategy::ConsistentHash(key, ring) => match key.hash(req, client_ip) {
+                // 沿着哈希环找到第一个可用的服务
+                Some(h) if !ring.is_empty() => {
+                    let pos = ring.partition_point(|(node, _)| *node < h);
+                    (0..ring.len())
+                        .map(|i| ring[(pos + i) % ring.len()].1)
+                        .find(|&i| usable[i])
+                        .unwrap_or(candidates[0])
+                }
+                // 请求中没有 hash key 时随机选择
+                _ => candidates[rand::rng().random_range(0..m)],
+            },
+            Strategy::PowerOfTwoChoices => {
+                let mut rng = rand::rng();
+                let a = rng.random_range(0..m);
+                let b = (a + rng.random_range(1..m.max(2))) % m;
+                let (a, b) = (candidates[a], candidates[b]);
+                if self.peers[b].active() < self.peers[a].active() {
+                    b
+                } else {
+                    a
+                }
+            }
+        };
+
+        Some(ChosenPeer::new(
+            self.servers[index].clone(),
+            self.sni[index].clone(),
+            self.peers[index].clone(),
+            self.passive_check.clone(),
+        ))
+    }
+
+    /// nginx 的 smooth weighted round-robin
+    fn next_weighted(&self, usable: &[bool]) -> usize {
+        let mut current = self
+            .state
+            .current_weights
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if current.len() != self.weights.len() {
+            *current = vec![0; self.weights.len()];
+        }
+
+        let mut total = 0;
+        let mut best: Option<usize> = None;
+        for (i, weight) in self.weights.iter().enumerate() {
+            if !usable[i] {
+                continue;
+            }
+            total += *weight as i64;
+            current[i] += *weight as i64;
+            if best.is_none_or(|b| current[i] > current[b]) {
+                best = Some(i);
+            }
+        }
+        let best = best.unwrap_or_default();
+        current[best] -= total;
+        best
+    }
+
+    /// 复用上一份配置中同名 upstream 的运行时状态
+    pub(crate) fn inherit_state(&mut self, previous: &UpstreamConfigResolved) {
+        if self.servers == previous.servers && self.weights == previous.weights {
+            self.state = previous.state.clone();
+        }
+
+        for (addr, peer) in self.servers.iter().zip(self.peers.iter_mut()) {
+            if let Some(i) = previous.servers.iter().position(|a| a == addr) {
+                *peer = previous.peers[i].clone();
+            }
+        }
+    }
+}
+
+fn now_ms() -> u64 {
+    EPOCH.elapsed().as_millis() as u64 + 1
+}
+
+impl PeerState {
+    /// 当前转发中的请求数
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// 主动健康检查的结果
+    pub fn is_healthy(&self) -> bool {
+        !self.unhealthy.load(Ordering::Relaxed)
+    }
+
+    /// 是否被被动检查摘除，冷却时间过后自动恢复
+    pub fn is_ejected(&self) -> bool {
+        self.ejected_until.load(Ordering::Relaxed) > now_ms()
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.is_healthy() && !self.is_ejected()
+    }
+
+    /// 记录一次主动健康检查的结果，健康状态变化时返回新的状态
+    pub fn record_check(&self, ok: bool, check: &HealthCheckResolved) -> Option<bool> {
+        let (count, reset, threshold) = if ok {
+            (&self.successes, &self.failures, check.rise)
+        } else {
+            (&self.failures, &self.successes, check.fall)
+        };
+        reset.store(0, Ordering::Relaxed);
+        let count = count.fetch_add(1, Ordering::Relaxed).saturating_add(1);
+
+        // ok 时从不健康变为健康，失败时从健康变为不健康
+        if count >= threshold && self.is_healthy() != ok {
+            self.unhealthy.store(!ok, Ordering::Relaxed);
+            return Some(ok);
+        }
+        None
+    }
+
+    /// 记录一次转发的结果，连续失败达到 max_fails 时摘除，返回是否刚被摘除
+    pub fn record_request(&self, ok: bool, check: &PassiveCheckResolved) -> bool {
+        if ok {
+            self.fails.store(0, Ordering::Relaxed);
+            return false;
+        }
+
+        let fails = self.fails.fetch_add(1, Ordering::Relaxed) + 1;
+        if fails < check.max_fails {
+            return false;
+        }
+        self.fails.store(0, Ordering::Relaxed);
+        let until = now_ms() + check.cool_down.as_millis() as u64;
+        self.ejected_until.store(until, Ordering::Relaxed);
+        true
+    }
+}
+
+impl ChosenPeer {
+    fn new(
+        addr: String,
+        sni: Option<String>,
+        state: Arc<PeerState>,
+        passive_check: Option<PassiveCheckResolved>,
+    ) -> Self {
+        state.active.fetch_add(1, Ordering::Relaxed);
+        Self {
+            addr,
+            sni,
+            state,
+            passive_check,
+        }
+    }
+
+    /// 报告这次转发是否成功，没有配置被动检查时什么都不做
+    pub fn report(&self, ok: bool) {
+        if let Some(check) = &self.passive_check
+            && self.state.record_request(ok, check)
+        {
+            warn!(
+                "upstream server {} ejected for {:?}",
+                self.addr, check.cool_down
+            );
+        }
+    }
+}
+
+impl Drop for ChosenPeer {
+    fn drop(&mut self) {
+        self.state.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::conf::*;
+    use axum::http::{Request, request::Parts};
+    use std::collections::HashMap;
+
+    fn resolve(yaml: &str) -> UpstreamConfigResolved {
+        let config: UpstreamConfig = serde_yaml::from_str(yaml).unwrap();
+        config.try_into().unwrap()
+    }
+
+    fn request(headers: &[(&str, &str)]) -> Parts {
+        let mut builder = Request::builder().uri("/");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    fn pick(upstream: &UpstreamConfigResolved, req: &Parts) -> String {
+        upstream.choose(req, None).unwrap().addr.clone()
+    }
+
+    #[test]
+    fn round_robin_should_work() {
+        let upstream = resolve("{ name: api, strategy: round_robin, servers: [a, b, c] }");
+        let req = request(&[]);
+
+        let picks: Vec<_> = (0..6).map(|_| pick(&upstream, &req)).collect();
+        assert_eq!(picks, ["a", "b", "c", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn weighted_round_robin_should_be_smooth() {
+        let upstream = resolve(
+            "{ name: api, strategy: weighted_round_robin, servers: [{ addr: a, weight: 5 }, b, c] }",
+        );
+        let req = request(&[]);
+
+        let picks: Vec<_> = (0..14).map(|_| pick(&upstream, &req)).collect();
+        let round = ["a", "a", "b", "a", "c", "a", "a"];
+        assert_eq!(picks[..7], round);
+        assert_eq!(picks[7..], round);
+    }
+
+    #[test]
+    fn least_conn_should_work() {
+        let upstream = resolve("{ name: api, strategy: least_conn, servers: [a, b, c] }");
+        let req = request(&[]);
+
+        let first = upstream.choose(&req, None).unwrap();
+        let second = upstream.choose(&req, None).unwrap();
+        let third = upstream.choose(&req, None).unwrap();
+        let mut addrs = vec![first.addr.clone(), second.addr.clone(), third.addr.clone()];
+        addrs.sort();
+        assert_eq!(addrs, ["a", "b", "c"]);
+        assert!(upstream.peers.iter().all(|p| p.active() == 1));
+
+        // 释放后这个服务的连接数最少
+        let addr = second.addr.clone();
+        drop(second);
+        for _ in 0..3 {
+            assert_eq!(pick(&upstream, &req), addr);
+        }
+        drop((first, third));
+        assert!(upstream.peers.iter().all(|p| p.active() == 0));
+    }
+
+    #[test]
+    fn power_of_two_choices_should_avoid_busy_peer() {
+        let upstream = resolve("{ name: api, strategy: power_of_two_choices, servers: [a, b] }");
+        let req = request(&[]);
+
+        let busy = upstream.choose(&req, None).unwrap();
+        for _ in 0..20 {
+            assert_ne!(pick(&upstream, &req), busy.addr);
+        }
+
+        let single = resolve("{ name: api, strategy: power_of_two_choices, servers: [a] }");
+        assert_eq!(pick(&single, &req), "a");
+    }
+
+    #[test]
+    fn consistent_hash_should_be_stable() {
+        let three = resolve(
+            "{ name: api, strategy: consistent_hash, hash_key: { header: x-user-id }, servers: [a, b, c] }",
+        );
+        let two = resolve(
+            "{ name: api, strategy: consistent_hash, hash_key: { header: x-user-id }, servers: [a, b] }",
+        );
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for i in 0..300 {
+            let req = request(&[("x-user-id", &i.to_string())]);
+            let addr = pick(&three, &req);
+            assert_eq!(pick(&three, &req), addr);
+            // 去掉 c 以后，原来落在 a/b 上的 key 不受影响
+            if addr != "c" {
+                assert_eq!(pick(&two, &req), addr);
+            }
+            *counts.entry(addr).or_default() += 1;
+        }
+        assert_eq!(counts.len(), 3);
+        assert!(counts.values().all(|n| *n > 50), "{:?}", counts);
+
+        // 没有 hash key 时也能选出服务
+        assert!(three.choose(&request(&[]), None).is_some());
+    }
+
+    #[test]
+    fn consistent_hash_on_cookie_and_client_ip_should_work() {
+        let by_cookie = resolve(
+            "{ name: api, strategy: consistent_hash, hash_key: { cookie: sid }, servers: [a, b, c] }",
+        );
+        let addr = pick(&by_cookie, &request(&[("cookie", "theme=dark; sid=42")]));
+        for _ in 0..10 {
+            let req = request(&[("cookie", "sid=42; theme=light")]);
+            assert_eq!(pick(&by_cookie, &req), addr);
+        }
+
+        let by_ip = resolve("{ name: api, strategy: consistent_hash, servers: [a, b, c] }");
+        let ip = "10.0.0.1".parse().ok();
+        let addr = by_ip.choose(&request(&[]), ip).unwrap().addr.clone();
+        for _ in 0..10 {
+            assert_eq!(by_ip.choose(&request(&[]), ip).unwrap().addr, addr);
+        }
+    }
+
+    #[test]
+    fn invalid_upstreams_should_fail() {
+        let config = |yaml: &str| {
+            let config: UpstreamConfig = serde_yaml::from_str(yaml).unwrap();
+            UpstreamConfigResolved::try_from(config)
+        };
+
+        assert!(config("{ name: api, servers: [{ addr: a, weight: 0 }] }").is_err());
+        assert!(config("{ name: api, servers: [{ addr: a, weight: 101 }] }").is_err());
+        assert!(config("{ name: api, servers: [{ addr: a, weight: 4294967295 }] }").is_err());
+        assert!(config("{ name: api, servers: [{ addr: a, weight: 100 }] }").is_ok());
+        assert!(config("{ name: api, hash_key: client_ip, servers: [a] }").is_err());
+        assert!(
+            config("{ name: api, strategy: consistent_hash, hash_key: { header: \"a b\" }, servers: [a] }")
+                .is_err()
+        );
+        assert!(
+            config("{ name: api, servers: [] }")
+                .unwrap()
+                .choose(&request(&[]), None)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn state_should_survive_hot_swap() {
+        let yaml = |servers: &str| {
+            format!(
+                "global: {{ port: 8080 }}\nservers:\n  - {{ server_name: [acme.com], upstream: web }}\nupstreams:\n  - {{ name: web, strategy: round_robin, servers: [{}] }}\n",
+                servers
+            )
+        };
+        let load = |servers: &str, previous: Option<&ProxyConfigResolved>| {
+            let config = SimpleProxyConfig::from_yaml_str(&yaml(servers)).unwrap();
+            ProxyConfigResolved::try_from_with_previous(config, previous).unwrap()
+        };
+        let req = request(&[]);
+        let choose = |config: &ProxyConfigResolved| {
+            let server = config.route("acme.com").unwrap();
+            server.upstream.choose(&req, None).unwrap()
+        };
+
+        let config = ProxyConfig::new(load("a, b, c", None));
+        let held = choose(&config.load());
+        assert_eq!(held.addr, "a");
+
+        // 服务列表不变: 轮询位置和连接数都保留
+        config.update(load("a, b, c", Some(&config.load())));
+        assert_eq!(choose(&config.load()).addr, "b");
+        assert_eq!(config.load().upstreams["web"].peers[0].active(), 1);
+
+        // 服务列表变化: 轮询重新开始，已有服务的连接数保留
+        config.update(load("c, a", Some(&config.load())));
+        assert_eq!(choose(&config.load()).addr, "c");
+        assert_eq!(config.load().upstreams["web"].peers[1].active(), 1);
+        drop(held);
+        assert_eq!(config.load().upstreams["web"].peers[1].active(), 0);
+
+        // 没有传入之前的配置时是新的状态
+        let fresh = load("a, b, c", None);
+        assert_eq!(choose(&fresh).addr, "a");
+    }
+
+    fn health_check(rise: u32, fall: u32) -> HealthCheckResolved {
+        HealthCheckConfig {
+            path: "/".into(),
+            expected_status: 200,
+            interval_ms: 1000,
+            timeout_ms: 1000,
+            rise,
+            fall,
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    #[test]
+    fn record_check_should_follow_rise_and_fall() {
+        let peer = PeerState::default();
+        let check = health_check(2, 3);
+        assert!(peer.is_healthy());
+
+        assert_eq!(peer.record_check(false, &check), None);
+        assert_eq!(peer.record_check(false, &check), None);
+        // 中间的成功会重新计数
+        assert_eq!(peer.record_check(true, &check), None);
+        assert_eq!(peer.record_check(false, &check), None);
+        assert_eq!(peer.record_check(false, &check), None);
+        assert_eq!(peer.record_check(false, &check), Some(false));
+        assert!(!peer.is_healthy());
+        assert_eq!(peer.record_check(false, &check), None);
+
+        assert_eq!(peer.record_check(true, &check), None);
+        assert_eq!(peer.record_check(true, &check), Some(true));
+        assert!(peer.is_healthy());
+        assert_eq!(peer.record_check(true, &check), None);
+    }
+
+    #[test]
+    fn choose_should_skip_unhealthy_peers() {
+        let check = health_check(1, 1);
+        let req = request(&[("x-user-id", "42")]);
+
+        for strategy in [
+            "random",
+            "round_robin",
+            "weighted_round_robin",
+            "least_conn",
+            "power_of_two_choices",
+        ] {
+            let upstream = resolve(&format!(
+                "{{ name: api, strategy: {}, servers: [a, {{ addr: b, weight: 3 }}, c] }}",
+                strategy
+            ));
+            upstream.peers[1].record_check(false, &check);
+            for _ in 0..20 {
+                assert_ne!(pick(&upstream, &req), "b", "{}", strategy);
+            }
+
+            // 全部不健康时仍然可以选出服务
+            upstream.peers[0].record_check(false, &check);
+            upstream.peers[2].record_check(false, &check);
+            assert!(upstream.choose(&req, None).is_some(), "{}", strategy);
+        }
+
+        let upstream = resolve(
+            "{ name: api, strategy: consistent_hash, hash_key: { header: x-user-id }, servers: [a, b, c] }",
+        );
+        let addr = pick(&upstream, &req);
+        let i = upstream.servers.iter().position(|s| *s == addr).unwrap();
+        upstream.peers[i].record_check(false, &check);
+        let other = pick(&upstream, &req);
+        assert_ne!(other, addr);
+        assert_eq!(pick(&upstream, &req), other);
+        // 恢复后回到原来的服务
+        upstream.peers[i].record_check(true, &check);
+        assert_eq!(pick(&upstream, &req), addr);
+    }
+
+    #[test]
+    fn passive_check_should_eject_and_recover() {
+        let upstream = resolve(
+            "{ name: api, strategy: round_robin, servers: [a, b], passive_check: { max_fails: 2, cool_down_ms: 100 } }",
+        );
+        let req = request(&[]);
+
+        let a = upstream.choose(&req, None).unwrap();
+        assert_eq!(a.addr, "a");
+        a.report(false);
+        a.report(true);
+        a.report(false);
+        assert!(upstream.peers[0].is_available());
+        a.report(false);
+        assert!(upstream.peers[0].is_ejected());
+        assert!(upstream.peers[0].is_healthy());
+        drop(a);
+
+        for _ in 0..4 {
+            assert_eq!(pick(&upstream, &req), "b");
+        }
+        std::thread::sleep(std::time::Duration::from_millis(150));
+        assert!(upstream.peers[0].is_available());
+        let picks: Vec<_> = (0..2).map(|_| pick(&upstream, &req)).collect();
+        assert!(picks.contains(&"a".to_string()));
+
+        // 没有配置被动检查时不会被摘除
+        let upstream = resolve("{ name: api, servers: [a] }");
+        let a = upstream.choose(&req, None).unwrap();
+        for _ in 0..10 {
+            a.report(false);
+        }
+        assert!(!upstream.peers[0].is_ejected());
+    }
+
+    #[test]
+    fn invalid_health_checks_should_fail() {
+        let config = |yaml: &str| {
+            let config: UpstreamConfig = serde_yaml::from_str(yaml).unwrap();
+            UpstreamConfigResolved::try_from(config)
+        };
+
+        let check =
+            config("{ name: api, servers: [a], health_check: { path: /healthz } }").unwrap();
+        let check = check.health_check.unwrap();
+        assert_eq!(check.expected_status, 200);
+        assert_eq!(check.interval, std::time::Duration::from_secs(5));
+        assert_eq!((check.rise, check.fall), (2, 3));
+
+        assert!(config("{ name: api, servers: [a], health_check: { path: healthz } }").is_err());
+        assert!(config("{ name: api, servers: [a], health_check: { path: /, fall: 0 } }").is_err());
+        assert!(
+            config("{ name: api, servers: [a], health_check: { path: /, interval_ms: 0 } }")
+                .is_err()
+        );
+        assert!(config("{ name: api, servers: [a], passive_check: { max_fails: 0 } }").is_err());
+    }
+}
@@ -1,8 +1,10 @@
+mod balance;
 mod raw;
 mod resolved;
 
 use anyhow::Result;
 use arc_swap::ArcSwap;
+pub use balance::*;
 pub use raw::*;
 pub use resolved::*;
 use std::{path::Path, sync::Arc};
@@ -21,6 +23,14 @@ impl ProxyConfig {
         Ok(Self::new(config.try_into()?))
     }
 
+    /// 重新加载配置文件，负载均衡的状态在新配置中继续使用
+    pub fn reload(&self, path: impl AsRef<Path>) -> Result<()> {
+        let config = SimpleProxyConfig::from_yaml_file(path)?;
+        let config = ProxyConfigResolved::try_from_with_previous(config, Some(&self.load()))?;
+        self.update(config);
+        Ok(())
+    }
+
     pub fn update(&self, config: ProxyConfigResolved) {
         self.store(Arc::new(config));
     }
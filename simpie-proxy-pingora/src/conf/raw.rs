@@ -1,6 +1,9 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
 /// The main configuration struct for Simple Proxy
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -27,9 +30,31 @@ pub struct GlobalConfig {
     /// Port on which the proxy listens
     pub port: u16,
 
-    /// TLS configuration (optional)
+    /// Name of the default certificate for the TLS listener (optional)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tls: Option<String>,
+
+    /// Port of the TLS listener, certificates are selected by SNI from the
+    /// matched server's `tls`, falling back to the global `tls` (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_port: Option<u16>,
+
+    /// Loopback address pingora listens on for the requests decrypted by the
+    /// TLS listener (optional, defaults to `127.0.0.1:<tls_port + 1>`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_internal: Option<String>,
+
+    /// Redirect every plaintext request on `port` to https on `tls_port`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub redirect_https: bool,
+
+    /// CA bundle used to verify https upstreams instead of the system roots (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_ca: Option<String>,
+
+    /// Address of the admin HTTP endpoint, e.g. `127.0.0.1:9090` (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -51,6 +76,65 @@ pub struct ServerConfig {
     /// Whether TLS is enabled for this server
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tls: Option<String>,
+
+    /// Handle requests whose host matches no server_name (at most one server)
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub default: bool,
+
+    /// Path based routing rules, the first matched rule wins.
+    /// Requests matching no rule go to `upstream`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<LocationConfig>,
+}
+
+/// Path based routing rule
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LocationConfig {
+    /// Path to match, a regex pattern when `match` is `regex`
+    pub path: String,
+
+    /// How `path` is matched against the request path
+    #[serde(default, rename = "match")]
+    pub match_type: PathMatchType,
+
+    /// Only match these methods (any method if empty)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<String>,
+
+    /// Only match requests carrying all these headers with the exact values
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+
+    /// Rewrite the path before forwarding (optional)
+    #[serde(
+        default,
+        with = "serde_yaml::with::singleton_map",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub rewrite: Option<RewriteConfig>,
+
+    /// Name of the upstream server group to forward matched requests to
+    pub upstream: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathMatchType {
+    #[default]
+    Prefix,
+    Exact,
+    Regex,
+}
+
+/// Path rewrite, e.g. `rewrite: strip_prefix` or
+/// `rewrite: { regex: { pattern: "^/v1/(.*)$", replace: "/$1" } }`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewriteConfig {
+    /// Remove the matched prefix, only for `prefix` locations
+    StripPrefix,
+    /// Replace the first match of `pattern`, `$1`/`${name}` refer to capture groups
+    Regex { pattern: String, replace: String },
 }
 
 /// TLS configuration
@@ -73,8 +157,144 @@ pub struct UpstreamConfig {
     /// Name of the upstream server group
     pub name: String,
 
-    /// List of server addresses in this group
-    pub servers: Vec<String>,
+    /// Load balancing strategy, `random` by default
+    #[serde(default)]
+    pub strategy: LbStrategy,
+
+    /// What `consistent_hash` hashes on, e.g. `hash_key: { header: x-user-id }`,
+    /// `hash_key: { cookie: session }` or `hash_key: client_ip` (default)
+    #[serde(
+        default,
+        with = "serde_yaml::with::singleton_map",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hash_key: Option<HashKey>,
+
+    /// List of server addresses in this group, either `host:port`
+    /// or `{ addr: host:port, weight: 3 }` (weight in `1..=100`). Use `https://host[:port]`
+    /// to connect over TLS
+    pub servers: Vec<UpstreamServer>,
+
+    /// SNI sent to https servers, defaults to the host in the address (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sni: Option<String>,
+
+    /// Verify the certificates of https servers, `true` by default
+    #[serde(default = "default_verify")]
+    pub verify_cert: bool,
+
+    /// Check that the certificates of https servers match the SNI, `true` by default
+    #[serde(default = "default_verify")]
+    pub verify_hostname: bool,
+
+    /// Periodically probe every server over HTTP (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheckConfig>,
+
+    /// Eject a server for a while after connection errors or 5xx responses (optional)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub passive_check: Option<PassiveCheckConfig>,
+}
+
+/// Active health check
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct HealthCheckConfig {
+    /// Path requested with `GET`
+    pub path: String,
+
+    /// Status code of a healthy response
+    #[serde(default = "default_expected_status")]
+    pub expected_status: u16,
+
+    /// Milliseconds between two checks
+    #[serde(default = "default_interval_ms")]
+    pub interval_ms: u64,
+
+    /// Milliseconds to wait for the response
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+
+    /// Consecutive successes to mark an unhealthy server healthy
+    #[serde(default = "default_rise")]
+    pub rise: u32,
+
+    /// Consecutive failures to mark a healthy server unhealthy
+    #[serde(default = "default_fall")]
+    pub fall: u32,
+}
+
+/// Passive health check
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PassiveCheckConfig {
+    /// Consecutive failed requests before the server is ejected
+    #[serde(default = "default_max_fails")]
+    pub max_fails: u32,
+
+    /// Milliseconds the server stays ejected
+    #[serde(default = "default_cool_down_ms")]
+    pub cool_down_ms: u64,
+}
+
+fn default_verify() -> bool {
+    true
+}
+
+fn default_expected_status() -> u16 {
+    200
+}
+
+fn default_interval_ms() -> u64 {
+    5000
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_rise() -> u32 {
+    2
+}
+
+fn default_fall() -> u32 {
+    3
+}
+
+fn default_max_fails() -> u32 {
+    1
+}
+
+fn default_cool_down_ms() -> u64 {
+    10000
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LbStrategy {
+    #[default]
+    Random,
+    RoundRobin,
+    /// Smooth weighted round-robin using the server weights
+    WeightedRoundRobin,
+    LeastConn,
+    /// Hash ring with virtual nodes proportional to the server weights
+    ConsistentHash,
+    /// Pick two random servers and use the one with fewer connections
+    PowerOfTwoChoices,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HashKey {
+    Header(String),
+    Cookie(String),
+    ClientIp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum UpstreamServer {
+    Addr(String),
+    Weighted { addr: String, weight: u32 },
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -119,6 +339,9 @@ mod tests {
 
         assert_eq!(config.global.port, 8080);
         assert!(config.global.tls.is_some());
+        assert_eq!(config.global.tls_port, Some(8443));
+        assert!(!config.global.redirect_https);
+        assert_eq!(config.servers[1].tls.as_deref(), Some("api_cert"));
 
         assert_eq!(config.servers.len(), 2);
         assert_eq!(
@@ -126,6 +349,54 @@ mod tests {
             vec!["acme.com", "www.acme.com"]
         );
         assert_eq!(config.servers[0].upstream, "web_servers");
+        assert!(config.servers[0].default);
+
+        let locations = &config.servers[0].locations;
+        assert_eq!(locations.len(), 3);
+        assert_eq!(locations[0].match_type, PathMatchType::Prefix);
+        assert_eq!(locations[0].rewrite, Some(RewriteConfig::StripPrefix));
+        assert_eq!(locations[1].match_type, PathMatchType::Regex);
+        assert_eq!(locations[1].methods, vec!["GET"]);
+        assert_eq!(
+            locations[1].rewrite,
+            Some(RewriteConfig::Regex {
+                pattern: r"^/v(\d+)/users/(\d+)$".into(),
+                replace: "/users/$2/v$1".into(),
+            })
+        );
+        assert_eq!(locations[2].match_type, PathMatchType::Exact);
+        assert_eq!(locations[2].headers["x-canary"], "true");
+        assert!(config.servers[1].locations.is_empty());
+
+        assert_eq!(config.global.admin.as_deref(), Some("127.0.0.1:9090"));
+        assert_eq!(config.upstreams[0].strategy, LbStrategy::ConsistentHash);
+        assert_eq!(
+            config.upstreams[0].hash_key,
+            Some(HashKey::Header("x-user-id".into()))
+        );
+        assert_eq!(
+            config.upstreams[1].servers,
+            vec![
+                UpstreamServer::Weighted {
+                    addr: "127.0.0.1:3003".into(),
+                    weight: 3
+                },
+                UpstreamServer::Addr("127.0.0.1:3004".into())
+            ]
+        );
+
+        let check = config.upstreams[0].health_check.as_ref().unwrap();
+        assert_eq!(check.path, "/healthz");
+        assert_eq!((check.rise, check.fall), (2, 3));
+        assert_eq!(
+            config.upstreams[0]
+                .passive_check
+                .as_ref()
+                .unwrap()
+                .max_fails,
+            3
+        );
+        assert!(config.upstreams[1].health_check.is_none());
 
         // assert_eq!(config.upstreams.len(), 2);
         // assert_eq!(config.upstreams[0].name, "web_servers");
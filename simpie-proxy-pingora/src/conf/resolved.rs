@@ -1,20 +1,33 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
 
 use anyhow::{Result, anyhow};
-use rand::seq::IndexedRandom;
+use axum::http::{HeaderName, Method, request::Parts, uri::Authority};
+use regex::Regex;
 
-use crate::conf::raw::*;
+use crate::conf::{MAX_WEIGHT, PeerState, Strategy, UpstreamState, raw::*};
 
 #[derive(Debug, Clone)]
 pub struct ProxyConfigResolved {
     pub global: GlobalConfigResolved,
-    pub servers: HashMap<String, ServerConfigResolved>,
+    /// 精确匹配的 host -> server，多个 server_name 共享同一个 server
+    pub servers: HashMap<String, Arc<ServerConfigResolved>>,
+    /// `*.acme.com` 形式的通配 host，按后缀 (`.acme.com`) 从长到短排序
+    pub wildcards: Vec<(String, Arc<ServerConfigResolved>)>,
+    /// 没有匹配到任何 server_name 时使用的 server
+    pub default: Option<Arc<ServerConfigResolved>>,
+    pub upstreams: HashMap<String, UpstreamConfigResolved>,
 }
 
 #[derive(Debug, Clone)]
 pub struct GlobalConfigResolved {
     pub port: u16,
     pub certs: Option<CertConfigResolved>,
+    pub tls_port: Option<u16>,
+    /// TLS 终止之后转发到的明文监听地址，配置了 tls_port 时才有
+    pub tls_internal: Option<SocketAddr>,
+    pub redirect_https: bool,
+    pub upstream_ca: Option<String>,
+    pub admin: Option<SocketAddr>,
     // pub tls: Option<TLSConfigResolved>,
 }
 
@@ -23,9 +36,33 @@ pub struct ServerConfigResolved {
     pub upstream: UpstreamConfigResolved,
     pub certs: Option<CertConfigResolved>,
     // pub tls: Option<String>,
+    pub locations: Vec<LocationResolved>,
 }
 
 #[derive(Debug, Clone)]
+pub struct LocationResolved {
+    pub path: PathMatcher,
+    /// 为空时匹配所有 method
+    pub methods: Vec<Method>,
+    pub headers: Vec<(HeaderName, String)>,
+    pub rewrite: Option<Rewrite>,
+    pub upstream: UpstreamConfigResolved,
+}
+
+#[derive(Debug, Clone)]
+pub enum PathMatcher {
+    Prefix(String),
+    Exact(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+pub enum Rewrite {
+    StripPrefix,
+    Regex(Regex, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CertConfigResolved {
     pub cert_path: String,
     pub key_path: String,
@@ -40,7 +77,36 @@ pub struct TLSConfigResolved {
 
 #[derive(Debug, Clone)]
 pub struct UpstreamConfigResolved {
+    /// `host:port`，`https://` 的服务去掉了 scheme
     pub servers: Vec<String>,
+    /// https 服务使用的 SNI，None 表示明文连接
+    pub sni: Vec<Option<String>>,
+    /// 是否校验 https 服务的证书，以及证书是否和 SNI 匹配
+    pub verify_cert: bool,
+    pub verify_hostname: bool,
+    pub weights: Vec<u32>,
+    pub strategy: Strategy,
+    /// 负载均衡的运行时状态，克隆出来的 upstream 共享同一份
+    pub(crate) state: Arc<UpstreamState>,
+    pub(crate) peers: Vec<Arc<PeerState>>,
+    pub health_check: Option<HealthCheckResolved>,
+    pub passive_check: Option<PassiveCheckResolved>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthCheckResolved {
+    pub path: String,
+    pub expected_status: u16,
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub rise: u32,
+    pub fall: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PassiveCheckResolved {
+    pub max_fails: u32,
+    pub cool_down: Duration,
 }
 
 impl GlobalConfigResolved {
@@ -51,41 +117,134 @@ impl GlobalConfigResolved {
     }
 }
 
+impl ProxyConfigResolved {
+    /// 根据请求的 Host 找到对应的 server: 精确匹配 > 最长的通配匹配 > 默认 server
+    pub fn route(&self, host: &str) -> Option<&Arc<ServerConfigResolved>> {
+        let host = normalize_host(host);
+        self.servers
+            .get(&host)
+            .or_else(|| {
+                self.wildcards
+                    .iter()
+                    .find(|(suffix, _)| host.len() > suffix.len() && host.ends_with(suffix))
+                    .map(|(_, server)| server)
+            })
+            .or(self.default.as_ref())
+    }
+}
+
+/// 去掉端口并转成小写，`[::1]:8080` 这样的 IPv6 地址保留方括号
+pub(crate) fn normalize_host(host: &str) -> String {
+    let host = host.trim();
+    let host = match host.strip_prefix('[') {
+        Some(rest) => rest
+            .split_once(']')
+            .map_or(host, |(ip, _)| &host[..ip.len() + 2]),
+        None => host.split(':').next().unwrap_or(host),
+    };
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
 impl TryFrom<SimpleProxyConfig> for ProxyConfigResolved {
     type Error = anyhow::Error;
 
-    fn try_from(mut value: SimpleProxyConfig) -> Result<Self> {
-        let mut serversMap: HashMap<String, ServerConfigResolved> = HashMap::new();
+    fn try_from(value: SimpleProxyConfig) -> Result<Self> {
+        Self::try_from_with_previous(value, None)
+    }
+}
 
-        let mut certMap: HashMap<String, CertConfigResolved> = HashMap::new();
+impl ProxyConfigResolved {
+    /// 热更新时传入当前的配置，同名 upstream 的负载均衡状态会被继续使用
+    pub fn try_from_with_previous(
+        mut value: SimpleProxyConfig,
+        previous: Option<&ProxyConfigResolved>,
+    ) -> Result<Self> {
+        let mut cert_map: HashMap<String, CertConfigResolved> = HashMap::new();
         for cert in value.certs {
-            certMap.insert(cert.name.clone(), cert.try_into()?);
+            cert_map.insert(cert.name.clone(), cert.try_into()?);
         }
 
-        let mut upstreamMap: HashMap<String, UpstreamConfigResolved> = HashMap::new();
+        let mut upstream_map: HashMap<String, UpstreamConfigResolved> = HashMap::new();
         for upstream in value.upstreams {
-            upstreamMap.insert(upstream.name.clone(), upstream.try_into()?);
+            let name = upstream.name.clone();
+            let mut upstream = UpstreamConfigResolved::try_from(upstream)
+                .map_err(|e| anyhow!("Upstream '{}': {}", name, e))?;
+            if let Some(prev) = previous.and_then(|p| p.upstreams.get(&name)) {
+                upstream.inherit_state(prev);
+            }
+            upstream_map.insert(name, upstream);
         }
 
-        for sv in value.servers.into_iter() {
-            serversMap.insert(
-                sv.upstream.clone(),
-                ServerConfigResolved::try_from_with_maps(&sv, &certMap, &upstreamMap)?,
-            );
-        }
+        let mut servers: HashMap<String, Arc<ServerConfigResolved>> = HashMap::new();
+        let mut wildcards: Vec<(String, Arc<ServerConfigResolved>)> = Vec::new();
+        let mut default = None;
+        for sv in value.servers.iter() {
+            let server = Arc::new(ServerConfigResolved::try_from_with_maps(
+                sv,
+                &cert_map,
+                &upstream_map,
+            )?);
+
+            for name in sv.server_name.iter() {
+                let host = normalize_host(name);
+                let duplicated = match host.strip_prefix('*') {
+                    Some(suffix) if suffix.starts_with('.') && suffix.len() > 1 => {
+                        let duplicated = wildcards.iter().any(|(s, _)| s == suffix);
+                        wildcards.push((suffix.to_owned(), server.clone()));
+                        duplicated
+                    }
+                    Some(_) => return Err(anyhow!("Invalid wildcard server_name '{}'", name)),
+                    None if host.is_empty() => return Err(anyhow!("Empty server_name")),
+                    None => servers.insert(host, server.clone()).is_some(),
+                };
+                if duplicated {
+                    return Err(anyhow!("Duplicated server_name '{}'", name));
+                }
+            }
 
-        let tls = value.global.tls.take();
+            if sv.default && default.replace(server).is_some() {
+                return Err(anyhow!("Only one server can be the default server"));
+            }
+        }
+        wildcards.sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+
+        let tls = value
+            .global
+            .tls
+            .take()
+            .map(|name| {
+                cert_map
+                    .get(&name)
+                    .ok_or_else(|| anyhow!("Global TLS certificate '{}' not found", name))
+            })
+            .transpose()?;
         let mut that = Self {
             global: value.global.try_into()?,
-            servers: serversMap,
+            servers,
+            wildcards,
+            default,
+            upstreams: upstream_map,
         };
 
-        if let Some(certName) = tls {
-            if let Some(cert) = certMap.get(&certName) {
-                if let Err(_) = that.global.new_certs(cert.to_owned()) {
-                    return Err(anyhow!("Failed to set global certs: "));
-                }
-            }
+        if let Some(cert) = tls
+            && that.global.new_certs(cert.to_owned()).is_err()
+        {
+            return Err(anyhow!("Failed to set global certs: "));
+        }
+
+        let global = &that.global;
+        if global.redirect_https && global.tls_port.is_none() {
+            return Err(anyhow!("redirect_https needs tls_port"));
+        }
+        let has_server_certs = that
+            .servers
+            .values()
+            .chain(that.wildcards.iter().map(|(_, server)| server))
+            .any(|server| server.certs.is_some());
+        if global.tls_port.is_some() && global.certs.is_none() && !has_server_certs {
+            return Err(anyhow!(
+                "tls_port needs the global tls or a server tls certificate"
+            ));
         }
 
         Ok(that)
@@ -96,9 +255,39 @@ impl TryFrom<GlobalConfig> for GlobalConfigResolved {
     type Error = anyhow::Error;
 
     fn try_from(value: GlobalConfig) -> Result<Self, Self::Error> {
+        let tls_internal = match (value.tls_port, value.tls_internal) {
+            (Some(_), Some(addr)) => Some(
+                addr.parse::<SocketAddr>()
+                    .map_err(|e| anyhow!("Invalid tls_internal address: {}", e))?,
+            ),
+            (Some(port), None) => {
+                let port = port
+                    .checked_add(1)
+                    .ok_or_else(|| anyhow!("tls_internal is needed when tls_port is {}", port))?;
+                Some(SocketAddr::from(([127, 0, 0, 1], port)))
+            }
+            (None, Some(_)) => return Err(anyhow!("tls_internal needs tls_port")),
+            (None, None) => None,
+        };
+        // 这个端口上是解密之后的明文，不能对外暴露
+        if let Some(addr) = tls_internal
+            && !addr.ip().is_loopback()
+        {
+            return Err(anyhow!("tls_internal must be a loopback address: {}", addr));
+        }
+
         Ok(Self {
             port: value.port,
             certs: None,
+            tls_port: value.tls_port,
+            tls_internal,
+            redirect_https: value.redirect_https,
+            upstream_ca: value.upstream_ca,
+            admin: value
+                .admin
+                .map(|addr| addr.parse())
+                .transpose()
+                .map_err(|e| anyhow!("Invalid admin address: {}", e))?,
             // tls: value.tls.map(|tls| tls.try_into()).transpose()?,
         })
     }
@@ -128,13 +317,118 @@ impl ServerConfigResolved {
             .ok_or_else(|| anyhow!("Upstream '{}' not found", upstream_name))?
             .clone();
 
-        Ok(ServerConfigResolved { certs, upstream })
+        let locations = server
+            .locations
+            .iter()
+            .map(|location| LocationResolved::try_from_with_maps(location, upstream_map))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ServerConfigResolved {
+            certs,
+            upstream,
+            locations,
+        })
     }
 
-    pub fn choose(&self) -> Option<&str> {
-        // 随机指定一个服务
-        let upstream = self.upstream.servers.choose(&mut rand::rng());
-        upstream.map(|m| m.as_str())
+    /// 按顺序找到第一个匹配请求的 location，返回它在 locations 中的下标
+    pub fn locate(&self, req: &Parts) -> Option<usize> {
+        self.locations
+            .iter()
+            .position(|location| location.matches(req))
+    }
+
+    /// 请求最终转发到的 upstream: 匹配到的 location 的 upstream，否则是 server 的 upstream
+    pub fn upstream_for(&self, location: Option<usize>) -> &UpstreamConfigResolved {
+        location
+            .and_then(|i| self.locations.get(i))
+            .map_or(&self.upstream, |location| &location.upstream)
+    }
+}
+
+impl LocationResolved {
+    fn try_from_with_maps(
+        location: &LocationConfig,
+        upstream_map: &HashMap<String, UpstreamConfigResolved>,
+    ) -> Result<Self> {
+        let path = match location.match_type {
+            PathMatchType::Prefix => PathMatcher::Prefix(location.path.clone()),
+            PathMatchType::Exact => PathMatcher::Exact(location.path.clone()),
+            PathMatchType::Regex => PathMatcher::Regex(Regex::new(&location.path)?),
+        };
+
+        let rewrite = match &location.rewrite {
+            Some(RewriteConfig::StripPrefix) if !matches!(path, PathMatcher::Prefix(_)) => {
+                return Err(anyhow!(
+                    "Location '{}': strip_prefix only works with prefix match",
+                    location.path
+                ));
+            }
+            Some(RewriteConfig::StripPrefix) => Some(Rewrite::StripPrefix),
+            Some(RewriteConfig::Regex { pattern, replace }) => {
+                Some(Rewrite::Regex(Regex::new(pattern)?, replace.clone()))
+            }
+            None => None,
+        };
+
+        let methods = location
+            .methods
+            .iter()
+            .map(|m| Method::from_bytes(m.to_ascii_uppercase().as_bytes()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let headers = location
+            .headers
+            .iter()
+            .map(|(name, value)| Ok((HeaderName::try_from(name.as_str())?, value.clone())))
+            .collect::<Result<Vec<_>>>()?;
+
+        let upstream = upstream_map
+            .get(&location.upstream)
+            .ok_or_else(|| anyhow!("Upstream '{}' not found", location.upstream))?
+            .clone();
+
+        Ok(Self {
+            path,
+            methods,
+            headers,
+            rewrite,
+            upstream,
+        })
+    }
+
+    pub fn matches(&self, req: &Parts) -> bool {
+        let path = req.uri.path();
+        let path_matched = match &self.path {
+            PathMatcher::Prefix(prefix) => path.starts_with(prefix.as_str()),
+            PathMatcher::Exact(exact) => path == exact,
+            PathMatcher::Regex(re) => re.is_match(path),
+        };
+
+        path_matched
+            && (self.methods.is_empty() || self.methods.contains(&req.method))
+            && self.headers.iter().all(|(name, value)| {
+                req.headers
+                    .get_all(name)
+                    .iter()
+                    .any(|v| v.as_bytes() == value.as_bytes())
+            })
+    }
+
+    /// 重写后的 path (不含 query)，不需要重写时返回 None
+    pub fn rewrite(&self, path: &str) -> Option<String> {
+        let rewritten = match (self.rewrite.as_ref()?, &self.path) {
+            (Rewrite::StripPrefix, PathMatcher::Prefix(prefix)) => {
+                path.strip_prefix(prefix.as_str())?.to_owned()
+            }
+            (Rewrite::StripPrefix, _) => return None,
+            (Rewrite::Regex(re, replace), _) => re.replace(path, replace.as_str()).into_owned(),
+        };
+
+        if rewritten.starts_with('/') {
+            Some(rewritten)
+        } else {
+            Some(format!("/{}", rewritten))
+        }
     }
 }
 
@@ -168,8 +462,108 @@ impl TryFrom<UpstreamConfig> for UpstreamConfigResolved {
     type Error = anyhow::Error;
 
     fn try_from(value: UpstreamConfig) -> Result<Self> {
+        let (addrs, weights): (Vec<String>, Vec<u32>) = value
+            .servers
+            .into_iter()
+            .map(|server| match server {
+                UpstreamServer::Addr(addr) => (addr, 1),
+                UpstreamServer::Weighted { addr, weight } => (addr, weight),
+            })
+            .unzip();
+        if weights.iter().any(|w| !(1..=MAX_WEIGHT).contains(w)) {
+            return Err(anyhow!("server weight must be in 1..={}", MAX_WEIGHT));
+        }
+
+        let (servers, sni): (Vec<String>, Vec<Option<String>>) = addrs
+            .iter()
+            .map(|addr| parse_server_addr(addr, value.sni.as_deref()))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
+        if value.sni.is_some() && sni.iter().all(Option::is_none) {
+            return Err(anyhow!("sni only works with https servers"));
+        }
+        if !(value.verify_cert && value.verify_hostname) && sni.iter().all(Option::is_none) {
+            return Err(anyhow!(
+                "verify_cert and verify_hostname only work with https servers"
+            ));
+        }
+
+        let strategy = Strategy::new(value.strategy, value.hash_key.as_ref(), &servers, &weights)?;
+        let peers = servers.iter().map(|_| Arc::default()).collect();
+
+        Ok(Self {
+            servers,
+            sni,
+            verify_cert: value.verify_cert,
+            verify_hostname: value.verify_hostname,
+            weights,
+            strategy,
+            state: Arc::default(),
+            peers,
+            health_check: value.health_check.map(TryInto::try_into).transpose()?,
+            passive_check: value.passive_check.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+/// `https://host[:port]` 解析为 (`host:port`, Some(sni))，其它地址原样返回
+fn parse_server_addr(addr: &str, sni: Option<&str>) -> Result<(String, Option<String>)> {
+    let (tls, authority) = match addr.split_once("://") {
+        Some(("https", rest)) => (true, rest),
+        Some(("http", rest)) => (false, rest),
+        Some((scheme, _)) => return Err(anyhow!("Unsupported scheme '{}' in '{}'", scheme, addr)),
+        None => return Ok((addr.to_owned(), None)),
+    };
+
+    let authority: Authority = authority
+        .trim_end_matches('/')
+        .parse()
+        .map_err(|e| anyhow!("Invalid server address '{}': {}", addr, e))?;
+    let port = authority.port_u16().unwrap_or(if tls { 443 } else { 80 });
+    let host = authority.host();
+    let sni = tls.then(|| {
+        sni.unwrap_or(host.trim_start_matches('[').trim_end_matches(']'))
+            .to_owned()
+    });
+    Ok((format!("{}:{}", host, port), sni))
+}
+
+impl TryFrom<HealthCheckConfig> for HealthCheckResolved {
+    type Error = anyhow::Error;
+
+    fn try_from(value: HealthCheckConfig) -> Result<Self> {
+        if !value.path.starts_with('/') {
+            return Err(anyhow!("health check path must start with '/'"));
+        }
+        if value.interval_ms == 0 || value.timeout_ms == 0 || value.rise == 0 || value.fall == 0 {
+            return Err(anyhow!(
+                "health check interval, timeout, rise and fall must be positive"
+            ));
+        }
+
         Ok(Self {
-            servers: value.servers.to_owned(),
+            path: value.path,
+            expected_status: value.expected_status,
+            interval: Duration::from_millis(value.interval_ms),
+            timeout: Duration::from_millis(value.timeout_ms),
+            rise: value.rise,
+            fall: value.fall,
+        })
+    }
+}
+
+impl TryFrom<PassiveCheckConfig> for PassiveCheckResolved {
+    type Error = anyhow::Error;
+
+    fn try_from(value: PassiveCheckConfig) -> Result<Self> {
+        if value.max_fails == 0 {
+            return Err(anyhow!("passive check max_fails must be positive"));
+        }
+
+        Ok(Self {
+            max_fails: value.max_fails,
+            cool_down: Duration::from_millis(value.cool_down_ms),
         })
     }
 }
@@ -177,8 +571,30 @@ impl TryFrom<UpstreamConfig> for UpstreamConfigResolved {
 #[cfg(test)]
 mod tests {
     use crate::conf::{raw::*, *};
+    use axum::http::{Request, request::Parts};
+
+    fn request(method: &str, uri: &str, headers: &[(&str, &str)]) -> Parts {
+        let mut builder = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    fn load_fixture() -> ProxyConfigResolved {
+        let yaml: &'static str = include_str!("../../fixtures/simple.yaml");
+        let config = SimpleProxyConfig::from_yaml_str(yaml).unwrap();
+        ProxyConfigResolved::try_from(config).unwrap()
+    }
+
+    fn upstream_of<'a>(config: &'a ProxyConfigResolved, host: &str) -> Option<&'a [String]> {
+        config
+            .route(host)
+            .map(|server| server.upstream.servers.as_slice())
+    }
 
     #[test]
+    #[allow(non_snake_case)]
     fn proxyConfigResolved_from_by_raw_should_ok() {
         let yaml: &'static str = include_str!("../../fixtures/simple.yaml");
         let config = SimpleProxyConfig::from_yaml_str(yaml).unwrap();
@@ -187,4 +603,268 @@ mod tests {
         assert!(proxyConfig.is_ok());
         println!("【 proxyConfig 】==> {:#?}", proxyConfig);
     }
+
+    #[test]
+    fn route_by_every_server_name_should_work() {
+        let config = load_fixture();
+        let web = ["127.0.0.1:3003", "127.0.0.1:3004"];
+        let api = ["127.0.0.1:3001", "127.0.0.1:3002"];
+
+        assert_eq!(config.servers.len(), 3);
+        assert_eq!(upstream_of(&config, "acme.com").unwrap(), web);
+        assert_eq!(upstream_of(&config, "www.acme.com").unwrap(), web);
+        assert_eq!(upstream_of(&config, "api.acme.com").unwrap(), api);
+        // 端口、大小写、结尾的点都不影响匹配
+        assert_eq!(upstream_of(&config, "WWW.Acme.com:8080").unwrap(), web);
+        assert_eq!(upstream_of(&config, "api.acme.com.").unwrap(), api);
+        // upstream 的名字不再是 host
+        assert!(!config.servers.contains_key("web_servers"));
+        assert!(!config.servers.contains_key("api_servers"));
+    }
+
+    #[test]
+    fn route_by_wildcard_and_default_should_work() {
+        let config = load_fixture();
+        let web = ["127.0.0.1:3003", "127.0.0.1:3004"];
+        let api = ["127.0.0.1:3001", "127.0.0.1:3002"];
+
+        assert_eq!(upstream_of(&config, "v1.api.acme.com").unwrap(), api);
+        assert_eq!(upstream_of(&config, "a.b.api.acme.com:443").unwrap(), api);
+        // 未知的 host 交给默认 server
+        assert_eq!(upstream_of(&config, "blog.acme.com").unwrap(), web);
+        assert_eq!(upstream_of(&config, "[::1]:8080").unwrap(), web);
+        assert_eq!(upstream_of(&config, "").unwrap(), web);
+    }
+
+    #[test]
+    fn route_longest_wildcard_should_win() {
+        let yaml = r#"
+global:
+  port: 8080
+servers:
+  - server_name: ["*.acme.com"]
+    upstream: web
+  - server_name: ["*.api.acme.com"]
+    upstream: api
+upstreams:
+  - name: web
+    servers: ["127.0.0.1:3003"]
+  - name: api
+    servers: ["127.0.0.1:3001"]
+"#;
+        let config = SimpleProxyConfig::from_yaml_str(yaml).unwrap();
+        let config = ProxyConfigResolved::try_from(config).unwrap();
+
+        assert_eq!(
+            upstream_of(&config, "www.acme.com").unwrap(),
+            ["127.0.0.1:3003"]
+        );
+        assert_eq!(
+            upstream_of(&config, "v1.api.acme.com").unwrap(),
+            ["127.0.0.1:3001"]
+        );
+        assert_eq!(
+            upstream_of(&config, "api.acme.com").unwrap(),
+            ["127.0.0.1:3003"]
+        );
+        // 通配不匹配裸域名，且没有默认 server
+        assert!(config.route("acme.com").is_none());
+        assert!(config.route("example.org").is_none());
+    }
+
+    #[test]
+    fn invalid_server_names_should_fail() {
+        let config = |servers: &str| {
+            let yaml = format!(
+                "global:\n  port: 8080\nservers:\n{}\nupstreams:\n  - name: web\n    servers: [\"127.0.0.1:3003\"]\n",
+                servers
+            );
+            ProxyConfigResolved::try_from(SimpleProxyConfig::from_yaml_str(&yaml).unwrap())
+        };
+
+        assert!(config("  - { server_name: [acme.com], upstream: web }").is_ok());
+        assert!(
+            config("  - { server_name: [acme.com], upstream: web }\n  - { server_name: [ACME.com], upstream: web }")
+                .is_err()
+        );
+        assert!(
+            config("  - { server_name: [\"*.acme.com\", \"*.acme.com\"], upstream: web }").is_err()
+        );
+        assert!(config("  - { server_name: [\"*acme.com\"], upstream: web }").is_err());
+        assert!(
+            config("  - { server_name: [a.com], upstream: web, default: true }\n  - { server_name: [b.com], upstream: web, default: true }")
+                .is_err()
+        );
+        assert!(config("  - { server_name: [a.com], upstream: other }").is_err());
+    }
+
+    #[test]
+    fn locate_by_path_method_and_header_should_work() {
+        let config = load_fixture();
+        let web = config.route("acme.com").unwrap();
+        let api = ["127.0.0.1:3001", "127.0.0.1:3002"];
+        let locate = |method, uri, headers| web.locate(&request(method, uri, headers));
+
+        assert_eq!(locate("GET", "/api/users", &[]), Some(0));
+        assert_eq!(locate("POST", "/api/", &[]), Some(0));
+        assert_eq!(locate("GET", "/api", &[]), None);
+        assert_eq!(locate("GET", "/v2/users/42", &[]), Some(1));
+        assert_eq!(locate("DELETE", "/v2/users/42", &[]), None);
+        assert_eq!(locate("GET", "/v2/users/42/posts", &[]), None);
+        assert_eq!(locate("GET", "/healthz", &[("x-canary", "true")]), Some(2));
+        assert_eq!(locate("GET", "/healthz", &[("X-Canary", "true")]), Some(2));
+        assert_eq!(locate("GET", "/healthz", &[("x-canary", "false")]), None);
+        assert_eq!(locate("GET", "/healthz/", &[("x-canary", "true")]), None);
+        assert_eq!(locate("GET", "/healthz", &[]), None);
+
+        assert_eq!(web.upstream_for(Some(0)).servers, api);
+        assert_eq!(web.upstream_for(None).servers, web.upstream.servers);
+        assert!(config.route("api.acme.com").unwrap().locations.is_empty());
+    }
+
+    #[test]
+    fn location_rewrite_should_work() {
+        let config = load_fixture();
+        let web = config.route("acme.com").unwrap();
+        let rewrite = |i: usize, path| web.locations[i].rewrite(path);
+
+        assert_eq!(rewrite(0, "/api/users"), Some("/users".into()));
+        assert_eq!(rewrite(0, "/api/"), Some("/".into()));
+        assert_eq!(rewrite(1, "/v2/users/42"), Some("/users/42/v2".into()));
+        // 没有配置 rewrite
+        assert_eq!(rewrite(2, "/healthz"), None);
+    }
+
+    #[test]
+    fn invalid_locations_should_fail() {
+        let config = |location: &str| {
+            let yaml = format!(
+                "global:\n  port: 8080\nservers:\n  - server_name: [acme.com]\n    upstream: web\n    locations:\n      - {}\nupstreams:\n  - name: web\n    servers: [\"127.0.0.1:3003\"]\n",
+                location
+            );
+            ProxyConfigResolved::try_from(SimpleProxyConfig::from_yaml_str(&yaml).unwrap())
+        };
+
+        assert!(config("{ path: /api, upstream: web, rewrite: strip_prefix }").is_ok());
+        assert!(config("{ path: /api, upstream: other }").is_err());
+        assert!(config("{ path: \"(\", match: regex, upstream: web }").is_err());
+        assert!(
+            config("{ path: /api, match: exact, upstream: web, rewrite: strip_prefix }").is_err()
+        );
+        assert!(config("{ path: /api, methods: [\"G T\"], upstream: web }").is_err());
+        assert!(config("{ path: /api, headers: { \"x y\": v }, upstream: web }").is_err());
+    }
+
+    #[test]
+    fn https_upstream_servers_should_work() {
+        let upstream = |servers: &str, sni: &str| {
+            let yaml = format!(
+                "global:\n  port: 8080\nservers:\n  - {{ server_name: [acme.com], upstream: web }}\nupstreams:\n  - name: web\n    servers: {}\n{}",
+                servers, sni
+            );
+            ProxyConfigResolved::try_from(SimpleProxyConfig::from_yaml_str(&yaml).unwrap())
+                .map(|config| config.upstreams["web"].clone())
+        };
+
+        let web = upstream(
+            r#"["https://web.internal", "https://10.0.0.1:8443/", "http://[::1]", "127.0.0.1:3003"]"#,
+            "",
+        )
+        .unwrap();
+        assert_eq!(
+            web.servers,
+            [
+                "web.internal:443",
+                "10.0.0.1:8443",
+                "[::1]:80",
+                "127.0.0.1:3003"
+            ]
+        );
+        assert_eq!(
+            web.sni,
+            [
+                Some("web.internal".into()),
+                Some("10.0.0.1".into()),
+                None,
+                None
+            ]
+        );
+
+        assert!(web.verify_cert && web.verify_hostname);
+
+        let web = upstream(r#"["https://10.0.0.1"]"#, "    sni: web.internal\n").unwrap();
+        assert_eq!(web.sni, [Some("web.internal".into())]);
+
+        let web = upstream(r#"["https://10.0.0.1"]"#, "    verify_hostname: false\n").unwrap();
+        assert!(web.verify_cert && !web.verify_hostname);
+        let web = upstream(r#"["https://10.0.0.1"]"#, "    verify_cert: false\n").unwrap();
+        assert!(!web.verify_cert && web.verify_hostname);
+
+        assert!(upstream(r#"["ftp://10.0.0.1"]"#, "").is_err());
+        assert!(upstream(r#"["https://"]"#, "").is_err());
+        assert!(upstream(r#"["127.0.0.1:3003"]"#, "    sni: web.internal\n").is_err());
+        assert!(upstream(r#"["127.0.0.1:3003"]"#, "    verify_cert: false\n").is_err());
+    }
+
+    #[test]
+    fn tls_listener_config_should_be_validated() {
+        let config = |global: &str, tls: &str| {
+            let yaml = format!(
+                "global:\n  port: 8080\n{}certs:\n  - {{ name: web_cert, cert_path: web.pem, key_path: web.key }}\nservers:\n  - {{ server_name: [acme.com], upstream: web{} }}\nupstreams:\n  - name: web\n    servers: [\"127.0.0.1:3003\"]\n",
+                global, tls
+            );
+            ProxyConfigResolved::try_from(SimpleProxyConfig::from_yaml_str(&yaml).unwrap())
+        };
+
+        let fixture = load_fixture();
+        assert_eq!(fixture.global.tls_port, Some(8443));
+        assert!(!fixture.global.redirect_https);
+        let api = fixture.route("v1.api.acme.com").unwrap();
+        assert!(api.certs.as_ref().unwrap().cert_path.ends_with("api.pem"));
+
+        assert!(config("  tls_port: 8443\n", ", tls: web_cert").is_ok());
+        assert!(config("  tls_port: 8443\n  tls: web_cert\n", "").is_ok());
+        assert!(
+            config(
+                "  tls_port: 8443\n  redirect_https: true\n",
+                ", tls: web_cert"
+            )
+            .is_ok()
+        );
+        // 没有任何证书
+        assert!(config("  tls_port: 8443\n", "").is_err());
+        // 证书不存在
+        assert!(config("  tls_port: 8443\n  tls: other\n", "").is_err());
+        assert!(config("  redirect_https: true\n", ", tls: web_cert").is_err());
+    }
+
+    #[test]
+    fn tls_internal_should_be_resolved() {
+        let config = |global: &str| {
+            let yaml = format!(
+                "global:\n  port: 8080\n  tls: web_cert\n{}certs:\n  - {{ name: web_cert, cert_path: web.pem, key_path: web.key }}\nservers:\n  - {{ server_name: [acme.com], upstream: web }}\nupstreams:\n  - name: web\n    servers: [\"127.0.0.1:3003\"]\n",
+                global
+            );
+            ProxyConfigResolved::try_from(SimpleProxyConfig::from_yaml_str(&yaml).unwrap())
+                .map(|config| config.global.tls_internal)
+        };
+
+        assert_eq!(
+            load_fixture().global.tls_internal,
+            "127.0.0.1:8081".parse().ok()
+        );
+        assert_eq!(config("").unwrap(), None);
+        assert_eq!(
+            config("  tls_port: 8443\n").unwrap(),
+            "127.0.0.1:8444".parse().ok()
+        );
+        assert_eq!(
+            config("  tls_port: 8443\n  tls_internal: \"[::1]:9443\"\n").unwrap(),
+            "[::1]:9443".parse().ok()
+        );
+        assert!(config("  tls_port: 65535\n").is_err());
+        assert!(config("  tls_port: 8443\n  tls_internal: 0.0.0.0:8081\n").is_err());
+        assert!(config("  tls_port: 8443\n  tls_internal: localhost\n").is_err());
+        assert!(config("  tls_internal: 127.0.0.1:8081\n").is_err());
+    }
 }
@@ -0,0 +1,307 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use pingora::{
+    server::ShutdownWatch,
+    services::background::BackgroundService,
+    tls::{ServerName, TlsConnector},
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::TcpStream,
+    time::Instant,
+};
+use tracing::{info, warn};
+
+use crate::{
+    conf::{HealthCheckResolved, PeerState, ProxyConfig},
+    tls::tls_connector,
+};
+
+/// 没有配置健康检查时多久看一次配置有没有更新
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 按每个 upstream 的 health_check 配置定期探测所有服务
+pub struct HealthCheck {
+    config: ProxyConfig,
+    /// 按 upstream_ca 缓存的 TLS 客户端，检查 https 服务时使用
+    connector: std::sync::Mutex<Option<(Option<String>, TlsConnector)>>,
+}
+
+impl HealthCheck {
+    pub fn new(config: ProxyConfig) -> Self {
+        Self {
+            config,
+            connector: Default::default(),
+        }
+    }
+
+    fn connector(&self, ca_file: Option<&str>) -> Option<TlsConnector> {
+        let mut cached = self.connector.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((ca, connector)) = cached.as_ref()
+            && ca.as_deref() == ca_file
+        {
+            return Some(connector.clone());
+        }
+
+        match tls_connector(ca_file) {
+            Ok(connector) => {
+                *cached = Some((ca_file.map(str::to_owned), connector.clone()));
+                Some(connector)
+            }
+            Err(e) => {
+                warn!("failed to load upstream ca: {}", e);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl BackgroundService for HealthCheck {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        // 每个 upstream 下一次检查的时间，每轮重新读取配置以支持热更新
+        let mut next_run: HashMap<String, Instant> = HashMap::new();
+        loop {
+            let config = self.config.load_full();
+            let connector = config
+                .upstreams
+                .values()
+                .any(|upstream| upstream.sni.iter().any(Option::is_some))
+                .then(|| self.connector(config.global.upstream_ca.as_deref()))
+                .flatten();
+            next_run.retain(|name, _| config.upstreams.contains_key(name));
+
+            let now = Instant::now();
+            let mut wake = now + IDLE_INTERVAL;
+            for (name, upstream) in config.upstreams.iter() {
+                let Some(check) = &upstream.health_check else {
+                    continue;
+                };
+                let due = next_run.entry(name.clone()).or_insert(now);
+                if *due <= now {
+                    *due = now + check.interval;
+                    let servers = upstream.servers.iter().zip(&upstream.sni);
+                    for ((addr, sni), peer) in servers.zip(&upstream.peers) {
+                        let tls = sni.clone().zip(connector.clone());
+                        tokio::spawn(check_peer(addr.clone(), tls, peer.clone(), check.clone()));
+                    }
+                }
+                wake = wake.min(*due);
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep_until(wake) => {}
+                _ = shutdown.changed() => {
+                    info!("health check stopped");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+async fn check_peer(
+    addr: String,
+    tls: Option<(String, TlsConnector)>,
+    peer: Arc<PeerState>,
+    check: HealthCheckResolved,
+) {
+    let tls = tls
+        .as_ref()
+        .map(|(sni, connector)| (connector, sni.as_str()));
+    let result = probe(&addr, tls, &check).await.and_then(|status| {
+        if status == check.expected_status {
+            Ok(())
+        } else {
+            Err(anyhow!("unexpected status {}", status))
+        }
+    });
+
+    match (peer.record_check(result.is_ok(), &check), result) {
+        (Some(true), _) => info!("upstream server {} is healthy", addr),
+        (Some(false), Err(e)) => warn!("upstream server {} is unhealthy: {}", addr, e),
+        _ => {}
+    }
+}
+
+/// 发送 `GET path` 并返回响应的状态码，tls 不为空时使用 https 并以 sni 作为 Host
+pub async fn probe(
+    addr: &str,
+    tls: Option<(&TlsConnector, &str)>,
+    check: &HealthCheckResolved,
+) -> Result<u16> {
+    let request = async {
+        let stream = TcpStream::connect(addr).await?;
+        match tls {
+            Some((connector, sni)) => {
+                let name = ServerName::try_from(sni.to_owned())?;
+                let stream = connector.connect(name, stream).await?;
+                request_status(stream, sni, &check.path).await
+            }
+            None => request_status(stream, addr, &check.path).await,
+        }
+    };
+
+    tokio::time::timeout(check.timeout, request)
+        .await
+        .map_err(|_| anyhow!("timeout after {:?}", check.timeout))?
+}
+
+async fn request_status<S>(mut stream: S, host: &str, path: &str) -> Result<u16>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let req = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: simpie-proxy-pingora\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    stream.write_all(req.as_bytes()).await?;
+
+    // 只需要状态行: HTTP/1.1 200 OK
+    let mut buf = Vec::with_capacity(128);
+    let mut chunk = [0; 128];
+    while !buf.contains(&b'\n') && buf.len() < 1024 {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let line = String::from_utf8_lossy(&buf);
+    let mut parts = line.split_whitespace();
+    match (parts.next(), parts.next().and_then(|s| s.parse().ok())) {
+        (Some(version), Some(status)) if version.starts_with("HTTP/") => Ok(status),
+        _ => Err(anyhow!("invalid response")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::HealthCheckConfig;
+    use pingora::tls::{ServerConfig, TlsAcceptor, load_certs_and_key_files};
+    use tokio::net::TcpListener;
+
+    fn health_check(timeout_ms: u64) -> HealthCheckResolved {
+        HealthCheckConfig {
+            path: "/healthz".into(),
+            expected_status: 200,
+            interval_ms: 1000,
+            timeout_ms,
+            rise: 1,
+            fall: 1,
+        }
+        .try_into()
+        .unwrap()
+    }
+
+    /// 对每个连接返回固定的响应，delay 后才开始返回
+    async fn serve(response: &'static str, delay: Duration) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                tokio::spawn(async move {
+                    let mut buf = [0; 1024];
+                    let n = stream.read(&mut buf).await.unwrap();
+                    assert!(buf[..n].starts_with(b"GET /healthz HTTP/1.1\r\n"));
+                    tokio::time::sleep(delay).await;
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn probe_should_return_status() {
+        let check = health_check(500);
+
+        let ok = serve(
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            Duration::ZERO,
+        )
+        .await;
+        assert_eq!(probe(&ok, None, &check).await.unwrap(), 200);
+
+        let down = serve("HTTP/1.1 503 Service Unavailable\r\n\r\n", Duration::ZERO).await;
+        assert_eq!(probe(&down, None, &check).await.unwrap(), 503);
+
+        let invalid = serve("SSH-2.0-OpenSSH\r\n", Duration::ZERO).await;
+        assert!(probe(&invalid, None, &check).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn probe_should_fail_on_timeout_and_refused() {
+        let slow = serve("HTTP/1.1 200 OK\r\n\r\n", Duration::from_secs(2)).await;
+        let err = probe(&slow, None, &health_check(100)).await.unwrap_err();
+        assert!(err.to_string().contains("timeout"));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = listener.local_addr().unwrap().to_string();
+        drop(listener);
+        assert!(probe(&closed, None, &health_check(500)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn check_peer_should_update_peer_state() {
+        let peer = Arc::new(PeerState::default());
+        let down = serve("HTTP/1.1 500 Internal Server Error\r\n\r\n", Duration::ZERO).await;
+        check_peer(down, None, peer.clone(), health_check(500)).await;
+        assert!(!peer.is_healthy());
+
+        let ok = serve("HTTP/1.1 200 OK\r\n\r\n", Duration::ZERO).await;
+        check_peer(ok, None, peer.clone(), health_check(500)).await;
+        assert!(peer.is_healthy());
+    }
+
+    /// 使用 fixtures 中 localhost 的证书，检查 Host 后返回 200
+    async fn serve_tls() -> String {
+        let (certs, key) =
+            load_certs_and_key_files("fixtures/certs/proxy.pem", "fixtures/certs/proxy.key")
+                .unwrap()
+                .unwrap();
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(certs, key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(mut stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut buf = [0; 1024];
+                    let n = stream.read(&mut buf).await.unwrap();
+                    let req = String::from_utf8_lossy(&buf[..n]);
+                    assert!(req.contains("\r\nHost: localhost\r\n"));
+                    let _ = stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await;
+                });
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn probe_https_should_verify_cert() {
+        let check = health_check(500);
+        let addr = serve_tls().await;
+        let connector = tls_connector(Some("fixtures/certs/ca.pem")).unwrap();
+
+        let tls = Some((&connector, "localhost"));
+        assert_eq!(probe(&addr, tls, &check).await.unwrap(), 200);
+        // 证书和 SNI 不匹配
+        let tls = Some((&connector, "api.acme.com"));
+        assert!(probe(&addr, tls, &check).await.is_err());
+        // 明文请求 TLS 服务
+        assert!(probe(&addr, None, &check).await.is_err());
+    }
+}
@@ -1,28 +1,84 @@
+pub mod admin;
 pub mod conf;
+pub mod health;
+pub mod tls;
 
 use async_trait::async_trait;
-use axum::http::{self, StatusCode};
-use conf::ProxyConfig;
+use axum::http::{self, StatusCode, Uri};
+use conf::{ChosenPeer, LocationResolved, ProxyConfig, ServerConfigResolved};
 use pingora::{http::ResponseHeader, prelude::*};
+use std::{net::SocketAddr, sync::Arc};
+use tls::TlsClients;
 use tracing::info;
 
 #[derive(Debug, Clone)]
 pub struct SimpProxy {
     pub(crate) config: ProxyConfig,
+    /// 经 TLS 监听转发进来的连接，用来还原客户端地址
+    pub(crate) tls_clients: TlsClients,
 }
 
 pub struct ProxyContext {
     pub(crate) config: ProxyConfig,
+    /// upstream_peer 中选中的 server 和 location 下标，配置热更新后也保持一致
+    pub(crate) server: Option<Arc<ServerConfigResolved>>,
+    pub(crate) location: Option<usize>,
+    /// 请求结束 (ctx 被释放) 前计入服务的活跃连接数
+    pub(crate) peer: Option<ChosenPeer>,
+}
+
+impl ProxyContext {
+    fn location(&self) -> Option<&LocationResolved> {
+        let server = self.server.as_ref()?;
+        server.locations.get(self.location?)
+    }
 }
 
 impl SimpProxy {
     pub fn new(config: ProxyConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            tls_clients: TlsClients::default(),
+        }
     }
 
     pub fn config(&self) -> ProxyConfig {
         self.config.clone()
     }
+
+    pub fn tls_clients(&self) -> TlsClients {
+        self.tls_clients.clone()
+    }
+
+    /// 真实的客户端地址，以及连接是否来自 TLS 监听
+    fn client_addr(&self, session: &Session) -> (Option<SocketAddr>, bool) {
+        let addr = session
+            .client_addr()
+            .and_then(|addr| addr.as_inet())
+            .copied();
+        match addr.and_then(|addr| self.tls_clients.get(&addr)) {
+            Some(client) => (Some(client), true),
+            None => (addr, false),
+        }
+    }
+}
+
+fn request_host(session: &Session) -> &str {
+    session
+        .get_header(http::header::HOST)
+        .and_then(|head| head.to_str().ok())
+        .or_else(|| session.req_header().uri.host())
+        .unwrap_or_default()
+}
+
+/// 把明文请求重定向到 https 的地址，tls_port 为 443 时省略端口
+fn https_location(host: &str, tls_port: u16, uri: &Uri) -> String {
+    let host = conf::normalize_host(host);
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    match tls_port {
+        443 => format!("https://{}{}", host, path),
+        port => format!("https://{}:{}{}", host, port, path),
+    }
 }
 
 #[async_trait]
@@ -32,57 +88,127 @@ impl ProxyHttp for SimpProxy {
     fn new_ctx(&self) -> Self::CTX {
         ProxyContext {
             config: self.config.clone(),
+            server: None,
+            location: None,
+            peer: None,
         }
     }
 
+    async fn request_filter(&self, session: &mut Session, ctx: &mut Self::CTX) -> Result<bool>
+    where
+        Self::CTX: Send + Sync,
+    {
+        let config = ctx.config.load();
+        let Some(tls_port) = config
+            .global
+            .tls_port
+            .filter(|_| config.global.redirect_https)
+        else {
+            return Ok(false);
+        };
+        if self.client_addr(session).1 {
+            return Ok(false);
+        }
+
+        let host = request_host(session);
+        if host.is_empty() {
+            session
+                .respond_error(StatusCode::BAD_REQUEST.into())
+                .await?;
+            return Ok(true);
+        }
+        let location = https_location(host, tls_port, &session.req_header().uri);
+        info!("redirect to {}", location);
+
+        let mut resp = ResponseHeader::build(StatusCode::MOVED_PERMANENTLY, Some(2))?;
+        resp.insert_header(http::header::LOCATION, location)?;
+        resp.insert_header(http::header::CONTENT_LENGTH, 0)?;
+        session.write_response_header(Box::new(resp), true).await?;
+        Ok(true)
+    }
+
     async fn upstream_peer(
         &self,
         session: &mut Session,
         ctx: &mut Self::CTX,
     ) -> Result<Box<HttpPeer>> {
         let config = ctx.config.load();
-        let host = session
-            .get_header(http::header::HOST)
-            .and_then(|head| head.to_str().ok())
-            .and_then(|s| Some(s.split(":").next().unwrap_or(s)))
-            .ok_or(pingora::Error::create(
-                HTTPStatus(StatusCode::NOT_FOUND.into()),
-                ErrorSource::Upstream,
-                None,
-                None,
-            ))?;
+        let host = request_host(session).to_owned();
         info!("host: {:?}", host);
-        let service = config.servers.get(host).ok_or(pingora::Error::create(
+        let service = config.route(&host).ok_or(pingora::Error::create(
             ErrorType::CustomCode("No host found", StatusCode::BAD_REQUEST.into()),
             ErrorSource::Upstream,
             None,
             None,
         ))?;
 
-        let server_host = service.choose().ok_or(pingora::Error::create(
-            HTTPStatus(StatusCode::NOT_FOUND.into()),
-            ErrorSource::Upstream,
-            None,
-            None,
-        ))?;
+        let location = service.locate(session.req_header());
+        let upstream = service.upstream_for(location);
+        let client_ip = self.client_addr(session).0.map(|addr| addr.ip());
+        let chosen =
+            upstream
+                .choose(session.req_header(), client_ip)
+                .ok_or(pingora::Error::create(
+                    HTTPStatus(StatusCode::NOT_FOUND.into()),
+                    ErrorSource::Upstream,
+                    None,
+                    None,
+                ))?;
 
-        let peer = HttpPeer::new(server_host.to_owned(), false, host.to_owned());
+        let tls = chosen.sni.is_some();
+        let sni = chosen
+            .sni
+            .clone()
+            .unwrap_or_else(|| conf::normalize_host(&host));
+        let mut peer = HttpPeer::new(chosen.addr.as_str(), tls, sni);
+        peer.options.verify_cert = upstream.verify_cert;
+        peer.options.verify_hostname = upstream.verify_hostname;
         info!("upstream peer: {}", peer.to_string());
 
+        ctx.server = Some(service.clone());
+        ctx.location = location;
+        ctx.peer = Some(chosen);
+
         Ok(Box::new(peer))
     }
 
+    fn fail_to_connect(
+        &self,
+        _session: &mut Session,
+        _peer: &HttpPeer,
+        ctx: &mut Self::CTX,
+        e: Box<pingora::Error>,
+    ) -> Box<pingora::Error> {
+        if let Some(peer) = &ctx.peer {
+            peer.report(false);
+        }
+        e
+    }
+
     async fn upstream_request_filter(
         &self,
         _session: &mut Session,
         upstream_request: &mut RequestHeader,
-        _ctx: &mut Self::CTX,
+        ctx: &mut Self::CTX,
     ) -> Result<()>
     where
         Self::CTX: Send + Sync,
     {
         upstream_request.append_header("x-token", "hi hi")?;
 
+        let uri = &upstream_request.uri;
+        if let Some(path) = ctx.location().and_then(|l| l.rewrite(uri.path())) {
+            let path_and_query = match uri.query() {
+                Some(query) => format!("{}?{}", path, query),
+                None => path,
+            };
+            let uri = Uri::try_from(path_and_query).map_err(|e| {
+                pingora::Error::because(InvalidHTTPHeader, "rewrite upstream uri", e)
+            })?;
+            info!("rewrite {} -> {}", upstream_request.uri, uri);
+            upstream_request.set_uri(uri);
+        }
+
         info!("-------request filter-------");
         Ok(())
     }
@@ -91,11 +217,37 @@ impl ProxyHttp for SimpProxy {
         &self,
         _session: &mut Session,
         upstream_response: &mut ResponseHeader,
-        _ctx: &mut Self::CTX,
+        ctx: &mut Self::CTX,
     ) -> Result<()> {
+        if let Some(peer) = &ctx.peer {
+            peer.report(!upstream_response.status.is_server_error());
+        }
         upstream_response.append_header("my-server", "zz/service")?;
 
         info!("-------response filter-------");
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn https_location_should_work() {
+        let uri = |s: &str| s.parse::<Uri>().unwrap();
+
+        assert_eq!(
+            https_location("acme.com", 443, &uri("/users?id=1")),
+            "https://acme.com/users?id=1"
+        );
+        assert_eq!(
+            https_location("WWW.acme.com:8080", 8443, &uri("/")),
+            "https://www.acme.com:8443/"
+        );
+        assert_eq!(
+            https_location("[::1]:8080", 8443, &uri("http://[::1]:8080/a")),
+            "https://[::1]:8443/a"
+        );
+    }
+}
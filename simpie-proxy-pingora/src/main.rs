@@ -1,10 +1,14 @@
-mod conf;
-
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use clap::Parser;
-use pingora::{proxy::http_proxy_service, server::Server};
-use simpie_proxy_pingora::{SimpProxy, conf::ProxyConfig};
-use std::path::PathBuf;
+use pingora::{
+    proxy::http_proxy_service,
+    server::{Server, configuration::ServerConf},
+    services::background::background_service,
+};
+use simpie_proxy_pingora::{
+    SimpProxy, admin::Admin, conf::ProxyConfig, health::HealthCheck, tls::TlsListener,
+};
+use std::{net::TcpListener, path::PathBuf};
 use tracing::info;
 
 #[derive(Debug, Parser)]
@@ -18,17 +22,41 @@ fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
     let args = Args::parse();
 
-    let mut server = Server::new(None)?;
+    let proxy_config = ProxyConfig::load(args.config)?;
+    let global = proxy_config.load().global.clone();
+
+    // 连接 https upstream 时使用的 CA
+    let mut conf = ServerConf::new().expect("default server conf");
+    conf.ca_file = global.upstream_ca.clone();
+    let mut server = Server::new_with_opt_and_conf(None, conf);
     server.bootstrap();
 
-    let proxy_config = ProxyConfig::load(args.config)?;
     let sp = SimpProxy::new(proxy_config);
-    let port = sp.config().load().global.port;
-    let proxy_addr = &format!("0.0.0.0:{}", port);
+    let config = sp.config();
+    let tls_clients = sp.tls_clients();
+    let proxy_addr = &format!("0.0.0.0:{}", global.port);
 
     let mut service = http_proxy_service(&server.configuration, sp);
     service.add_tcp(proxy_addr);
+    if let Some(internal) = global.tls_internal {
+        // TLS 在前面终止，再转发到只监听回环地址的明文端口。
+        // pingora 在后台线程里监听，监听失败时进程不会退出，所以先确认这个地址可用
+        TcpListener::bind(internal)
+            .map_err(|e| anyhow!("tls_internal {} is not available: {}", internal, e))?;
+        service.add_tcp(&internal.to_string());
+        let listener = TlsListener::new(config.clone(), internal, tls_clients)?;
+        let mut tls = background_service("tls", listener);
+        tls.threads = Some(server.configuration.threads);
+        server.add_service(tls);
+    }
     server.add_service(service);
+
+    let health_check = background_service("health check", HealthCheck::new(config.clone()));
+    server.add_service(health_check);
+    if let Some(admin_addr) = config.load().global.admin {
+        let admin = background_service("admin", Admin::new(config.clone(), admin_addr));
+        server.add_service(admin);
+    }
     info!("proxy running on: {}", proxy_addr);
     server.run_forever();
 
@@ -0,0 +1,323 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use pingora::{
+    server::ShutdownWatch,
+    services::background::BackgroundService,
+    tls::{
+        ClientConfig, RootCertStore, ServerConfig, TlsAcceptor, TlsConnector,
+        load_ca_file_into_store, load_certs_and_key_files, load_platform_certs_incl_env_into_store,
+    },
+};
+use rustls::{
+    crypto::CryptoProvider,
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use tokio::{
+    io::copy_bidirectional,
+    net::{TcpListener, TcpStream},
+};
+use tracing::{info, warn};
+
+use crate::conf::{CertConfigResolved, ProxyConfig};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TLS 连接转发到 pingora 时使用的本地地址 -> 真实的客户端地址
+///
+/// 也用来判断请求是否来自 TLS 监听
+#[derive(Debug, Clone, Default)]
+pub struct TlsClients(Arc<Mutex<HashMap<SocketAddr, SocketAddr>>>);
+
+impl TlsClients {
+    pub fn get(&self, local: &SocketAddr) -> Option<SocketAddr> {
+        self.lock().get(local).copied()
+    }
+
+    fn insert(&self, local: SocketAddr, client: SocketAddr) {
+        self.lock().insert(local, client);
+    }
+
+    fn remove(&self, local: &SocketAddr) {
+        self.lock().remove(local);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<SocketAddr, SocketAddr>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// 按 SNI 找到对应 server 的证书，没有时使用全局证书
+#[derive(Debug)]
+struct SniResolver {
+    config: ProxyConfig,
+    keys: Mutex<HashMap<CertConfigResolved, Arc<CertifiedKey>>>,
+}
+
+impl SniResolver {
+    fn cert_for(&self, sni: Option<&str>) -> Option<CertConfigResolved> {
+        let config = self.config.load();
+        config
+            .route(sni.unwrap_or_default())
+            .and_then(|server| server.certs.clone())
+            .or_else(|| config.global.certs.clone())
+    }
+
+    /// 加载证书并缓存，热更新后新增的证书在第一次使用时加载
+    fn load(&self, cert: &CertConfigResolved) -> Result<Arc<CertifiedKey>> {
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(key) = keys.get(cert) {
+            return Ok(key.clone());
+        }
+
+        let (certs, key) = load_certs_and_key_files(&cert.cert_path, &cert.key_path)?
+            .ok_or_else(|| anyhow!("no certificate in {}", cert.cert_path))?;
+        let provider =
+            CryptoProvider::get_default().ok_or_else(|| anyhow!("no default crypto provider"))?;
+        let key = Arc::new(CertifiedKey::from_der(certs, key, provider)?);
+        keys.insert(cert.clone(), key.clone());
+        Ok(key)
+    }
+}
+
+impl ResolvesServerCert for SniResolver {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let sni = client_hello.server_name();
+        let cert = self.cert_for(sni)?;
+        self.load(&cert)
+            .inspect_err(|e| warn!("failed to load certificate {}: {}", cert.cert_path, e))
+            .ok()
+    }
+}
+
+/// TLS 监听: 按 SNI 选择证书完成握手，再把明文转发给 pingora 在回环地址上的监听
+pub struct TlsListener {
+    addr: SocketAddr,
+    upstream: SocketAddr,
+    acceptor: TlsAcceptor,
+    clients: TlsClients,
+}
+
+impl TlsListener {
+    /// `upstream` 是 pingora 的明文监听地址，所有引用到的证书在这里预先加载
+    pub fn new(config: ProxyConfig, upstream: SocketAddr, clients: TlsClients) -> Result<Self> {
+        let resolved = config.load_full();
+        let port = resolved
+            .global
+            .tls_port
+            .ok_or_else(|| anyhow!("tls_port is not configured"))?;
+
+        if CryptoProvider::get_default().is_none() {
+            let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+        }
+        let resolver = SniResolver {
+            config,
+            keys: Mutex::default(),
+        };
+        let certs = resolved
+            .servers
+            .values()
+            .chain(resolved.wildcards.iter().map(|(_, server)| server))
+            .filter_map(|server| server.certs.as_ref())
+            .chain(resolved.global.certs.as_ref());
+        for cert in certs {
+            resolver
+                .load(cert)
+                .map_err(|e| anyhow!("Failed to load certificate {}: {}", cert.cert_path, e))?;
+        }
+
+        let mut tls = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::new(resolver));
+        tls.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+        Ok(Self {
+            addr: SocketAddr::from(([0, 0, 0, 0], port)),
+            upstream,
+            acceptor: TlsAcceptor::from(Arc::new(tls)),
+            clients,
+        })
+    }
+
+    async fn handle(
+        acceptor: TlsAcceptor,
+        stream: TcpStream,
+        client: SocketAddr,
+        upstream: SocketAddr,
+        clients: TlsClients,
+    ) -> Result<()> {
+        let mut tls = tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream))
+            .await
+            .map_err(|_| anyhow!("handshake timeout"))??;
+
+        let mut upstream = TcpStream::connect(upstream).await?;
+        let local = upstream.local_addr()?;
+        clients.insert(local, client);
+        let result = copy_bidirectional(&mut tls, &mut upstream).await;
+        clients.remove(&local);
+        result?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl BackgroundService for TlsListener {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        let listener = match TcpListener::bind(self.addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("tls failed to listen on {}: {}", self.addr, e);
+                return;
+            }
+        };
+        info!("tls running on: {}", self.addr);
+
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, client) = match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            warn!("tls accept failed: {}", e);
+                            continue;
+                        }
+                    };
+                    let (acceptor, clients) = (self.acceptor.clone(), self.clients.clone());
+                    let upstream = self.upstream;
+                    tokio::spawn(async move {
+                        if let Err(e) = Self::handle(acceptor, stream, client, upstream, clients).await {
+                            info!("tls connection from {} closed: {}", client, e);
+                        }
+                    });
+                }
+                _ = shutdown.changed() => {
+                    info!("tls stopped");
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// 连接 https 服务用的 TLS 客户端配置，ca_file 为空时使用系统证书
+pub fn tls_connector(ca_file: Option<&str>) -> Result<TlsConnector> {
+    let mut roots = RootCertStore::empty();
+    match ca_file {
+        Some(path) => load_ca_file_into_store(path, &mut roots)?,
+        None => load_platform_certs_incl_env_into_store(&mut roots)?,
+    }
+    let config = ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(TlsConnector::from(Arc::new(config)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::{ProxyConfigResolved, SimpleProxyConfig};
+    use pingora::tls::ServerName;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn listener(upstream: SocketAddr, clients: TlsClients) -> TlsListener {
+        let yaml: &'static str = include_str!("../fixtures/simple.yaml");
+        let config = SimpleProxyConfig::from_yaml_str(yaml).unwrap();
+        let config = ProxyConfig::new(ProxyConfigResolved::try_from(config).unwrap());
+        TlsListener::new(config, upstream, clients).unwrap()
+    }
+
+    /// 明文的 echo 服务，代替 pingora 的内部监听
+    async fn echo(clients: TlsClients) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, peer)) = listener.accept().await {
+                let clients = clients.clone();
+                tokio::spawn(async move {
+                    let mut buf = [0; 64];
+                    let n = stream.read(&mut buf).await.unwrap();
+                    // 能查到 TLS 连接对应的客户端地址
+                    let client = clients.get(&peer).unwrap();
+                    let reply = format!("{} {}", String::from_utf8_lossy(&buf[..n]), client);
+                    stream.write_all(reply.as_bytes()).await.unwrap();
+                });
+            }
+        });
+        addr
+    }
+
+    /// 通过 TLS 监听发送 hello，返回 echo 的结果和本地地址
+    async fn send(tls: &TlsListener, sni: &str) -> Result<(String, SocketAddr)> {
+        let front = TcpListener::bind("127.0.0.1:0").await?;
+        let front_addr = front.local_addr()?;
+        let (acceptor, upstream, clients) =
+            (tls.acceptor.clone(), tls.upstream, tls.clients.clone());
+        tokio::spawn(async move {
+            let (stream, client) = front.accept().await.unwrap();
+            let _ = TlsListener::handle(acceptor, stream, client, upstream, clients).await;
+        });
+
+        let connector = tls_connector(Some("fixtures/certs/ca.pem"))?;
+        let stream = TcpStream::connect(front_addr).await?;
+        let local = stream.local_addr()?;
+        let mut stream = connector
+            .connect(ServerName::try_from(sni.to_owned())?, stream)
+            .await?;
+        stream.write_all(b"hello").await?;
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).await?;
+        Ok((reply, local))
+    }
+
+    #[tokio::test]
+    async fn tls_listener_should_select_cert_by_sni() {
+        let clients = TlsClients::default();
+        let tls = listener(echo(clients.clone()).await, clients.clone());
+
+        for sni in [
+            "acme.com",
+            "www.acme.com",
+            "api.acme.com",
+            "v1.api.acme.com",
+        ] {
+            let (reply, local) = send(&tls, sni).await.unwrap();
+            assert_eq!(reply, format!("hello {}", local));
+        }
+        // 未知的域名使用默认 server 的证书，和 SNI 不匹配
+        assert!(send(&tls, "example.org").await.is_err());
+        // 连接结束后清理
+        for _ in 0..100 {
+            if clients.lock().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(clients.lock().is_empty());
+    }
+
+    #[test]
+    fn tls_listener_should_fail_on_missing_cert() {
+        let yaml = r#"
+global:
+  port: 8080
+  tls_port: 8443
+certs:
+  - { name: web_cert, cert_path: ./missing.pem, key_path: ./missing.key }
+servers:
+  - { server_name: [acme.com], upstream: web, tls: web_cert }
+upstreams:
+  - { name: web, servers: ["127.0.0.1:3003"] }
+"#;
+        let config = SimpleProxyConfig::from_yaml_str(yaml).unwrap();
+        let config = ProxyConfig::new(ProxyConfigResolved::try_from(config).unwrap());
+        let addr = "127.0.0.1:1".parse().unwrap();
+        assert!(TlsListener::new(config, addr, TlsClients::default()).is_err());
+    }
+}
@@ -1,60 +1,52 @@
 mod redis_core;
 
-use anyhow::Result;
-use redis_core::command;
-use std::io;
-use tokio::{
-    io::AsyncWriteExt as _,
-    net::{TcpListener, TcpStream},
+use anyhow::{anyhow, Result};
+use redis_core::{
+    backend::{Backend, ACTIVE_EXPIRE_PERIOD},
+    cluster,
+    config::ServerConfig,
+    persistence, replication, server,
 };
-use tracing::{error, info};
-
-const BUF_SIZE: usize = 4096;
+use tokio::net::TcpListener;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     tracing_subscriber::fmt::init();
 
-    let addr = "127.0.0.1:6379";
-    let lis = TcpListener::bind(addr).await?;
-    info!("listening on {:?}", addr);
+    let config = parse_args(std::env::args().skip(1))?;
+    let lis = TcpListener::bind((config.bind.as_str(), config.port)).await?;
 
-    loop {
-        let (stream, addr) = lis.accept().await?;
-        tokio::spawn(async move {
-            info!("accepted connection from {:?}", addr);
-            if let Err(e) = process_redis_task(stream).await {
-                error!("[process_redis_task] error {:?}", e);
-            };
-            info!("connected over {:?}", addr);
-        });
+    let backend = Backend::new();
+    let replicaof = config.replicaof.clone();
+    backend.config().replace(config);
+    persistence::load(&backend)?;
+    cluster::load(&backend)?;
+    tokio::spawn(backend.clone().run_active_expire(ACTIVE_EXPIRE_PERIOD));
+    tokio::spawn(persistence::run_aof_fsync(backend.clone()));
+    if replicaof.is_some() {
+        replication::replicaof(&backend, replicaof);
     }
+    server::serve(lis, backend).await
 }
 
-async fn process_redis_task(mut stream: TcpStream) -> Result<()> {
-    loop {
-        stream.readable().await?;
-        let mut buf = Vec::with_capacity(BUF_SIZE);
-        match stream.try_read_buf(&mut buf) {
-            Ok(0) => {
-                return Ok(());
-            }
-            Ok(n) => {
-                let cmd_str = String::from_utf8_lossy(&buf[..n]);
-                info!("{:?}", cmd_str);
-                let cmd_line = command::CmdLine::new_from_str(&cmd_str)?;
-                info!("{:?}", cmd_line);
-                let resp = b"+PONG\r\n";
-                // stream.writable().await?;
-                stream.write_all(resp).await?;
-                info!("{:?}", String::from_utf8_lossy(resp));
-            }
-            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
-                continue;
-            }
-            Err(e) => {
-                return Err(e.into());
-            }
-        }
+/// 和 redis-server 一样，第一个参数可以是配置文件，之后是 `--name value` 形式的选项，覆盖配置文件里的值，
+/// 比如 `redis.conf --port 6380 --replicaof "127.0.0.1 6379"`
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<ServerConfig> {
+    let mut config = ServerConfig::default();
+    let mut args = args.by_ref().peekable();
+    if let Some(path) = args.next_if(|arg| !arg.starts_with("--")) {
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("failed to read config file '{}': {}", path, e))?;
+        config.load(&content)?;
+    }
+    while let Some(arg) = args.next() {
+        let name = arg
+            .strip_prefix("--")
+            .ok_or_else(|| anyhow!("invalid argument '{}'", arg))?;
+        let value = args
+            .next()
+            .ok_or_else(|| anyhow!("missing value for '{}'", arg))?;
+        config.set(name, &value)?;
     }
+    Ok(config)
 }
@@ -0,0 +1,67 @@
+use std::{
+    fmt::Debug,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// keyspace 用的时钟，返回 unix 时间戳（毫秒）；测试时换成可以手动拨动的时钟
+pub trait Clock: Debug + Send + Sync {
+    fn now_ms(&self) -> u64;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default()
+    }
+}
+
+/// 停在 0 的时钟，加载 AOF 时用
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoadingClock;
+
+impl Clock for LoadingClock {
+    fn now_ms(&self) -> u64 {
+        0
+    }
+}
+
+#[cfg(test)]
+pub use mock::MockClock;
+
+#[cfg(test)]
+mod mock {
+    use std::{
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::Clock;
+
+    /// 只有调用 advance 才会走的时钟，clone 之后共享同一个时间
+    #[derive(Debug, Clone)]
+    pub struct MockClock(Arc<AtomicU64>);
+
+    impl MockClock {
+        pub fn new(now_ms: u64) -> Self {
+            Self(Arc::new(AtomicU64::new(now_ms)))
+        }
+
+        pub fn advance(&self, d: Duration) {
+            self.0.fetch_add(d.as_millis() as u64, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now_ms(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+}
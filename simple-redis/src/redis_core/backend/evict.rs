@@ -0,0 +1,333 @@
+use std::{fmt, ops::Bound, str::FromStr};
+
+use super::{Backend, Entry, Keyspace};
+use crate::redis_core::{config::ConfigError, RespArray, RespFrame};
+
+/// 估算内存时每个 key 额外的开销，包括哈希表的槽位、过期时间、访问计数这些
+pub(super) const ENTRY_OVERHEAD: usize = 64;
+/// 新的 key 的 LFU 计数，和 redis 一样不从 0 开始，避免刚写入就被淘汰
+pub(super) const LFU_INIT_VAL: u8 = 5;
+/// 计数越大增长得越慢，和 redis 默认的 lfu-log-factor 一样
+const LFU_LOG_FACTOR: f64 = 10.0;
+/// 没有访问时每过这么久计数减一，和 redis 默认的 lfu-decay-time 一样是一分钟
+const LFU_DECAY_MS: u64 = 60_000;
+
+/// 可能增加内存的命令，内存超过 maxmemory 又淘汰不了 key 时返回 OOM
+const DENYOOM_COMMANDS: [&str; 9] = [
+    "set", "incr", "decr", "mset", "hset", "lpush", "rpush", "sadd", "zadd",
+];
+
+/// maxmemory-policy：内存超过 maxmemory 时淘汰哪些 key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaxMemoryPolicy {
+    /// 不淘汰，写命令返回 OOM
+    #[default]
+    NoEviction,
+    /// 所有的 key 中最久没有访问的
+    AllKeysLru,
+    /// 所有的 key 中访问最少的
+    AllKeysLfu,
+    /// 设置了过期时间的 key 中最久没有访问的
+    VolatileLru,
+    /// 设置了过期时间的 key 中最快过期的
+    VolatileTtl,
+}
+
+impl FromStr for MaxMemoryPolicy {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "noeviction" => Ok(MaxMemoryPolicy::NoEviction),
+            "allkeys-lru" => Ok(MaxMemoryPolicy::AllKeysLru),
+            "allkeys-lfu" => Ok(MaxMemoryPolicy::AllKeysLfu),
+            "volatile-lru" => Ok(MaxMemoryPolicy::VolatileLru),
+            "volatile-ttl" => Ok(MaxMemoryPolicy::VolatileTtl),
+            _ => Err(ConfigError::InvalidValue(
+                "maxmemory-policy".into(),
+                s.into(),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MaxMemoryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            MaxMemoryPolicy::NoEviction => "noeviction",
+            MaxMemoryPolicy::AllKeysLru => "allkeys-lru",
+            MaxMemoryPolicy::AllKeysLfu => "allkeys-lfu",
+            MaxMemoryPolicy::VolatileLru => "volatile-lru",
+            MaxMemoryPolicy::VolatileTtl => "volatile-ttl",
+        };
+        f.write_str(s)
+    }
+}
+
+pub fn is_denyoom(name: &str) -> bool {
+    DENYOOM_COMMANDS.contains(&name)
+}
+
+impl Entry {
+    /// 距离上次访问每过一分钟，计数减一
+    fn decayed_lfu(&self, now: u64) -> u8 {
+        let periods = now.saturating_sub(self.access) / LFU_DECAY_MS;
+        self.lfu.saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+
+    /// 记录一次访问：计数先衰减，再按 1 / ((计数 - 初始值) * factor + 1) 的概率加一
+    pub(super) fn record_access(&mut self, now: u64, random: u64) {
+        let mut lfu = self.decayed_lfu(now);
+        if lfu < u8::MAX {
+            let base = lfu.saturating_sub(LFU_INIT_VAL) as f64;
+            let p = 1.0 / (base * LFU_LOG_FACTOR + 1.0);
+            // 取高 53 位得到 [0, 1) 之间的浮点数
+            let r = (random >> 11) as f64 / (1u64 << 53) as f64;
+            if r < p {
+                lfu += 1;
+            }
+        }
+        self.lfu = lfu;
+        self.access = now;
+    }
+}
+
+impl Keyspace {
+    /// splitmix64，不需要密码学安全
+    pub(super) fn random(&mut self) -> u64 {
+        self.seed = self.seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.seed;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// 和 redis 一样不是精确的 LRU/LFU：抽样 samples 个 key，淘汰其中最合适的那个
+    fn evict_candidate(&mut self, policy: MaxMemoryPolicy, samples: usize) -> Option<String> {
+        let sampled = match policy {
+            MaxMemoryPolicy::NoEviction => return None,
+            MaxMemoryPolicy::AllKeysLru | MaxMemoryPolicy::AllKeysLfu => self.sample_all(samples),
+            MaxMemoryPolicy::VolatileLru | MaxMemoryPolicy::VolatileTtl => {
+                self.sample_volatile(samples)
+            }
+        };
+        let now = self.now_ms;
+        // 分数越大越应该被淘汰
+        let score = |entry: &Entry| match policy {
+            MaxMemoryPolicy::AllKeysLru | MaxMemoryPolicy::VolatileLru => {
+                now.saturating_sub(entry.access)
+            }
+            MaxMemoryPolicy::AllKeysLfu => (u8::MAX - entry.decayed_lfu(now)) as u64,
+            MaxMemoryPolicy::VolatileTtl => u64::MAX - entry.expire_at.unwrap_or(u64::MAX),
+            MaxMemoryPolicy::NoEviction => 0,
+        };
+        sampled
+            .into_iter()
+            .filter_map(|key| self.entries.get(&key).map(|entry| (score(entry), key)))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, key)| key)
+    }
+
+    /// key 不多于 samples 个时全部返回，否则随机抽样
+    fn sample_all(&mut self, samples: usize) -> Vec<String> {
+        if self.slots.len() <= samples {
+            return self.slots.clone();
+        }
+        (0..samples)
+            .map(|_| {
+                let i = (self.random() % self.slots.len() as u64) as usize;
+                self.slots[i].clone()
+            })
+            .collect()
+    }
+
+    /// 从上次停下的地方继续取 samples 个设置了过期时间的 key，到末尾之后从头开始
+    fn sample_volatile(&mut self, samples: usize) -> Vec<String> {
+        let start = match &self.evict_cursor {
+            Some(cursor) => Bound::Excluded(cursor.as_str()),
+            None => Bound::Unbounded,
+        };
+        let mut keys: Vec<String> = self
+            .volatile
+            .range::<str, _>((start, Bound::Unbounded))
+            .take(samples)
+            .cloned()
+            .collect();
+        if keys.len() < samples {
+            let more = samples - keys.len();
+            keys.extend(
+                self.volatile
+                    .iter()
+                    .take(more)
+                    .filter(|key| !keys.contains(key))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            );
+        }
+        self.evict_cursor = keys.last().cloned();
+        keys
+    }
+}
+
+impl Backend {
+    /// 估算的内存超过 maxmemory 时按 maxmemory-policy 淘汰 key，被淘汰的 key 以 DEL 的形式
+    /// 传播到 AOF 和从节点；返回内存有没有降到 maxmemory 以下，maxmemory 为 0 表示不限制
+    pub fn free_memory(&self) -> bool {
+        let (maxmemory, policy, samples) = self
+            .config()
+            .read(|c| (c.maxmemory, c.maxmemory_policy, c.maxmemory_samples));
+        if maxmemory == 0 {
+            return true;
+        }
+        let mut sinks = self.propagator().lock();
+        let mut ks = self.lock();
+        let mut evicted = vec![];
+        let freed = loop {
+            if ks.used_memory <= maxmemory {
+                break true;
+            }
+            match ks.evict_candidate(policy, samples) {
+                Some(key) => {
+                    ks.remove(&key);
+                    ks.evicted_keys += 1;
+                    evicted.push(key);
+                }
+                None => break false,
+            }
+        };
+        drop(ks);
+        sinks.propagate(
+            evicted
+                .into_iter()
+                .map(|key| RespArray::new(["DEL".into(), key.as_str().into()]).into())
+                .collect::<Vec<RespFrame>>(),
+        );
+        freed
+    }
+
+    /// 所有 key 估算的内存之和
+    pub fn used_memory(&self) -> usize {
+        self.lock().used_memory
+    }
+
+    pub fn evicted_keys(&self) -> u64 {
+        self.lock().evicted_keys
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::redis_core::{
+        backend::MockClock, command::CommandError, session::Session, RespNullBulkString,
+        SimpleString,
+    };
+
+    fn run(session: &mut Session, cmd: &str) -> RespFrame {
+        let args: Vec<RespFrame> = cmd.split_whitespace().map(|arg| arg.into()).collect();
+        session.handle(RespArray::new(args).into()).remove(0)
+    }
+
+    /// 写入 3 个 key 之后设置 maxmemory 为当前的内存，再写入的 key 会导致淘汰
+    fn setup(policy: &str, clock: &MockClock) -> (Backend, Session) {
+        let backend = Backend::with_clock(clock.clone());
+        let mut session = Session::new(backend.clone());
+        for cmd in ["set a 1", "set b 2 ex 100", "set c 3 ex 50"] {
+            run(&mut session, cmd);
+            clock.advance(Duration::from_secs(1));
+        }
+        let maxmemory = backend.used_memory().to_string();
+        run(&mut session, &format!("config set maxmemory {}", maxmemory));
+        run(
+            &mut session,
+            &format!("config set maxmemory-policy {}", policy),
+        );
+        (backend, session)
+    }
+
+    fn exists(backend: &Backend, key: &str) -> bool {
+        backend.exists(&[key.into()]) == 1
+    }
+
+    #[test]
+    fn noeviction_should_reply_oom() {
+        let clock = MockClock::new(1_000);
+        let (backend, mut session) = setup("noeviction", &clock);
+        // 执行之前没有超过 maxmemory，写入之后才超过
+        assert_eq!(run(&mut session, "set d 4"), SimpleString::new("OK").into());
+        assert_eq!(
+            run(&mut session, "set e 5"),
+            CommandError::OutOfMemory.into()
+        );
+        assert_eq!(run(&mut session, "get e"), RespNullBulkString.into());
+        // 删除和读取不受影响
+        assert_eq!(run(&mut session, "del d"), RespFrame::Integer(1));
+        assert_eq!(run(&mut session, "set e 5"), SimpleString::new("OK").into());
+        assert_eq!(backend.evicted_keys(), 0);
+    }
+
+    #[test]
+    fn lru_should_evict_least_recently_used() {
+        let clock = MockClock::new(1_000);
+        let (backend, mut session) = setup("allkeys-lru", &clock);
+        run(&mut session, "get a");
+        run(&mut session, "set d 4");
+        run(&mut session, "set e 5");
+        assert!(!exists(&backend, "b"));
+        assert!(exists(&backend, "a") && exists(&backend, "c"));
+        assert_eq!(backend.evicted_keys(), 1);
+    }
+
+    #[test]
+    fn lfu_should_evict_least_frequently_used() {
+        let clock = MockClock::new(1_000);
+        let (backend, mut session) = setup("allkeys-lfu", &clock);
+        for _ in 0..10 {
+            run(&mut session, "get a");
+            run(&mut session, "get c");
+        }
+        run(&mut session, "set d 4");
+        // b 写入之后一直没有访问过，计数还是初始值，是最小的
+        run(&mut session, "get d");
+        run(&mut session, "set e 5");
+        assert!(!exists(&backend, "b"));
+        assert!(exists(&backend, "a") && exists(&backend, "c") && exists(&backend, "d"));
+    }
+
+    #[test]
+    fn volatile_policies_should_only_evict_volatile_keys() {
+        let clock = MockClock::new(1_000);
+        let (backend, mut session) = setup("volatile-ttl", &clock);
+        run(&mut session, "set d 4");
+        run(&mut session, "set e 5");
+        // c 最快过期
+        assert!(!exists(&backend, "c"));
+        assert!(exists(&backend, "b"));
+
+        run(&mut session, "config set maxmemory-policy volatile-lru");
+        run(&mut session, "set f 6");
+        assert!(!exists(&backend, "b"));
+        // 没有可以淘汰的 key 了
+        assert_eq!(
+            run(&mut session, "set g 7"),
+            CommandError::OutOfMemory.into()
+        );
+        assert!(exists(&backend, "a"));
+    }
+
+    #[test]
+    fn used_memory_should_follow_writes() {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        run(&mut session, "rpush l a b c");
+        let used = backend.used_memory();
+        run(&mut session, "rpush l d");
+        assert!(backend.used_memory() > used);
+        run(&mut session, "lpop l");
+        assert_eq!(backend.used_memory(), used);
+        run(&mut session, "del l");
+        assert_eq!(backend.used_memory(), 0);
+    }
+}
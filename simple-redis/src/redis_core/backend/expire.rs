@@ -0,0 +1,138 @@
+use std::{
+    ops::Bound,
+    time::{Duration, Instant},
+};
+
+use tokio::time::{self, MissedTickBehavior};
+use tracing::debug;
+
+use super::{Backend, Keyspace};
+
+/// 每次抽样检查的 key 个数，和 redis 的 ACTIVE_EXPIRE_CYCLE_KEYS_PER_LOOP 一样
+const KEYS_PER_LOOP: usize = 20;
+/// 抽样中过期的 key 不超过 25% 时，认为剩下的过期 key 不多了，等下个周期再清理
+const ACCEPTABLE_STALE_PERCENT: usize = 25;
+/// 一个周期最多占用的时间，避免长时间占着锁
+const CYCLE_TIME_LIMIT: Duration = Duration::from_millis(25);
+/// 周期的间隔，相当于 redis 默认的 hz 10
+pub const ACTIVE_EXPIRE_PERIOD: Duration = Duration::from_millis(100);
+
+impl Keyspace {
+    /// 从上次停下的地方继续检查最多 count 个设置了过期时间的 key，返回 (检查的个数, 删除的个数)
+    fn expire_sample(&mut self, now: u64, count: usize) -> (usize, usize) {
+        let start = match &self.expire_cursor {
+            Some(cursor) => Bound::Excluded(cursor.as_str()),
+            None => Bound::Unbounded,
+        };
+        let keys: Vec<String> = self
+            .volatile
+            .range::<str, _>((start, Bound::Unbounded))
+            .take(count)
+            .cloned()
+            .collect();
+        // 游标已经在末尾，从头开始
+        if keys.is_empty() && self.expire_cursor.is_some() {
+            self.expire_cursor = None;
+            return self.expire_sample(now, count);
+        }
+        // 遍历到末尾之后下次从头开始
+        self.expire_cursor = match keys.len() < count {
+            true => None,
+            false => keys.last().cloned(),
+        };
+
+        let expired = keys
+            .iter()
+            .filter(|key| {
+                let expired = self.entries.get(*key).is_some_and(|e| e.is_expired(now));
+                if expired {
+                    self.remove(key);
+                    self.expired_keys += 1;
+                }
+                expired
+            })
+            .count();
+        (keys.len(), expired)
+    }
+}
+
+impl Backend {
+    /// 主动删除过期的 key：不断抽样，直到过期 key 的比例降下来或者用完这个周期的时间，返回删除的个数。
+    /// 每次抽样单独加锁，不会长时间阻塞其它命令
+    pub fn active_expire_cycle(&self) -> usize {
+        let started = Instant::now();
+        let mut total = 0;
+        loop {
+            let (sampled, expired) = self.lock().expire_sample(self.now_ms(), KEYS_PER_LOOP);
+            total += expired;
+            if sampled == 0
+                || expired * 100 <= sampled * ACCEPTABLE_STALE_PERCENT
+                || started.elapsed() > CYCLE_TIME_LIMIT
+            {
+                break;
+            }
+        }
+        total
+    }
+
+    /// 在后台定期执行 active_expire_cycle
+    pub async fn run_active_expire(self, period: Duration) {
+        let mut interval = time::interval(period);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            let expired = self.active_expire_cycle();
+            if expired > 0 {
+                debug!("active expire cycle removed {} keys", expired);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis_core::backend::MockClock;
+
+    fn backend_with_keys(clock: &MockClock, n: usize, ttl: Duration) -> Backend {
+        let backend = Backend::with_clock(clock.clone());
+        for i in 0..n {
+            let key = format!("k{:03}", i);
+            backend.set(key, b"v".to_vec(), Some(ttl), None);
+        }
+        backend
+    }
+
+    #[test]
+    fn active_expire_should_remove_expired_keys_without_access() {
+        let clock = MockClock::new(0);
+        let backend = backend_with_keys(&clock, 100, Duration::from_secs(1));
+        backend.set("persistent".into(), b"v".to_vec(), None, None);
+        assert_eq!(backend.active_expire_cycle(), 0);
+
+        // 全部过期，抽样的过期比例一直很高，一个周期就能清理完
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(backend.active_expire_cycle(), 100);
+        let ks = backend.lock();
+        assert_eq!(ks.entries.len(), 1);
+        assert!(ks.volatile.is_empty());
+    }
+
+    #[test]
+    fn active_expire_should_stop_when_few_keys_are_stale() {
+        let clock = MockClock::new(0);
+        let backend = backend_with_keys(&clock, 100, Duration::from_secs(10));
+        // 只有 4 个 key 会过期，每次抽样的过期比例都不超过 25%，一个周期只抽样一次
+        for i in [0, 1, 50, 99] {
+            backend.expire_at(&format!("k{:03}", i), 1_000);
+        }
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(backend.active_expire_cycle(), 2);
+        assert_eq!(backend.lock().expire_cursor.as_deref(), Some("k019"));
+
+        // 后面的周期从游标处继续，转一圈之后全部清理掉
+        let removed: usize = (0..5).map(|_| backend.active_expire_cycle()).sum();
+        assert_eq!(removed, 2);
+        assert_eq!(backend.lock().entries.len(), 96);
+    }
+}
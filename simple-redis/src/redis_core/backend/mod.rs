@@ -0,0 +1,723 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+mod clock;
+mod evict;
+mod expire;
+mod value;
+mod watch;
+mod zset;
+
+#[cfg(test)]
+pub use clock::MockClock;
+pub use clock::{Clock, LoadingClock, SystemClock};
+pub use evict::{is_denyoom, MaxMemoryPolicy};
+pub use expire::ACTIVE_EXPIRE_PERIOD;
+pub use value::{Collection, HashValue, ListValue, SetValue, Value};
+pub use zset::SortedSet;
+
+use super::{
+    clients::Clients,
+    cluster::Cluster,
+    command::CommandError,
+    config::Config,
+    info::Stats,
+    pattern::glob_match,
+    persistence::{Persistence, SnapshotEntry},
+    propagate::Propagator,
+    pubsub::PubSub,
+    replication::Replication,
+    slowlog::SlowLog,
+};
+
+/// 内存中的 keyspace，clone 之后共享同一份数据
+#[derive(Debug, Clone)]
+pub struct Backend {
+    keyspace: Arc<Mutex<Keyspace>>,
+    clock: Arc<dyn Clock>,
+    pubsub: PubSub,
+    propagator: Propagator,
+    persistence: Persistence,
+    replication: Replication,
+    config: Config,
+    clients: Clients,
+    slowlog: SlowLog,
+    stats: Stats,
+    cluster: Cluster,
+}
+
+/// key 和值，另外记录设置了过期时间的 key，方便主动清理时遍历
+#[derive(Debug, Default)]
+struct Keyspace {
+    entries: HashMap<String, Entry>,
+    volatile: BTreeSet<String>,
+    // 主动清理从上次停下的地方继续遍历 volatile
+    expire_cursor: Option<String>,
+    // 被 WATCH 的 key，修改时增加版本号
+    watched: HashMap<String, watch::WatchedKey>,
+    // 因为过期被删除的 key 的个数
+    expired_keys: u64,
+    // 所有的 key，淘汰时从这里随机抽样，Entry::slot 是 key 在这里的下标
+    slots: Vec<String>,
+    // 所有 key 估算的内存之和
+    used_memory: usize,
+    // 因为内存超过 maxmemory 被淘汰的 key 的个数
+    evicted_keys: u64,
+    // 从上次停下的地方继续抽样 volatile 里的 key
+    evict_cursor: Option<String>,
+    // 加锁时的时间，unix 时间戳，毫秒
+    now_ms: u64,
+    // 抽样和 LFU 计数用的随机数状态
+    seed: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    value: Value,
+    // unix 时间戳，毫秒
+    expire_at: Option<u64>,
+    // 估算的内存，包括 key
+    size: usize,
+    slot: usize,
+    // 最近一次访问的时间，unix 时间戳，毫秒
+    access: u64,
+    // 对数增长的访问计数，和 redis 的 LFU 一样每分钟衰减
+    lfu: u8,
+}
+
+/// SET 的 NX/XX 条件
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetCondition {
+    /// 只在 key 不存在时设置
+    Nx,
+    /// 只在 key 存在时设置
+    Xx,
+}
+
+impl Entry {
+    fn new(value: Value) -> Self {
+        Self::with_expire(value, None)
+    }
+
+    fn with_expire(value: Value, expire_at: Option<u64>) -> Self {
+        Self {
+            value,
+            expire_at,
+            size: 0,
+            slot: 0,
+            access: 0,
+            lfu: 0,
+        }
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expire_at.is_some_and(|at| at <= now)
+    }
+}
+
+impl Keyspace {
+    /// 访问时顺便删除已经过期的 key
+    fn live(&mut self, key: &str, now: u64) -> Option<&mut Entry> {
+        if self.entries.get(key)?.is_expired(now) {
+            self.remove(key);
+            self.expired_keys += 1;
+            return None;
+        }
+        let random = self.random();
+        let entry = self.entries.get_mut(key)?;
+        entry.record_access(now, random);
+        Some(entry)
+    }
+
+    /// 覆盖已有的 key 时保留原来的访问计数
+    fn insert(&mut self, key: String, mut entry: Entry) {
+        match entry.expire_at {
+            Some(_) => self.volatile.insert(key.clone()),
+            None => self.volatile.remove(&key),
+        };
+        self.touch(&key);
+        entry.size = entry_size(&key, &entry.value);
+        self.used_memory += entry.size;
+        let random = self.random();
+        match self.entries.get(&key) {
+            Some(old) => {
+                self.used_memory -= old.size;
+                entry.slot = old.slot;
+                entry.lfu = old.lfu;
+                entry.access = old.access;
+                entry.record_access(self.now_ms, random);
+            }
+            None => {
+                entry.slot = self.slots.len();
+                entry.lfu = evict::LFU_INIT_VAL;
+                entry.access = self.now_ms;
+                self.slots.push(key.clone());
+            }
+        }
+        self.entries.insert(key, entry);
+    }
+
+    fn remove(&mut self, key: &str) -> Option<Entry> {
+        self.volatile.remove(key);
+        self.touch(key);
+        let entry = self.entries.remove(key)?;
+        self.used_memory -= entry.size;
+        // 最后一个 key 移到被删除的 key 的位置
+        self.slots.swap_remove(entry.slot);
+        if let Some(moved) = self.slots.get(entry.slot) {
+            if let Some(moved) = self.entries.get_mut(moved) {
+                moved.slot = entry.slot;
+            }
+        }
+        Some(entry)
+    }
+
+    /// 原地修改值之后重新估算内存
+    fn resize(&mut self, key: &str) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            let size = entry_size(key, &entry.value);
+            self.used_memory = self.used_memory - entry.size + size;
+            entry.size = size;
+        }
+    }
+
+    fn set_expire(&mut self, key: &str, expire_at: Option<u64>) {
+        self.touch(key);
+        if let Some(entry) = self.entries.get_mut(key) {
+            entry.expire_at = expire_at;
+            match expire_at {
+                Some(_) => self.volatile.insert(key.to_string()),
+                None => self.volatile.remove(key),
+            };
+        }
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl Backend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_clock(clock: impl Clock + 'static) -> Self {
+        Self {
+            keyspace: Default::default(),
+            clock: Arc::new(clock),
+            pubsub: Default::default(),
+            propagator: Default::default(),
+            persistence: Default::default(),
+            replication: Default::default(),
+            config: Default::default(),
+            clients: Default::default(),
+            slowlog: Default::default(),
+            stats: Default::default(),
+            cluster: Default::default(),
+        }
+    }
+
+    /// 共享同一份数据，但是时钟停在 0，加载 AOF 时用，重放的过程中不会有 key 过期
+    pub fn loading(&self) -> Self {
+        Self {
+            clock: Arc::new(LoadingClock),
+            ..self.clone()
+        }
+    }
+
+    pub fn pubsub(&self) -> &PubSub {
+        &self.pubsub
+    }
+
+    pub fn propagator(&self) -> &Propagator {
+        &self.propagator
+    }
+
+    pub fn persistence(&self) -> &Persistence {
+        &self.persistence
+    }
+
+    pub fn replication(&self) -> &Replication {
+        &self.replication
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn clients(&self) -> &Clients {
+        &self.clients
+    }
+
+    pub fn slowlog(&self) -> &SlowLog {
+        &self.slowlog
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    pub fn cluster(&self) -> &Cluster {
+        &self.cluster
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Keyspace> {
+        // 持有锁时不会 panic，poisoned 的数据仍然是完整的
+        let mut ks = self.keyspace.lock().unwrap_or_else(|e| e.into_inner());
+        ks.now_ms = self.now_ms();
+        ks
+    }
+
+    /// 当前的 unix 时间戳，毫秒
+    pub fn now_ms(&self) -> u64 {
+        self.clock.now_ms()
+    }
+
+    /// 读取字符串类型的值，key 是其它类型时返回 WRONGTYPE
+    pub fn get(&self, key: &str) -> Result<Option<Vec<u8>>, CommandError> {
+        match self.lock().live(key, self.now_ms()) {
+            Some(Entry {
+                value: Value::String(value),
+                ..
+            }) => Ok(Some(value.clone())),
+            Some(_) => Err(CommandError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// 设置 key，不管原来是什么类型都会被覆盖，没有过期时间时会清除原来的过期时间；条件不满足时返回 false
+    pub fn set(
+        &self,
+        key: String,
+        value: Vec<u8>,
+        expire: Option<Duration>,
+        condition: Option<SetCondition>,
+    ) -> bool {
+        let mut ks = self.lock();
+        let now = self.now_ms();
+        let exists = ks.live(&key, now).is_some();
+        match condition {
+            Some(SetCondition::Nx) if exists => return false,
+            Some(SetCondition::Xx) if !exists => return false,
+            _ => {}
+        }
+        let expire_at = expire.map(|ttl| {
+            let ttl = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+            now.saturating_add(ttl)
+        });
+        ks.insert(key, Entry::with_expire(Value::String(value), expire_at));
+        true
+    }
+
+    /// 一次设置多个 key，整体是原子的
+    pub fn mset(&self, pairs: Vec<(String, Vec<u8>)>) {
+        let mut ks = self.lock();
+        for (key, value) in pairs {
+            ks.insert(key, Entry::new(Value::String(value)));
+        }
+    }
+
+    /// 不存在或者不是字符串类型的 key 都返回 None
+    pub fn mget(&self, keys: &[String]) -> Vec<Option<Vec<u8>>> {
+        let mut ks = self.lock();
+        let now = self.now_ms();
+        keys.iter()
+            .map(|key| match ks.live(key, now) {
+                Some(Entry {
+                    value: Value::String(value),
+                    ..
+                }) => Some(value.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// 返回删除的 key 的个数
+    pub fn del(&self, keys: &[String]) -> i64 {
+        let mut ks = self.lock();
+        let now = self.now_ms();
+        keys.iter()
+            .filter(|key| ks.live(key, now).is_some() && ks.remove(key).is_some())
+            .count() as i64
+    }
+
+    /// 返回存在的 key 的个数，重复的 key 重复计数
+    pub fn exists(&self, keys: &[String]) -> i64 {
+        let mut ks = self.lock();
+        let now = self.now_ms();
+        keys.iter()
+            .filter(|key| ks.live(key, now).is_some())
+            .count() as i64
+    }
+
+    /// 把 key 的值当作十进制整数加上 delta，key 不存在时从 0 开始，保留原来的过期时间
+    pub fn incr_by(&self, key: &str, delta: i64) -> Result<i64, CommandError> {
+        let mut ks = self.lock();
+        let value = match ks.live(key, self.now_ms()) {
+            Some(Entry {
+                value: Value::String(value),
+                ..
+            }) => {
+                let n = std::str::from_utf8(value)
+                    .ok()
+                    .and_then(|s| s.parse::<i64>().ok())
+                    .ok_or(CommandError::NotInteger)?;
+                let n = n.checked_add(delta).ok_or(CommandError::Overflow)?;
+                *value = n.to_string().into_bytes();
+                ks.touch(key);
+                ks.resize(key);
+                n
+            }
+            Some(_) => return Err(CommandError::WrongType),
+            None => {
+                let value = Value::String(delta.to_string().into_bytes());
+                ks.insert(key.to_string(), Entry::new(value));
+                delta
+            }
+        };
+        Ok(value)
+    }
+
+    /// 只读访问集合类型的值，key 不存在时返回 None
+    pub fn read<T, R>(&self, key: &str, f: impl FnOnce(&T) -> R) -> Result<Option<R>, CommandError>
+    where
+        T: Collection,
+    {
+        match self.lock().live(key, self.now_ms()) {
+            Some(entry) => T::from_value(&entry.value)
+                .map(|value| Some(f(value)))
+                .ok_or(CommandError::WrongType),
+            None => Ok(None),
+        }
+    }
+
+    /// 修改集合类型的值：key 不存在时从空集合开始，修改完如果集合为空就删除 key
+    pub fn write<T, R>(&self, key: &str, f: impl FnOnce(&mut T) -> R) -> Result<R, CommandError>
+    where
+        T: Collection,
+    {
+        let mut ks = self.lock();
+        let Some(entry) = ks.live(key, self.now_ms()) else {
+            let mut value = T::default();
+            let ret = f(&mut value);
+            if !value.is_empty() {
+                ks.insert(key.to_string(), Entry::new(value.into_value()));
+            }
+            return Ok(ret);
+        };
+        let value = T::from_value_mut(&mut entry.value).ok_or(CommandError::WrongType)?;
+        let ret = f(value);
+        if value.is_empty() {
+            ks.remove(key);
+        } else {
+            ks.touch(key);
+            ks.resize(key);
+        }
+        Ok(ret)
+    }
+
+    /// 设置过期时间（unix 时间戳，毫秒），时间已经过了的话直接删除 key；key 不存在时返回 false
+    pub fn expire_at(&self, key: &str, at: i64) -> bool {
+        let mut ks = self.lock();
+        let now = self.now_ms();
+        if ks.live(key, now).is_none() {
+            return false;
+        }
+        match u64::try_from(at) {
+            Ok(at) if at > now => ks.set_expire(key, Some(at)),
+            _ => {
+                ks.remove(key);
+            }
+        }
+        true
+    }
+
+    /// 剩余的生存时间：key 不存在时返回 None，没有过期时间时返回 Some(None)
+    pub fn ttl(&self, key: &str) -> Option<Option<Duration>> {
+        let mut ks = self.lock();
+        let now = self.now_ms();
+        let entry = ks.live(key, now)?;
+        Some(entry.expire_at.map(|at| Duration::from_millis(at - now)))
+    }
+
+    /// key 是否存在，不算一次访问，集群判断 key 有没有迁移走时使用
+    pub fn contains(&self, key: &str) -> bool {
+        let ks = self.lock();
+        let now = self.now_ms();
+        ks.entries
+            .get(key)
+            .is_some_and(|entry| !entry.is_expired(now))
+    }
+
+    /// 过期的时间点（unix 时间戳，毫秒）：key 不存在时返回 None，没有过期时间时返回 Some(None)
+    ///
+    /// 只在传播写命令时使用，不算一次访问，不影响淘汰
+    pub fn expire_time(&self, key: &str) -> Option<Option<u64>> {
+        let ks = self.lock();
+        let now = self.now_ms();
+        ks.entries
+            .get(key)
+            .filter(|entry| !entry.is_expired(now))
+            .map(|entry| entry.expire_at)
+    }
+
+    /// 复制一份没有过期的 key，用来保存快照
+    pub fn dump(&self) -> Vec<SnapshotEntry> {
+        let ks = self.lock();
+        let now = self.now_ms();
+        ks.entries
+            .iter()
+            .filter(|(_, entry)| !entry.is_expired(now))
+            .map(|(key, entry)| (key.clone(), entry.value.clone(), entry.expire_at))
+            .collect()
+    }
+
+    /// 从快照恢复一个 key
+    pub fn restore(&self, key: String, value: Value, expire_at: Option<u64>) {
+        self.lock()
+            .insert(key, Entry::with_expire(value, expire_at));
+    }
+
+    /// 删除所有的 key
+    pub fn flush(&self) {
+        let mut ks = self.lock();
+        let keys: Vec<String> = ks.watched.keys().cloned().collect();
+        keys.iter().for_each(|key| ks.touch(key));
+        ks.entries.clear();
+        ks.volatile.clear();
+        ks.expire_cursor = None;
+        ks.slots.clear();
+        ks.used_memory = 0;
+        ks.evict_cursor = None;
+    }
+
+    /// key 的个数，包括已经过期但是还没有被删除的 key
+    pub fn dbsize(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// 设置了过期时间的 key 的个数
+    pub fn volatile_count(&self) -> usize {
+        self.lock().volatile.len()
+    }
+
+    pub fn expired_keys(&self) -> u64 {
+        self.lock().expired_keys
+    }
+
+    /// KEYS：名字匹配 pattern 的所有没有过期的 key
+    pub fn keys(&self, pattern: &str) -> Vec<String> {
+        let ks = self.lock();
+        let now = self.now_ms();
+        ks.entries
+            .iter()
+            .filter(|(key, entry)| {
+                !entry.is_expired(now) && glob_match(pattern.as_bytes(), key.as_bytes())
+            })
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// SCAN：按 key 的哈希值从小到大遍历，游标是下一个要返回的哈希值，0 表示遍历结束。
+    /// 哈希值不随插入、删除变化，所以遍历期间一直存在的 key 一定会被返回；
+    /// 和 redis 一样先取 count 个 key 再按 pattern 和类型过滤，返回的个数可能少于 count
+    pub fn scan(
+        &self,
+        cursor: u64,
+        count: usize,
+        pattern: Option<&str>,
+        type_name: Option<&str>,
+    ) -> (u64, Vec<String>) {
+        let ks = self.lock();
+        let now = self.now_ms();
+        let mut candidates: Vec<(u64, &String, &Entry)> = ks
+            .entries
+            .iter()
+            .map(|(key, entry)| (scan_hash(key), key, entry))
+            .filter(|(hash, _, _)| *hash >= cursor)
+            .collect();
+        candidates.sort_unstable_by_key(|(hash, _, _)| *hash);
+        let next = candidates.get(count).map_or(0, |(hash, _, _)| *hash);
+        let keys = candidates
+            .into_iter()
+            .take(count)
+            .filter(|(_, key, entry)| {
+                !entry.is_expired(now)
+                    && pattern.is_none_or(|p| glob_match(p.as_bytes(), key.as_bytes()))
+                    && type_name.is_none_or(|t| entry.value.type_name().eq_ignore_ascii_case(t))
+            })
+            .map(|(_, key, _)| key.clone())
+            .collect();
+        (next, keys)
+    }
+
+    /// 去掉过期时间，原来没有过期时间或者 key 不存在时返回 false
+    pub fn persist(&self, key: &str) -> bool {
+        let mut ks = self.lock();
+        let now = self.now_ms();
+        match ks.live(key, now) {
+            Some(entry) if entry.expire_at.is_some() => {
+                ks.set_expire(key, None);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 估算一个 key 占用的内存
+fn entry_size(key: &str, value: &Value) -> usize {
+    key.len() + value.mem_usage() + evict::ENTRY_OVERHEAD
+}
+
+/// SCAN 用的哈希值，固定的 key，重启之后也不变
+fn scan_hash(key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_with_condition_should_work() {
+        let backend = Backend::new();
+        assert!(!backend.set("k".into(), b"v".to_vec(), None, Some(SetCondition::Xx)));
+        assert!(backend.set("k".into(), b"v".to_vec(), None, Some(SetCondition::Nx)));
+        assert!(!backend.set("k".into(), b"v2".to_vec(), None, Some(SetCondition::Nx)));
+        assert_eq!(backend.get("k"), Ok(Some(b"v".to_vec())));
+    }
+
+    #[test]
+    fn expired_key_should_be_removed() {
+        let clock = MockClock::new(1_000);
+        let backend = Backend::with_clock(clock.clone());
+        backend.set(
+            "k".into(),
+            b"v".to_vec(),
+            Some(Duration::from_secs(1)),
+            None,
+        );
+        assert_eq!(backend.ttl("k"), Some(Some(Duration::from_secs(1))));
+
+        clock.advance(Duration::from_millis(999));
+        assert_eq!(backend.get("k"), Ok(Some(b"v".to_vec())));
+        clock.advance(Duration::from_millis(1));
+        assert_eq!(backend.get("k"), Ok(None));
+        assert_eq!(backend.exists(&["k".into()]), 0);
+        assert_eq!(backend.lock().entries.len(), 0);
+        assert!(backend.lock().volatile.is_empty());
+    }
+
+    #[test]
+    fn expire_and_persist_should_work() {
+        let clock = MockClock::new(1_000);
+        let backend = Backend::with_clock(clock.clone());
+        assert!(!backend.expire_at("k", 2_000));
+        assert_eq!(backend.ttl("k"), None);
+
+        backend.set("k".into(), b"v".to_vec(), None, None);
+        assert_eq!(backend.ttl("k"), Some(None));
+        assert!(backend.expire_at("k", 3_000));
+        assert_eq!(backend.ttl("k"), Some(Some(Duration::from_secs(2))));
+
+        // INCR 这类修改不会影响过期时间，SET 会清除过期时间
+        backend.set(
+            "n".into(),
+            b"1".to_vec(),
+            Some(Duration::from_secs(5)),
+            None,
+        );
+        backend.incr_by("n", 1).unwrap();
+        assert_eq!(backend.ttl("n"), Some(Some(Duration::from_secs(5))));
+        backend.set("n".into(), b"1".to_vec(), None, None);
+        assert_eq!(backend.ttl("n"), Some(None));
+
+        assert!(backend.persist("k"));
+        assert!(!backend.persist("k"));
+        assert_eq!(backend.ttl("k"), Some(None));
+        assert!(backend.lock().volatile.is_empty());
+
+        // 过去的时间直接删除
+        assert!(backend.expire_at("k", 1_000));
+        assert_eq!(backend.ttl("k"), None);
+    }
+
+    #[test]
+    fn incr_by_should_work() {
+        let backend = Backend::new();
+        assert_eq!(backend.incr_by("n", 1), Ok(1));
+        assert_eq!(backend.incr_by("n", -3), Ok(-2));
+
+        backend.set("n".into(), i64::MAX.to_string().into_bytes(), None, None);
+        assert_eq!(backend.incr_by("n", 1), Err(CommandError::Overflow));
+        backend.set("s".into(), b"abc".to_vec(), None, None);
+        assert_eq!(backend.incr_by("s", 1), Err(CommandError::NotInteger));
+    }
+
+    #[test]
+    fn collections_should_be_typed() {
+        let backend = Backend::new();
+        let len = backend.write("h", |hash: &mut HashValue| {
+            hash.insert("f".into(), b"v".to_vec());
+            hash.len()
+        });
+        assert_eq!(len, Ok(1));
+        assert_eq!(backend.get("h"), Err(CommandError::WrongType));
+        assert_eq!(backend.incr_by("h", 1), Err(CommandError::WrongType));
+        assert_eq!(
+            backend.read("h", |list: &ListValue| list.len()),
+            Err(CommandError::WrongType)
+        );
+        assert_eq!(backend.mget(&["h".into()]), vec![None]);
+
+        // 集合变空之后 key 被删除，读取不存在的 key 不会创建它
+        assert_eq!(
+            backend.write("h", |hash: &mut HashValue| hash.clear()),
+            Ok(())
+        );
+        assert_eq!(backend.read("h", |hash: &HashValue| hash.len()), Ok(None));
+        assert_eq!(backend.write("s", |set: &mut SetValue| set.len()), Ok(0));
+        assert_eq!(backend.exists(&["h".into(), "s".into()]), 0);
+
+        // SET 会覆盖其它类型
+        backend.set("h".into(), b"v".to_vec(), None, None);
+        assert_eq!(backend.get("h"), Ok(Some(b"v".to_vec())));
+    }
+
+    #[test]
+    fn scan_should_return_every_key_once() {
+        let backend = Backend::new();
+        for i in 0..100 {
+            backend.set(format!("k{}", i), b"v".to_vec(), None, None);
+        }
+        let mut seen = BTreeSet::new();
+        let mut cursor = 0;
+        loop {
+            let (next, keys) = backend.scan(cursor, 7, None, None);
+            assert!(keys.len() <= 7);
+            for key in keys {
+                assert!(seen.insert(key));
+            }
+            // 遍历期间删除的 key 不影响其它 key
+            backend.del(&["k0".into()]);
+            if next == 0 {
+                break;
+            }
+            cursor = next;
+        }
+        assert!(seen.len() >= 99);
+        assert_eq!(backend.keys("k?").len(), 9);
+        assert_eq!(backend.scan(0, 1000, Some("k1*"), None).1.len(), 11);
+        assert!(backend.scan(0, 1000, None, Some("hash")).1.is_empty());
+    }
+}
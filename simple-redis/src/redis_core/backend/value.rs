@@ -0,0 +1,88 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::zset::SortedSet;
+
+/// 估算内存时每个集合元素额外的开销，包括指针、长度和哈希表的槽位，只是个粗略的值
+pub(super) const ELEMENT_OVERHEAD: usize = 32;
+
+pub type HashValue = HashMap<String, Vec<u8>>;
+pub type ListValue = VecDeque<Vec<u8>>;
+pub type SetValue = HashSet<Vec<u8>>;
+
+/// keyspace 中保存的值，每个 key 只能是其中一种类型
+#[derive(Debug, Clone)]
+pub enum Value {
+    String(Vec<u8>),
+    Hash(HashValue),
+    List(ListValue),
+    Set(SetValue),
+    ZSet(SortedSet),
+}
+
+impl Value {
+    /// TYPE 命令返回的类型名
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Hash(_) => "hash",
+            Value::List(_) => "list",
+            Value::Set(_) => "set",
+            Value::ZSet(_) => "zset",
+        }
+    }
+
+    /// 估算值占用的内存，单位字节
+    pub fn mem_usage(&self) -> usize {
+        match self {
+            Value::String(value) => value.len(),
+            Value::Hash(hash) => hash
+                .iter()
+                .map(|(field, value)| field.len() + value.len() + ELEMENT_OVERHEAD)
+                .sum(),
+            Value::List(list) => list.iter().map(|v| v.len() + ELEMENT_OVERHEAD).sum(),
+            Value::Set(set) => set.iter().map(|v| v.len() + ELEMENT_OVERHEAD).sum(),
+            Value::ZSet(zset) => zset.mem_usage(),
+        }
+    }
+}
+
+/// hash、list、set、zset 这些集合类型：key 不存在时当作空集合，集合变空后 key 被删除
+pub trait Collection: Default {
+    fn from_value(value: &Value) -> Option<&Self>;
+    fn from_value_mut(value: &mut Value) -> Option<&mut Self>;
+    fn into_value(self) -> Value;
+    fn is_empty(&self) -> bool;
+}
+
+macro_rules! impl_collection {
+    ($ty:ty, $variant:ident) => {
+        impl Collection for $ty {
+            fn from_value(value: &Value) -> Option<&Self> {
+                match value {
+                    Value::$variant(v) => Some(v),
+                    _ => None,
+                }
+            }
+
+            fn from_value_mut(value: &mut Value) -> Option<&mut Self> {
+                match value {
+                    Value::$variant(v) => Some(v),
+                    _ => None,
+                }
+            }
+
+            fn into_value(self) -> Value {
+                Value::$variant(self)
+            }
+
+            fn is_empty(&self) -> bool {
+                self.is_empty()
+            }
+        }
+    };
+}
+
+impl_collection!(HashValue, Hash);
+impl_collection!(ListValue, List);
+impl_collection!(SetValue, Set);
+impl_collection!(SortedSet, ZSet);
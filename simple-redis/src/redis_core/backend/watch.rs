@@ -0,0 +1,98 @@
+use super::{Backend, Keyspace};
+
+/// 被 WATCH 的 key 的版本号，每次修改（包括删除和过期）加一；没有连接 WATCH 时删除
+#[derive(Debug, Default)]
+pub(super) struct WatchedKey {
+    version: u64,
+    watchers: usize,
+}
+
+impl Keyspace {
+    /// 记录 key 被修改了，只有被 WATCH 的 key 才需要
+    pub(super) fn touch(&mut self, key: &str) {
+        if let Some(watched) = self.watched.get_mut(key) {
+            watched.version += 1;
+        }
+    }
+}
+
+impl Backend {
+    /// WATCH 一个 key，返回当前的版本号，EXEC 时用 is_touched 检查有没有被修改过
+    pub fn watch(&self, key: &str) -> u64 {
+        let mut ks = self.lock();
+        // 先删除已经过期的 key，WATCH 之后才过期的算作修改
+        ks.live(key, self.now_ms());
+        let watched = ks.watched.entry(key.to_string()).or_default();
+        watched.watchers += 1;
+        watched.version
+    }
+
+    /// WATCH 之后 key 有没有被修改过
+    pub fn is_touched(&self, key: &str, version: u64) -> bool {
+        let mut ks = self.lock();
+        ks.live(key, self.now_ms());
+        ks.watched.get(key).is_none_or(|w| w.version != version)
+    }
+
+    pub fn unwatch(&self, key: &str) {
+        let mut ks = self.lock();
+        if let Some(watched) = ks.watched.get_mut(key) {
+            watched.watchers -= 1;
+            if watched.watchers == 0 {
+                ks.watched.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::redis_core::backend::{MockClock, SetValue};
+
+    #[test]
+    fn modifications_should_touch_watched_keys() {
+        let backend = Backend::new();
+        let v = backend.watch("k");
+        assert!(!backend.is_touched("k", v));
+        // 读取不算修改
+        backend.get("k").unwrap();
+        assert!(!backend.is_touched("k", v));
+        backend.incr_by("k", 1).unwrap();
+        assert!(backend.is_touched("k", v));
+
+        let v = backend.watch("s");
+        backend
+            .write("s", |set: &mut SetValue| set.insert(b"m".to_vec()))
+            .unwrap();
+        assert!(backend.is_touched("s", v));
+        let v = backend.watch("s");
+        backend.del(&["s".into()]);
+        assert!(backend.is_touched("s", v));
+
+        // 没有被 WATCH 的 key 不记录版本
+        backend.unwatch("k");
+        backend.unwatch("s");
+        backend.unwatch("s");
+        assert!(backend.lock().watched.is_empty());
+    }
+
+    #[test]
+    fn expired_key_should_be_touched() {
+        let clock = MockClock::new(0);
+        let backend = Backend::with_clock(clock.clone());
+        backend.set(
+            "k".into(),
+            b"v".to_vec(),
+            Some(Duration::from_secs(1)),
+            None,
+        );
+        let v = backend.watch("k");
+        clock.advance(Duration::from_millis(999));
+        assert!(!backend.is_touched("k", v));
+        clock.advance(Duration::from_millis(1));
+        assert!(backend.is_touched("k", v));
+    }
+}
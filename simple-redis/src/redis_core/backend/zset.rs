@@ -0,0 +1,148 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    ops::Bound,
+};
+
+use super::value::ELEMENT_OVERHEAD;
+
+/// 有序集合：按 (score, member) 排序，score 相同时按 member 的字典序
+#[derive(Debug, Clone, Default)]
+pub struct SortedSet {
+    scores: HashMap<Vec<u8>, f64>,
+    ordered: BTreeSet<(Score, Vec<u8>)>,
+}
+
+/// score 不会是 NaN，用 total_cmp 排序
+#[derive(Debug, Clone, Copy)]
+struct Score(f64);
+
+impl PartialEq for Score {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Score {}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // 0.0 和 -0.0 当作同一个 score
+        (self.0 + 0.0).total_cmp(&(other.0 + 0.0))
+    }
+}
+
+impl SortedSet {
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// 添加或者更新 member 的 score，返回 member 是不是新加入的
+    pub fn insert(&mut self, member: Vec<u8>, score: f64) -> bool {
+        match self.scores.insert(member.clone(), score) {
+            Some(old) => {
+                self.ordered.remove(&(Score(old), member.clone()));
+                self.ordered.insert((Score(score), member));
+                false
+            }
+            None => {
+                self.ordered.insert((Score(score), member));
+                true
+            }
+        }
+    }
+
+    pub fn remove(&mut self, member: &[u8]) -> bool {
+        match self.scores.remove(member) {
+            Some(score) => self.ordered.remove(&(Score(score), member.to_vec())),
+            None => false,
+        }
+    }
+
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// 估算占用的内存：member 在哈希表和有序集合里各存了一份
+    pub(super) fn mem_usage(&self) -> usize {
+        self.scores
+            .keys()
+            .map(|member| 2 * (member.len() + ELEMENT_OVERHEAD + std::mem::size_of::<f64>()))
+            .sum()
+    }
+
+    /// 按排名从小到大遍历
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], f64)> {
+        self.ordered
+            .iter()
+            .map(|(score, member)| (member.as_slice(), score.0))
+    }
+
+    /// score 在 [min, max] 之间的 member，边界可以是开区间
+    pub fn range_by_score(
+        &self,
+        min: Bound<f64>,
+        max: Bound<f64>,
+    ) -> impl Iterator<Item = (&[u8], f64)> {
+        self.iter()
+            .skip_while(move |(_, score)| match min {
+                Bound::Included(min) => *score < min,
+                Bound::Excluded(min) => *score <= min,
+                Bound::Unbounded => false,
+            })
+            .take_while(move |(_, score)| match max {
+                Bound::Included(max) => *score <= max,
+                Bound::Excluded(max) => *score < max,
+                Bound::Unbounded => true,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sorted_set_should_order_by_score_then_member() {
+        let mut zset = SortedSet::default();
+        assert!(zset.insert(b"b".to_vec(), 2.0));
+        assert!(zset.insert(b"a".to_vec(), 2.0));
+        assert!(zset.insert(b"c".to_vec(), 1.0));
+        // 更新 score 会调整位置
+        assert!(!zset.insert(b"c".to_vec(), 3.0));
+        let members: Vec<_> = zset.iter().map(|(m, _)| m.to_vec()).collect();
+        assert_eq!(members, [b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+
+        assert!(zset.remove(b"a"));
+        assert!(!zset.remove(b"a"));
+        assert_eq!(zset.len(), 2);
+        assert_eq!(zset.score(b"c"), Some(3.0));
+    }
+
+    #[test]
+    fn range_by_score_should_respect_bounds() {
+        let mut zset = SortedSet::default();
+        for (i, m) in ["a", "b", "c", "d"].iter().enumerate() {
+            zset.insert(m.as_bytes().to_vec(), i as f64);
+        }
+        let range =
+            |min, max| -> Vec<f64> { zset.range_by_score(min, max).map(|(_, s)| s).collect() };
+        assert_eq!(
+            range(Bound::Included(1.0), Bound::Included(2.0)),
+            [1.0, 2.0]
+        );
+        assert_eq!(range(Bound::Excluded(1.0), Bound::Unbounded), [2.0, 3.0]);
+        assert_eq!(range(Bound::Unbounded, Bound::Excluded(1.0)), [0.0]);
+        assert!(range(Bound::Excluded(2.0), Bound::Excluded(3.0)).is_empty());
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+
+use tokio::sync::Notify;
+
+/// 所有连接的信息，CLIENT LIST / CLIENT KILL 和 INFO clients 用，clone 之后共享同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct Clients(Arc<Mutex<BTreeMap<u64, ClientInfo>>>);
+
+/// 一个连接的信息，由 Session 在每个命令之后更新
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    pub id: u64,
+    pub addr: Option<SocketAddr>,
+    pub name: Option<String>,
+    pub created: Instant,
+    pub last_interaction: Instant,
+    /// 最近执行的命令
+    pub cmd: String,
+    pub sub: usize,
+    pub psub: usize,
+    /// MULTI 之后排队的命令个数
+    pub multi: Option<usize>,
+    /// N 普通连接，P 订阅了频道，x 在事务中，S 从节点的复制连接
+    pub flags: &'static str,
+    // CLIENT KILL 通知连接所在的任务断开
+    kill: Arc<Notify>,
+}
+
+impl ClientInfo {
+    /// CLIENT LIST 里的一行
+    pub fn line(&self) -> String {
+        let now = Instant::now();
+        format!(
+            "id={} addr={} name={} age={} idle={} flags={} db=0 sub={} psub={} multi={} cmd={}",
+            self.id,
+            self.addr.map(|addr| addr.to_string()).unwrap_or_default(),
+            self.name.as_deref().unwrap_or_default(),
+            now.duration_since(self.created).as_secs(),
+            now.duration_since(self.last_interaction).as_secs(),
+            self.flags,
+            self.sub,
+            self.psub,
+            self.multi.map_or(-1, |n| n as i64),
+            if self.cmd.is_empty() {
+                "NULL"
+            } else {
+                &self.cmd
+            },
+        )
+    }
+}
+
+impl Clients {
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<u64, ClientInfo>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 新的连接，返回 CLIENT KILL 时收到通知的 Notify
+    pub fn register(&self, id: u64, addr: Option<SocketAddr>) -> Arc<Notify> {
+        let kill = Arc::new(Notify::new());
+        let now = Instant::now();
+        let info = ClientInfo {
+            id,
+            addr,
+            name: None,
+            created: now,
+            last_interaction: now,
+            cmd: String::new(),
+            sub: 0,
+            psub: 0,
+            multi: None,
+            flags: "N",
+            kill: kill.clone(),
+        };
+        self.lock().insert(id, info);
+        kill
+    }
+
+    pub fn unregister(&self, id: u64) {
+        self.lock().remove(&id);
+    }
+
+    pub fn update(&self, id: u64, f: impl FnOnce(&mut ClientInfo)) {
+        if let Some(info) = self.lock().get_mut(&id) {
+            f(info);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// 按 id 排序
+    pub fn list(&self) -> Vec<ClientInfo> {
+        self.lock().values().cloned().collect()
+    }
+
+    /// 通知满足条件的连接断开，返回连接的个数；连接退出时自己注销
+    pub fn kill(&self, filter: impl Fn(&ClientInfo) -> bool) -> usize {
+        self.lock()
+            .values()
+            .filter(|info| filter(info))
+            .map(|info| info.kill.notify_one())
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn kill_should_notify_client() {
+        let clients = Clients::default();
+        let addr: SocketAddr = "127.0.0.1:5000".parse().unwrap();
+        let kill = clients.register(1, Some(addr));
+        clients.register(2, None);
+        clients.update(2, |info| info.name = Some("worker".into()));
+        assert_eq!(clients.len(), 2);
+        assert!(clients.list()[1].line().contains("name=worker"));
+
+        assert_eq!(clients.kill(|info| info.addr == Some(addr)), 1);
+        // 通知在等待之前发出也不会丢
+        kill.notified().await;
+
+        clients.unregister(1);
+        assert_eq!(clients.kill(|info| info.id == 1), 0);
+        assert_eq!(clients.len(), 1);
+    }
+}
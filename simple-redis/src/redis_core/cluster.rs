@@ -0,0 +1,668 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use anyhow::{anyhow, Result};
+use tracing::info;
+
+use super::{
+    backend::Backend, command::CommandError, config::ConfigError, RespArray, RespFrame, RespMap,
+};
+
+/// 和 redis 一样有 16384 个 slot
+pub const SLOTS: usize = 16384;
+
+/// 集群的拓扑，cluster-enabled 为 no 时是 None，clone 之后共享同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct Cluster(Arc<Mutex<Option<Topology>>>);
+
+/// 集群里所有的节点和每个 slot 属于哪个节点，从 cluster-config-file 读取，运行时不会改变
+#[derive(Debug, Clone, PartialEq)]
+pub struct Topology {
+    myself: usize,
+    nodes: Vec<Node>,
+    // 下标是 slot，值是 nodes 的下标
+    owners: Vec<Option<usize>>,
+    // 正在从自己迁移到别的节点的 slot
+    migrating: BTreeMap<u16, usize>,
+    // 正在从别的节点迁移到自己的 slot
+    importing: BTreeMap<u16, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub id: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Cluster {
+    fn lock(&self) -> MutexGuard<'_, Option<Topology>> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_topology(&self, topology: Topology) {
+        *self.lock() = Some(topology);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    /// 没有开启集群时返回 None
+    pub fn read<R>(&self, f: impl FnOnce(&Topology) -> R) -> Option<R> {
+        self.lock().as_ref().map(f)
+    }
+}
+
+impl Node {
+    pub fn addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
+
+/// 和 redis 的 nodes.conf 类似，每行一个节点，`#` 开头的是注释：
+///
+/// ```text
+/// <id> <host:port> <flags> [<slot> | <start>-<end> | [<slot>->-<id>] | [<slot>-<-<id>] ...]
+/// ```
+///
+/// flags 是逗号分隔的 myself、master，只能有一个节点是 myself；
+/// `[slot->-id]` 表示这个 slot 正在迁移到 id，`[slot-<-id]` 表示正在从 id 迁移过来，只能写在 myself 这一行
+impl FromStr for Topology {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<(usize, Vec<&str>)> = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.split_whitespace().collect::<Vec<_>>()))
+            .filter(|(_, fields)| fields.first().is_some_and(|f| !f.starts_with('#')))
+            .collect();
+        let bad = |line: usize, msg: &str| ConfigError::BadDirective(line, msg.into());
+
+        let mut myself = None;
+        let mut nodes = vec![];
+        for (line, fields) in &lines {
+            let [id, addr, flags, ..] = fields.as_slice() else {
+                return Err(bad(*line, "expect <id> <host:port> <flags> [slot ...]"));
+            };
+            if nodes.iter().any(|node: &Node| node.id == *id) {
+                return Err(bad(*line, "duplicate node id"));
+            }
+            // 兼容 redis 的 host:port@cport 格式
+            let addr = addr.split('@').next().unwrap_or_default();
+            let (host, port) = addr
+                .rsplit_once(':')
+                .and_then(|(host, port)| Some((host, port.parse().ok()?)))
+                .ok_or_else(|| bad(*line, "invalid address"))?;
+            if flags.split(',').any(|flag| flag == "myself") {
+                if myself.is_some() {
+                    return Err(bad(*line, "more than one node is myself"));
+                }
+                myself = Some(nodes.len());
+            }
+            nodes.push(Node {
+                id: id.to_string(),
+                host: host.into(),
+                port,
+            });
+        }
+        let myself = myself.ok_or_else(|| bad(0, "no node is myself"))?;
+
+        let mut topology = Self {
+            myself,
+            owners: vec![None; SLOTS],
+            nodes,
+            migrating: BTreeMap::new(),
+            importing: BTreeMap::new(),
+        };
+        for (node, (line, fields)) in lines.iter().enumerate() {
+            for field in &fields[3..] {
+                topology
+                    .assign(node, field)
+                    .map_err(|msg| bad(*line, &format!("{}: '{}'", msg, field)))?;
+            }
+        }
+        Ok(topology)
+    }
+}
+
+impl Topology {
+    /// 解析 nodes.conf 的一个 slot 字段
+    fn assign(&mut self, node: usize, field: &str) -> Result<(), &'static str> {
+        if let Some(migration) = field.strip_prefix('[').and_then(|f| f.strip_suffix(']')) {
+            if node != self.myself {
+                return Err("migrating slots can only be set for myself");
+            }
+            let (slot, peer, importing) = match migration.split_once("->-") {
+                Some((slot, peer)) => (slot, peer, false),
+                None => {
+                    let (slot, peer) = migration.split_once("-<-").ok_or("invalid slot")?;
+                    (slot, peer, true)
+                }
+            };
+            let slot = parse_slot(slot)?;
+            let peer = self
+                .nodes
+                .iter()
+                .position(|n| n.id == peer)
+                .ok_or("unknown node")?;
+            match importing {
+                true => self.importing.insert(slot, peer),
+                false => self.migrating.insert(slot, peer),
+            };
+            return Ok(());
+        }
+        let (start, end) = match field.split_once('-') {
+            Some((start, end)) => (parse_slot(start)?, parse_slot(end)?),
+            None => (parse_slot(field)?, parse_slot(field)?),
+        };
+        for slot in start..=end {
+            let owner = &mut self.owners[slot as usize];
+            if owner.is_some() {
+                return Err("slot already assigned");
+            }
+            *owner = Some(node);
+        }
+        Ok(())
+    }
+
+    pub fn myself(&self) -> &Node {
+        &self.nodes[self.myself]
+    }
+
+    /// 每个节点连续的 slot 区间，按 slot 排序
+    fn ranges(&self) -> Vec<(u16, u16, usize)> {
+        let mut ranges: Vec<(u16, u16, usize)> = vec![];
+        for (slot, owner) in self.owners.iter().enumerate() {
+            let Some(owner) = *owner else {
+                continue;
+            };
+            match ranges.last_mut() {
+                Some((_, end, node)) if *node == owner && *end as usize + 1 == slot => {
+                    *end = slot as u16
+                }
+                _ => ranges.push((slot as u16, slot as u16, owner)),
+            }
+        }
+        ranges
+    }
+
+    /// 集群里的节点只处理自己的 slot 里的 key，其它的 key 回复 MOVED；
+    /// 正在迁移的 slot 里不存在的 key 回复 ASK，客户端先发 ASKING 再到目标节点执行
+    fn route(
+        &self,
+        keys: &[String],
+        asking: bool,
+        exists: impl Fn(&str) -> bool,
+    ) -> Result<(), CommandError> {
+        let Some(slot) = keys.first().map(|key| key_slot(key.as_bytes())) else {
+            return Ok(());
+        };
+        if keys.iter().any(|key| key_slot(key.as_bytes()) != slot) {
+            return Err(CommandError::CrossSlot);
+        }
+        let missing = || keys.iter().filter(|key| !exists(key)).count();
+        match self.owners[slot as usize] {
+            None => Err(CommandError::ClusterDown),
+            Some(owner) if owner == self.myself => match self.migrating.get(&slot) {
+                Some(&target) if missing() == keys.len() => {
+                    Err(CommandError::Ask(slot, self.nodes[target].addr()))
+                }
+                // 一部分 key 已经迁移走了，等迁移完成之后再试
+                Some(_) if missing() > 0 => Err(CommandError::TryAgain),
+                _ => Ok(()),
+            },
+            Some(_) if asking && self.importing.contains_key(&slot) => {
+                match keys.len() > 1 && missing() > 0 {
+                    true => Err(CommandError::TryAgain),
+                    false => Ok(()),
+                }
+            }
+            Some(owner) => Err(CommandError::Moved(slot, self.nodes[owner].addr())),
+        }
+    }
+
+    /// CLUSTER SLOTS：[[start, end, [host, port, id]], ...]
+    pub fn slots_frame(&self) -> RespFrame {
+        let ranges: Vec<RespFrame> = self
+            .ranges()
+            .into_iter()
+            .map(|(start, end, node)| {
+                RespArray::new([
+                    (start as i64).into(),
+                    (end as i64).into(),
+                    node_frame(&self.nodes[node]),
+                ])
+                .into()
+            })
+            .collect();
+        RespArray::new(ranges).into()
+    }
+
+    /// CLUSTER SHARDS：每个节点是一个 shard，{slots: [start, end, ...], nodes: [{id, ...}]}
+    pub fn shards_frame(&self) -> RespFrame {
+        let ranges = self.ranges();
+        let shards: Vec<RespFrame> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| {
+                let slots: Vec<RespFrame> = ranges
+                    .iter()
+                    .filter(|(_, _, owner)| *owner == i)
+                    .flat_map(|(start, end, _)| [(*start as i64).into(), (*end as i64).into()])
+                    .collect();
+                let info = BTreeMap::from([
+                    ("id".to_string(), node.id.as_str().into()),
+                    ("port".to_string(), (node.port as i64).into()),
+                    ("ip".to_string(), node.host.as_str().into()),
+                    ("endpoint".to_string(), node.host.as_str().into()),
+                    ("role".to_string(), "master".into()),
+                    ("health".to_string(), "online".into()),
+                ]);
+                let shard = BTreeMap::from([
+                    ("slots".to_string(), RespArray::new(slots).into()),
+                    (
+                        "nodes".to_string(),
+                        RespArray::new([RespMap::new(info).into()]).into(),
+                    ),
+                ]);
+                RespMap::new(shard).into()
+            })
+            .collect();
+        RespArray::new(shards).into()
+    }
+
+    /// CLUSTER NODES：和 redis 的格式一样，每行一个节点，正在迁移的 slot 只出现在 myself 这一行
+    pub fn nodes_text(&self) -> String {
+        let ranges = self.ranges();
+        let mut text = String::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let flags = if i == self.myself {
+                "myself,master"
+            } else {
+                "master"
+            };
+            let _ = write!(
+                text,
+                "{} {}:{}@{} {} - 0 0 {} connected",
+                node.id,
+                node.host,
+                node.port,
+                node.port as u32 + 10000,
+                flags,
+                i + 1
+            );
+            for (start, end, _) in ranges.iter().filter(|(_, _, owner)| *owner == i) {
+                let _ = match start == end {
+                    true => write!(text, " {}", start),
+                    false => write!(text, " {}-{}", start, end),
+                };
+            }
+            if i == self.myself {
+                for (slot, target) in &self.migrating {
+                    let _ = write!(text, " [{}->-{}]", slot, self.nodes[*target].id);
+                }
+                for (slot, source) in &self.importing {
+                    let _ = write!(text, " [{}-<-{}]", slot, self.nodes[*source].id);
+                }
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+fn node_frame(node: &Node) -> RespFrame {
+    RespArray::new([
+        node.host.as_str().into(),
+        (node.port as i64).into(),
+        node.id.as_str().into(),
+    ])
+    .into()
+}
+
+fn parse_slot(s: &str) -> Result<u16, &'static str> {
+    s.parse()
+        .ok()
+        .filter(|slot| (*slot as usize) < SLOTS)
+        .ok_or("invalid slot")
+}
+
+/// CRC16-CCITT（XMODEM），和 redis 集群用的一样
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x1021,
+        })
+    })
+}
+
+/// key 里有 `{...}` 并且括号里不是空的，只用第一对括号里的内容计算 slot，
+/// 这样 `{user1}.name` 和 `{user1}.age` 在同一个 slot
+pub fn key_slot(key: &[u8]) -> u16 {
+    let tag = key.iter().position(|&b| b == b'{').and_then(|start| {
+        let len = key[start + 1..].iter().position(|&b| b == b'}')?;
+        (len > 0).then(|| &key[start + 1..start + 1 + len])
+    });
+    crc16(tag.unwrap_or(key)) % SLOTS as u16
+}
+
+/// 命令的参数里哪些是 key：(第一个, 最后一个, 间隔)，下标从命令名之后开始，最后一个为 None 表示到末尾
+fn key_spec(name: &str) -> Option<(usize, Option<usize>, usize)> {
+    match name {
+        "del" | "exists" | "mget" | "watch" => Some((0, None, 1)),
+        "mset" => Some((0, None, 2)),
+        "get" | "set" | "incr" | "decr" | "hset" | "hget" | "hgetall" | "hdel" | "lpush"
+        | "rpush" | "lpop" | "rpop" | "lrange" | "sadd" | "srem" | "smembers" | "sismember"
+        | "zadd" | "zrange" | "zrangebyscore" | "zrem" | "expire" | "pexpire" | "expireat"
+        | "pexpireat" | "ttl" | "pttl" | "persist" => Some((0, Some(0), 1)),
+        _ => None,
+    }
+}
+
+/// 命令里的 key，还没有解析命令，参数个数不对时由之后的解析返回错误
+fn command_keys(name: &str, frame: &RespFrame) -> Vec<String> {
+    let (Some((first, last, step)), RespFrame::Array(array)) = (key_spec(name), frame) else {
+        return vec![];
+    };
+    let args: Vec<&[u8]> = array
+        .0
+        .iter()
+        .skip(1)
+        .filter_map(|frame| match frame {
+            RespFrame::BulkString(s) => Some(s.0.as_slice()),
+            _ => None,
+        })
+        .collect();
+    let last = last.unwrap_or(usize::MAX).min(args.len().saturating_sub(1));
+    args.iter()
+        .enumerate()
+        .skip(first)
+        .take_while(|(i, _)| *i <= last)
+        .step_by(step)
+        .map(|(_, key)| String::from_utf8_lossy(key).into_owned())
+        .collect()
+}
+
+/// 检查命令能不能在这个节点执行，没有开启集群时总是可以
+pub fn route(
+    backend: &Backend,
+    name: &str,
+    frame: &RespFrame,
+    asking: bool,
+) -> Result<(), CommandError> {
+    backend
+        .cluster()
+        .read(|topology| {
+            let keys = command_keys(name, frame);
+            topology.route(&keys, asking, |key| backend.contains(key))
+        })
+        .unwrap_or(Ok(()))
+}
+
+/// cluster-enabled 为 yes 时读取 cluster-config-file，和快照一样放在 dir 里
+pub fn load(backend: &Backend) -> Result<()> {
+    let config = backend.config().get();
+    if !config.cluster_enabled {
+        return Ok(());
+    }
+    let path = config.persistence.dir.join(&config.cluster_config_file);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("failed to read cluster config file {:?}: {}", path, e))?;
+    let topology: Topology = content.parse()?;
+    info!(
+        "cluster node {} with {} nodes",
+        topology.myself().id,
+        topology.nodes.len()
+    );
+    backend.cluster().set_topology(topology);
+    Ok(())
+}
+
+/// INFO cluster
+pub fn info(backend: &Backend) -> String {
+    format!(
+        "cluster_enabled:{}\r\n",
+        backend.cluster().is_enabled() as u8
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{SinkExt, StreamExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::codec::Framed;
+
+    use super::*;
+    use crate::redis_core::{codec::RespFrameCodec, server, SimpleString};
+
+    #[test]
+    fn key_slot_should_follow_hash_tags() {
+        assert_eq!(crc16(b"123456789"), 0x31c3);
+        assert_eq!(key_slot(b"foo"), 12182);
+        assert_eq!(key_slot(b"bar"), 5061);
+        assert_eq!(key_slot(b"{bar}.x"), 5061);
+        assert_eq!(key_slot(b"{user1}.name"), key_slot(b"{user1}.age"));
+        // 空的 {} 不算 hash tag
+        assert_eq!(key_slot(b"foo{}{bar}"), crc16(b"foo{}{bar}") % SLOTS as u16);
+        assert_eq!(key_slot(b"foo{{bar}}zap"), crc16(b"{bar") % SLOTS as u16);
+    }
+
+    #[test]
+    fn topology_should_parse() {
+        let topology: Topology = "
+            # two nodes
+            a 127.0.0.1:7000@17000 myself,master 0-8191 [100->-b] [9000-<-b]
+            b 127.0.0.1:7001 master 8192-16383
+        "
+        .parse()
+        .unwrap();
+        assert_eq!(topology.myself().port, 7000);
+        assert_eq!(topology.ranges(), vec![(0, 8191, 0), (8192, 16383, 1)]);
+        assert_eq!(
+            topology.nodes_text(),
+            "a 127.0.0.1:7000@17000 myself,master - 0 0 1 connected 0-8191 [100->-b] [9000-<-b]\n\
+             b 127.0.0.1:7001@17001 master - 0 0 2 connected 8192-16383\n"
+        );
+        assert_eq!(
+            topology.route(&["foo".into()], false, |_| true),
+            Err(CommandError::Moved(12182, "127.0.0.1:7001".into()))
+        );
+        assert_eq!(
+            topology.route(&["foo".into(), "bar".into()], false, |_| true),
+            Err(CommandError::CrossSlot)
+        );
+
+        for (content, line) in [
+            ("a 127.0.0.1:7000 master 0-100", 0),
+            (
+                "a 127.0.0.1:7000 myself 0-100\nb 127.0.0.1:7001 master 100",
+                2,
+            ),
+            ("a 127.0.0.1:7000 myself 16384", 1),
+            ("a 127.0.0.1:7000 myself [1->-c]", 1),
+            ("a 127.0.0.1 myself 1", 1),
+        ] {
+            assert!(
+                matches!(content.parse::<Topology>(), Err(ConfigError::BadDirective(l, _)) if l == line),
+                "{}",
+                content
+            );
+        }
+    }
+
+    async fn start() -> (Backend, u16) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let backend = Backend::new();
+        tokio::spawn(server::serve(listener, backend.clone()));
+        (backend, port)
+    }
+
+    async fn connect(port: u16) -> Framed<TcpStream, RespFrameCodec> {
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        Framed::new(stream, RespFrameCodec)
+    }
+
+    async fn call(client: &mut Framed<TcpStream, RespFrameCodec>, cmd: &str) -> RespFrame {
+        let args: Vec<RespFrame> = cmd.split_whitespace().map(|arg| arg.into()).collect();
+        client.send(RespArray::new(args).into()).await.unwrap();
+        client.next().await.unwrap().unwrap()
+    }
+
+    /// 和支持集群的客户端一样，收到 MOVED 时到新的节点重试，收到 ASK 时先发 ASKING
+    async fn call_cluster(port: u16, cmd: &str) -> RespFrame {
+        let mut client = connect(port).await;
+        let mut reply = call(&mut client, cmd).await;
+        for _ in 0..3 {
+            let RespFrame::Error(e) = &reply else {
+                break;
+            };
+            let fields: Vec<&str> = e.0.split(' ').collect();
+            let (kind, addr) = (fields[0], fields[2]);
+            let port = addr.rsplit_once(':').unwrap().1.parse().unwrap();
+            client = connect(port).await;
+            if kind == "ASK" {
+                call(&mut client, "asking").await;
+            }
+            reply = call(&mut client, cmd).await;
+        }
+        reply
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn nodes_should_redirect_to_slot_owner() {
+        let (a, a_port) = start().await;
+        let (b, b_port) = start().await;
+        // bar 所在的 5061 正在从 a 迁移到 b
+        let content = format!(
+            "a 127.0.0.1:{} myself,master 0-8191 [5061->-b]\nb 127.0.0.1:{} master 8192-16383",
+            a_port, b_port
+        );
+        a.cluster().set_topology(content.parse().unwrap());
+        let content = format!(
+            "a 127.0.0.1:{} master 0-8191\nb 127.0.0.1:{} myself,master 8192-16383 [5061-<-a]",
+            a_port, b_port
+        );
+        b.cluster().set_topology(content.parse().unwrap());
+        let mut ca = connect(a_port).await;
+        let mut cb = connect(b_port).await;
+
+        let b_addr = format!("127.0.0.1:{}", b_port);
+        assert_eq!(
+            call(&mut ca, "set foo 1").await,
+            CommandError::Moved(12182, b_addr.clone()).into()
+        );
+        assert_eq!(
+            call(&mut cb, "set foo 1").await,
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(
+            call(&mut ca, "mset {user}.a 1 foo 2").await,
+            CommandError::CrossSlot.into()
+        );
+        assert_eq!(
+            call(&mut ca, "cluster keyslot foo").await,
+            RespFrame::Integer(12182)
+        );
+
+        // 迁移中的 slot：a 上已有的 key 还在 a 执行，没有的 key 要到 b 执行
+        a.set("{bar}.old".into(), b"1".to_vec(), None, None);
+        assert_eq!(call(&mut ca, "get {bar}.old").await, RespFrame::from("1"));
+        assert_eq!(
+            call(&mut ca, "get bar").await,
+            CommandError::Ask(5061, b_addr.clone()).into()
+        );
+        assert_eq!(
+            call(&mut ca, "mget bar {bar}.old").await,
+            CommandError::TryAgain.into()
+        );
+        assert_eq!(
+            call(&mut cb, "set bar 1").await,
+            CommandError::Moved(5061, format!("127.0.0.1:{}", a_port)).into()
+        );
+        assert_eq!(
+            call(&mut cb, "asking").await,
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(
+            call(&mut cb, "set bar 1").await,
+            SimpleString::new("OK").into()
+        );
+        // ASKING 只对下一个命令有效
+        assert!(matches!(
+            call(&mut cb, "get bar").await,
+            RespFrame::Error(e) if e.0.starts_with("MOVED")
+        ));
+
+        // 跟着重定向执行，每个 key 都写到了自己的 slot 所在的节点
+        for i in 0..20 {
+            let cmd = format!("set key:{} {}", i, i);
+            assert_eq!(
+                call_cluster(a_port, &cmd).await,
+                SimpleString::new("OK").into()
+            );
+        }
+        for i in 0..20 {
+            let key = format!("key:{}", i);
+            let owner = match key_slot(key.as_bytes()) {
+                5061 => &b,
+                slot if slot < 8192 => &a,
+                _ => &b,
+            };
+            assert_eq!(owner.get(&key), Ok(Some(i.to_string().into_bytes())));
+        }
+        assert_eq!(a.dbsize() + b.dbsize(), 20 + 3);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn cluster_commands_should_describe_topology() {
+        let (a, a_port) = start().await;
+        let topology: Topology = format!(
+            "a 127.0.0.1:{} myself,master 0-100 200\nb 10.0.0.2:7001 master 101-199 201-16383",
+            a_port
+        )
+        .parse()
+        .unwrap();
+        let mut client = connect(a_port).await;
+        assert_eq!(
+            call(&mut client, "cluster slots").await,
+            CommandError::ClusterDisabled.into()
+        );
+        a.cluster().set_topology(topology);
+
+        let RespFrame::Array(slots) = call(&mut client, "cluster slots").await else {
+            panic!("expect an array");
+        };
+        assert_eq!(slots.0.len(), 4);
+        assert_eq!(
+            slots.0[1],
+            RespArray::new([
+                RespFrame::Integer(101),
+                RespFrame::Integer(199),
+                RespArray::new(["10.0.0.2".into(), RespFrame::Integer(7001), "b".into()]).into()
+            ])
+            .into()
+        );
+        let RespFrame::Array(shards) = call(&mut client, "cluster shards").await else {
+            panic!("expect an array");
+        };
+        assert_eq!(shards.0.len(), 2);
+        let RespFrame::BulkString(nodes) = call(&mut client, "cluster nodes").await else {
+            panic!("expect a bulk string");
+        };
+        let nodes = String::from_utf8(nodes.0).unwrap();
+        assert!(nodes.contains("myself,master - 0 0 1 connected 0-100 200\n"));
+        let RespFrame::BulkString(info) = call(&mut client, "info cluster").await else {
+            panic!("expect a bulk string");
+        };
+        assert!(String::from_utf8_lossy(&info.0).contains("cluster_enabled:1"));
+    }
+}
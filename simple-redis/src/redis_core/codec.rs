@@ -0,0 +1,197 @@
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::{RespDecode, RespEncode, RespError, RespFrame};
+
+/// 一个 frame 最多缓存的字节数，和 redis 的 client-query-buffer-limit 默认值一样
+pub const MAX_FRAME_LEN: usize = 1024 * 1024 * 1024;
+
+/// 按 RESP 协议拆分/拼装 TCP 字节流：
+/// 数据不够一个完整的 frame 时留在缓冲区里等下一次读取，一次读到多个 frame（pipeline）时逐个返回
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RespFrameCodec;
+
+impl Decoder for RespFrameCodec {
+    type Item = RespFrame;
+    type Error = RespError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let len = match RespFrame::expect_length(src) {
+            Ok(len) => len,
+            // 数据还没到齐，但已经超过了上限，不再继续缓存
+            Err(RespError::NotComplete) if src.len() > MAX_FRAME_LEN => {
+                return Err(RespError::InvalidFrame(format!(
+                    "frame exceeds {} bytes",
+                    MAX_FRAME_LEN
+                )))
+            }
+            Err(RespError::NotComplete) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut data = src.split_to(len);
+        RespFrame::decode(&mut data).map(Some)
+    }
+}
+
+impl Encoder<RespFrame> for RespFrameCodec {
+    type Error = RespError;
+
+    fn encode(&mut self, item: RespFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.extend_from_slice(&item.encode());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+    use crate::redis_core::*;
+
+    fn resp_frame() -> impl Strategy<Value = RespFrame> {
+        // simple string、error 和 map 的 key 不能包含 "\r\n"
+        let simple = "[a-zA-Z0-9 _.:-]{0,16}";
+        let bytes = || vec(any::<u8>(), 0..32);
+        let leaf = prop_oneof![
+            any::<i64>().prop_map(RespFrame::Integer),
+            simple.prop_map(|s| SimpleString::new(s).into()),
+            simple.prop_map(|s| SimpleError::new(s).into()),
+            bytes().prop_map(|b| BulkString::new(b).into()),
+            Just(RespNullBulkString.into()),
+            Just(RespFrame::Null(RespNull)),
+            Just(RespFrame::NullArray(RespNullArray)),
+            any::<bool>().prop_map(RespFrame::Boolean),
+            // NaN 和自己不相等，单独测试
+            any::<f64>()
+                .prop_filter("NaN", |f| !f.is_nan())
+                .prop_map(RespFrame::Double),
+            "-?(0|[1-9][0-9]{0,40})".prop_map(|s| BigNumber::new(s).into()),
+            bytes().prop_map(|b| BulkError::new(b).into()),
+            ("txt|mkd", bytes()).prop_map(|(f, b)| VerbatimString::new(f, b).into()),
+        ];
+        leaf.prop_recursive(3, 32, 4, |inner| {
+            let map = || {
+                proptest::collection::btree_map("[a-z]{1,8}", inner.clone(), 0..4)
+                    .prop_map(RespMap::new)
+            };
+            prop_oneof![
+                vec(inner.clone(), 0..4).prop_map(|v| RespArray::new(v).into()),
+                vec(inner.clone(), 0..4).prop_map(|v| RespSet::new(v).into()),
+                vec(inner.clone(), 0..4).prop_map(|v| RespPush::new(v).into()),
+                map().prop_map(RespFrame::from),
+                (map(), inner.clone()).prop_map(|(m, f)| RespAttribute::new(m, f).into()),
+            ]
+        })
+    }
+
+    fn decode_all(codec: &mut RespFrameCodec, buf: &mut BytesMut) -> Vec<RespFrame> {
+        let mut frames = vec![];
+        while let Some(frame) = codec.decode(buf).unwrap() {
+            frames.push(frame);
+        }
+        frames
+    }
+
+    proptest! {
+        #[test]
+        fn encode_decode_should_round_trip(frame in resp_frame()) {
+            let data = frame.clone().encode();
+            prop_assert_eq!(RespFrame::expect_length(&data), Ok(data.len()));
+            let mut buf = BytesMut::from(&data[..]);
+            prop_assert_eq!(RespFrame::decode(&mut buf), Ok(frame));
+            prop_assert!(buf.is_empty());
+        }
+
+        #[test]
+        fn codec_should_handle_split_and_pipelined_frames(
+            frames in vec(resp_frame(), 1..8),
+            chunk in 1usize..64,
+        ) {
+            let mut codec = RespFrameCodec;
+            let mut data = BytesMut::new();
+            for frame in frames.iter() {
+                codec.encode(frame.clone(), &mut data).unwrap();
+            }
+
+            // 每次只“读到” chunk 个字节
+            let mut buf = BytesMut::new();
+            let mut decoded = vec![];
+            for piece in data.chunks(chunk) {
+                buf.extend_from_slice(piece);
+                decoded.extend(decode_all(&mut codec, &mut buf));
+            }
+            prop_assert_eq!(decoded, frames);
+            prop_assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn codec_should_decode_nan_and_resp3_frames() {
+        let mut codec = RespFrameCodec;
+        let mut buf = BytesMut::from(
+            &b",nan\r\n=15\r\ntxt:Some string\r\n(-3492890328409238509324850943850943825024385\r\n"
+                [..],
+        );
+        match codec.decode(&mut buf) {
+            Ok(Some(RespFrame::Double(f))) => assert!(f.is_nan()),
+            other => panic!("expect nan, got {:?}", other),
+        }
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            vec![
+                VerbatimString::new("txt", "Some string").into(),
+                BigNumber::new("-3492890328409238509324850943850943825024385").into(),
+            ]
+        );
+
+        // 属性附加在后面的 frame 上，整体是一个 frame
+        let mut buf = BytesMut::from(&b"|1\r\n$3\r\nttl\r\n:3\r\n*1\r\n:1\r\n+OK\r\n"[..]);
+        let attrs = RespMap::new(BTreeMap::from([("ttl".into(), RespFrame::Integer(3))]));
+        assert_eq!(
+            decode_all(&mut codec, &mut buf),
+            vec![
+                RespAttribute::new(attrs, RespArray::new([RespFrame::Integer(1)]).into()).into(),
+                SimpleString::new("OK").into(),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_frame_should_fail() {
+        let mut codec = RespFrameCodec;
+        let mut buf = BytesMut::from(&b"?1\r\n"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+        let mut buf = BytesMut::from(&b"(12a\r\n"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+        let mut buf = BytesMut::from(&b"=3\r\ntxt\r\n"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+    }
+
+    #[test]
+    fn oversized_frame_should_fail() {
+        let mut codec = RespFrameCodec;
+        // 不等 bulk string 的数据到齐，看到长度就报错
+        let mut buf = BytesMut::from(&b"*1\r\n$1000000000000\r\n"[..]);
+        assert_eq!(
+            codec.decode(&mut buf),
+            Err(RespError::InvalidFrameLength(1_000_000_000_000))
+        );
+        let mut buf = BytesMut::from("*1\r\n".repeat(200_000).as_bytes());
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(RespError::InvalidFrame(_))
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+use super::{check_arity, to_i64, to_key, CommandError};
+
+/// CLIENT LIST / ID / GETNAME / SETNAME name / KILL
+///
+/// 需要知道当前连接是哪一个，由 Session 执行
+#[derive(Debug, Clone, PartialEq)]
+pub enum Client {
+    List,
+    Id,
+    GetName,
+    SetName(String),
+    Kill(ClientKill),
+}
+
+/// CLIENT KILL ip:port，或者 CLIENT KILL [ID id] [ADDR ip:port] [SKIPME yes|no]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientKill {
+    pub(crate) id: Option<u64>,
+    pub(crate) addr: Option<String>,
+    /// 新的格式默认不会断开自己
+    pub(crate) skipme: bool,
+    /// 旧的格式只有一个地址参数，回复 OK 而不是断开的个数
+    pub(crate) legacy: bool,
+}
+
+impl Client {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("client", &args, 1, None)?;
+        let mut args = args.into_iter();
+        let sub = args.next().unwrap_or_default().to_ascii_lowercase();
+        let args: Vec<Vec<u8>> = args.collect();
+        match sub.as_slice() {
+            b"list" => Ok(Client::List),
+            b"id" => check_arity("client|id", &args, 0, Some(0)).map(|_| Client::Id),
+            b"getname" => check_arity("client|getname", &args, 0, Some(0)).map(|_| Client::GetName),
+            b"setname" => {
+                check_arity("client|setname", &args, 1, Some(1))?;
+                let name = to_key(args.into_iter().next().unwrap_or_default());
+                // 名字会出现在 CLIENT LIST 里，不能有空格这些字符
+                if name.chars().any(|c| !c.is_ascii_graphic()) {
+                    return Err(CommandError::InvalidClientName);
+                }
+                Ok(Client::SetName(name))
+            }
+            b"kill" => ClientKill::parse(args).map(Client::Kill),
+            _ => Err(CommandError::UnknownSubcommand(
+                String::from_utf8_lossy(&sub).into(),
+                "CLIENT",
+            )),
+        }
+    }
+}
+
+impl ClientKill {
+    fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("client|kill", &args, 1, None)?;
+        if args.len() == 1 {
+            return Ok(Self {
+                id: None,
+                addr: Some(to_key(args.into_iter().next().unwrap_or_default())),
+                skipme: false,
+                legacy: true,
+            });
+        }
+        if !args.len().is_multiple_of(2) {
+            return Err(CommandError::SyntaxError);
+        }
+        let mut kill = Self {
+            id: None,
+            addr: None,
+            skipme: true,
+            legacy: false,
+        };
+        for pair in args.chunks(2) {
+            match pair[0].to_ascii_lowercase().as_slice() {
+                b"id" => {
+                    let id =
+                        u64::try_from(to_i64(&pair[1])?).map_err(|_| CommandError::NotInteger)?;
+                    kill.id = Some(id);
+                }
+                b"addr" => kill.addr = Some(to_key(pair[1].clone())),
+                b"skipme" => {
+                    kill.skipme = match pair[1].to_ascii_lowercase().as_slice() {
+                        b"yes" => true,
+                        b"no" => false,
+                        _ => return Err(CommandError::SyntaxError),
+                    }
+                }
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(kill)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(cmd: &str) -> Result<Client, CommandError> {
+        Client::parse(
+            cmd.split_whitespace()
+                .map(|s| s.as_bytes().to_vec())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn client_should_parse() {
+        assert_eq!(
+            parse("SETNAME worker"),
+            Ok(Client::SetName("worker".into()))
+        );
+        assert_eq!(
+            parse("setname"),
+            Err(CommandError::WrongArity("client|setname"))
+        );
+        assert_eq!(
+            parse("kill 127.0.0.1:5000"),
+            Ok(Client::Kill(ClientKill {
+                id: None,
+                addr: Some("127.0.0.1:5000".into()),
+                skipme: false,
+                legacy: true,
+            }))
+        );
+        assert_eq!(
+            parse("kill id 3 skipme no"),
+            Ok(Client::Kill(ClientKill {
+                id: Some(3),
+                addr: None,
+                skipme: false,
+                legacy: false,
+            }))
+        );
+        assert_eq!(parse("kill id 3 user x"), Err(CommandError::SyntaxError));
+        assert_eq!(
+            parse("pause 10"),
+            Err(CommandError::UnknownSubcommand("pause".into(), "CLIENT"))
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use super::{check_arity, CommandError, CommandExecutor};
+use crate::redis_core::{backend::Backend, cluster, protocol::Protocol, RespFrame};
+
+/// CLUSTER KEYSLOT key / SLOTS / SHARDS / NODES
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cluster {
+    KeySlot(Vec<u8>),
+    Slots,
+    Shards,
+    Nodes,
+}
+
+/// ASKING
+///
+/// 下一个命令可以访问正在迁移到这个节点的 slot，由 Session 执行
+#[derive(Debug, Clone, PartialEq)]
+pub struct Asking;
+
+impl Cluster {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("cluster", &args, 1, None)?;
+        let mut args = args.into_iter();
+        let sub = args.next().unwrap_or_default().to_ascii_lowercase();
+        let args: Vec<Vec<u8>> = args.collect();
+        match sub.as_slice() {
+            b"keyslot" => {
+                check_arity("cluster|keyslot", &args, 1, Some(1))?;
+                Ok(Cluster::KeySlot(
+                    args.into_iter().next().unwrap_or_default(),
+                ))
+            }
+            b"slots" => check_arity("cluster|slots", &args, 0, Some(0)).map(|_| Cluster::Slots),
+            b"shards" => check_arity("cluster|shards", &args, 0, Some(0)).map(|_| Cluster::Shards),
+            b"nodes" => check_arity("cluster|nodes", &args, 0, Some(0)).map(|_| Cluster::Nodes),
+            _ => Err(CommandError::UnknownSubcommand(
+                String::from_utf8_lossy(&sub).into(),
+                "CLUSTER",
+            )),
+        }
+    }
+}
+
+impl Asking {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("asking", &args, 0, Some(0))?;
+        Ok(Self)
+    }
+}
+
+impl CommandExecutor for Cluster {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .cluster()
+            .read(|topology| match self {
+                Cluster::KeySlot(key) => (cluster::key_slot(&key) as i64).into(),
+                Cluster::Slots => topology.slots_frame(),
+                Cluster::Shards => topology.shards_frame(),
+                Cluster::Nodes => topology.nodes_text().as_str().into(),
+            })
+            .unwrap_or_else(|| CommandError::ClusterDisabled.into())
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::BTreeMap;
+
+use super::{check_arity, to_i64, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{
+    backend::Backend, protocol::Protocol, RespArray, RespFrame, RespMap, SimpleString,
+};
+
+/// PING [message]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ping {
+    message: Option<Vec<u8>>,
+}
+
+/// ECHO message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Echo {
+    message: Vec<u8>,
+}
+
+/// HELLO [protover [AUTH username password] [SETNAME clientname]]
+///
+/// 会修改连接的协议版本，由 Session 执行
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hello {
+    pub(crate) protocol: Option<Protocol>,
+    pub(crate) name: Option<String>,
+}
+
+impl Ping {
+    pub(super) fn parse(mut args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("ping", &args, 0, Some(1))?;
+        Ok(Self {
+            message: args.pop(),
+        })
+    }
+}
+
+impl Echo {
+    pub(super) fn parse(mut args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("echo", &args, 1, Some(1))?;
+        Ok(Self {
+            message: args.remove(0),
+        })
+    }
+}
+
+impl Ping {
+    /// RESP2 的连接订阅之后，PING 的回复也是 array
+    pub fn into_pubsub_reply(self) -> RespFrame {
+        let message = self.message.unwrap_or_default();
+        RespArray::new(["pong".into(), message.into()]).into()
+    }
+}
+
+impl Hello {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let mut args = args.into_iter();
+        let mut hello = Hello {
+            protocol: None,
+            name: None,
+        };
+        let Some(version) = args.next() else {
+            return Ok(hello);
+        };
+        let version = to_i64(&version).map_err(|_| CommandError::InvalidProtocolVersion)?;
+        hello.protocol = Some(Protocol::try_from(version).map_err(|_| CommandError::NoProto)?);
+
+        while let Some(opt) = args.next() {
+            match opt.to_ascii_uppercase().as_slice() {
+                // 没有配置密码，默认用户可以用任意密码登录
+                b"AUTH" => {
+                    args.next().ok_or(CommandError::SyntaxError)?;
+                    args.next().ok_or(CommandError::SyntaxError)?;
+                }
+                b"SETNAME" => {
+                    let name = args.next().ok_or(CommandError::SyntaxError)?;
+                    hello.name = Some(to_key(name));
+                }
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(hello)
+    }
+
+    /// HELLO 的回复是一个 map，RESP2 客户端收到的是展开的 array
+    pub fn reply(id: u64, protocol: Protocol) -> RespFrame {
+        let info: BTreeMap<String, RespFrame> = BTreeMap::from([
+            ("server".into(), "redis".into()),
+            ("version".into(), env!("CARGO_PKG_VERSION").into()),
+            ("proto".into(), protocol.version().into()),
+            ("id".into(), (id as i64).into()),
+            ("mode".into(), "standalone".into()),
+            ("role".into(), "master".into()),
+            ("modules".into(), RespArray::new([]).into()),
+        ]);
+        RespMap::new(info).into()
+    }
+}
+
+impl CommandExecutor for Ping {
+    fn execute(self, _backend: &Backend, _protocol: Protocol) -> RespFrame {
+        match self.message {
+            Some(message) => message.into(),
+            None => SimpleString::new("PONG").into(),
+        }
+    }
+}
+
+impl CommandExecutor for Echo {
+    fn execute(self, _backend: &Backend, _protocol: Protocol) -> RespFrame {
+        self.message.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::run;
+    use super::*;
+
+    #[test]
+    fn ping_echo_should_work() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "ping"), SimpleString::new("PONG").into());
+        assert_eq!(run(&backend, "ping hello"), RespFrame::from("hello"));
+        assert_eq!(run(&backend, "echo hello"), RespFrame::from("hello"));
+        assert_eq!(
+            run(&backend, "ping a b"),
+            CommandError::WrongArity("ping").into()
+        );
+    }
+
+    #[test]
+    fn hello_should_parse_options() {
+        let parse =
+            |args: &[&str]| Hello::parse(args.iter().map(|arg| arg.as_bytes().to_vec()).collect());
+        assert_eq!(
+            parse(&[]),
+            Ok(Hello {
+                protocol: None,
+                name: None
+            })
+        );
+        assert_eq!(
+            parse(&["3", "AUTH", "default", "pass", "setname", "app"]),
+            Ok(Hello {
+                protocol: Some(Protocol::Resp3),
+                name: Some("app".into())
+            })
+        );
+        assert_eq!(parse(&["4"]), Err(CommandError::NoProto));
+        assert_eq!(parse(&["x"]), Err(CommandError::InvalidProtocolVersion));
+        assert_eq!(
+            parse(&["3", "AUTH", "default"]),
+            Err(CommandError::SyntaxError)
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use super::{check_arity, to_i64, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{backend::Backend, protocol::Protocol, RespFrame};
+
+/// EXPIRE key seconds / PEXPIRE key milliseconds /
+/// EXPIREAT key unix-time-seconds / PEXPIREAT key unix-time-milliseconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expire {
+    name: &'static str,
+    key: String,
+    time: i64,
+    // 1000 表示秒，1 表示毫秒
+    unit_ms: i64,
+    absolute: bool,
+}
+
+/// TTL key / PTTL key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ttl {
+    key: String,
+    unit_ms: u128,
+}
+
+/// PERSIST key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Persist {
+    key: String,
+}
+
+impl Expire {
+    pub(super) fn parse(
+        name: &'static str,
+        unit_ms: i64,
+        absolute: bool,
+        args: Vec<Vec<u8>>,
+    ) -> Result<Self, CommandError> {
+        check_arity(name, &args, 2, Some(2))?;
+        Ok(Self {
+            name,
+            key: to_key(args[0].clone()),
+            time: to_i64(&args[1])?,
+            unit_ms,
+            absolute,
+        })
+    }
+}
+
+impl Ttl {
+    pub(super) fn parse(
+        name: &'static str,
+        unit_ms: u128,
+        args: Vec<Vec<u8>>,
+    ) -> Result<Self, CommandError> {
+        check_arity(name, &args, 1, Some(1))?;
+        let key = args.into_iter().map(to_key).next().unwrap_or_default();
+        Ok(Self { key, unit_ms })
+    }
+}
+
+impl Persist {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("persist", &args, 1, Some(1))?;
+        let key = args.into_iter().map(to_key).next().unwrap_or_default();
+        Ok(Self { key })
+    }
+}
+
+impl CommandExecutor for Expire {
+    /// 转换成毫秒的 unix 时间戳，已经过去的时间会直接删除 key；key 不存在时返回 0
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let at = self
+            .time
+            .checked_mul(self.unit_ms)
+            .and_then(|ms| match self.absolute {
+                true => Some(ms),
+                false => ms.checked_add(backend.now_ms() as i64),
+            });
+        match at {
+            Some(at) => (backend.expire_at(&self.key, at) as i64).into(),
+            None => CommandError::InvalidExpireTime(self.name).into(),
+        }
+    }
+}
+
+impl CommandExecutor for Ttl {
+    /// key 不存在返回 -2，没有过期时间返回 -1，秒数按四舍五入
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let ttl = match backend.ttl(&self.key) {
+            None => -2,
+            Some(None) => -1,
+            Some(Some(ttl)) => ((ttl.as_millis() + self.unit_ms / 2) / self.unit_ms) as i64,
+        };
+        ttl.into()
+    }
+}
+
+impl CommandExecutor for Persist {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        (backend.persist(&self.key) as i64).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::super::tests::run;
+    use super::*;
+    use crate::redis_core::{backend::MockClock, RespNullBulkString};
+
+    const NOW_MS: u64 = 1_700_000_000_000;
+
+    fn setup() -> (MockClock, Backend) {
+        let clock = MockClock::new(NOW_MS);
+        (clock.clone(), Backend::with_clock(clock))
+    }
+
+    #[test]
+    fn set_ex_px_should_expire() {
+        let (clock, backend) = setup();
+        run(&backend, "set a 1 ex 10");
+        run(&backend, "set b 1 px 1500");
+        assert_eq!(run(&backend, "ttl a"), RespFrame::Integer(10));
+        assert_eq!(run(&backend, "pttl b"), RespFrame::Integer(1500));
+        assert_eq!(run(&backend, "ttl b"), RespFrame::Integer(2));
+
+        clock.advance(Duration::from_millis(1500));
+        assert_eq!(run(&backend, "get b"), RespNullBulkString.into());
+        assert_eq!(run(&backend, "pttl a"), RespFrame::Integer(8500));
+        clock.advance(Duration::from_millis(8500));
+        assert_eq!(run(&backend, "exists a b"), RespFrame::Integer(0));
+        assert_eq!(run(&backend, "ttl a"), RespFrame::Integer(-2));
+    }
+
+    #[test]
+    fn expire_commands_should_work() {
+        let (clock, backend) = setup();
+        assert_eq!(run(&backend, "expire k 10"), RespFrame::Integer(0));
+        run(&backend, "rpush k a");
+        assert_eq!(run(&backend, "ttl k"), RespFrame::Integer(-1));
+
+        assert_eq!(run(&backend, "expire k 10"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "pttl k"), RespFrame::Integer(10_000));
+        assert_eq!(run(&backend, "pexpire k 2500"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "pttl k"), RespFrame::Integer(2500));
+
+        let at = NOW_MS / 1000 + 100;
+        assert_eq!(
+            run(&backend, &format!("expireat k {}", at)),
+            RespFrame::Integer(1)
+        );
+        assert_eq!(run(&backend, "ttl k"), RespFrame::Integer(100));
+        assert_eq!(
+            run(&backend, &format!("pexpireat k {}", NOW_MS + 500)),
+            RespFrame::Integer(1)
+        );
+        // 集合类型的修改不影响过期时间
+        run(&backend, "rpush k b");
+        assert_eq!(run(&backend, "pttl k"), RespFrame::Integer(500));
+
+        assert_eq!(run(&backend, "persist k"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "persist k"), RespFrame::Integer(0));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(run(&backend, "ttl k"), RespFrame::Integer(-1));
+
+        // 时间已经过去，key 被立即删除
+        assert_eq!(run(&backend, "expire k -1"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "exists k"), RespFrame::Integer(0));
+    }
+
+    #[test]
+    fn invalid_expire_time_should_fail() {
+        let (_, backend) = setup();
+        run(&backend, "set k v");
+        assert_eq!(
+            run(&backend, &format!("expire k {}", i64::MAX)),
+            CommandError::InvalidExpireTime("expire").into()
+        );
+        assert_eq!(
+            run(&backend, "pexpire k abc"),
+            CommandError::NotInteger.into()
+        );
+        assert_eq!(run(&backend, "ttl k"), RespFrame::Integer(-1));
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::BTreeMap;
+
+use super::{check_arity, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{
+    backend::{Backend, HashValue},
+    protocol::Protocol,
+    RespFrame, RespMap, RespNullBulkString,
+};
+
+/// HSET key field value [field value ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HSet {
+    key: String,
+    pairs: Vec<(String, Vec<u8>)>,
+}
+
+/// HGET key field
+#[derive(Debug, Clone, PartialEq)]
+pub struct HGet {
+    key: String,
+    field: String,
+}
+
+/// HGETALL key
+#[derive(Debug, Clone, PartialEq)]
+pub struct HGetAll {
+    key: String,
+}
+
+/// HDEL key field [field ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct HDel {
+    key: String,
+    fields: Vec<String>,
+}
+
+impl HSet {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("hset", &args, 3, None)?;
+        if args.len().is_multiple_of(2) {
+            return Err(CommandError::WrongArity("hset"));
+        }
+        let mut args = args.into_iter();
+        let key = args.next().map(to_key).unwrap_or_default();
+        let mut pairs = vec![];
+        while let (Some(field), Some(value)) = (args.next(), args.next()) {
+            pairs.push((to_key(field), value));
+        }
+        Ok(Self { key, pairs })
+    }
+}
+
+impl HGet {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("hget", &args, 2, Some(2))?;
+        let mut args = args.into_iter().map(to_key);
+        Ok(Self {
+            key: args.next().unwrap_or_default(),
+            field: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl HGetAll {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("hgetall", &args, 1, Some(1))?;
+        let key = args.into_iter().map(to_key).next().unwrap_or_default();
+        Ok(Self { key })
+    }
+}
+
+impl HDel {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("hdel", &args, 2, None)?;
+        let mut args = args.into_iter().map(to_key);
+        Ok(Self {
+            key: args.next().unwrap_or_default(),
+            fields: args.collect(),
+        })
+    }
+}
+
+impl CommandExecutor for HSet {
+    /// 返回新增的 field 个数，已经存在的 field 只更新值
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .write(&self.key, |hash: &mut HashValue| {
+                self.pairs
+                    .into_iter()
+                    .map(|(field, value)| hash.insert(field, value))
+                    .filter(Option::is_none)
+                    .count() as i64
+            })
+            .into()
+    }
+}
+
+impl CommandExecutor for HGet {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let value = backend.read(&self.key, |hash: &HashValue| hash.get(&self.field).cloned());
+        match value {
+            Ok(Some(Some(value))) => value.into(),
+            Ok(_) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for HGetAll {
+    /// RESP3 返回 map，RESP2 返回 field、value 交替的 array
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .read(&self.key, |hash: &HashValue| {
+                hash.iter()
+                    .map(|(field, value)| (field.clone(), value.clone().into()))
+                    .collect::<BTreeMap<String, RespFrame>>()
+            })
+            .map(|map| RespMap::new(map.unwrap_or_default()))
+            .into()
+    }
+}
+
+impl CommandExecutor for HDel {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .write(&self.key, |hash: &mut HashValue| {
+                self.fields
+                    .iter()
+                    .filter(|field| hash.remove(*field).is_some())
+                    .count() as i64
+            })
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{run, run_with};
+    use super::*;
+    use crate::redis_core::RespArray;
+
+    #[test]
+    fn hset_hget_hdel_should_work() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "hset h f1 v1 f2 v2"), RespFrame::Integer(2));
+        assert_eq!(run(&backend, "hset h f1 v3 f3 v3"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "hget h f1"), RespFrame::from("v3"));
+        assert_eq!(run(&backend, "hget h f4"), RespNullBulkString.into());
+        assert_eq!(run(&backend, "hget nokey f1"), RespNullBulkString.into());
+
+        assert_eq!(run(&backend, "hdel h f1 f4"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "hdel h f2 f3"), RespFrame::Integer(2));
+        // 最后一个 field 删除后 key 也不存在了
+        assert_eq!(run(&backend, "exists h"), RespFrame::Integer(0));
+        assert_eq!(
+            run(&backend, "hset h f1"),
+            CommandError::WrongArity("hset").into()
+        );
+    }
+
+    #[test]
+    fn hgetall_should_reply_map_for_resp3() {
+        let backend = Backend::new();
+        run(&backend, "hset h b 2 a 1");
+        assert_eq!(
+            run(&backend, "hgetall h"),
+            RespArray::new(["a".into(), "1".into(), "b".into(), "2".into()]).into()
+        );
+        let map = BTreeMap::from([("a".into(), "1".into()), ("b".into(), "2".into())]);
+        assert_eq!(
+            run_with(&backend, Protocol::Resp3, "hgetall h"),
+            RespMap::new(map).into()
+        );
+        assert_eq!(
+            run_with(&backend, Protocol::Resp3, "hgetall nokey"),
+            RespMap::new(BTreeMap::new()).into()
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use super::{check_arity, to_i64, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{backend::Backend, protocol::Protocol, RespArray, RespFrame, SimpleString};
+
+/// SCAN 不指定 COUNT 时每次返回的个数
+const DEFAULT_SCAN_COUNT: usize = 10;
+
+/// DBSIZE
+#[derive(Debug, Clone, PartialEq)]
+pub struct DbSize;
+
+/// FLUSHDB / FLUSHALL [ASYNC | SYNC]，只有一个 db，两个命令是一样的
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlushDb;
+
+/// KEYS pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keys {
+    pattern: String,
+}
+
+/// SCAN cursor [MATCH pattern] [COUNT count] [TYPE type]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    cursor: u64,
+    pattern: Option<String>,
+    count: usize,
+    type_name: Option<String>,
+}
+
+impl DbSize {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("dbsize", &args, 0, Some(0))?;
+        Ok(Self)
+    }
+}
+
+impl FlushDb {
+    pub(super) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity(name, &args, 0, Some(1))?;
+        // 删除总是同步完成的，ASYNC 和 SYNC 没有区别
+        match args.first() {
+            Some(arg)
+                if !arg.eq_ignore_ascii_case(b"async") && !arg.eq_ignore_ascii_case(b"sync") =>
+            {
+                Err(CommandError::SyntaxError)
+            }
+            _ => Ok(Self),
+        }
+    }
+}
+
+impl Keys {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("keys", &args, 1, Some(1))?;
+        Ok(Self {
+            pattern: to_key(args.into_iter().next().unwrap_or_default()),
+        })
+    }
+}
+
+impl Scan {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("scan", &args, 1, None)?;
+        let mut args = args.into_iter();
+        let cursor = args.next().unwrap_or_default();
+        let cursor = std::str::from_utf8(&cursor)
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .ok_or(CommandError::InvalidCursor)?;
+        let mut scan = Self {
+            cursor,
+            pattern: None,
+            count: DEFAULT_SCAN_COUNT,
+            type_name: None,
+        };
+        while let Some(option) = args.next() {
+            let value = args.next().ok_or(CommandError::SyntaxError)?;
+            match option.to_ascii_lowercase().as_slice() {
+                b"match" => scan.pattern = Some(to_key(value)),
+                b"count" => {
+                    scan.count = match to_i64(&value)? {
+                        n if n >= 1 => n as usize,
+                        _ => return Err(CommandError::SyntaxError),
+                    }
+                }
+                b"type" => scan.type_name = Some(to_key(value)),
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(scan)
+    }
+}
+
+impl CommandExecutor for DbSize {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        (backend.dbsize() as i64).into()
+    }
+}
+
+impl CommandExecutor for FlushDb {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend.flush();
+        SimpleString::new("OK").into()
+    }
+}
+
+impl CommandExecutor for Keys {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let keys: Vec<RespFrame> = backend
+            .keys(&self.pattern)
+            .into_iter()
+            .map(|key| key.into_bytes().into())
+            .collect();
+        RespArray::new(keys).into()
+    }
+}
+
+/// 回复 [下一个游标, [key ...]]，游标是十进制的字符串
+impl CommandExecutor for Scan {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let (next, keys) = backend.scan(
+            self.cursor,
+            self.count,
+            self.pattern.as_deref(),
+            self.type_name.as_deref(),
+        );
+        let keys: Vec<RespFrame> = keys
+            .into_iter()
+            .map(|key| key.into_bytes().into())
+            .collect();
+        RespArray::new([
+            next.to_string().as_str().into(),
+            RespArray::new(keys).into(),
+        ])
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::run;
+    use super::*;
+
+    #[test]
+    fn keyspace_commands_should_work() {
+        let backend = Backend::new();
+        run(&backend, "mset a 1 b 2 c 3");
+        run(&backend, "hset h f v");
+        assert_eq!(run(&backend, "dbsize"), RespFrame::Integer(4));
+        assert_eq!(
+            run(&backend, "keys h*"),
+            RespArray::new(["h".into()]).into()
+        );
+        assert_eq!(
+            run(&backend, "scan 0 type hash count 100"),
+            RespArray::new(["0".into(), RespArray::new(["h".into()]).into()]).into()
+        );
+        assert_eq!(
+            run(&backend, "scan abc"),
+            CommandError::InvalidCursor.into()
+        );
+        assert_eq!(
+            run(&backend, "scan 0 count 0"),
+            CommandError::SyntaxError.into()
+        );
+        assert_eq!(
+            run(&backend, "flushall async"),
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(run(&backend, "dbsize"), RespFrame::Integer(0));
+        assert_eq!(
+            run(&backend, "flushdb now"),
+            CommandError::SyntaxError.into()
+        );
+    }
+}
@@ -0,0 +1,193 @@
+use super::{check_arity, normalize_range, to_i64, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{
+    backend::{Backend, ListValue},
+    protocol::Protocol,
+    RespArray, RespFrame, RespNullArray, RespNullBulkString,
+};
+
+/// LPUSH key element [element ...] / RPUSH key element [element ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Push {
+    key: String,
+    elements: Vec<Vec<u8>>,
+    left: bool,
+}
+
+/// LPOP key [count] / RPOP key [count]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pop {
+    key: String,
+    count: Option<usize>,
+    left: bool,
+}
+
+/// LRANGE key start stop
+#[derive(Debug, Clone, PartialEq)]
+pub struct LRange {
+    key: String,
+    start: i64,
+    stop: i64,
+}
+
+impl Push {
+    pub(super) fn parse(
+        name: &'static str,
+        left: bool,
+        args: Vec<Vec<u8>>,
+    ) -> Result<Self, CommandError> {
+        check_arity(name, &args, 2, None)?;
+        let mut args = args.into_iter();
+        Ok(Self {
+            key: args.next().map(to_key).unwrap_or_default(),
+            elements: args.collect(),
+            left,
+        })
+    }
+}
+
+impl Pop {
+    pub(super) fn parse(
+        name: &'static str,
+        left: bool,
+        args: Vec<Vec<u8>>,
+    ) -> Result<Self, CommandError> {
+        check_arity(name, &args, 1, Some(2))?;
+        let mut args = args.into_iter();
+        let key = args.next().map(to_key).unwrap_or_default();
+        let count = match args.next() {
+            Some(count) => {
+                let count = to_i64(&count).map_err(|_| CommandError::NotPositive)?;
+                Some(usize::try_from(count).map_err(|_| CommandError::NotPositive)?)
+            }
+            None => None,
+        };
+        Ok(Self { key, count, left })
+    }
+}
+
+impl LRange {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("lrange", &args, 3, Some(3))?;
+        Ok(Self {
+            key: to_key(args[0].clone()),
+            start: to_i64(&args[1])?,
+            stop: to_i64(&args[2])?,
+        })
+    }
+}
+
+impl CommandExecutor for Push {
+    /// 逐个插入到头部或者尾部，返回插入后的长度
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .write(&self.key, |list: &mut ListValue| {
+                for element in self.elements {
+                    match self.left {
+                        true => list.push_front(element),
+                        false => list.push_back(element),
+                    }
+                }
+                list.len() as i64
+            })
+            .into()
+    }
+}
+
+impl CommandExecutor for Pop {
+    /// 不带 count 时返回一个元素，带 count 时返回 array；key 不存在时分别返回 null 和 null array
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let left = self.left;
+        let count = self.count.unwrap_or(1);
+        let popped = backend.write(&self.key, |list: &mut ListValue| {
+            // 存在的 list 不会是空的
+            let exists = !list.is_empty();
+            let n = count.min(list.len());
+            let popped: Vec<_> = match left {
+                true => list.drain(..n).collect(),
+                false => list.drain(list.len() - n..).rev().collect(),
+            };
+            (exists, popped)
+        });
+        match (popped, self.count) {
+            (Err(e), _) => e.into(),
+            (Ok((_, popped)), None) => match popped.into_iter().next() {
+                Some(element) => element.into(),
+                None => RespNullBulkString.into(),
+            },
+            (Ok((false, _)), Some(_)) => RespNullArray.into(),
+            (Ok((true, popped)), Some(_)) => {
+                RespArray::new(popped.into_iter().map(RespFrame::from).collect::<Vec<_>>()).into()
+            }
+        }
+    }
+}
+
+impl CommandExecutor for LRange {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .read(&self.key, |list: &ListValue| {
+                match normalize_range(self.start, self.stop, list.len()) {
+                    Some(range) => list
+                        .range(range)
+                        .map(|element| element.clone().into())
+                        .collect(),
+                    None => vec![],
+                }
+            })
+            .map(|elements| RespArray::new(elements.unwrap_or_default()))
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{run, run_with};
+    use super::*;
+    use crate::redis_core::RespNull;
+
+    fn array(elements: &[&str]) -> RespFrame {
+        RespArray::new(
+            elements
+                .iter()
+                .map(|e| RespFrame::from(*e))
+                .collect::<Vec<_>>(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn push_and_lrange_should_work() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "rpush l b c"), RespFrame::Integer(2));
+        assert_eq!(run(&backend, "lpush l a z"), RespFrame::Integer(4));
+        assert_eq!(run(&backend, "lrange l 0 -1"), array(&["z", "a", "b", "c"]));
+        assert_eq!(run(&backend, "lrange l 1 2"), array(&["a", "b"]));
+        assert_eq!(run(&backend, "lrange l -2 100"), array(&["b", "c"]));
+        assert_eq!(run(&backend, "lrange l 3 1"), array(&[]));
+        assert_eq!(run(&backend, "lrange nokey 0 -1"), array(&[]));
+        assert_eq!(
+            run(&backend, "lrange l a 1"),
+            CommandError::NotInteger.into()
+        );
+    }
+
+    #[test]
+    fn pop_should_work() {
+        let backend = Backend::new();
+        run(&backend, "rpush l a b c d e");
+        assert_eq!(run(&backend, "lpop l"), RespFrame::from("a"));
+        assert_eq!(run(&backend, "rpop l"), RespFrame::from("e"));
+        assert_eq!(run(&backend, "rpop l 2"), array(&["d", "c"]));
+        assert_eq!(run(&backend, "lpop l 0"), array(&[]));
+        assert_eq!(run(&backend, "lpop l 5"), array(&["b"]));
+        assert_eq!(run(&backend, "exists l"), RespFrame::Integer(0));
+
+        assert_eq!(run(&backend, "lpop l"), RespNullBulkString.into());
+        assert_eq!(run(&backend, "lpop l 1"), RespNullArray.into());
+        assert_eq!(
+            run_with(&backend, Protocol::Resp3, "lpop l 1"),
+            RespNull.into()
+        );
+        assert_eq!(run(&backend, "lpop l -1"), CommandError::NotPositive.into());
+    }
+}
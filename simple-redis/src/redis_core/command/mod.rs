@@ -0,0 +1,486 @@
+mod client;
+mod cluster;
+mod connection;
+mod expire;
+mod hash;
+mod keyspace;
+mod list;
+mod pubsub;
+mod replication;
+mod server;
+mod set;
+mod string;
+mod transaction;
+mod zset;
+
+pub use client::*;
+pub use cluster::*;
+pub use connection::*;
+pub use expire::*;
+pub use hash::*;
+pub use keyspace::*;
+pub use list::*;
+pub use pubsub::*;
+pub use replication::*;
+pub use server::*;
+pub use set::*;
+pub use string::*;
+pub use transaction::*;
+pub use zset::*;
+
+use std::ops::RangeInclusive;
+
+use thiserror::Error;
+
+use super::{backend::Backend, protocol::Protocol, RespFrame, SimpleError};
+
+/// 命令执行失败时返回给客户端的错误，Display 就是 RESP 错误信息
+#[derive(Debug, Error, PartialEq)]
+pub enum CommandError {
+    #[error("ERR Protocol error: {0}")]
+    InvalidFrame(String),
+    #[error("ERR unknown command '{0}', with args beginning with: {1}")]
+    UnknownCommand(String, String),
+    #[error("ERR wrong number of arguments for '{0}' command")]
+    WrongArity(&'static str),
+    #[error("ERR syntax error")]
+    SyntaxError,
+    #[error("ERR value is not an integer or out of range")]
+    NotInteger,
+    #[error("ERR increment or decrement would overflow")]
+    Overflow,
+    #[error("ERR invalid expire time in '{0}' command")]
+    InvalidExpireTime(&'static str),
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongType,
+    #[error("ERR value is not a valid float")]
+    NotFloat,
+    #[error("ERR min or max is not a float")]
+    MinMaxNotFloat,
+    #[error("ERR value is out of range, must be positive")]
+    NotPositive,
+    #[error("ERR XX and NX options at the same time are not compatible")]
+    NxXxConflict,
+    #[error("ERR Protocol version is not an integer or out of range")]
+    InvalidProtocolVersion,
+    #[error("NOPROTO unsupported protocol version")]
+    NoProto,
+    #[error("ERR Can't execute '{0}': only (P|S)SUBSCRIBE / (P|S)UNSUBSCRIBE / PING / QUIT / RESET are allowed in this context")]
+    SubscribedContext(String),
+    #[error("ERR {0}")]
+    Persistence(String),
+    #[error("ERR MULTI calls can not be nested")]
+    NestedMulti,
+    #[error("ERR {0} without MULTI")]
+    WithoutMulti(&'static str),
+    #[error("ERR WATCH inside MULTI is not allowed")]
+    WatchInMulti,
+    #[error("ERR Command not allowed inside a transaction")]
+    NotAllowedInMulti,
+    #[error("EXECABORT Transaction discarded because of previous errors.")]
+    ExecAbort,
+    #[error("READONLY You can't write against a read only replica.")]
+    ReadOnly,
+    #[error("ERR unknown subcommand '{0}'. Try {1} HELP.")]
+    UnknownSubcommand(String, &'static str),
+    #[error("ERR CONFIG SET failed - {0}")]
+    Config(String),
+    #[error("ERR invalid cursor")]
+    InvalidCursor,
+    #[error("ERR Client names cannot contain spaces, newlines or special characters.")]
+    InvalidClientName,
+    #[error("ERR No such client")]
+    NoSuchClient,
+    #[error("ERR count should be greater than or equal to -1")]
+    InvalidSlowLogCount,
+    #[error("OOM command not allowed when used memory > 'maxmemory'.")]
+    OutOfMemory,
+    #[error("MOVED {0} {1}")]
+    Moved(u16, String),
+    #[error("ASK {0} {1}")]
+    Ask(u16, String),
+    #[error("CROSSSLOT Keys in request don't hash to the same slot")]
+    CrossSlot,
+    #[error("TRYAGAIN Multiple keys request during rehashing of slot")]
+    TryAgain,
+    #[error("CLUSTERDOWN Hash slot not served")]
+    ClusterDown,
+    #[error("ERR This instance has cluster support disabled")]
+    ClusterDisabled,
+}
+
+impl From<CommandError> for RespFrame {
+    fn from(e: CommandError) -> Self {
+        SimpleError::new(e.to_string()).into()
+    }
+}
+
+/// 执行结果是正常的回复或者错误信息
+impl<T: Into<RespFrame>> From<Result<T, CommandError>> for RespFrame {
+    fn from(result: Result<T, CommandError>) -> Self {
+        match result {
+            Ok(v) => v.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+/// 命令按 RESP3 的类型构造回复，由连接转换成协商好的协议；
+/// 只有少数回复的结构和协议有关（比如 ZRANGE WITHSCORES），所以把协议也传进来
+pub trait CommandExecutor {
+    fn execute(self, backend: &Backend, protocol: Protocol) -> RespFrame;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Ping(Ping),
+    Echo(Echo),
+    Get(Get),
+    Set(Set),
+    Del(Del),
+    Exists(Exists),
+    Incr(Incr),
+    MGet(MGet),
+    MSet(MSet),
+    Hello(Hello),
+    HSet(HSet),
+    HGet(HGet),
+    HGetAll(HGetAll),
+    HDel(HDel),
+    Push(Push),
+    Pop(Pop),
+    LRange(LRange),
+    SAdd(SAdd),
+    SRem(SRem),
+    SMembers(SMembers),
+    SIsMember(SIsMember),
+    ZAdd(ZAdd),
+    ZRange(ZRange),
+    ZRangeByScore(ZRangeByScore),
+    ZRem(ZRem),
+    Expire(Expire),
+    Ttl(Ttl),
+    Persist(Persist),
+    Subscribe(Subscribe),
+    Unsubscribe(Unsubscribe),
+    Publish(Publish),
+    Save(Save),
+    BgSave(BgSave),
+    Transaction(Transaction),
+    Watch(Watch),
+    Info(Info),
+    ReplicaOf(ReplicaOf),
+    ReplConf(ReplConf),
+    PSync(PSync),
+    Config(Config),
+    SlowLog(SlowLog),
+    Client(Client),
+    DbSize(DbSize),
+    FlushDb(FlushDb),
+    Keys(Keys),
+    Scan(Scan),
+    Cluster(Cluster),
+    Asking(Asking),
+}
+
+impl CommandExecutor for Command {
+    fn execute(self, backend: &Backend, protocol: Protocol) -> RespFrame {
+        match self {
+            Command::Ping(cmd) => cmd.execute(backend, protocol),
+            Command::Echo(cmd) => cmd.execute(backend, protocol),
+            Command::Get(cmd) => cmd.execute(backend, protocol),
+            Command::Set(cmd) => cmd.execute(backend, protocol),
+            Command::Del(cmd) => cmd.execute(backend, protocol),
+            Command::Exists(cmd) => cmd.execute(backend, protocol),
+            Command::Incr(cmd) => cmd.execute(backend, protocol),
+            Command::MGet(cmd) => cmd.execute(backend, protocol),
+            Command::MSet(cmd) => cmd.execute(backend, protocol),
+            // 这些命令会修改连接的状态，由 Session 处理，走到这里说明代码逻辑有问题
+            Command::Hello(_)
+            | Command::Subscribe(_)
+            | Command::Unsubscribe(_)
+            | Command::Transaction(_)
+            | Command::Watch(_)
+            | Command::ReplConf(_)
+            | Command::PSync(_)
+            | Command::Client(_)
+            | Command::Asking(_) => unreachable!(),
+            Command::HSet(cmd) => cmd.execute(backend, protocol),
+            Command::HGet(cmd) => cmd.execute(backend, protocol),
+            Command::HGetAll(cmd) => cmd.execute(backend, protocol),
+            Command::HDel(cmd) => cmd.execute(backend, protocol),
+            Command::Push(cmd) => cmd.execute(backend, protocol),
+            Command::Pop(cmd) => cmd.execute(backend, protocol),
+            Command::LRange(cmd) => cmd.execute(backend, protocol),
+            Command::SAdd(cmd) => cmd.execute(backend, protocol),
+            Command::SRem(cmd) => cmd.execute(backend, protocol),
+            Command::SMembers(cmd) => cmd.execute(backend, protocol),
+            Command::SIsMember(cmd) => cmd.execute(backend, protocol),
+            Command::ZAdd(cmd) => cmd.execute(backend, protocol),
+            Command::ZRange(cmd) => cmd.execute(backend, protocol),
+            Command::ZRangeByScore(cmd) => cmd.execute(backend, protocol),
+            Command::ZRem(cmd) => cmd.execute(backend, protocol),
+            Command::Expire(cmd) => cmd.execute(backend, protocol),
+            Command::Ttl(cmd) => cmd.execute(backend, protocol),
+            Command::Persist(cmd) => cmd.execute(backend, protocol),
+            Command::Publish(cmd) => cmd.execute(backend, protocol),
+            Command::Save(cmd) => cmd.execute(backend, protocol),
+            Command::BgSave(cmd) => cmd.execute(backend, protocol),
+            Command::Info(cmd) => cmd.execute(backend, protocol),
+            Command::ReplicaOf(cmd) => cmd.execute(backend, protocol),
+            Command::Config(cmd) => cmd.execute(backend, protocol),
+            Command::SlowLog(cmd) => cmd.execute(backend, protocol),
+            Command::DbSize(cmd) => cmd.execute(backend, protocol),
+            Command::FlushDb(cmd) => cmd.execute(backend, protocol),
+            Command::Keys(cmd) => cmd.execute(backend, protocol),
+            Command::Scan(cmd) => cmd.execute(backend, protocol),
+            Command::Cluster(cmd) => cmd.execute(backend, protocol),
+        }
+    }
+}
+
+/// 客户端发来的命令都是 bulk string 组成的数组，第一个元素是命令名，不区分大小写
+impl TryFrom<RespFrame> for Command {
+    type Error = CommandError;
+
+    fn try_from(frame: RespFrame) -> Result<Self, Self::Error> {
+        let RespFrame::Array(array) = frame else {
+            return Err(CommandError::InvalidFrame("expect an array".into()));
+        };
+        let mut args = array
+            .0
+            .into_iter()
+            .map(|frame| match frame {
+                RespFrame::BulkString(s) => Ok(s.0),
+                _ => Err(CommandError::InvalidFrame("expect bulk strings".into())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if args.is_empty() {
+            return Err(CommandError::InvalidFrame("empty command".into()));
+        }
+        let name = String::from_utf8_lossy(&args.remove(0)).to_lowercase();
+
+        match name.as_str() {
+            "ping" => Ping::parse(args).map(Command::Ping),
+            "echo" => Echo::parse(args).map(Command::Echo),
+            "get" => Get::parse(args).map(Command::Get),
+            "set" => Set::parse(args).map(Command::Set),
+            "del" => Del::parse(args).map(Command::Del),
+            "exists" => Exists::parse(args).map(Command::Exists),
+            "incr" => Incr::parse("incr", 1, args).map(Command::Incr),
+            "decr" => Incr::parse("decr", -1, args).map(Command::Incr),
+            "mget" => MGet::parse(args).map(Command::MGet),
+            "mset" => MSet::parse(args).map(Command::MSet),
+            "hello" => Hello::parse(args).map(Command::Hello),
+            "hset" => HSet::parse(args).map(Command::HSet),
+            "hget" => HGet::parse(args).map(Command::HGet),
+            "hgetall" => HGetAll::parse(args).map(Command::HGetAll),
+            "hdel" => HDel::parse(args).map(Command::HDel),
+            "lpush" => Push::parse("lpush", true, args).map(Command::Push),
+            "rpush" => Push::parse("rpush", false, args).map(Command::Push),
+            "lpop" => Pop::parse("lpop", true, args).map(Command::Pop),
+            "rpop" => Pop::parse("rpop", false, args).map(Command::Pop),
+            "lrange" => LRange::parse(args).map(Command::LRange),
+            "sadd" => SAdd::parse(args).map(Command::SAdd),
+            "srem" => SRem::parse(args).map(Command::SRem),
+            "smembers" => SMembers::parse(args).map(Command::SMembers),
+            "sismember" => SIsMember::parse(args).map(Command::SIsMember),
+            "zadd" => ZAdd::parse(args).map(Command::ZAdd),
+            "zrange" => ZRange::parse(args).map(Command::ZRange),
+            "zrangebyscore" => ZRangeByScore::parse(args).map(Command::ZRangeByScore),
+            "zrem" => ZRem::parse(args).map(Command::ZRem),
+            "expire" => Expire::parse("expire", 1000, false, args).map(Command::Expire),
+            "pexpire" => Expire::parse("pexpire", 1, false, args).map(Command::Expire),
+            "expireat" => Expire::parse("expireat", 1000, true, args).map(Command::Expire),
+            "pexpireat" => Expire::parse("pexpireat", 1, true, args).map(Command::Expire),
+            "ttl" => Ttl::parse("ttl", 1000, args).map(Command::Ttl),
+            "pttl" => Ttl::parse("pttl", 1, args).map(Command::Ttl),
+            "persist" => Persist::parse(args).map(Command::Persist),
+            "subscribe" => Subscribe::parse("subscribe", false, args).map(Command::Subscribe),
+            "psubscribe" => Subscribe::parse("psubscribe", true, args).map(Command::Subscribe),
+            "unsubscribe" => Unsubscribe::parse(false, args).map(Command::Unsubscribe),
+            "punsubscribe" => Unsubscribe::parse(true, args).map(Command::Unsubscribe),
+            "publish" => Publish::parse(args).map(Command::Publish),
+            "save" => Save::parse(args).map(Command::Save),
+            "bgsave" => {
+                BgSave::parse("bgsave", "Background saving started", args).map(Command::BgSave)
+            }
+            "bgrewriteaof" => BgSave::parse(
+                "bgrewriteaof",
+                "Background append only file rewriting started",
+                args,
+            )
+            .map(Command::BgSave),
+            "multi" => {
+                Transaction::parse("multi", Transaction::Multi, args).map(Command::Transaction)
+            }
+            "exec" => Transaction::parse("exec", Transaction::Exec, args).map(Command::Transaction),
+            "discard" => {
+                Transaction::parse("discard", Transaction::Discard, args).map(Command::Transaction)
+            }
+            "unwatch" => {
+                Transaction::parse("unwatch", Transaction::Unwatch, args).map(Command::Transaction)
+            }
+            "watch" => Watch::parse(args).map(Command::Watch),
+            "info" => Info::parse(args).map(Command::Info),
+            "replicaof" => ReplicaOf::parse("replicaof", args).map(Command::ReplicaOf),
+            "slaveof" => ReplicaOf::parse("slaveof", args).map(Command::ReplicaOf),
+            "replconf" => ReplConf::parse(args).map(Command::ReplConf),
+            "psync" => PSync::parse(args).map(Command::PSync),
+            "config" => Config::parse(args).map(Command::Config),
+            "slowlog" => SlowLog::parse(args).map(Command::SlowLog),
+            "client" => Client::parse(args).map(Command::Client),
+            "dbsize" => DbSize::parse(args).map(Command::DbSize),
+            "flushdb" => FlushDb::parse("flushdb", args).map(Command::FlushDb),
+            "flushall" => FlushDb::parse("flushall", args).map(Command::FlushDb),
+            "keys" => Keys::parse(args).map(Command::Keys),
+            "scan" => Scan::parse(args).map(Command::Scan),
+            "cluster" => Cluster::parse(args).map(Command::Cluster),
+            "asking" => Asking::parse(args).map(Command::Asking),
+            _ => {
+                let args = args
+                    .iter()
+                    .map(|arg| format!("'{}' ", String::from_utf8_lossy(arg)))
+                    .collect();
+                Err(CommandError::UnknownCommand(name, args))
+            }
+        }
+    }
+}
+
+/// 检查参数个数，`max` 为 None 表示不限
+fn check_arity(
+    name: &'static str,
+    args: &[Vec<u8>],
+    min: usize,
+    max: Option<usize>,
+) -> Result<(), CommandError> {
+    if args.len() < min || max.is_some_and(|max| args.len() > max) {
+        return Err(CommandError::WrongArity(name));
+    }
+    Ok(())
+}
+
+fn to_key(arg: Vec<u8>) -> String {
+    String::from_utf8(arg).unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into())
+}
+
+fn to_i64(arg: &[u8]) -> Result<i64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(CommandError::NotInteger)
+}
+
+/// 可以是 inf、+inf、-inf，但不能是 nan
+fn to_f64(arg: &[u8]) -> Result<f64, CommandError> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .filter(|f| !f.is_nan())
+        .ok_or(CommandError::NotFloat)
+}
+
+/// 把 LRANGE/ZRANGE 的 start、stop 转换成下标，负数表示从末尾开始数，超出范围的部分被截掉
+fn normalize_range(start: i64, stop: i64, len: usize) -> Option<RangeInclusive<usize>> {
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let stop = if stop < 0 {
+        len + stop
+    } else {
+        stop.min(len - 1)
+    };
+    if start > stop || start >= len {
+        return None;
+    }
+    Some(start as usize..=stop as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis_core::{RespArray, RespDecode, RespEncode, SimpleString};
+    use bytes::BytesMut;
+
+    /// 按 redis-cli 的方式把命令编码成 RESP，再解析、执行
+    pub(super) fn run(backend: &Backend, cmd: &str) -> RespFrame {
+        run_with(backend, Protocol::Resp2, cmd)
+    }
+
+    /// 用指定的协议执行，回复也转换成这个协议
+    pub(super) fn run_with(backend: &Backend, protocol: Protocol, cmd: &str) -> RespFrame {
+        let args: Vec<RespFrame> = cmd.split_whitespace().map(|arg| arg.into()).collect();
+        let mut buf = BytesMut::from(&RespFrame::Array(RespArray::new(args)).encode()[..]);
+        let frame = RespFrame::decode(&mut buf).unwrap();
+        let resp: RespFrame = match Command::try_from(frame) {
+            Ok(cmd) => cmd.execute(backend, protocol),
+            Err(e) => e.into(),
+        };
+        resp.into_protocol(protocol)
+    }
+
+    #[test]
+    fn command_name_should_be_case_insensitive() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "PiNg"), SimpleString::new("PONG").into());
+    }
+
+    #[test]
+    fn invalid_command_should_fail() {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, "foo bar"),
+            SimpleError::new("ERR unknown command 'foo', with args beginning with: 'bar' ").into()
+        );
+        assert_eq!(run(&backend, "get"), CommandError::WrongArity("get").into());
+        assert_eq!(
+            run(&backend, "zadd z 1 a 2"),
+            CommandError::SyntaxError.into()
+        );
+        assert_eq!(
+            Command::try_from(RespFrame::Integer(1)),
+            Err(CommandError::InvalidFrame("expect an array".into()))
+        );
+    }
+
+    #[test]
+    fn normalize_range_should_work() {
+        assert_eq!(normalize_range(0, -1, 3), Some(0..=2));
+        assert_eq!(normalize_range(-100, 100, 3), Some(0..=2));
+        assert_eq!(normalize_range(1, -2, 3), Some(1..=1));
+        assert_eq!(normalize_range(2, 1, 3), None);
+        assert_eq!(normalize_range(3, 5, 3), None);
+        assert_eq!(normalize_range(0, -1, 0), None);
+    }
+
+    #[test]
+    fn wrong_type_should_be_an_error_reply() {
+        let backend = Backend::new();
+        run(&backend, "set s v");
+        run(&backend, "lpush l a");
+        let wrong_type = RespFrame::from(CommandError::WrongType);
+        assert_eq!(
+            wrong_type,
+            SimpleError::new("WRONGTYPE Operation against a key holding the wrong kind of value")
+                .into()
+        );
+        for cmd in [
+            "get l",
+            "incr l",
+            "hset s f v",
+            "hget l f",
+            "lpush s a",
+            "lrange s 0 -1",
+            "sadd l a",
+            "smembers s",
+            "zadd s 1 a",
+            "zrange l 0 -1",
+        ] {
+            assert_eq!(run(&backend, cmd), wrong_type, "{}", cmd);
+        }
+    }
+}
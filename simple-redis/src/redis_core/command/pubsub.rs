@@ -0,0 +1,86 @@
+use super::{check_arity, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{backend::Backend, protocol::Protocol, RespFrame};
+
+/// SUBSCRIBE channel [channel ...] / PSUBSCRIBE pattern [pattern ...]
+///
+/// 会修改连接的订阅状态，由 Session 执行
+#[derive(Debug, Clone, PartialEq)]
+pub struct Subscribe {
+    pub(crate) channels: Vec<String>,
+    pub(crate) pattern: bool,
+}
+
+/// UNSUBSCRIBE [channel ...] / PUNSUBSCRIBE [pattern ...]，不带参数时取消所有订阅
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unsubscribe {
+    pub(crate) channels: Vec<String>,
+    pub(crate) pattern: bool,
+}
+
+/// PUBLISH channel message
+#[derive(Debug, Clone, PartialEq)]
+pub struct Publish {
+    channel: String,
+    message: Vec<u8>,
+}
+
+impl Subscribe {
+    pub(super) fn parse(
+        name: &'static str,
+        pattern: bool,
+        args: Vec<Vec<u8>>,
+    ) -> Result<Self, CommandError> {
+        check_arity(name, &args, 1, None)?;
+        Ok(Self {
+            channels: args.into_iter().map(to_key).collect(),
+            pattern,
+        })
+    }
+}
+
+impl Unsubscribe {
+    pub(super) fn parse(pattern: bool, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        Ok(Self {
+            channels: args.into_iter().map(to_key).collect(),
+            pattern,
+        })
+    }
+}
+
+impl Publish {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("publish", &args, 2, Some(2))?;
+        let mut args = args.into_iter();
+        Ok(Self {
+            channel: args.next().map(to_key).unwrap_or_default(),
+            message: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for Publish {
+    /// 返回收到消息的订阅者个数
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend.pubsub().publish(&self.channel, self.message).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::run;
+    use super::*;
+
+    #[test]
+    fn publish_should_return_receiver_count() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "publish news hi"), RespFrame::Integer(0));
+        let _rx = backend.pubsub().register(1);
+        backend.pubsub().subscribe(1, "news");
+        backend.pubsub().psubscribe(1, "n*");
+        assert_eq!(run(&backend, "publish news hi"), RespFrame::Integer(2));
+        assert_eq!(
+            run(&backend, "publish news"),
+            CommandError::WrongArity("publish").into()
+        );
+    }
+}
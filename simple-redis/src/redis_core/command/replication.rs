@@ -0,0 +1,111 @@
+use super::{check_arity, to_i64, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{
+    backend::Backend, protocol::Protocol, replication, RespFrame, SimpleString,
+};
+
+/// REPLICAOF host port / REPLICAOF NO ONE
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicaOf {
+    master: Option<(String, u16)>,
+}
+
+/// REPLCONF option value [option value ...]
+///
+/// 从节点在 PSYNC 之前告诉主节点自己的信息，由 Session 执行
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplConf {
+    pub(crate) options: Vec<(String, String)>,
+}
+
+/// PSYNC replid offset
+///
+/// 之后连接变成复制的连接，由 Session 执行
+#[derive(Debug, Clone, PartialEq)]
+pub struct PSync {
+    pub(crate) replid: String,
+    pub(crate) offset: i64,
+}
+
+impl ReplicaOf {
+    pub(super) fn parse(name: &'static str, args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity(name, &args, 2, Some(2))?;
+        if args[0].eq_ignore_ascii_case(b"no") && args[1].eq_ignore_ascii_case(b"one") {
+            return Ok(Self { master: None });
+        }
+        let port = u16::try_from(to_i64(&args[1])?).map_err(|_| CommandError::NotInteger)?;
+        let host = to_key(args.into_iter().next().unwrap_or_default());
+        Ok(Self {
+            master: Some((host, port)),
+        })
+    }
+}
+
+impl ReplConf {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        if !args.len().is_multiple_of(2) {
+            return Err(CommandError::SyntaxError);
+        }
+        let options = args
+            .chunks(2)
+            .map(|pair| {
+                (
+                    String::from_utf8_lossy(&pair[0]).to_lowercase(),
+                    String::from_utf8_lossy(&pair[1]).into_owned(),
+                )
+            })
+            .collect();
+        Ok(Self { options })
+    }
+}
+
+impl PSync {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("psync", &args, 2, Some(2))?;
+        Ok(Self {
+            offset: to_i64(&args[1])?,
+            replid: to_key(args.into_iter().next().unwrap_or_default()),
+        })
+    }
+}
+
+impl CommandExecutor for ReplicaOf {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        replication::replicaof(backend, self.master);
+        SimpleString::new("OK").into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replicaof_should_parse() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.as_bytes().to_vec()).collect();
+        assert_eq!(
+            ReplicaOf::parse("replicaof", args(&["NO", "one"])),
+            Ok(ReplicaOf { master: None })
+        );
+        assert_eq!(
+            ReplicaOf::parse("replicaof", args(&["127.0.0.1", "6380"])),
+            Ok(ReplicaOf {
+                master: Some(("127.0.0.1".into(), 6380))
+            })
+        );
+        assert_eq!(
+            ReplicaOf::parse("replicaof", args(&["127.0.0.1", "65536"])),
+            Err(CommandError::NotInteger)
+        );
+        assert_eq!(
+            PSync::parse(args(&["?", "-1"])),
+            Ok(PSync {
+                replid: "?".into(),
+                offset: -1
+            })
+        );
+        assert_eq!(
+            ReplConf::parse(args(&["listening-port"])),
+            Err(CommandError::SyntaxError)
+        );
+    }
+}
@@ -0,0 +1,316 @@
+use std::collections::BTreeMap;
+
+use super::{check_arity, to_i64, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{
+    backend::Backend,
+    cluster,
+    config::{ConfigError, ServerConfig},
+    info,
+    persistence::{self, PersistenceError},
+    protocol::Protocol,
+    replication, RespArray, RespFrame, RespMap, SimpleString,
+};
+
+/// 生成 INFO 其中一节的内容
+type InfoSection = fn(&Backend) -> String;
+
+/// INFO 的每一节的名字，按输出的顺序排列
+const INFO_SECTIONS: [(&str, InfoSection); 7] = [
+    ("server", info::server),
+    ("clients", info::clients),
+    ("memory", info::memory),
+    ("stats", info::stats),
+    ("replication", replication::info),
+    ("cluster", cluster::info),
+    ("keyspace", info::keyspace),
+];
+
+/// SLOWLOG GET 不指定个数时返回的条数
+const DEFAULT_SLOWLOG_COUNT: usize = 10;
+
+/// SAVE
+#[derive(Debug, Clone, PartialEq)]
+pub struct Save;
+
+/// BGSAVE / BGREWRITEAOF
+///
+/// AOF 只记录最近一次快照之后的写命令，所以重写 AOF 和后台保存快照是同一件事
+#[derive(Debug, Clone, PartialEq)]
+pub struct BgSave {
+    reply: &'static str,
+}
+
+/// INFO [section [section ...]]，不带参数时返回所有的节
+#[derive(Debug, Clone, PartialEq)]
+pub struct Info {
+    sections: Vec<String>,
+}
+
+/// CONFIG GET pattern [pattern ...] / CONFIG SET name value [name value ...]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Config {
+    Get(Vec<String>),
+    Set(Vec<(String, String)>),
+}
+
+/// SLOWLOG GET [count] / LEN / RESET
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlowLog {
+    /// None 表示返回全部
+    Get(Option<usize>),
+    Len,
+    Reset,
+}
+
+impl Save {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("save", &args, 0, Some(0))?;
+        Ok(Self)
+    }
+}
+
+impl BgSave {
+    pub(super) fn parse(
+        name: &'static str,
+        reply: &'static str,
+        args: Vec<Vec<u8>>,
+    ) -> Result<Self, CommandError> {
+        check_arity(name, &args, 0, Some(0))?;
+        Ok(Self { reply })
+    }
+}
+
+impl Info {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let sections = args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).to_lowercase())
+            .collect();
+        Ok(Self { sections })
+    }
+}
+
+impl Config {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("config", &args, 1, None)?;
+        let mut args = args.into_iter();
+        let sub = args.next().unwrap_or_default().to_ascii_lowercase();
+        let args: Vec<String> = args.map(to_key).collect();
+        match sub.as_slice() {
+            b"get" if !args.is_empty() => Ok(Config::Get(args)),
+            b"get" => Err(CommandError::WrongArity("config|get")),
+            b"set" if !args.is_empty() && args.len().is_multiple_of(2) => Ok(Config::Set(
+                args.chunks(2)
+                    .map(|pair| (pair[0].clone(), pair[1].clone()))
+                    .collect(),
+            )),
+            b"set" => Err(CommandError::WrongArity("config|set")),
+            _ => Err(CommandError::UnknownSubcommand(
+                String::from_utf8_lossy(&sub).into(),
+                "CONFIG",
+            )),
+        }
+    }
+}
+
+impl SlowLog {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("slowlog", &args, 1, None)?;
+        let sub = args[0].to_ascii_lowercase();
+        match sub.as_slice() {
+            b"get" => {
+                check_arity("slowlog|get", &args, 1, Some(2))?;
+                let count = match args.get(1).map(|arg| to_i64(arg)).transpose()? {
+                    None => Some(DEFAULT_SLOWLOG_COUNT),
+                    Some(-1) => None,
+                    Some(n) if n >= 0 => Some(n as usize),
+                    Some(_) => return Err(CommandError::InvalidSlowLogCount),
+                };
+                Ok(SlowLog::Get(count))
+            }
+            b"len" => check_arity("slowlog|len", &args, 1, Some(1)).map(|_| SlowLog::Len),
+            b"reset" => check_arity("slowlog|reset", &args, 1, Some(1)).map(|_| SlowLog::Reset),
+            _ => Err(CommandError::UnknownSubcommand(
+                String::from_utf8_lossy(&sub).into(),
+                "SLOWLOG",
+            )),
+        }
+    }
+}
+
+impl From<ConfigError> for CommandError {
+    fn from(e: ConfigError) -> Self {
+        CommandError::Config(e.to_string())
+    }
+}
+
+impl From<PersistenceError> for CommandError {
+    fn from(e: PersistenceError) -> Self {
+        CommandError::Persistence(e.to_string())
+    }
+}
+
+impl CommandExecutor for Save {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        persistence::save(backend)
+            .map(|_| SimpleString::new("OK"))
+            .map_err(CommandError::from)
+            .into()
+    }
+}
+
+impl CommandExecutor for BgSave {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        persistence::bgsave(backend)
+            .map(|_| SimpleString::new(self.reply))
+            .map_err(CommandError::from)
+            .into()
+    }
+}
+
+impl CommandExecutor for Info {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let all = self.sections.is_empty()
+            || self
+                .sections
+                .iter()
+                .any(|section| section == "all" || section == "default" || section == "everything");
+        let mut info = vec![];
+        for (name, section) in INFO_SECTIONS {
+            if all || self.sections.iter().any(|s| s == name) {
+                let mut title = name.to_string();
+                title[..1].make_ascii_uppercase();
+                info.push(format!("# {}\r\n{}", title, section(backend)));
+            }
+        }
+        RespFrame::from(info.join("\r\n").as_str())
+    }
+}
+
+impl CommandExecutor for Config {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        match self {
+            Config::Get(patterns) => {
+                let config = backend.config().get();
+                let map: BTreeMap<String, RespFrame> = patterns
+                    .iter()
+                    .flat_map(|pattern| config.matches(pattern))
+                    .map(|(name, value)| (name, value.into_bytes().into()))
+                    .collect();
+                RespMap::new(map).into()
+            }
+            Config::Set(pairs) => match backend.config().set(&pairs) {
+                Ok(config) => {
+                    apply_config(backend, &config);
+                    SimpleString::new("OK").into()
+                }
+                Err(e) => CommandError::from(e).into(),
+            },
+        }
+    }
+}
+
+/// 新的配置立即生效；端口由 serve 切换到新的 listener
+fn apply_config(backend: &Backend, config: &ServerConfig) {
+    if let Some(aof) = backend.propagator().lock().aof.as_mut() {
+        aof.set_fsync(config.persistence.appendfsync);
+    }
+    backend.slowlog().truncate(config.slowlog_max_len);
+    // maxmemory 改小之后立即淘汰
+    backend.free_memory();
+}
+
+impl CommandExecutor for SlowLog {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        match self {
+            SlowLog::Get(count) => {
+                let entries: Vec<RespFrame> = backend
+                    .slowlog()
+                    .get(count)
+                    .iter()
+                    .map(|entry| entry.to_frame())
+                    .collect();
+                RespArray::new(entries).into()
+            }
+            SlowLog::Len => (backend.slowlog().len() as i64).into(),
+            SlowLog::Reset => {
+                backend.slowlog().reset();
+                SimpleString::new("OK").into()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::run;
+    use super::*;
+    use crate::redis_core::SimpleError;
+    use std::time::Duration;
+
+    #[test]
+    fn config_get_set_should_work() {
+        let backend = Backend::new();
+        assert_eq!(
+            run(&backend, "config get maxclients"),
+            RespArray::new(["maxclients".into(), "10000".into()]).into()
+        );
+        assert_eq!(
+            run(
+                &backend,
+                "config set maxclients 2 slowlog-log-slower-than -1"
+            ),
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(backend.config().read(|c| c.maxclients), 2);
+        assert_eq!(
+            run(&backend, "config set appendonly yes"),
+            SimpleError::new("ERR CONFIG SET failed - can't set immutable config 'appendonly'")
+                .into()
+        );
+        assert_eq!(
+            run(&backend, "config set maxclients"),
+            CommandError::WrongArity("config|set").into()
+        );
+    }
+
+    #[test]
+    fn slowlog_and_info_should_work() {
+        let backend = Backend::new();
+        let args = vec![b"keys".to_vec(), b"*".to_vec()];
+        backend
+            .slowlog()
+            .record(args, Duration::from_millis(15), None, None, 128);
+        assert_eq!(run(&backend, "slowlog len"), RespFrame::Integer(1));
+        let RespFrame::Array(entries) = run(&backend, "slowlog get") else {
+            panic!("expect an array");
+        };
+        let RespFrame::Array(entry) = &entries.0[0] else {
+            panic!("expect an array");
+        };
+        assert_eq!(entry.0[2], RespFrame::Integer(15_000));
+        assert_eq!(
+            run(&backend, "slowlog reset"),
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(run(&backend, "slowlog len"), RespFrame::Integer(0));
+        assert_eq!(
+            run(&backend, "slowlog get -2"),
+            CommandError::InvalidSlowLogCount.into()
+        );
+
+        run(&backend, "set k v");
+        let RespFrame::BulkString(info) = run(&backend, "info") else {
+            panic!("expect a bulk string");
+        };
+        let info = String::from_utf8_lossy(&info.0).to_string();
+        for section in ["# Server", "# Clients", "# Memory", "# Stats", "# Keyspace"] {
+            assert!(info.contains(section), "{}", section);
+        }
+        assert!(info.contains("db0:keys=1,expires=0"));
+        let RespFrame::BulkString(info) = run(&backend, "info clients") else {
+            panic!("expect a bulk string");
+        };
+        assert!(!String::from_utf8_lossy(&info.0).contains("# Server"));
+    }
+}
@@ -0,0 +1,176 @@
+use super::{check_arity, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{
+    backend::{Backend, SetValue},
+    protocol::Protocol,
+    RespFrame, RespSet,
+};
+
+/// SADD key member [member ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SAdd {
+    key: String,
+    members: Vec<Vec<u8>>,
+}
+
+/// SREM key member [member ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SRem {
+    key: String,
+    members: Vec<Vec<u8>>,
+}
+
+/// SMEMBERS key
+#[derive(Debug, Clone, PartialEq)]
+pub struct SMembers {
+    key: String,
+}
+
+/// SISMEMBER key member
+#[derive(Debug, Clone, PartialEq)]
+pub struct SIsMember {
+    key: String,
+    member: Vec<u8>,
+}
+
+/// key 后面跟着一个或多个 member
+fn parse_members(
+    name: &'static str,
+    args: Vec<Vec<u8>>,
+) -> Result<(String, Vec<Vec<u8>>), CommandError> {
+    check_arity(name, &args, 2, None)?;
+    let mut args = args.into_iter();
+    let key = args.next().map(to_key).unwrap_or_default();
+    Ok((key, args.collect()))
+}
+
+impl SAdd {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let (key, members) = parse_members("sadd", args)?;
+        Ok(Self { key, members })
+    }
+}
+
+impl SRem {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        let (key, members) = parse_members("srem", args)?;
+        Ok(Self { key, members })
+    }
+}
+
+impl SMembers {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("smembers", &args, 1, Some(1))?;
+        let key = args.into_iter().map(to_key).next().unwrap_or_default();
+        Ok(Self { key })
+    }
+}
+
+impl SIsMember {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("sismember", &args, 2, Some(2))?;
+        let mut args = args.into_iter();
+        Ok(Self {
+            key: args.next().map(to_key).unwrap_or_default(),
+            member: args.next().unwrap_or_default(),
+        })
+    }
+}
+
+impl CommandExecutor for SAdd {
+    /// 返回新增的 member 个数
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .write(&self.key, |set: &mut SetValue| {
+                self.members
+                    .into_iter()
+                    .map(|member| set.insert(member))
+                    .filter(|added| *added)
+                    .count() as i64
+            })
+            .into()
+    }
+}
+
+impl CommandExecutor for SRem {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .write(&self.key, |set: &mut SetValue| {
+                self.members
+                    .iter()
+                    .filter(|member| set.remove(*member))
+                    .count() as i64
+            })
+            .into()
+    }
+}
+
+impl CommandExecutor for SMembers {
+    /// RESP3 返回 set，RESP2 返回 array；按字节序排好，方便客户端比较
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .read(&self.key, |set: &SetValue| {
+                let mut members: Vec<_> = set.iter().cloned().collect();
+                members.sort();
+                members
+            })
+            .map(|members| {
+                let members = members.unwrap_or_default();
+                RespSet::new(members.into_iter().map(RespFrame::from).collect::<Vec<_>>())
+            })
+            .into()
+    }
+}
+
+impl CommandExecutor for SIsMember {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .read(&self.key, |set: &SetValue| {
+                set.contains(&self.member) as i64
+            })
+            .map(|exists| exists.unwrap_or(0))
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{run, run_with};
+    use super::*;
+    use crate::redis_core::RespArray;
+
+    #[test]
+    fn sadd_srem_sismember_should_work() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "sadd s a b a"), RespFrame::Integer(2));
+        assert_eq!(run(&backend, "sadd s b c"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "sismember s a"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "sismember s d"), RespFrame::Integer(0));
+        assert_eq!(run(&backend, "sismember nokey a"), RespFrame::Integer(0));
+
+        assert_eq!(run(&backend, "srem s a d"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "srem s b c"), RespFrame::Integer(2));
+        assert_eq!(run(&backend, "exists s"), RespFrame::Integer(0));
+        assert_eq!(
+            run(&backend, "sadd s"),
+            CommandError::WrongArity("sadd").into()
+        );
+    }
+
+    #[test]
+    fn smembers_should_reply_set_for_resp3() {
+        let backend = Backend::new();
+        run(&backend, "sadd s b a");
+        assert_eq!(
+            run(&backend, "smembers s"),
+            RespArray::new(["a".into(), "b".into()]).into()
+        );
+        assert_eq!(
+            run_with(&backend, Protocol::Resp3, "smembers s"),
+            RespSet::new(["a".into(), "b".into()]).into()
+        );
+        assert_eq!(
+            run_with(&backend, Protocol::Resp3, "smembers nokey"),
+            RespSet::new([]).into()
+        );
+    }
+}
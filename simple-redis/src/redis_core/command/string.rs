@@ -0,0 +1,300 @@
+use std::time::Duration;
+
+use super::{check_arity, to_i64, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{
+    backend::{Backend, SetCondition},
+    protocol::Protocol,
+    RespArray, RespFrame, RespNullBulkString, SimpleString,
+};
+
+/// GET key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Get {
+    key: String,
+}
+
+/// SET key value [NX | XX] [EX seconds | PX milliseconds]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Set {
+    key: String,
+    value: Vec<u8>,
+    expire: Option<Duration>,
+    condition: Option<SetCondition>,
+}
+
+/// DEL key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Del {
+    keys: Vec<String>,
+}
+
+/// EXISTS key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exists {
+    keys: Vec<String>,
+}
+
+/// INCR key / DECR key
+#[derive(Debug, Clone, PartialEq)]
+pub struct Incr {
+    key: String,
+    delta: i64,
+}
+
+/// MGET key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MGet {
+    keys: Vec<String>,
+}
+
+/// MSET key value [key value ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MSet {
+    pairs: Vec<(String, Vec<u8>)>,
+}
+
+impl Get {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("get", &args, 1, Some(1))?;
+        let key = args.into_iter().map(to_key).next().unwrap_or_default();
+        Ok(Self { key })
+    }
+}
+
+impl Set {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("set", &args, 2, None)?;
+        let mut args = args.into_iter();
+        let key = args.next().map(to_key).unwrap_or_default();
+        let value = args.next().unwrap_or_default();
+        let mut set = Set {
+            key,
+            value,
+            expire: None,
+            condition: None,
+        };
+
+        while let Some(opt) = args.next() {
+            match opt.to_ascii_uppercase().as_slice() {
+                b"NX" | b"XX" => {
+                    let condition = match opt[0].to_ascii_uppercase() {
+                        b'N' => SetCondition::Nx,
+                        _ => SetCondition::Xx,
+                    };
+                    // NX 和 XX 不能同时出现
+                    if set.condition.is_some_and(|c| c != condition) {
+                        return Err(CommandError::SyntaxError);
+                    }
+                    set.condition = Some(condition);
+                }
+                unit @ (b"EX" | b"PX") => {
+                    let ttl = args.next().ok_or(CommandError::SyntaxError)?;
+                    if set.expire.is_some() {
+                        return Err(CommandError::SyntaxError);
+                    }
+                    let ttl = to_i64(&ttl)?;
+                    if ttl <= 0 {
+                        return Err(CommandError::InvalidExpireTime("set"));
+                    }
+                    set.expire = Some(match unit {
+                        b"EX" => Duration::from_secs(ttl as u64),
+                        _ => Duration::from_millis(ttl as u64),
+                    });
+                }
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+
+        Ok(set)
+    }
+}
+
+impl Del {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("del", &args, 1, None)?;
+        Ok(Self {
+            keys: args.into_iter().map(to_key).collect(),
+        })
+    }
+}
+
+impl Exists {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("exists", &args, 1, None)?;
+        Ok(Self {
+            keys: args.into_iter().map(to_key).collect(),
+        })
+    }
+}
+
+impl Incr {
+    pub(super) fn parse(
+        name: &'static str,
+        delta: i64,
+        args: Vec<Vec<u8>>,
+    ) -> Result<Self, CommandError> {
+        check_arity(name, &args, 1, Some(1))?;
+        let key = args.into_iter().map(to_key).next().unwrap_or_default();
+        Ok(Self { key, delta })
+    }
+}
+
+impl MGet {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("mget", &args, 1, None)?;
+        Ok(Self {
+            keys: args.into_iter().map(to_key).collect(),
+        })
+    }
+}
+
+impl MSet {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("mset", &args, 2, None)?;
+        if !args.len().is_multiple_of(2) {
+            return Err(CommandError::WrongArity("mset"));
+        }
+        let mut args = args.into_iter();
+        let mut pairs = vec![];
+        while let (Some(key), Some(value)) = (args.next(), args.next()) {
+            pairs.push((to_key(key), value));
+        }
+        Ok(Self { pairs })
+    }
+}
+
+impl CommandExecutor for Get {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        match backend.get(&self.key) {
+            Ok(Some(value)) => value.into(),
+            Ok(None) => RespNullBulkString.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Set {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        // NX/XX 的条件不满足时返回 null
+        match backend.set(self.key, self.value, self.expire, self.condition) {
+            true => SimpleString::new("OK").into(),
+            false => RespNullBulkString.into(),
+        }
+    }
+}
+
+impl CommandExecutor for Del {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend.del(&self.keys).into()
+    }
+}
+
+impl CommandExecutor for Exists {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend.exists(&self.keys).into()
+    }
+}
+
+impl CommandExecutor for Incr {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        match backend.incr_by(&self.key, self.delta) {
+            Ok(value) => value.into(),
+            Err(e) => e.into(),
+        }
+    }
+}
+
+impl CommandExecutor for MGet {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let values = backend
+            .mget(&self.keys)
+            .into_iter()
+            .map(|value| match value {
+                Some(value) => value.into(),
+                None => RespNullBulkString.into(),
+            })
+            .collect::<Vec<RespFrame>>();
+        RespArray::new(values).into()
+    }
+}
+
+impl CommandExecutor for MSet {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend.mset(self.pairs);
+        SimpleString::new("OK").into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::run;
+    use super::*;
+
+    fn ok() -> RespFrame {
+        SimpleString::new("OK").into()
+    }
+
+    #[test]
+    fn get_set_should_work() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "get k1"), RespNullBulkString.into());
+        assert_eq!(run(&backend, "set k1 v1"), ok());
+        assert_eq!(run(&backend, "get k1"), RespFrame::from("v1"));
+
+        assert_eq!(run(&backend, "set k1 v2 nx"), RespNullBulkString.into());
+        assert_eq!(run(&backend, "set k2 v2 xx"), RespNullBulkString.into());
+        assert_eq!(run(&backend, "set k1 v2 XX EX 10"), ok());
+        assert_eq!(run(&backend, "get k1"), RespFrame::from("v2"));
+    }
+
+    #[test]
+    fn set_options_should_be_validated() {
+        let backend = Backend::new();
+        let syntax_error = RespFrame::from(CommandError::SyntaxError);
+        assert_eq!(run(&backend, "set k v nx xx"), syntax_error);
+        assert_eq!(run(&backend, "set k v ex 1 px 1"), syntax_error);
+        assert_eq!(run(&backend, "set k v ex"), syntax_error);
+        assert_eq!(run(&backend, "set k v foo"), syntax_error);
+        assert_eq!(
+            run(&backend, "set k v ex 0"),
+            CommandError::InvalidExpireTime("set").into()
+        );
+        assert_eq!(
+            run(&backend, "set k v px abc"),
+            CommandError::NotInteger.into()
+        );
+    }
+
+    #[test]
+    fn del_exists_should_work() {
+        let backend = Backend::new();
+        run(&backend, "mset k1 v1 k2 v2");
+        assert_eq!(run(&backend, "exists k1 k1 k3"), RespFrame::Integer(2));
+        assert_eq!(run(&backend, "del k1 k2 k3"), RespFrame::Integer(2));
+        assert_eq!(run(&backend, "exists k1"), RespFrame::Integer(0));
+    }
+
+    #[test]
+    fn incr_decr_should_work() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "incr n"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "decr n"), RespFrame::Integer(0));
+        assert_eq!(run(&backend, "decr n"), RespFrame::Integer(-1));
+        run(&backend, "set s abc");
+        assert_eq!(run(&backend, "incr s"), CommandError::NotInteger.into());
+    }
+
+    #[test]
+    fn mget_mset_should_work() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "mset k1 v1 k2 v2"), ok());
+        assert_eq!(
+            run(&backend, "mget k1 k3 k2"),
+            RespArray::new(["v1".into(), RespNullBulkString.into(), "v2".into()]).into()
+        );
+        assert_eq!(
+            run(&backend, "mset k1 v1 k2"),
+            CommandError::WrongArity("mset").into()
+        );
+    }
+}
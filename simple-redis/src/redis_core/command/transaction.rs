@@ -0,0 +1,38 @@
+use super::{check_arity, to_key, CommandError};
+
+/// MULTI / EXEC / DISCARD / UNWATCH，都没有参数
+///
+/// 事务的状态属于连接，这些命令都由 Session 执行
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transaction {
+    Multi,
+    Exec,
+    Discard,
+    Unwatch,
+}
+
+/// WATCH key [key ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Watch {
+    pub(crate) keys: Vec<String>,
+}
+
+impl Transaction {
+    pub(super) fn parse(
+        name: &'static str,
+        cmd: Transaction,
+        args: Vec<Vec<u8>>,
+    ) -> Result<Self, CommandError> {
+        check_arity(name, &args, 0, Some(0))?;
+        Ok(cmd)
+    }
+}
+
+impl Watch {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("watch", &args, 1, None)?;
+        Ok(Self {
+            keys: args.into_iter().map(to_key).collect(),
+        })
+    }
+}
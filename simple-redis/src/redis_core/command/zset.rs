@@ -0,0 +1,324 @@
+use std::ops::Bound;
+
+use super::{check_arity, normalize_range, to_f64, to_i64, to_key, CommandError, CommandExecutor};
+use crate::redis_core::{
+    backend::{Backend, SetCondition, SortedSet},
+    protocol::Protocol,
+    RespArray, RespFrame,
+};
+
+/// ZADD key [NX | XX] score member [score member ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZAdd {
+    key: String,
+    condition: Option<SetCondition>,
+    pairs: Vec<(f64, Vec<u8>)>,
+}
+
+/// ZRANGE key start stop [WITHSCORES]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRange {
+    key: String,
+    start: i64,
+    stop: i64,
+    with_scores: bool,
+}
+
+/// ZRANGEBYSCORE key min max [WITHSCORES] [LIMIT offset count]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRangeByScore {
+    key: String,
+    min: Bound<f64>,
+    max: Bound<f64>,
+    with_scores: bool,
+    limit: Option<(i64, i64)>,
+}
+
+/// ZREM key member [member ...]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZRem {
+    key: String,
+    members: Vec<Vec<u8>>,
+}
+
+impl ZAdd {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("zadd", &args, 3, None)?;
+        let mut args = args.into_iter().peekable();
+        let key = args.next().map(to_key).unwrap_or_default();
+
+        let mut condition = None;
+        while let Some(opt) =
+            args.next_if(|opt| opt.eq_ignore_ascii_case(b"NX") || opt.eq_ignore_ascii_case(b"XX"))
+        {
+            let c = match opt[0].to_ascii_uppercase() {
+                b'N' => SetCondition::Nx,
+                _ => SetCondition::Xx,
+            };
+            if condition.is_some_and(|old| old != c) {
+                return Err(CommandError::NxXxConflict);
+            }
+            condition = Some(c);
+        }
+
+        let args: Vec<_> = args.collect();
+        if args.is_empty() || !args.len().is_multiple_of(2) {
+            return Err(CommandError::SyntaxError);
+        }
+        let pairs = args
+            .chunks(2)
+            .map(|pair| Ok((to_f64(&pair[0])?, pair[1].clone())))
+            .collect::<Result<_, CommandError>>()?;
+        Ok(Self {
+            key,
+            condition,
+            pairs,
+        })
+    }
+}
+
+impl ZRange {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("zrange", &args, 3, Some(4))?;
+        let with_scores = match args.get(3) {
+            Some(opt) if opt.eq_ignore_ascii_case(b"WITHSCORES") => true,
+            Some(_) => return Err(CommandError::SyntaxError),
+            None => false,
+        };
+        Ok(Self {
+            key: to_key(args[0].clone()),
+            start: to_i64(&args[1])?,
+            stop: to_i64(&args[2])?,
+            with_scores,
+        })
+    }
+}
+
+impl ZRangeByScore {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("zrangebyscore", &args, 3, None)?;
+        let mut cmd = Self {
+            key: to_key(args[0].clone()),
+            min: to_score_bound(&args[1])?,
+            max: to_score_bound(&args[2])?,
+            with_scores: false,
+            limit: None,
+        };
+
+        let mut opts = args[3..].iter();
+        while let Some(opt) = opts.next() {
+            match opt.to_ascii_uppercase().as_slice() {
+                b"WITHSCORES" => cmd.with_scores = true,
+                b"LIMIT" => {
+                    let (Some(offset), Some(count)) = (opts.next(), opts.next()) else {
+                        return Err(CommandError::SyntaxError);
+                    };
+                    cmd.limit = Some((to_i64(offset)?, to_i64(count)?));
+                }
+                _ => return Err(CommandError::SyntaxError),
+            }
+        }
+        Ok(cmd)
+    }
+}
+
+impl ZRem {
+    pub(super) fn parse(args: Vec<Vec<u8>>) -> Result<Self, CommandError> {
+        check_arity("zrem", &args, 2, None)?;
+        let mut args = args.into_iter();
+        Ok(Self {
+            key: args.next().map(to_key).unwrap_or_default(),
+            members: args.collect(),
+        })
+    }
+}
+
+/// min、max 前面加 "(" 表示不包含边界，"-inf"、"+inf" 表示不限
+fn to_score_bound(arg: &[u8]) -> Result<Bound<f64>, CommandError> {
+    let bound = match arg.strip_prefix(b"(") {
+        Some(score) => to_f64(score).map(Bound::Excluded),
+        None => to_f64(arg).map(Bound::Included),
+    };
+    bound.map_err(|_| CommandError::MinMaxNotFloat)
+}
+
+/// 带 score 时 RESP2 返回 member、score 交替的 array，RESP3 返回 [member, score] 组成的 array
+fn scored_reply(
+    items: Option<Vec<(Vec<u8>, f64)>>,
+    with_scores: bool,
+    protocol: Protocol,
+) -> RespArray {
+    let items = items.unwrap_or_default().into_iter();
+    let frames: Vec<RespFrame> = match (with_scores, protocol) {
+        (false, _) => items.map(|(member, _)| member.into()).collect(),
+        (true, Protocol::Resp2) => items
+            .flat_map(|(member, score)| [member.into(), score.into()])
+            .collect(),
+        (true, Protocol::Resp3) => items
+            .map(|(member, score)| RespArray::new([member.into(), score.into()]).into())
+            .collect(),
+    };
+    RespArray::new(frames)
+}
+
+impl CommandExecutor for ZAdd {
+    /// 返回新增的 member 个数；NX 只添加新的 member，XX 只更新已有的 member
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        let condition = self.condition;
+        backend
+            .write(&self.key, |zset: &mut SortedSet| {
+                let mut added = 0i64;
+                for (score, member) in self.pairs {
+                    let exists = zset.score(&member).is_some();
+                    match condition {
+                        Some(SetCondition::Nx) if exists => continue,
+                        Some(SetCondition::Xx) if !exists => continue,
+                        _ => {}
+                    }
+                    if zset.insert(member, score) {
+                        added += 1;
+                    }
+                }
+                added
+            })
+            .into()
+    }
+}
+
+impl CommandExecutor for ZRange {
+    fn execute(self, backend: &Backend, protocol: Protocol) -> RespFrame {
+        backend
+            .read(&self.key, |zset: &SortedSet| {
+                match normalize_range(self.start, self.stop, zset.len()) {
+                    Some(range) => zset
+                        .iter()
+                        .skip(*range.start())
+                        .take(range.count())
+                        .map(|(member, score)| (member.to_vec(), score))
+                        .collect(),
+                    None => vec![],
+                }
+            })
+            .map(|items| scored_reply(items, self.with_scores, protocol))
+            .into()
+    }
+}
+
+impl CommandExecutor for ZRangeByScore {
+    /// LIMIT 的 offset 为负数时返回空，count 为负数时返回 offset 之后的全部
+    fn execute(self, backend: &Backend, protocol: Protocol) -> RespFrame {
+        let (offset, count) = self.limit.unwrap_or((0, -1));
+        backend
+            .read(&self.key, |zset: &SortedSet| {
+                if offset < 0 {
+                    return vec![];
+                }
+                let count = usize::try_from(count).unwrap_or(usize::MAX);
+                zset.range_by_score(self.min, self.max)
+                    .skip(offset as usize)
+                    .take(count)
+                    .map(|(member, score)| (member.to_vec(), score))
+                    .collect()
+            })
+            .map(|items| scored_reply(items, self.with_scores, protocol))
+            .into()
+    }
+}
+
+impl CommandExecutor for ZRem {
+    fn execute(self, backend: &Backend, _protocol: Protocol) -> RespFrame {
+        backend
+            .write(&self.key, |zset: &mut SortedSet| {
+                self.members
+                    .iter()
+                    .filter(|member| zset.remove(member))
+                    .count() as i64
+            })
+            .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{run, run_with};
+    use super::*;
+
+    fn array(elements: &[&str]) -> RespFrame {
+        RespArray::new(
+            elements
+                .iter()
+                .map(|e| RespFrame::from(*e))
+                .collect::<Vec<_>>(),
+        )
+        .into()
+    }
+
+    #[test]
+    fn zadd_zrem_should_work() {
+        let backend = Backend::new();
+        assert_eq!(run(&backend, "zadd z 1 a 2 b"), RespFrame::Integer(2));
+        assert_eq!(run(&backend, "zadd z 3 a 3 c"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "zadd z nx 0 a 4 d"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "zadd z xx 0 b 5 e"), RespFrame::Integer(0));
+        assert_eq!(run(&backend, "zrange z 0 -1"), array(&["b", "a", "c", "d"]));
+
+        assert_eq!(
+            run(&backend, "zadd z nx xx 1 a"),
+            CommandError::NxXxConflict.into()
+        );
+        assert_eq!(run(&backend, "zadd z x a"), CommandError::NotFloat.into());
+        assert_eq!(run(&backend, "zadd z nan a"), CommandError::NotFloat.into());
+
+        assert_eq!(run(&backend, "zrem z a e"), RespFrame::Integer(1));
+        assert_eq!(run(&backend, "zrem z b c d"), RespFrame::Integer(3));
+        assert_eq!(run(&backend, "exists z"), RespFrame::Integer(0));
+    }
+
+    #[test]
+    fn zrange_should_work() {
+        let backend = Backend::new();
+        run(&backend, "zadd z 1 a 2 b 3.5 c");
+        assert_eq!(run(&backend, "zrange z 1 -1"), array(&["b", "c"]));
+        assert_eq!(run(&backend, "zrange z 5 10"), array(&[]));
+        assert_eq!(
+            run(&backend, "zrange z 0 1 withscores"),
+            array(&["a", "1", "b", "2"])
+        );
+        assert_eq!(
+            run_with(&backend, Protocol::Resp3, "zrange z -1 -1 WITHSCORES"),
+            RespArray::new([RespArray::new(["c".into(), RespFrame::Double(3.5)]).into()]).into()
+        );
+        assert_eq!(
+            run(&backend, "zrange z 0 1 foo"),
+            CommandError::SyntaxError.into()
+        );
+    }
+
+    #[test]
+    fn zrangebyscore_should_work() {
+        let backend = Backend::new();
+        run(&backend, "zadd z 1 a 2 b 3 c 4 d");
+        assert_eq!(run(&backend, "zrangebyscore z 2 3"), array(&["b", "c"]));
+        assert_eq!(run(&backend, "zrangebyscore z (2 +inf"), array(&["c", "d"]));
+        assert_eq!(
+            run(&backend, "zrangebyscore z -inf (2 withscores"),
+            array(&["a", "1"])
+        );
+        assert_eq!(
+            run(&backend, "zrangebyscore z -inf +inf limit 1 2"),
+            array(&["b", "c"])
+        );
+        assert_eq!(
+            run(&backend, "zrangebyscore z -inf +inf limit 2 -1"),
+            array(&["c", "d"])
+        );
+        assert_eq!(
+            run(&backend, "zrangebyscore z a 1"),
+            CommandError::MinMaxNotFloat.into()
+        );
+        assert_eq!(
+            run(&backend, "zrangebyscore z 0 1 limit 1"),
+            CommandError::SyntaxError.into()
+        );
+    }
+}
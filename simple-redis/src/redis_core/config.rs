@@ -0,0 +1,369 @@
+use std::{
+    net::TcpListener,
+    str::FromStr,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use thiserror::Error;
+use tokio::sync::mpsc;
+
+use super::{backend::MaxMemoryPolicy, pattern::glob_match, persistence::PersistenceConfig};
+
+/// 服务器自己的选项，持久化的选项在 PersistenceConfig 里
+const NAMES: [&str; 11] = [
+    "bind",
+    "port",
+    "maxclients",
+    "slowlog-log-slower-than",
+    "slowlog-max-len",
+    "replicaof",
+    "maxmemory",
+    "maxmemory-policy",
+    "maxmemory-samples",
+    "cluster-enabled",
+    "cluster-config-file",
+];
+
+/// 只能在配置文件和命令行里设置，运行时不能修改
+const IMMUTABLE: [&str; 6] = [
+    "bind",
+    "replicaof",
+    "appendonly",
+    "appendfilename",
+    "cluster-enabled",
+    "cluster-config-file",
+];
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ConfigError {
+    #[error("unknown option '{0}'")]
+    Unknown(String),
+    #[error("invalid value '{1}' for '{0}'")]
+    InvalidValue(String, String),
+    #[error("can't set immutable config '{0}'")]
+    Immutable(String),
+    #[error("unable to listen on port {0}: {1}")]
+    Bind(u16, String),
+    #[error("bad directive at line {0}: {1}")]
+    BadDirective(usize, String),
+}
+
+/// 所有的选项，名字和 redis.conf 一样
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    pub bind: String,
+    pub port: u16,
+    pub maxclients: usize,
+    /// 执行时间超过这么多微秒的命令记到 slowlog，负数表示不记录
+    pub slowlog_log_slower_than: i64,
+    pub slowlog_max_len: usize,
+    pub replicaof: Option<(String, u16)>,
+    /// 所有 key 估算的内存的上限，单位字节，0 表示不限制
+    pub maxmemory: usize,
+    pub maxmemory_policy: MaxMemoryPolicy,
+    /// 淘汰时每次抽样的 key 的个数
+    pub maxmemory_samples: usize,
+    pub cluster_enabled: bool,
+    /// 集群的拓扑，和快照一样放在 dir 里
+    pub cluster_config_file: String,
+    pub persistence: PersistenceConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1".into(),
+            port: 6379,
+            maxclients: 10000,
+            slowlog_log_slower_than: 10000,
+            slowlog_max_len: 128,
+            replicaof: None,
+            maxmemory: 0,
+            maxmemory_policy: MaxMemoryPolicy::default(),
+            maxmemory_samples: 5,
+            cluster_enabled: false,
+            cluster_config_file: "nodes.conf".into(),
+            persistence: PersistenceConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// 按名字设置一个选项，名字不区分大小写
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ConfigError> {
+        let name = name.to_ascii_lowercase();
+        match name.as_str() {
+            "bind" => self.bind = value.into(),
+            "port" => self.port = parse(&name, value)?,
+            "maxclients" => self.maxclients = parse(&name, value)?,
+            "slowlog-log-slower-than" => self.slowlog_log_slower_than = parse(&name, value)?,
+            "slowlog-max-len" => self.slowlog_max_len = parse(&name, value)?,
+            "replicaof" | "slaveof" => {
+                self.replicaof = match value.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    [host, port] => Some((host.to_string(), parse(&name, port)?)),
+                    _ => return Err(ConfigError::InvalidValue(name, value.into())),
+                }
+            }
+            "maxmemory" => {
+                self.maxmemory = parse_memory(value)
+                    .ok_or_else(|| ConfigError::InvalidValue(name, value.into()))?
+            }
+            "maxmemory-policy" => self.maxmemory_policy = value.parse()?,
+            "maxmemory-samples" => {
+                self.maxmemory_samples = match parse(&name, value)? {
+                    0 => return Err(ConfigError::InvalidValue(name, value.into())),
+                    n => n,
+                }
+            }
+            "cluster-enabled" => {
+                self.cluster_enabled = match value.to_ascii_lowercase().as_str() {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return Err(ConfigError::InvalidValue(name, value.into())),
+                }
+            }
+            "cluster-config-file" => self.cluster_config_file = value.into(),
+            _ => self.persistence.set(&name, value)?,
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "bind" => self.bind.clone(),
+            "port" => self.port.to_string(),
+            "maxclients" => self.maxclients.to_string(),
+            "slowlog-log-slower-than" => self.slowlog_log_slower_than.to_string(),
+            "slowlog-max-len" => self.slowlog_max_len.to_string(),
+            "replicaof" => self
+                .replicaof
+                .as_ref()
+                .map(|(host, port)| format!("{} {}", host, port))
+                .unwrap_or_default(),
+            "maxmemory" => self.maxmemory.to_string(),
+            "maxmemory-policy" => self.maxmemory_policy.to_string(),
+            "maxmemory-samples" => self.maxmemory_samples.to_string(),
+            "cluster-enabled" => if self.cluster_enabled { "yes" } else { "no" }.to_string(),
+            "cluster-config-file" => self.cluster_config_file.clone(),
+            _ => return self.persistence.get(name),
+        };
+        Some(value)
+    }
+
+    /// CONFIG GET：名字匹配 pattern 的选项和值
+    pub fn matches(&self, pattern: &str) -> Vec<(String, String)> {
+        let pattern = pattern.to_ascii_lowercase();
+        NAMES
+            .iter()
+            .chain(PersistenceConfig::NAMES.iter())
+            .filter(|name| glob_match(pattern.as_bytes(), name.as_bytes()))
+            .filter_map(|name| Some((name.to_string(), self.get(name)?)))
+            .collect()
+    }
+
+    /// 读取 redis.conf 格式的配置：每行一个选项，`#` 开头的是注释，值可以用引号括起来
+    pub fn load(&mut self, content: &str) -> Result<(), ConfigError> {
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| ConfigError::BadDirective(i + 1, line.into()))?;
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            self.set(name, value)
+                .map_err(|e| ConfigError::BadDirective(i + 1, e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+fn parse<T: FromStr>(name: &str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::InvalidValue(name.into(), value.into()))
+}
+
+/// 和 redis.conf 一样可以带单位：1k = 1000、1kb = 1024，m、mb、g、gb 类似，不区分大小写
+fn parse_memory(value: &str) -> Option<usize> {
+    let value = value.to_ascii_lowercase();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let unit: usize = match unit {
+        "" | "b" => 1,
+        "k" => 1000,
+        "kb" => 1024,
+        "m" => 1000 * 1000,
+        "mb" => 1024 * 1024,
+        "g" => 1000 * 1000 * 1000,
+        "gb" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// 运行时的配置，clone 之后共享同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct Config {
+    inner: Arc<Mutex<ServerConfig>>,
+    // CONFIG SET port 绑定新的端口之后，通过这里交给正在 accept 的任务
+    listeners: Arc<Mutex<Option<mpsc::UnboundedSender<TcpListener>>>>,
+}
+
+impl Config {
+    fn lock(&self) -> MutexGuard<'_, ServerConfig> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self) -> ServerConfig {
+        self.lock().clone()
+    }
+
+    /// 只读取其中一部分，不用复制整个配置
+    pub fn read<R>(&self, f: impl FnOnce(&ServerConfig) -> R) -> R {
+        f(&self.lock())
+    }
+
+    pub fn replace(&self, config: ServerConfig) {
+        *self.lock() = config;
+    }
+
+    pub fn persistence(&self) -> PersistenceConfig {
+        self.lock().persistence.clone()
+    }
+
+    pub fn set_listener_channel(&self, tx: mpsc::UnboundedSender<TcpListener>) {
+        *self.listeners.lock().unwrap_or_else(|e| e.into_inner()) = Some(tx);
+    }
+
+    /// CONFIG SET：全部检查通过之后才生效；修改端口时先绑定新的端口，失败就返回错误
+    pub fn set(&self, pairs: &[(String, String)]) -> Result<ServerConfig, ConfigError> {
+        let mut config = self.lock();
+        let mut updated = config.clone();
+        for (name, value) in pairs {
+            let name = name.to_ascii_lowercase();
+            if IMMUTABLE.contains(&name.as_str()) {
+                return Err(ConfigError::Immutable(name));
+            }
+            updated.set(&name, value)?;
+        }
+        if updated.port != config.port {
+            let listener = TcpListener::bind((updated.bind.as_str(), updated.port))
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+                .map_err(|e| ConfigError::Bind(updated.port, e.to_string()))?;
+            if let Some(tx) = self
+                .listeners
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .as_ref()
+            {
+                let _ = tx.send(listener);
+            }
+        }
+        *config = updated.clone();
+        Ok(updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis_core::persistence::AppendFsync;
+
+    #[test]
+    fn config_file_should_load() {
+        let mut config = ServerConfig::default();
+        let content = r#"
+            # comment
+            port 6380
+            maxclients 10
+            replicaof 127.0.0.1 6379
+            dir "/tmp/redis data"
+            appendfsync always
+            cluster-enabled yes
+        "#;
+        config.load(content).unwrap();
+        assert_eq!(config.port, 6380);
+        assert_eq!(config.maxclients, 10);
+        assert_eq!(config.replicaof, Some(("127.0.0.1".into(), 6379)));
+        assert_eq!(
+            config.persistence.dir,
+            std::path::PathBuf::from("/tmp/redis data")
+        );
+        assert_eq!(config.persistence.appendfsync, AppendFsync::Always);
+        assert!(config.cluster_enabled);
+
+        assert_eq!(
+            config.load("port abc"),
+            Err(ConfigError::BadDirective(
+                1,
+                "invalid value 'abc' for 'port'".into()
+            ))
+        );
+        assert!(config.load("nosuchoption 1").is_err());
+
+        config
+            .load("maxmemory 100mb\nmaxmemory-policy allkeys-lru")
+            .unwrap();
+        assert_eq!(config.maxmemory, 100 * 1024 * 1024);
+        assert_eq!(
+            config.get("maxmemory-policy").as_deref(),
+            Some("allkeys-lru")
+        );
+        assert!(config.set("maxmemory", "1tb").is_err());
+        assert!(config.set("maxmemory-policy", "allkeys-random").is_err());
+        assert!(config.set("maxmemory-samples", "0").is_err());
+        assert!(config.load("port").is_err());
+    }
+
+    #[test]
+    fn config_get_should_match_pattern() {
+        let config = ServerConfig::default();
+        assert_eq!(
+            config.matches("slowlog-*"),
+            vec![
+                ("slowlog-log-slower-than".into(), "10000".into()),
+                ("slowlog-max-len".into(), "128".into()),
+            ]
+        );
+        assert_eq!(config.matches("PORT"), vec![("port".into(), "6379".into())]);
+        assert_eq!(config.matches("maxmemory*").len(), 3);
+        assert_eq!(
+            config.matches("append*").len(),
+            3,
+            "appendonly, appendfilename, appendfsync"
+        );
+    }
+
+    #[test]
+    fn config_set_should_be_atomic() {
+        let config = Config::default();
+        assert_eq!(
+            config.set(&[
+                ("maxclients".into(), "5".into()),
+                ("bind".into(), "0.0.0.0".into())
+            ]),
+            Err(ConfigError::Immutable("bind".into()))
+        );
+        assert_eq!(
+            config.set(&[
+                ("maxclients".into(), "5".into()),
+                ("slowlog-max-len".into(), "x".into())
+            ]),
+            Err(ConfigError::InvalidValue(
+                "slowlog-max-len".into(),
+                "x".into()
+            ))
+        );
+        assert_eq!(config.get().maxclients, 10000);
+        config.set(&[("MAXCLIENTS".into(), "5".into())]).unwrap();
+        assert_eq!(config.get().maxclients, 5);
+    }
+}
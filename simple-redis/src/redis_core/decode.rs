@@ -16,38 +16,63 @@ impl RespDecode for RespFrame {
             Some(b'%') => Ok(RespFrame::Map(RespMap::decode(buf)?)),
             Some(b'~') => Ok(RespFrame::Set(RespSet::decode(buf)?)),
             Some(b'#') => Ok(RespFrame::Boolean(bool::decode(buf)?)),
-            Some(b'*') => match RespNullArray::decode(buf) {
-                Ok(r) => Ok(RespFrame::NullArray(r)),
-                Err(RespError::NotComplete) => Err(RespError::NotComplete),
-                Err(_) => Ok(RespFrame::Array(RespArray::decode(buf)?)),
-            },
-            Some(b'$') => match RespNullBulkString::decode(buf) {
-                Ok(r) => Ok(RespFrame::NullBulkString(r)),
-                Err(RespError::NotComplete) => Err(RespError::NotComplete),
-                Err(_) => Ok(RespFrame::BulkString(BulkString::decode(buf)?)),
-            },
-            None => Ok(RespFrame::Null(RespNull::decode(buf)?)),
-            _ => Err(RespError::NotComplete),
+            // 长度为 -1 的是 null，只看前两个字节就能区分，不用等整个 frame 到齐
+            Some(b'*') if buf.starts_with(b"*-") => {
+                Ok(RespFrame::NullArray(RespNullArray::decode(buf)?))
+            }
+            Some(b'*') => Ok(RespFrame::Array(RespArray::decode(buf)?)),
+            Some(b'$') if buf.starts_with(b"$-") => {
+                Ok(RespFrame::NullBulkString(RespNullBulkString::decode(buf)?))
+            }
+            Some(b'$') => Ok(RespFrame::BulkString(BulkString::decode(buf)?)),
+            Some(b'(') => Ok(RespFrame::BigNumber(BigNumber::decode(buf)?)),
+            Some(b'!') => Ok(RespFrame::BulkError(BulkError::decode(buf)?)),
+            Some(b'=') => Ok(RespFrame::VerbatimString(VerbatimString::decode(buf)?)),
+            Some(b'>') => Ok(RespFrame::Push(RespPush::decode(buf)?)),
+            Some(b'|') => Ok(RespFrame::Attribute(RespAttribute::decode(buf)?)),
+            None => Err(RespError::NotComplete),
+            Some(prefix) => Err(RespError::InvalidFrame(format!(
+                "unknown frame prefix: {:?}",
+                **prefix as char
+            ))),
         };
 
         resp
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        let mut iter = buf.iter().peekable();
-        match iter.peek() {
-            Some(b':') => i64::expect_length(buf),
-            Some(b'+') => SimpleString::expect_length(buf),
-            Some(b'-') => SimpleError::expect_length(buf),
-            Some(b'*') => RespArray::expect_length(buf),
-            Some(b'~') => RespSet::expect_length(buf),
-            Some(b'%') => RespMap::expect_length(buf),
-            Some(b'#') => bool::expect_length(buf),
-            Some(b',') => f64::expect_length(buf),
-            Some(b'_') => RespNull::expect_length(buf),
-            Some(b'$') => BulkString::expect_length(buf),
-            _ => Err(RespError::NotComplete),
+        frame_length(buf, 0)
+    }
+}
+
+/// 嵌套在第 `depth` 层的 frame 的长度，聚合类型的子 frame 在下一层
+pub(super) fn frame_length(buf: &[u8], depth: usize) -> Result<usize, RespError> {
+    let mut iter = buf.iter().peekable();
+    match iter.peek() {
+        Some(b':') => i64::expect_length(buf),
+        Some(b'+') => SimpleString::expect_length(buf),
+        Some(b'-') => SimpleError::expect_length(buf),
+        Some(b'*') => aggregate_length(buf, "*", 1, depth),
+        Some(b'~') => aggregate_length(buf, "~", 1, depth),
+        Some(b'%') => aggregate_length(buf, "%", 2, depth),
+        Some(b'#') => bool::expect_length(buf),
+        Some(b',') => f64::expect_length(buf),
+        Some(b'_') => RespNull::expect_length(buf),
+        Some(b'$') => BulkString::expect_length(buf),
+        Some(b'(') => BigNumber::expect_length(buf),
+        Some(b'!') => BulkError::expect_length(buf),
+        Some(b'=') => VerbatimString::expect_length(buf),
+        Some(b'>') => aggregate_length(buf, ">", 1, depth),
+        Some(b'|') => {
+            // 属性的 key/value 之后还有一个真正的 frame
+            let attrs = aggregate_length(buf, "|", 2, depth)?;
+            Ok(attrs + frame_length(&buf[attrs..], depth + 1)?)
         }
+        None => Err(RespError::NotComplete),
+        Some(prefix) => Err(RespError::InvalidFrame(format!(
+            "unknown frame prefix: {:?}",
+            **prefix as char
+        ))),
     }
 }
 
@@ -59,7 +84,7 @@ impl RespDecode for RespNull {
     }
 
     fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
-        todo!()
+        fixed_frame_length(buf, 3)
     }
 }
 
@@ -70,20 +95,23 @@ impl RespDecode for SimpleString {
 
         Ok(SimpleString(content.to_string()))
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        simple_frame_length(buf)
+    }
 }
 
 impl RespDecode for BulkString {
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
-        let (end, len) = parse_length(buf, "$")?;
-        let remained = &buf[end + CRLF_LEN..];
-        if remained.len() < len + CRLF_LEN {
-            return Err(RespError::NotComplete);
-        }
-
-        buf.advance(end + CRLF_LEN);
-        let data = buf.split_to(len + CRLF_LEN);
+        Ok(BulkString(extract_bulk_data(buf, "$")?))
+    }
 
-        Ok(BulkString(data[..len].to_vec()))
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        // $-1\r\n 是 null bulk string
+        if buf.starts_with(b"$-1\r\n") {
+            return Ok(5);
+        }
+        bulk_frame_length(buf, "$")
     }
 }
 
@@ -93,6 +121,10 @@ impl RespDecode for RespNullBulkString {
 
         Ok(RespNullBulkString)
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        fixed_frame_length(buf, 5)
+    }
 }
 
 impl RespDecode for SimpleError {
@@ -102,6 +134,10 @@ impl RespDecode for SimpleError {
 
         Ok(SimpleError(content.to_string()))
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        simple_frame_length(buf)
+    }
 }
 
 impl RespDecode for bool {
@@ -114,6 +150,10 @@ impl RespDecode for bool {
             },
         }
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        fixed_frame_length(buf, 4)
+    }
 }
 
 impl RespDecode for i64 {
@@ -123,6 +163,10 @@ impl RespDecode for i64 {
 
         Ok(content.parse()?)
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        simple_frame_length(buf)
+    }
 }
 
 impl RespDecode for f64 {
@@ -132,10 +176,16 @@ impl RespDecode for f64 {
 
         Ok(content.parse()?)
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        simple_frame_length(buf)
+    }
 }
 
 impl RespDecode for RespArray {
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        // 先确认整个 frame 都到了，避免只消费了一部分
+        Self::expect_length(buf)?;
         let content = extract_simple_frame_data(buf, "*")?;
         let len = content.parse::<i64>()? as usize;
 
@@ -147,6 +197,10 @@ impl RespDecode for RespArray {
 
         Ok(RespArray(frames))
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        aggregate_length(buf, "*", 1, 0)
+    }
 }
 
 impl RespDecode for RespNullArray {
@@ -155,26 +209,38 @@ impl RespDecode for RespNullArray {
 
         Ok(RespNullArray)
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        fixed_frame_length(buf, 5)
+    }
 }
 
 impl RespDecode for RespMap {
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        // 先确认整个 frame 都到了，避免只消费了一部分
+        Self::expect_length(buf)?;
         let content = extract_simple_frame_data(buf, "%")?;
         let len = content.parse::<i64>()? as usize;
 
         let mut frames: BTreeMap<String, RespFrame> = BTreeMap::new();
         for _ in 0..len {
-            let key = SimpleString::decode(buf)?;
+            let key = decode_map_key(buf)?;
             let value = RespFrame::decode(buf)?;
-            frames.insert(key.0, value);
+            frames.insert(key, value);
         }
 
         Ok(RespMap(frames))
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        aggregate_length(buf, "%", 2, 0)
+    }
 }
 
 impl RespDecode for RespSet {
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        // 先确认整个 frame 都到了，避免只消费了一部分
+        Self::expect_length(buf)?;
         let content = extract_simple_frame_data(buf, "~")?;
         let len = content.parse::<i64>()? as usize;
 
@@ -186,8 +252,118 @@ impl RespDecode for RespSet {
 
         Ok(RespSet(frames))
     }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        aggregate_length(buf, "~", 1, 0)
+    }
+}
+
+impl RespDecode for BigNumber {
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let content = extract_simple_frame_data(buf, "(")?;
+        let digits = content.strip_prefix(['+', '-']).unwrap_or(&content);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(RespError::InvalidFrame(format!(
+                "invalid big number: {}",
+                content
+            )));
+        }
+
+        Ok(BigNumber(content))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        simple_frame_length(buf)
+    }
+}
+
+impl RespDecode for BulkError {
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        Ok(BulkError(extract_bulk_data(buf, "!")?))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        bulk_frame_length(buf, "!")
+    }
 }
 
+impl RespDecode for VerbatimString {
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        let data = extract_bulk_data(buf, "=")?;
+        // 数据以 3 个字符的格式和 ':' 开头
+        if data.len() < 4 || data[3] != b':' {
+            return Err(RespError::InvalidFrame(
+                "verbatim string need a format like 'txt:'".into(),
+            ));
+        }
+
+        Ok(VerbatimString {
+            format: String::from_utf8_lossy(&data[..3]).into(),
+            data: data[4..].to_vec(),
+        })
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        bulk_frame_length(buf, "=")
+    }
+}
+
+impl RespDecode for RespPush {
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        // 先确认整个 frame 都到了，避免只消费了一部分
+        Self::expect_length(buf)?;
+        let content = extract_simple_frame_data(buf, ">")?;
+        let len = content.parse::<usize>()?;
+
+        let mut frames = Vec::with_capacity(len);
+        for _ in 0..len {
+            frames.push(RespFrame::decode(buf)?);
+        }
+
+        Ok(RespPush(frames))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        aggregate_length(buf, ">", 1, 0)
+    }
+}
+
+impl RespDecode for RespAttribute {
+    fn decode(buf: &mut BytesMut) -> Result<Self, RespError> {
+        // 先确认整个 frame 都到了，避免只消费了一部分
+        Self::expect_length(buf)?;
+        let content = extract_simple_frame_data(buf, "|")?;
+        let len = content.parse::<usize>()?;
+
+        let mut attrs = BTreeMap::new();
+        for _ in 0..len {
+            let key = decode_map_key(buf)?;
+            let value = RespFrame::decode(buf)?;
+            attrs.insert(key, value);
+        }
+        let frame = RespFrame::decode(buf)?;
+
+        Ok(RespAttribute::new(RespMap(attrs), frame))
+    }
+
+    fn expect_length(buf: &[u8]) -> Result<usize, RespError> {
+        frame_length(buf, 0)
+    }
+}
+
+/// map 的 key 可以是 simple string 也可以是 bulk string
+fn decode_map_key(buf: &mut BytesMut) -> Result<String, RespError> {
+    match RespFrame::decode(buf)? {
+        RespFrame::SimpleString(s) => Ok(s.0),
+        RespFrame::BulkString(s) => Ok(String::from_utf8_lossy(&s.0).into()),
+        frame => Err(RespError::InvalidFrameType(format!(
+            "expect string as map key, but got: {:?}",
+            frame
+        ))),
+    }
+}
+
+#[cfg(test)]
 mod test_decode {
     use crate::redis_core::*;
     use anyhow::Result;
@@ -317,4 +493,97 @@ mod test_decode {
 
         Ok(())
     }
+
+    #[test]
+    fn test_partial_and_pipelined_frames() -> Result<()> {
+        let mut buf = BytesMut::new();
+
+        // 不完整的数组不能消费任何数据
+        buf.extend_from_slice(b"*2\r\n$3\r\nget\r\n$5\r\nhel");
+        assert_eq!(RespFrame::expect_length(&buf), Err(RespError::NotComplete));
+        assert_eq!(RespFrame::decode(&mut buf), Err(RespError::NotComplete));
+        assert_eq!(buf.len(), 20);
+
+        buf.extend_from_slice(b"lo\r\n*1\r\n$4\r\nping\r\n");
+        assert_eq!(RespFrame::expect_length(&buf)?, 24);
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(
+            frame,
+            RespFrame::Array(RespArray::new(["get".into(), "hello".into()]))
+        );
+        let frame = RespFrame::decode(&mut buf)?;
+        assert_eq!(frame, RespFrame::Array(RespArray::new(["ping".into()])));
+        assert!(buf.is_empty());
+
+        buf.extend_from_slice(b"*-1\r\n$-1\r\n");
+        assert_eq!(
+            RespFrame::decode(&mut buf)?,
+            RespFrame::NullArray(RespNullArray)
+        );
+        assert_eq!(
+            RespFrame::decode(&mut buf)?,
+            RespFrame::NullBulkString(RespNullBulkString)
+        );
+
+        buf.extend_from_slice(b"$-2\r\n");
+        assert_eq!(
+            RespFrame::expect_length(&buf),
+            Err(RespError::InvalidFrameLength(-2))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nesting_depth_limit() -> Result<()> {
+        // 深层嵌套直接报错，不会把栈耗尽，也不需要等数据到齐
+        let buf = "*1\r\n".repeat(200_000);
+        assert!(matches!(
+            RespFrame::expect_length(buf.as_bytes()),
+            Err(RespError::InvalidFrame(_))
+        ));
+        let buf = ">1\r\n%1\r\n+k\r\n".repeat(MAX_NESTING_DEPTH);
+        assert!(matches!(
+            RespFrame::decode(&mut BytesMut::from(buf.as_bytes())),
+            Err(RespError::InvalidFrame(_))
+        ));
+
+        // 上限之内的嵌套可以正常解析
+        let mut buf = BytesMut::from("*1\r\n".repeat(MAX_NESTING_DEPTH - 1).as_bytes());
+        buf.extend_from_slice(b"$2\r\nok\r\n");
+        let mut frame = RespFrame::decode(&mut buf)?;
+        for _ in 0..MAX_NESTING_DEPTH - 1 {
+            let RespFrame::Array(mut array) = frame else {
+                panic!("expect array");
+            };
+            frame = array.0.remove(0);
+        }
+        assert_eq!(frame, "ok".into());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_length_limits() {
+        let bulk = format!("${}\r\n", MAX_BULK_LEN + 1);
+        assert_eq!(
+            RespFrame::expect_length(bulk.as_bytes()),
+            Err(RespError::InvalidFrameLength(MAX_BULK_LEN as isize + 1))
+        );
+        assert_eq!(
+            RespFrame::expect_length(b"$1000000000000\r\n"),
+            Err(RespError::InvalidFrameLength(1_000_000_000_000))
+        );
+        let array = format!("*{}\r\n", MAX_MULTIBULK_LEN + 1);
+        assert_eq!(
+            RespFrame::expect_length(array.as_bytes()),
+            Err(RespError::InvalidFrameLength(MAX_MULTIBULK_LEN + 1))
+        );
+        // map 的元素个数是 key/value 的对数
+        let map = format!("%{}\r\n", MAX_MULTIBULK_LEN);
+        assert_eq!(
+            RespFrame::expect_length(map.as_bytes()),
+            Err(RespError::NotComplete)
+        );
+    }
 }
@@ -17,14 +17,19 @@ impl RespEncode for RespFrame {
             RespFrame::Double(d) => d.encode(),
             RespFrame::Map(m) => m.encode(),
             RespFrame::Set(s) => s.encode(),
+            RespFrame::BigNumber(n) => n.encode(),
+            RespFrame::BulkError(e) => e.encode(),
+            RespFrame::VerbatimString(s) => s.encode(),
+            RespFrame::Push(p) => p.encode(),
+            RespFrame::Attribute(a) => a.encode(),
         }
     }
 }
 
 impl RespEncode for i64 {
     fn encode(self) -> Vec<u8> {
-        let sign = if self < 0 { "" } else { "+" };
-        format!("{}{}\r\n", sign, self).into_bytes()
+        // 协议允许 `:+1`，但 hiredis 等客户端只认负号
+        format!(":{}\r\n", self).into_bytes()
     }
 }
 
@@ -42,11 +47,7 @@ impl RespEncode for SimpleError {
 
 impl RespEncode for BulkString {
     fn encode(self) -> Vec<u8> {
-        let mut buf: Vec<u8> = Vec::with_capacity(self.0.len() + 16);
-        buf.extend_from_slice(format!("${}\r\n", self.0.len()).as_bytes());
-        buf.extend_from_slice(&self.0);
-        buf.extend_from_slice(b"\r\n");
-        buf
+        encode_bulk("$", &[&self.0])
     }
 }
 
@@ -73,7 +74,7 @@ impl RespEncode for bool {
         if self {
             b"#t\r\n".into()
         } else {
-            b"$f\r\n".into()
+            b"#f\r\n".into()
         }
     }
 }
@@ -81,11 +82,16 @@ impl RespEncode for bool {
 impl RespEncode for f64 {
     fn encode(self) -> Vec<u8> {
         let mut buf = Vec::with_capacity(32);
-        let ret = if self.abs() > 1e+8 {
+        // 无穷大和 NaN 按协议写成 inf/-inf/nan；`{:+}` 对 -0.0 输出 "-0"
+        let ret = if self.is_nan() {
+            ",nan\r\n".to_string()
+        } else if self.is_infinite() {
+            let sign = if self < 0.0 { "-" } else { "" };
+            format!(",{}inf\r\n", sign)
+        } else if self.abs() > 1e+8 {
             format!(",{:+e}\r\n", self)
         } else {
-            let sign = if self < 0.0 { "" } else { "+" };
-            format!(",{}{}\r\n", sign, self)
+            format!(",{:+}\r\n", self)
         };
 
         buf.extend_from_slice(ret.as_bytes());
@@ -128,3 +134,107 @@ impl RespEncode for RespSet {
         buf
     }
 }
+
+impl RespEncode for BigNumber {
+    fn encode(self) -> Vec<u8> {
+        format!("({}\r\n", self.0).into_bytes()
+    }
+}
+
+impl RespEncode for BulkError {
+    fn encode(self) -> Vec<u8> {
+        encode_bulk("!", &[&self.0])
+    }
+}
+
+impl RespEncode for VerbatimString {
+    fn encode(self) -> Vec<u8> {
+        encode_bulk("=", &[self.format.as_bytes(), b":", &self.data])
+    }
+}
+
+impl RespEncode for RespPush {
+    fn encode(self) -> Vec<u8> {
+        let slf = self.0;
+        let mut buf: Vec<u8> = Vec::with_capacity(slf.len() * RESP_FRAME_CAP);
+        buf.extend_from_slice(format!(">{}\r\n", slf.len()).as_bytes());
+        for frame in slf {
+            buf.extend(frame.encode());
+        }
+        buf
+    }
+}
+
+impl RespEncode for RespAttribute {
+    fn encode(self) -> Vec<u8> {
+        let attrs = self.attrs.0;
+        let mut buf: Vec<u8> = Vec::with_capacity((attrs.len() + 1) * RESP_FRAME_CAP);
+        buf.extend_from_slice(format!("|{}\r\n", attrs.len()).as_bytes());
+        for (key, value) in attrs {
+            buf.extend(SimpleString(key).encode());
+            buf.extend(value.encode());
+        }
+        buf.extend(self.frame.encode());
+        buf
+    }
+}
+
+/// 长度 + "\r\n" + 数据 + "\r\n"，数据由几段拼起来
+fn encode_bulk(prefix: &str, parts: &[&[u8]]) -> Vec<u8> {
+    let len: usize = parts.iter().map(|part| part.len()).sum();
+    let mut buf: Vec<u8> = Vec::with_capacity(len + 16);
+    buf.extend_from_slice(format!("{}{}\r\n", prefix, len).as_bytes());
+    for part in parts {
+        buf.extend_from_slice(part);
+    }
+    buf.extend_from_slice(b"\r\n");
+    buf
+}
+
+#[cfg(test)]
+mod test_encode {
+    use crate::redis_core::*;
+
+    #[test]
+    fn test_integer_encode() {
+        assert_eq!(RespFrame::Integer(123).encode(), b":123\r\n");
+        assert_eq!(RespFrame::Integer(-123).encode(), b":-123\r\n");
+    }
+
+    #[test]
+    fn test_boolean_encode() {
+        assert_eq!(RespFrame::Boolean(true).encode(), b"#t\r\n");
+        assert_eq!(RespFrame::Boolean(false).encode(), b"#f\r\n");
+    }
+
+    #[test]
+    fn test_double_encode() {
+        assert_eq!(RespFrame::Double(1.5).encode(), b",+1.5\r\n");
+        assert_eq!(RespFrame::Double(-0.0).encode(), b",-0\r\n");
+        assert_eq!(RespFrame::Double(f64::NEG_INFINITY).encode(), b",-inf\r\n");
+        assert_eq!(RespFrame::Double(f64::NAN).encode(), b",nan\r\n");
+    }
+
+    #[test]
+    fn test_resp3_encode() {
+        let frame: RespFrame = VerbatimString::new("txt", "Some string").into();
+        assert_eq!(frame.encode(), b"=15\r\ntxt:Some string\r\n");
+        let frame: RespFrame = BulkError::new("SYNTAX invalid syntax").into();
+        assert_eq!(frame.encode(), b"!21\r\nSYNTAX invalid syntax\r\n");
+
+        let attrs = RespMap::new([("ttl".to_string(), RespFrame::Integer(3))].into());
+        let frame: RespFrame = RespAttribute::new(attrs, "v".into()).into();
+        assert_eq!(frame.encode(), b"|1\r\n+ttl\r\n:3\r\n$1\r\nv\r\n");
+    }
+
+    #[test]
+    fn test_array_encode() {
+        let frame: RespFrame = RespArray::new([
+            "get".into(),
+            RespNullBulkString.into(),
+            RespFrame::Integer(1),
+        ])
+        .into();
+        assert_eq!(frame.encode(), b"*3\r\n$3\r\nget\r\n$-1\r\n:1\r\n");
+    }
+}
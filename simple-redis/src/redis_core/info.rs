@@ -0,0 +1,128 @@
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use super::{backend::Backend, memory, replication};
+
+/// INFO stats 的计数器，clone 之后共享同一份数据
+#[derive(Debug, Clone)]
+pub struct Stats(Arc<Counters>);
+
+#[derive(Debug)]
+struct Counters {
+    started: Instant,
+    connections_received: AtomicU64,
+    rejected_connections: AtomicU64,
+    commands_processed: AtomicU64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self(Arc::new(Counters {
+            started: Instant::now(),
+            connections_received: AtomicU64::new(0),
+            rejected_connections: AtomicU64::new(0),
+            commands_processed: AtomicU64::new(0),
+        }))
+    }
+}
+
+impl Stats {
+    pub fn connection_received(&self) {
+        self.0.connections_received.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 超过 maxclients 被拒绝的连接
+    pub fn connection_rejected(&self) {
+        self.0.rejected_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn command_processed(&self) {
+        self.0.commands_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn uptime_secs(&self) -> u64 {
+        self.0.started.elapsed().as_secs()
+    }
+}
+
+/// # Server
+pub fn server(backend: &Backend) -> String {
+    let port = backend.config().read(|c| c.port);
+    let mode = match backend.cluster().is_enabled() {
+        true => "cluster",
+        false => "standalone",
+    };
+    let uptime = backend.stats().uptime_secs();
+    format!(
+        "redis_version:{}\r\nredis_mode:{}\r\nos:{} {}\r\nprocess_id:{}\r\ntcp_port:{}\r\n\
+         uptime_in_seconds:{}\r\nuptime_in_days:{}\r\n",
+        env!("CARGO_PKG_VERSION"),
+        mode,
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        std::process::id(),
+        port,
+        uptime,
+        uptime / 86400
+    )
+}
+
+/// # Clients
+pub fn clients(backend: &Backend) -> String {
+    format!(
+        "connected_clients:{}\r\nmaxclients:{}\r\n",
+        backend.clients().len(),
+        backend.config().read(|c| c.maxclients)
+    )
+}
+
+/// # Memory：used_memory 是进程实际分配的内存，used_memory_dataset 是所有 key 估算的内存，
+/// maxmemory 限制的是后者
+pub fn memory(backend: &Backend) -> String {
+    let (used, peak) = (memory::used(), memory::peak());
+    let (maxmemory, policy) = backend.config().read(|c| (c.maxmemory, c.maxmemory_policy));
+    format!(
+        "used_memory:{}\r\nused_memory_human:{}\r\nused_memory_peak:{}\r\nused_memory_peak_human:{}\r\n\
+         used_memory_dataset:{}\r\nmaxmemory:{}\r\nmaxmemory_human:{}\r\nmaxmemory_policy:{}\r\n",
+        used,
+        memory::human(used),
+        peak,
+        memory::human(peak),
+        backend.used_memory(),
+        maxmemory,
+        memory::human(maxmemory),
+        policy
+    )
+}
+
+/// # Stats，包括复制的全量、增量同步次数
+pub fn stats(backend: &Backend) -> String {
+    let stats = &backend.stats().0;
+    format!(
+        "total_connections_received:{}\r\ntotal_commands_processed:{}\r\nrejected_connections:{}\r\n\
+         expired_keys:{}\r\nevicted_keys:{}\r\n{}",
+        stats.connections_received.load(Ordering::Relaxed),
+        stats.commands_processed.load(Ordering::Relaxed),
+        stats.rejected_connections.load(Ordering::Relaxed),
+        backend.expired_keys(),
+        backend.evicted_keys(),
+        replication::info_stats(backend)
+    )
+}
+
+/// # Keyspace，只有一个 db0，没有 key 时这一节是空的
+pub fn keyspace(backend: &Backend) -> String {
+    match backend.dbsize() {
+        0 => String::new(),
+        keys => format!(
+            "db0:keys={},expires={},avg_ttl=0\r\n",
+            keys,
+            backend.volatile_count()
+        ),
+    }
+}
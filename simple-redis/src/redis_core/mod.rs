@@ -1,6 +1,22 @@
+pub mod backend;
+pub mod clients;
+pub mod cluster;
+pub mod codec;
 pub mod command;
+pub mod config;
 pub mod decode;
 pub mod encode;
+pub mod info;
+pub mod memory;
+pub mod pattern;
+pub mod persistence;
+pub mod propagate;
+pub mod protocol;
+pub mod pubsub;
+pub mod replication;
+pub mod server;
+pub mod session;
+pub mod slowlog;
 
 use bytes::{Buf, BytesMut};
 use std::collections::BTreeMap;
@@ -8,12 +24,18 @@ use thiserror::Error;
 
 const CRLF_LEN: usize = 2;
 
+/// 聚合类型最多嵌套的层数，防止深层嵌套的 frame 把解析时的栈耗尽
+pub const MAX_NESTING_DEPTH: usize = 128;
+/// bulk string 的最大长度，和 redis 的 proto-max-bulk-len 默认值一样
+pub const MAX_BULK_LEN: usize = 512 * 1024 * 1024;
+/// 聚合类型最多包含的元素个数，和 redis 的 multibulk 长度上限一样
+pub const MAX_MULTIBULK_LEN: isize = i32::MAX as isize;
+
 pub trait RespEncode {
     fn encode(self) -> Vec<u8>;
 }
 
 pub trait RespDecode: Sized {
-    const PREFIX: &'static str = "";
     fn decode(buf: &mut BytesMut) -> Result<Self, RespError>;
     // fn expect_prefix(buf: &mut BytesMut) -> Result<(), RespError>;
     fn expect_length(buf: &[u8]) -> Result<usize, RespError>;
@@ -33,6 +55,11 @@ pub enum RespFrame {
     Double(f64),
     Map(RespMap),
     Set(RespSet),
+    BigNumber(BigNumber),
+    BulkError(BulkError),
+    VerbatimString(VerbatimString),
+    Push(RespPush),
+    Attribute(RespAttribute),
 }
 
 // 为了区分 SimpleString 和 SimpleError，实际上他们都是 String
@@ -54,6 +81,26 @@ pub struct RespNullArray;
 pub struct RespMap(BTreeMap<String, RespFrame>);
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct RespSet(Vec<RespFrame>);
+// 超过 i64 范围的整数，保存十进制的字符串
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct BigNumber(String);
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct BulkError(Vec<u8>);
+// format 是 3 个字符，比如 txt、mkd
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct VerbatimString {
+    format: String,
+    data: Vec<u8>,
+}
+// 服务器主动推送的数据，比如 RESP3 的 pub/sub 消息
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct RespPush(Vec<RespFrame>);
+// 附加在后面那个 frame 上的属性，frame 本身才是真正的回复
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct RespAttribute {
+    attrs: RespMap,
+    frame: Box<RespFrame>,
+}
 
 #[derive(Debug, Error, PartialEq)]
 pub enum RespError {
@@ -66,9 +113,17 @@ pub enum RespError {
     #[error("frame is not complete")]
     NotComplete,
     #[error("Parse int error: {0}")]
-    parseIntError(#[from] std::num::ParseIntError), // 从 std::num::ParseIntError 转换到自定义的 parseIntError
+    ParseIntError(#[from] std::num::ParseIntError), // 从 std::num::ParseIntError 转换到自定义的 ParseIntError
     #[error("Parse float error: {0}")]
-    parseFloatError(#[from] std::num::ParseFloatError), // 从 std::num::ParseFloatError 转换到自定义的 parseFloatError
+    ParseFloatError(#[from] std::num::ParseFloatError), // 从 std::num::ParseFloatError 转换到自定义的 ParseFloatError
+    #[error("I/O error: {0}")]
+    Io(String), // std::io::Error 没有实现 PartialEq，只保存错误信息
+}
+
+impl From<std::io::Error> for RespError {
+    fn from(e: std::io::Error) -> Self {
+        RespError::Io(e.to_string())
+    }
 }
 
 impl SimpleString {
@@ -77,6 +132,69 @@ impl SimpleString {
     }
 }
 
+impl SimpleError {
+    fn new(s: impl Into<String>) -> Self {
+        Self(s.into())
+    }
+}
+
+impl BulkString {
+    fn new(s: impl Into<Vec<u8>>) -> Self {
+        Self(s.into())
+    }
+}
+
+#[cfg(test)]
+impl BigNumber {
+    fn new(s: impl Into<String>) -> Self {
+        Self(s.into())
+    }
+}
+
+#[cfg(test)]
+impl BulkError {
+    fn new(s: impl Into<Vec<u8>>) -> Self {
+        Self(s.into())
+    }
+}
+
+#[cfg(test)]
+impl VerbatimString {
+    fn new(format: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            format: format.into(),
+            data: data.into(),
+        }
+    }
+}
+
+impl RespMap {
+    fn new(map: BTreeMap<String, RespFrame>) -> Self {
+        Self(map)
+    }
+}
+
+impl RespSet {
+    fn new(v: impl Into<Vec<RespFrame>>) -> Self {
+        Self(v.into())
+    }
+}
+
+impl RespPush {
+    fn new(v: impl Into<Vec<RespFrame>>) -> Self {
+        Self(v.into())
+    }
+}
+
+impl RespAttribute {
+    fn new(attrs: RespMap, frame: RespFrame) -> Self {
+        Self {
+            attrs,
+            frame: Box::new(frame),
+        }
+    }
+}
+
 fn extract_fixed_data(
     buf: &mut BytesMut,
     extract: &str,
@@ -112,7 +230,7 @@ fn extract_simple_frame_data(buf: &mut BytesMut, prefix: &str) -> Result<String,
     }
 
     // end is "\r\n"
-    let end = match find_nth_crlf(&buf, 1) {
+    let end = match find_nth_crlf(buf, 1) {
         Some(end) => end,
         None => return Err(RespError::NotComplete),
     };
@@ -125,7 +243,7 @@ fn extract_simple_frame_data(buf: &mut BytesMut, prefix: &str) -> Result<String,
 
 fn find_nth_crlf(buf: &[u8], nth: usize) -> Option<usize> {
     let mut count: usize = 0;
-    for i in 0..buf.len() - 1 {
+    for i in 0..buf.len().saturating_sub(1) {
         if buf[i] == b'\r' && buf[i + 1] == b'\n' {
             count += 1;
             if count == nth {
@@ -137,8 +255,12 @@ fn find_nth_crlf(buf: &[u8], nth: usize) -> Option<usize> {
     None
 }
 
-fn parse_length(buf: &mut BytesMut, prefix: &str) -> Result<(usize, usize), RespError> {
-    let end = match find_nth_crlf(&buf, 1) {
+fn parse_length<T>(buf: &[u8], prefix: &str) -> Result<(usize, T), RespError>
+where
+    T: std::str::FromStr,
+    RespError: From<T::Err>,
+{
+    let end = match find_nth_crlf(buf, 1) {
         Some(end) => end,
         None => return Err(RespError::NotComplete),
     };
@@ -146,17 +268,71 @@ fn parse_length(buf: &mut BytesMut, prefix: &str) -> Result<(usize, usize), Resp
     Ok((end, content.parse()?))
 }
 
-fn calc_total_length(buf: &[u8], len: usize, prefix: &str) -> Result<usize, RespError> {
-    let data = &buf[len + CRLF_LEN..];
-    match prefix {
-        "*" | "~" => find_nth_crlf(data, len)
-            .map(|end| end + CRLF_LEN + len)
-            .ok_or(RespError::NotComplete),
-        "%" => find_nth_crlf(data, len * 2)
-            .map(|end| end + CRLF_LEN + len)
-            .ok_or(RespError::NotComplete),
-        _ => Ok(len + CRLF_LEN),
+/// `$`、`!`、`=` 开头的 frame：长度 + "\r\n" + 数据 + "\r\n"，不处理长度为 -1 的 null
+fn bulk_frame_length(buf: &[u8], prefix: &str) -> Result<usize, RespError> {
+    let (end, len) = parse_length::<isize>(buf, prefix)?;
+    if len < 0 || len as usize > MAX_BULK_LEN {
+        return Err(RespError::InvalidFrameLength(len));
+    }
+    let total = end + CRLF_LEN + len as usize + CRLF_LEN;
+    if buf.len() < total {
+        return Err(RespError::NotComplete);
+    }
+    Ok(total)
+}
+
+fn extract_bulk_data(buf: &mut BytesMut, prefix: &str) -> Result<Vec<u8>, RespError> {
+    if !buf.starts_with(prefix.as_bytes()) {
+        return Err(RespError::InvalidFrameType(format!(
+            "Bulk Frame need char '{}' start",
+            prefix
+        )));
+    }
+    let total = bulk_frame_length(buf, prefix)?;
+    let (end, _) = parse_length::<usize>(buf, prefix)?;
+    let data = buf.split_to(total);
+    Ok(data[end + CRLF_LEN..total - CRLF_LEN].to_vec())
+}
+
+/// 简单类型的 frame 以第一个 "\r\n" 结尾
+fn simple_frame_length(buf: &[u8]) -> Result<usize, RespError> {
+    find_nth_crlf(buf, 1)
+        .map(|end| end + CRLF_LEN)
+        .ok_or(RespError::NotComplete)
+}
+
+/// 嵌套在第 `depth` 层的聚合类型 frame 的长度：头部 + 依次累加子 frame 的长度，
+/// 每个元素有 `per_entry` 个子 frame（map 是 key 和 value 两个），子 frame 可能还是聚合类型
+fn aggregate_length(
+    buf: &[u8],
+    prefix: &str,
+    per_entry: isize,
+    depth: usize,
+) -> Result<usize, RespError> {
+    if depth >= MAX_NESTING_DEPTH {
+        return Err(RespError::InvalidFrame(format!(
+            "nesting depth exceeds {}",
+            MAX_NESTING_DEPTH
+        )));
+    }
+    let (end, count) = parse_length::<isize>(buf, prefix)?;
+    // 只有 null 的长度可以是 -1
+    if !(-1..=MAX_MULTIBULK_LEN).contains(&count) {
+        return Err(RespError::InvalidFrameLength(count));
+    }
+    let mut total = end + CRLF_LEN;
+    for _ in 0..count.max(0) * per_entry {
+        total += decode::frame_length(&buf[total..], depth + 1)?;
     }
+    Ok(total)
+}
+
+/// null、bool 这些固定长度的 frame，也要确认数据已经到齐
+fn fixed_frame_length(buf: &[u8], len: usize) -> Result<usize, RespError> {
+    if buf.len() < len {
+        return Err(RespError::NotComplete);
+    }
+    Ok(len)
 }
 
 impl RespArray {
@@ -167,7 +343,7 @@ impl RespArray {
 
 impl From<&[u8]> for SimpleString {
     fn from(s: &[u8]) -> Self {
-        s.into()
+        SimpleString::new(String::from_utf8_lossy(s))
     }
 }
 
@@ -191,7 +367,97 @@ impl From<&str> for RespFrame {
 
 impl From<&[u8]> for RespFrame {
     fn from(s: &[u8]) -> Self {
-        s.into()
+        BulkString::new(s).into()
+    }
+}
+
+impl From<Vec<u8>> for RespFrame {
+    fn from(s: Vec<u8>) -> Self {
+        BulkString::new(s).into()
+    }
+}
+
+impl From<i64> for RespFrame {
+    fn from(i: i64) -> Self {
+        RespFrame::Integer(i)
+    }
+}
+
+impl From<SimpleError> for RespFrame {
+    fn from(e: SimpleError) -> Self {
+        RespFrame::Error(e)
+    }
+}
+
+impl From<RespArray> for RespFrame {
+    fn from(a: RespArray) -> Self {
+        RespFrame::Array(a)
+    }
+}
+
+impl From<RespNullBulkString> for RespFrame {
+    fn from(n: RespNullBulkString) -> Self {
+        RespFrame::NullBulkString(n)
+    }
+}
+
+impl From<RespNull> for RespFrame {
+    fn from(n: RespNull) -> Self {
+        RespFrame::Null(n)
+    }
+}
+
+impl From<RespNullArray> for RespFrame {
+    fn from(n: RespNullArray) -> Self {
+        RespFrame::NullArray(n)
+    }
+}
+
+impl From<f64> for RespFrame {
+    fn from(f: f64) -> Self {
+        RespFrame::Double(f)
+    }
+}
+
+impl From<RespMap> for RespFrame {
+    fn from(v: RespMap) -> Self {
+        RespFrame::Map(v)
+    }
+}
+
+impl From<RespSet> for RespFrame {
+    fn from(v: RespSet) -> Self {
+        RespFrame::Set(v)
+    }
+}
+
+impl From<BigNumber> for RespFrame {
+    fn from(v: BigNumber) -> Self {
+        RespFrame::BigNumber(v)
+    }
+}
+
+impl From<BulkError> for RespFrame {
+    fn from(v: BulkError) -> Self {
+        RespFrame::BulkError(v)
+    }
+}
+
+impl From<VerbatimString> for RespFrame {
+    fn from(v: VerbatimString) -> Self {
+        RespFrame::VerbatimString(v)
+    }
+}
+
+impl From<RespPush> for RespFrame {
+    fn from(v: RespPush) -> Self {
+        RespFrame::Push(v)
+    }
+}
+
+impl From<RespAttribute> for RespFrame {
+    fn from(v: RespAttribute) -> Self {
+        RespFrame::Attribute(v)
     }
 }
 
@@ -0,0 +1,235 @@
+use std::{
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use bytes::BytesMut;
+use tracing::warn;
+
+use super::PersistenceError;
+use crate::redis_core::{
+    backend::Backend,
+    command::{Command, CommandExecutor},
+    config::ConfigError,
+    propagate::{command_name, is_write_command},
+    protocol::Protocol,
+    RespDecode, RespError, RespFrame,
+};
+
+/// appendfsync：什么时候把 AOF 刷到磁盘
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AppendFsync {
+    /// 每个写命令都 fsync
+    Always,
+    /// 最多每秒 fsync 一次，宕机最多丢一秒的数据
+    #[default]
+    EverySec,
+    /// 只写到操作系统，由操作系统决定什么时候刷盘
+    No,
+}
+
+impl FromStr for AppendFsync {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "always" => Ok(AppendFsync::Always),
+            "everysec" => Ok(AppendFsync::EverySec),
+            "no" => Ok(AppendFsync::No),
+            _ => Err(ConfigError::InvalidValue("appendfsync".into(), s.into())),
+        }
+    }
+}
+
+impl fmt::Display for AppendFsync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            AppendFsync::Always => "always",
+            AppendFsync::EverySec => "everysec",
+            AppendFsync::No => "no",
+        };
+        f.write_str(s)
+    }
+}
+
+/// 追加写命令的日志，内容是 RESP 编码的命令数组，和客户端发来的格式一样。
+/// 只记录最近一次快照之后的写命令，启动时先加载快照再重放 AOF
+#[derive(Debug)]
+pub struct Aof {
+    path: PathBuf,
+    file: File,
+    fsync: AppendFsync,
+    last_fsync: Instant,
+    dirty: bool,
+    // 后台保存快照期间的写命令，快照完成后成为新的 AOF
+    rewrite_buf: Option<Vec<u8>>,
+}
+
+impl Aof {
+    pub fn open(path: impl Into<PathBuf>, fsync: AppendFsync) -> io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            fsync,
+            last_fsync: Instant::now(),
+            dirty: false,
+            rewrite_buf: None,
+        })
+    }
+
+    /// 追加 RESP 编码好的命令
+    pub fn append(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.file.write_all(buf)?;
+        if let Some(rewrite_buf) = self.rewrite_buf.as_mut() {
+            rewrite_buf.extend_from_slice(buf);
+        }
+        self.dirty = true;
+        match self.fsync {
+            AppendFsync::Always => self.fsync(),
+            AppendFsync::EverySec if self.last_fsync.elapsed() >= Duration::from_secs(1) => {
+                self.fsync()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// CONFIG SET appendfsync 之后立即生效
+    pub fn set_fsync(&mut self, fsync: AppendFsync) {
+        self.fsync = fsync;
+    }
+
+    /// everysec 模式下由后台任务定期调用，保证空闲时数据也能在一秒内落盘
+    pub fn fsync_if_dirty(&mut self) -> io::Result<()> {
+        match self.fsync {
+            AppendFsync::EverySec if self.dirty => self.fsync(),
+            _ => Ok(()),
+        }
+    }
+
+    fn fsync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        self.last_fsync = Instant::now();
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// 开始保存快照，之后的写命令同时记到 rewrite_buf
+    pub fn start_rewrite(&mut self) {
+        self.rewrite_buf = Some(vec![]);
+    }
+
+    /// 快照保存失败，继续使用原来的 AOF；删掉新的 AOF 的临时文件，免得启动时被当成已经提交的 AOF
+    pub fn abort_rewrite(&mut self) {
+        self.rewrite_buf = None;
+        let tmp = temp_path(&self.path);
+        if let Err(e) = fs::remove_file(&tmp) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("failed to remove {:?}: {:?}", tmp, e);
+            }
+        }
+    }
+
+    /// 把快照之后的写命令写到临时文件，快照替换完成后再调用 install_rewrite
+    pub fn write_rewrite(&mut self) -> io::Result<PathBuf> {
+        let tmp = temp_path(&self.path);
+        let mut file = File::create(&tmp)?;
+        file.write_all(self.rewrite_buf.as_deref().unwrap_or_default())?;
+        file.sync_all()?;
+        Ok(tmp)
+    }
+
+    /// 用新的 AOF 替换原来的，之后追加到新的文件
+    pub fn install_rewrite(&mut self) -> io::Result<()> {
+        fs::rename(temp_path(&self.path), &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.rewrite_buf = None;
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+pub(super) fn temp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    tmp.into()
+}
+
+/// 重放 AOF 里的写命令，返回命令的个数。
+/// 末尾不完整的命令或事务（比如写到一半时宕机）会被截掉，和 redis 的 aof-load-truncated yes 一样
+pub fn replay(path: &Path, backend: &Backend) -> Result<usize, PersistenceError> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut buf = BytesMut::from(&data[..]);
+    let mut count = 0;
+    // MULTI 开始的位置和之后排队的命令，EXEC 时一起执行
+    let mut multi: Option<(usize, Vec<Command>)> = None;
+    while !buf.is_empty() {
+        let offset = data.len() - buf.len();
+        let frame = match RespFrame::expect_length(&buf) {
+            Ok(len) => RespFrame::decode(&mut buf.split_to(len))?,
+            Err(RespError::NotComplete) => break,
+            Err(e) => return Err(e.into()),
+        };
+        // AOF 里只有写命令和包着它们的 MULTI/EXEC
+        let cmd = match command_name(&frame).as_deref() {
+            Some("multi") if multi.is_none() => {
+                multi = Some((offset, vec![]));
+                continue;
+            }
+            Some("exec") if multi.is_some() => {
+                let (_, queued) = multi.take().unwrap_or_default();
+                count += queued.len();
+                queued.into_iter().for_each(|cmd| execute(cmd, backend));
+                continue;
+            }
+            Some(name) if is_write_command(name) => Command::try_from(frame)?,
+            _ => {
+                return Err(PersistenceError::Aof(format!(
+                    "unexpected frame {:?}",
+                    frame
+                )))
+            }
+        };
+        match multi.as_mut() {
+            Some((_, queued)) => queued.push(cmd),
+            None => {
+                execute(cmd, backend);
+                count += 1;
+            }
+        }
+    }
+
+    // 末尾不完整的命令或者没有 EXEC 的事务都截掉
+    let valid = match multi {
+        Some((offset, _)) => offset,
+        None => data.len() - buf.len(),
+    };
+    if valid < data.len() {
+        warn!(
+            "AOF {:?} is truncated, drop the last {} bytes",
+            path,
+            data.len() - valid
+        );
+        OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(valid as u64)?;
+    }
+    Ok(count)
+}
+
+fn execute(cmd: Command, backend: &Backend) {
+    if let RespFrame::Error(e) = cmd.execute(backend, Protocol::Resp2) {
+        warn!("AOF command failed: {:?}", e);
+    }
+}
@@ -0,0 +1,535 @@
+mod aof;
+mod snapshot;
+
+pub use aof::{Aof, AppendFsync};
+pub use snapshot::{SnapshotEntry, SnapshotError};
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use thiserror::Error;
+use tokio::time;
+use tracing::{error, info, warn};
+
+use super::{
+    backend::Backend, command::CommandError, config::ConfigError, propagate::Sinks, RespError,
+};
+
+#[derive(Debug, Error)]
+pub enum PersistenceError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid snapshot: {0}")]
+    Snapshot(#[from] SnapshotError),
+    #[error("invalid AOF: {0}")]
+    Aof(String),
+    #[error("Background save already in progress")]
+    InProgress,
+}
+
+impl From<RespError> for PersistenceError {
+    fn from(e: RespError) -> Self {
+        PersistenceError::Aof(e.to_string())
+    }
+}
+
+impl From<CommandError> for PersistenceError {
+    fn from(e: CommandError) -> Self {
+        PersistenceError::Aof(e.to_string())
+    }
+}
+
+/// 快照和 AOF 的配置，名字和 redis.conf 一样
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersistenceConfig {
+    pub dir: PathBuf,
+    pub dbfilename: String,
+    pub appendonly: bool,
+    pub appendfilename: String,
+    pub appendfsync: AppendFsync,
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        Self {
+            dir: ".".into(),
+            dbfilename: "dump.rdb".into(),
+            appendonly: false,
+            appendfilename: "appendonly.aof".into(),
+            appendfsync: AppendFsync::default(),
+        }
+    }
+}
+
+impl PersistenceConfig {
+    pub const NAMES: [&'static str; 5] = [
+        "dir",
+        "dbfilename",
+        "appendonly",
+        "appendfilename",
+        "appendfsync",
+    ];
+
+    /// 按名字设置一个选项，比如命令行参数 `--appendonly yes`
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), ConfigError> {
+        match name.to_ascii_lowercase().as_str() {
+            "dir" => self.dir = value.into(),
+            "dbfilename" => self.dbfilename = value.into(),
+            "appendfilename" => self.appendfilename = value.into(),
+            "appendfsync" => self.appendfsync = value.parse()?,
+            "appendonly" => {
+                self.appendonly = match value.to_ascii_lowercase().as_str() {
+                    "yes" => true,
+                    "no" => false,
+                    _ => return Err(ConfigError::InvalidValue("appendonly".into(), value.into())),
+                }
+            }
+            _ => return Err(ConfigError::Unknown(name.into())),
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "dir" => self.dir.display().to_string(),
+            "dbfilename" => self.dbfilename.clone(),
+            "appendonly" => if self.appendonly { "yes" } else { "no" }.to_string(),
+            "appendfilename" => self.appendfilename.clone(),
+            "appendfsync" => self.appendfsync.to_string(),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    pub fn snapshot_path(&self) -> PathBuf {
+        self.dir.join(&self.dbfilename)
+    }
+
+    pub fn aof_path(&self) -> PathBuf {
+        self.dir.join(&self.appendfilename)
+    }
+}
+
+/// 后台保存的状态，clone 之后共享同一份数据；配置在 Backend::config 里
+#[derive(Debug, Clone, Default)]
+pub struct Persistence {
+    bgsave_in_progress: Arc<AtomicBool>,
+}
+
+/// 启动时先加载快照，开启了 appendonly 时再重放 AOF，之后的写命令继续追加到 AOF
+pub fn load(backend: &Backend) -> Result<(), PersistenceError> {
+    let config = backend.config().persistence();
+    if config.appendonly {
+        recover_rewrite(&config)?;
+    }
+    let path = config.snapshot_path();
+    match fs::read(&path) {
+        Ok(data) => {
+            let count = load_snapshot(backend, &data)?;
+            info!("loaded {} keys from {:?}", count, path);
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    if config.appendonly {
+        let path = config.aof_path();
+        // 重放时不让 key 过期，否则结果会和原来执行时不一样
+        let count = aof::replay(&path, &backend.loading())?;
+        info!("replayed {} commands from {:?}", count, path);
+        backend.propagator().lock().aof = Some(Aof::open(path, config.appendfsync)?);
+    }
+    Ok(())
+}
+
+/// 编码所有的 key，全量同步时发给从节点
+pub fn dump_snapshot(backend: &Backend) -> Vec<u8> {
+    snapshot::encode(&backend.dump())
+}
+
+/// 加载快照里的 key，返回 key 的个数
+pub fn load_snapshot(backend: &Backend, data: &[u8]) -> Result<usize, PersistenceError> {
+    let entries = snapshot::decode(data)?;
+    let count = entries.len();
+    for (key, value, expire_at) in entries {
+        backend.restore(key, value, expire_at);
+    }
+    Ok(count)
+}
+
+/// SAVE：在当前线程保存快照，保存期间阻塞所有写命令
+pub fn save(backend: &Backend) -> Result<(), PersistenceError> {
+    let persistence = backend.persistence();
+    if persistence.bgsave_in_progress.load(Ordering::SeqCst) {
+        return Err(PersistenceError::InProgress);
+    }
+    let path = backend.config().persistence().snapshot_path();
+    let mut sinks = backend.propagator().lock();
+    if let Some(aof) = sinks.aof.as_mut() {
+        aof.start_rewrite();
+    }
+    match write_snapshot(&path, &backend.dump()) {
+        Ok(tmp) => install(&mut sinks, &tmp, &path),
+        Err(e) => {
+            abort_rewrite(&mut sinks);
+            Err(e)
+        }
+    }
+}
+
+/// BGSAVE / BGREWRITEAOF：复制一份 keyspace 之后在后台线程保存快照，不阻塞写命令；
+/// 保存期间的写命令会成为新的 AOF 的内容，旧的 AOF 就被压缩掉了
+pub fn bgsave(backend: &Backend) -> Result<JoinHandle<()>, PersistenceError> {
+    let persistence = backend.persistence().clone();
+    if persistence.bgsave_in_progress.swap(true, Ordering::SeqCst) {
+        return Err(PersistenceError::InProgress);
+    }
+    let path = backend.config().persistence().snapshot_path();
+    let entries = {
+        let mut sinks = backend.propagator().lock();
+        if let Some(aof) = sinks.aof.as_mut() {
+            aof.start_rewrite();
+        }
+        backend.dump()
+    };
+
+    let backend = backend.clone();
+    let handle = thread::spawn(move || {
+        let result = match write_snapshot(&path, &entries) {
+            Ok(tmp) => install(&mut backend.propagator().lock(), &tmp, &path),
+            Err(e) => {
+                abort_rewrite(&mut backend.propagator().lock());
+                Err(e)
+            }
+        };
+        match result {
+            Ok(()) => info!("background saving terminated with success"),
+            Err(e) => error!("background saving failed: {:?}", e),
+        }
+        persistence
+            .bgsave_in_progress
+            .store(false, Ordering::SeqCst);
+    });
+    Ok(handle)
+}
+
+/// 先写到临时文件，写完再替换，保存到一半失败也不会破坏原来的快照
+fn write_snapshot(path: &Path, entries: &[SnapshotEntry]) -> Result<PathBuf, PersistenceError> {
+    let tmp = aof::temp_path(path);
+    let mut file = File::create(&tmp)?;
+    file.write_all(&snapshot::encode(entries))?;
+    file.sync_all()?;
+    Ok(tmp)
+}
+
+/// 替换快照，同时把 AOF 换成快照之后的写命令。
+///
+/// 新的 AOF 先完整地写到临时文件，替换快照是这次保存的提交点：替换之前宕机，启动时还是原来的快照和 AOF；
+/// 替换之后、AOF 替换之前宕机，启动时由 [`recover_rewrite`] 完成 AOF 的替换，
+/// 不会在新的快照上再重放一遍原来的 AOF
+///
+/// 提交之前出错时放弃这次保存，继续使用原来的 AOF；提交之后原来的 AOF 就不能再用了，
+/// 一定要接着替换 AOF
+fn install(sinks: &mut Sinks, tmp: &Path, path: &Path) -> Result<(), PersistenceError> {
+    let Some(aof) = sinks.aof.as_mut() else {
+        fs::rename(tmp, path)?;
+        sync_dir(path)?;
+        return Ok(());
+    };
+
+    if let Err(e) = aof.write_rewrite().and_then(|_| fs::rename(tmp, path)) {
+        aof.abort_rewrite();
+        return Err(e.into());
+    }
+    // 确保快照的替换先落盘，再替换 AOF
+    if let Err(e) = sync_dir(path) {
+        error!("failed to fsync the directory of {:?}: {:?}", path, e);
+    }
+    aof.install_rewrite()?;
+    sync_dir(path)?;
+    Ok(())
+}
+
+/// 快照保存失败，继续使用原来的 AOF
+fn abort_rewrite(sinks: &mut Sinks) {
+    if let Some(aof) = sinks.aof.as_mut() {
+        aof.abort_rewrite();
+    }
+}
+
+/// 处理上次保存时留下的 AOF 临时文件：快照的临时文件还在说明快照没有替换，这次保存没有完成，
+/// 丢掉两个临时文件；否则快照已经替换了，用新的 AOF 替换原来的
+fn recover_rewrite(config: &PersistenceConfig) -> Result<(), PersistenceError> {
+    let path = config.aof_path();
+    let aof_tmp = aof::temp_path(&path);
+    if !aof_tmp.exists() {
+        return Ok(());
+    }
+
+    let snapshot_tmp = aof::temp_path(&config.snapshot_path());
+    if snapshot_tmp.exists() {
+        warn!("discard the unfinished snapshot {:?}", snapshot_tmp);
+        fs::remove_file(&snapshot_tmp)?;
+        fs::remove_file(&aof_tmp)?;
+    } else {
+        warn!("finish replacing AOF {:?} after the saved snapshot", path);
+        fs::rename(&aof_tmp, &path)?;
+    }
+    sync_dir(&path)?;
+    Ok(())
+}
+
+/// rename 之后 fsync 文件所在的目录，rename 才会在宕机之后仍然有效
+fn sync_dir(path: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        File::open(dir)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// appendfsync everysec 时每秒把 AOF 刷到磁盘
+pub async fn run_aof_fsync(backend: Backend) {
+    let mut interval = time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        if let Some(aof) = backend.propagator().lock().aof.as_mut() {
+            if let Err(e) = aof.fsync_if_dirty() {
+                error!("failed to fsync AOF: {:?}", e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis_core::{
+        backend::MockClock, config::ServerConfig, session::Session, RespArray, RespFrame,
+        RespNullBulkString, SimpleString,
+    };
+
+    fn run(session: &mut Session, cmd: &str) -> RespFrame {
+        let args: Vec<RespFrame> = cmd.split_whitespace().map(|arg| arg.into()).collect();
+        session.handle(RespArray::new(args).into()).remove(0)
+    }
+
+    fn open(dir: &Path, appendonly: bool, clock: &MockClock) -> Backend {
+        let backend = Backend::with_clock(clock.clone());
+        let mut config = ServerConfig::default();
+        config.persistence.dir = dir.into();
+        config
+            .set("appendonly", if appendonly { "yes" } else { "no" })
+            .unwrap();
+        backend.config().replace(config);
+        load(&backend).unwrap();
+        backend
+    }
+
+    #[test]
+    fn snapshot_should_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = MockClock::new(1_000);
+        let mut session = Session::new(open(dir.path(), false, &clock));
+        run(&mut session, "set s v px 10000");
+        run(&mut session, "rpush l a b c");
+        run(&mut session, "zadd z 1.5 m");
+        assert_eq!(run(&mut session, "save"), SimpleString::new("OK").into());
+
+        clock.advance(Duration::from_secs(4));
+        let mut session = Session::new(open(dir.path(), false, &clock));
+        assert_eq!(run(&mut session, "get s"), "v".into());
+        assert_eq!(run(&mut session, "pttl s"), 6000.into());
+        assert_eq!(
+            run(&mut session, "lrange l 0 -1"),
+            RespArray::new(["a".into(), "b".into(), "c".into()]).into()
+        );
+        assert_eq!(
+            run(&mut session, "zrange z 0 -1 withscores"),
+            RespArray::new(["m".into(), "1.5".into()]).into()
+        );
+
+        clock.advance(Duration::from_secs(6));
+        let mut session = Session::new(open(dir.path(), false, &clock));
+        assert_eq!(run(&mut session, "get s"), RespNullBulkString.into());
+    }
+
+    #[test]
+    fn aof_should_replay_write_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = MockClock::new(1_000);
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        run(&mut session, "set a 1");
+        run(&mut session, "pexpire a 10000");
+        run(&mut session, "incr counter");
+        run(&mut session, "incr counter");
+        // 没有修改数据的命令不写到 AOF
+        run(&mut session, "set counter x nx");
+        run(&mut session, "persist counter");
+        run(&mut session, "get a");
+        run(&mut session, "hset h f");
+
+        let path = dir.path().join("appendonly.aof");
+        let aof = fs::read_to_string(&path).unwrap();
+        assert!(aof.contains("PEXPIREAT\r\n$1\r\na\r\n$5\r\n11000\r\n"));
+        assert!(!aof.contains("pexpire\r\n"));
+        assert!(!aof.contains("nx"));
+        assert!(!aof.contains("get"));
+        assert!(!aof.contains("hset"));
+
+        // 写到一半的命令在重放时被截掉
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"*3\r\n$3\r\nset\r\n$1\r\nb")
+            .unwrap();
+        clock.advance(Duration::from_secs(5));
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        assert_eq!(run(&mut session, "get counter"), "2".into());
+        assert_eq!(run(&mut session, "pttl a"), 5000.into());
+        assert_eq!(run(&mut session, "exists b"), 0.into());
+        assert_eq!(fs::read_to_string(&path).unwrap(), aof);
+    }
+
+    #[test]
+    fn bgsave_should_compact_aof() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = MockClock::new(1_000);
+        let backend = open(dir.path(), true, &clock);
+        let mut session = Session::new(backend.clone());
+        for _ in 0..10 {
+            run(&mut session, "incr counter");
+        }
+        bgsave(&backend).unwrap().join().unwrap();
+        let path = dir.path().join("appendonly.aof");
+        assert_eq!(fs::read(&path).unwrap(), b"");
+
+        run(&mut session, "sadd s m");
+        assert!(fs::read_to_string(&path).unwrap().contains("sadd"));
+        drop(session);
+
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        assert_eq!(run(&mut session, "get counter"), "10".into());
+        assert_eq!(run(&mut session, "sismember s m"), 1.into());
+    }
+
+    /// 模拟保存快照时在 `install` 中间宕机
+    fn interrupt_save(backend: &Backend, dir: &Path, replace_snapshot: bool) {
+        let mut sinks = backend.propagator().lock();
+        let aof = sinks.aof.as_mut().unwrap();
+        aof.start_rewrite();
+        let tmp = write_snapshot(&dir.join("dump.rdb"), &backend.dump()).unwrap();
+        aof.write_rewrite().unwrap();
+        if replace_snapshot {
+            fs::rename(&tmp, dir.join("dump.rdb")).unwrap();
+        }
+    }
+
+    #[test]
+    fn failed_save_should_keep_the_aof() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = MockClock::new(1_000);
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        run(&mut session, "incr counter");
+
+        // 快照不能替换（目标是一个非空目录），这次保存被放弃
+        fs::create_dir_all(dir.path().join("dump.rdb/x")).unwrap();
+        assert!(matches!(run(&mut session, "save"), RespFrame::Error(_)));
+        assert!(!dir.path().join("appendonly.aof.tmp").exists());
+
+        // 之后的写命令还是追加到原来的 AOF
+        run(&mut session, "incr counter");
+        drop(session);
+        fs::remove_dir_all(dir.path().join("dump.rdb")).unwrap();
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        assert_eq!(run(&mut session, "get counter"), "2".into());
+    }
+
+    #[test]
+    fn interrupted_save_should_not_replay_aof_twice() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = MockClock::new(1_000);
+        let backend = open(dir.path(), true, &clock);
+        let mut session = Session::new(backend.clone());
+        for _ in 0..3 {
+            run(&mut session, "incr counter");
+        }
+
+        // 快照已经替换，AOF 还没有替换
+        interrupt_save(&backend, dir.path(), true);
+        drop(session);
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        assert_eq!(run(&mut session, "get counter"), "3".into());
+        assert_eq!(fs::read(dir.path().join("appendonly.aof")).unwrap(), b"");
+        assert!(!dir.path().join("appendonly.aof.tmp").exists());
+    }
+
+    #[test]
+    fn unfinished_save_should_be_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = MockClock::new(1_000);
+        let backend = open(dir.path(), true, &clock);
+        let mut session = Session::new(backend.clone());
+        for _ in 0..3 {
+            run(&mut session, "incr counter");
+        }
+        let aof = fs::read(dir.path().join("appendonly.aof")).unwrap();
+
+        // 两个临时文件都写完了，快照还没有替换
+        interrupt_save(&backend, dir.path(), false);
+        drop(session);
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        assert_eq!(run(&mut session, "get counter"), "3".into());
+        assert_eq!(fs::read(dir.path().join("appendonly.aof")).unwrap(), aof);
+        assert!(!dir.path().join("dump.rdb").exists());
+        assert!(!dir.path().join("dump.rdb.tmp").exists());
+        assert!(!dir.path().join("appendonly.aof.tmp").exists());
+    }
+
+    #[test]
+    fn aof_should_replay_transactions_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let clock = MockClock::new(1_000);
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        run(&mut session, "set a 1");
+        run(&mut session, "multi");
+        run(&mut session, "incr a");
+        run(&mut session, "get a");
+        run(&mut session, "sadd s m");
+        run(&mut session, "exec");
+        // 没有写命令的事务不写到 AOF
+        run(&mut session, "multi");
+        run(&mut session, "get a");
+        run(&mut session, "exec");
+
+        let path = dir.path().join("appendonly.aof");
+        let aof = fs::read_to_string(&path).unwrap();
+        assert_eq!(aof.matches("MULTI").count(), 1);
+        assert!(aof.ends_with("*1\r\n$4\r\nEXEC\r\n"));
+
+        // 没有 EXEC 的事务在重放时被截掉
+        let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"*1\r\n$5\r\nMULTI\r\n*2\r\n$4\r\nincr\r\n$1\r\na\r\n")
+            .unwrap();
+        let mut session = Session::new(open(dir.path(), true, &clock));
+        assert_eq!(run(&mut session, "get a"), "2".into());
+        assert_eq!(run(&mut session, "sismember s m"), 1.into());
+        assert_eq!(fs::read_to_string(&path).unwrap(), aof);
+    }
+}
@@ -0,0 +1,273 @@
+//! 快照的二进制格式，和 RDB 类似：
+//!
+//! ```text
+//! "SREDIS" 版本号(1 字节)
+//! { [0xFC 过期时间(u64, unix 毫秒)] 类型(1 字节) key value }*
+//! 0xFF
+//! ```
+//!
+//! 长度都用 LEB128 变长编码，字符串是长度 + 字节，score 是大端的 f64
+
+use bytes::{Buf, BufMut};
+use thiserror::Error;
+
+use crate::redis_core::backend::{HashValue, ListValue, SetValue, SortedSet, Value};
+
+const MAGIC: &[u8] = b"SREDIS";
+const VERSION: u8 = 1;
+
+const OPCODE_EXPIRE_MS: u8 = 0xFC;
+const OPCODE_EOF: u8 = 0xFF;
+
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_HASH: u8 = 4;
+
+/// 快照里的一个 key：(key, 值, 过期时间)
+pub type SnapshotEntry = (String, Value, Option<u64>);
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SnapshotError {
+    #[error("invalid snapshot header")]
+    InvalidHeader,
+    #[error("unsupported snapshot version: {0}")]
+    UnsupportedVersion(u8),
+    #[error("snapshot is truncated")]
+    Truncated,
+    #[error("unknown value type: {0}")]
+    UnknownType(u8),
+    #[error("invalid utf-8 in key or field")]
+    InvalidUtf8,
+}
+
+pub fn encode(entries: &[SnapshotEntry]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(1024);
+    buf.put_slice(MAGIC);
+    buf.put_u8(VERSION);
+    for (key, value, expire_at) in entries {
+        if let Some(at) = expire_at {
+            buf.put_u8(OPCODE_EXPIRE_MS);
+            buf.put_u64(*at);
+        }
+        let ty = match value {
+            Value::String(_) => TYPE_STRING,
+            Value::List(_) => TYPE_LIST,
+            Value::Set(_) => TYPE_SET,
+            Value::ZSet(_) => TYPE_ZSET,
+            Value::Hash(_) => TYPE_HASH,
+        };
+        buf.put_u8(ty);
+        put_bytes(&mut buf, key.as_bytes());
+        encode_value(&mut buf, value);
+    }
+    buf.put_u8(OPCODE_EOF);
+    buf
+}
+
+fn encode_value(buf: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::String(s) => put_bytes(buf, s),
+        Value::List(list) => {
+            put_len(buf, list.len());
+            list.iter().for_each(|element| put_bytes(buf, element));
+        }
+        Value::Set(set) => {
+            put_len(buf, set.len());
+            set.iter().for_each(|member| put_bytes(buf, member));
+        }
+        Value::ZSet(zset) => {
+            put_len(buf, zset.len());
+            for (member, score) in zset.iter() {
+                put_bytes(buf, member);
+                buf.put_f64(score);
+            }
+        }
+        Value::Hash(hash) => {
+            put_len(buf, hash.len());
+            for (field, value) in hash.iter() {
+                put_bytes(buf, field.as_bytes());
+                put_bytes(buf, value);
+            }
+        }
+    }
+}
+
+pub fn decode(mut buf: &[u8]) -> Result<Vec<SnapshotEntry>, SnapshotError> {
+    if !buf.starts_with(MAGIC) {
+        return Err(SnapshotError::InvalidHeader);
+    }
+    buf.advance(MAGIC.len());
+    match get_u8(&mut buf)? {
+        VERSION => {}
+        version => return Err(SnapshotError::UnsupportedVersion(version)),
+    }
+
+    let mut entries = vec![];
+    loop {
+        let mut expire_at = None;
+        let mut ty = get_u8(&mut buf)?;
+        if ty == OPCODE_EXPIRE_MS {
+            if buf.remaining() < 8 {
+                return Err(SnapshotError::Truncated);
+            }
+            expire_at = Some(buf.get_u64());
+            ty = get_u8(&mut buf)?;
+        }
+        if ty == OPCODE_EOF {
+            return Ok(entries);
+        }
+        let key = get_string(&mut buf)?;
+        let value = decode_value(&mut buf, ty)?;
+        entries.push((key, value, expire_at));
+    }
+}
+
+fn decode_value(buf: &mut &[u8], ty: u8) -> Result<Value, SnapshotError> {
+    let value = match ty {
+        TYPE_STRING => Value::String(get_bytes(buf)?),
+        TYPE_LIST => {
+            let len = get_len(buf)?;
+            let list = (0..len)
+                .map(|_| get_bytes(buf))
+                .collect::<Result<ListValue, _>>()?;
+            Value::List(list)
+        }
+        TYPE_SET => {
+            let len = get_len(buf)?;
+            let set = (0..len)
+                .map(|_| get_bytes(buf))
+                .collect::<Result<SetValue, _>>()?;
+            Value::Set(set)
+        }
+        TYPE_ZSET => {
+            let mut zset = SortedSet::default();
+            for _ in 0..get_len(buf)? {
+                let member = get_bytes(buf)?;
+                if buf.remaining() < 8 {
+                    return Err(SnapshotError::Truncated);
+                }
+                zset.insert(member, buf.get_f64());
+            }
+            Value::ZSet(zset)
+        }
+        TYPE_HASH => {
+            let mut hash = HashValue::new();
+            for _ in 0..get_len(buf)? {
+                let field = get_string(buf)?;
+                hash.insert(field, get_bytes(buf)?);
+            }
+            Value::Hash(hash)
+        }
+        ty => return Err(SnapshotError::UnknownType(ty)),
+    };
+    Ok(value)
+}
+
+fn put_len(buf: &mut Vec<u8>, mut len: usize) {
+    while len >= 0x80 {
+        buf.put_u8((len as u8 & 0x7F) | 0x80);
+        len >>= 7;
+    }
+    buf.put_u8(len as u8);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, data: &[u8]) {
+    put_len(buf, data.len());
+    buf.put_slice(data);
+}
+
+fn get_u8(buf: &mut &[u8]) -> Result<u8, SnapshotError> {
+    if !buf.has_remaining() {
+        return Err(SnapshotError::Truncated);
+    }
+    Ok(buf.get_u8())
+}
+
+fn get_len(buf: &mut &[u8]) -> Result<usize, SnapshotError> {
+    let mut len = 0usize;
+    for shift in (0..usize::BITS).step_by(7) {
+        let byte = get_u8(buf)?;
+        len |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(len);
+        }
+    }
+    Err(SnapshotError::Truncated)
+}
+
+fn get_bytes(buf: &mut &[u8]) -> Result<Vec<u8>, SnapshotError> {
+    let len = get_len(buf)?;
+    if buf.remaining() < len {
+        return Err(SnapshotError::Truncated);
+    }
+    let data = buf[..len].to_vec();
+    buf.advance(len);
+    Ok(data)
+}
+
+fn get_string(buf: &mut &[u8]) -> Result<String, SnapshotError> {
+    String::from_utf8(get_bytes(buf)?).map_err(|_| SnapshotError::InvalidUtf8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<SnapshotEntry> {
+        let mut zset = SortedSet::default();
+        zset.insert(b"a".to_vec(), 1.5);
+        zset.insert(b"b".to_vec(), f64::NEG_INFINITY);
+        vec![
+            (
+                "s".into(),
+                Value::String(vec![0; 300]),
+                Some(1_700_000_000_000),
+            ),
+            (
+                "l".into(),
+                Value::List([b"x".to_vec(), b"y".to_vec()].into()),
+                None,
+            ),
+            ("set".into(), Value::Set([b"m".to_vec()].into()), None),
+            ("z".into(), Value::ZSet(zset), Some(1)),
+            (
+                "h".into(),
+                Value::Hash([("f".to_string(), b"v".to_vec())].into()),
+                None,
+            ),
+        ]
+    }
+
+    #[test]
+    fn snapshot_should_round_trip() {
+        let entries = sample();
+        let data = encode(&entries);
+        assert!(data.starts_with(b"SREDIS\x01"));
+        // 不能直接比较 Value，比较重新编码的结果
+        assert_eq!(encode(&decode(&data).unwrap()), data);
+        assert!(decode(&encode(&[])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_snapshot_should_fail() {
+        let data = encode(&sample());
+        assert_eq!(
+            decode(b"REDIS0011").err(),
+            Some(SnapshotError::InvalidHeader)
+        );
+        assert_eq!(
+            decode(b"SREDIS\x09\xFF").err(),
+            Some(SnapshotError::UnsupportedVersion(9))
+        );
+        // 任何位置截断都能发现
+        for len in 0..data.len() {
+            assert!(decode(&data[..len]).is_err(), "len {}", len);
+        }
+        assert_eq!(
+            decode(b"SREDIS\x01\x09\x01k").err(),
+            Some(SnapshotError::UnknownType(9))
+        );
+    }
+}
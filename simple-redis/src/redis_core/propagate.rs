@@ -0,0 +1,132 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tracing::error;
+
+use super::{
+    backend::Backend, persistence::Aof, replication::Feed, RespArray, RespEncode, RespFrame,
+};
+
+/// 会修改 keyspace 的命令，执行成功后要写到 AOF 并发给从节点
+const WRITE_COMMANDS: [&str; 22] = [
+    "set",
+    "del",
+    "incr",
+    "decr",
+    "mset",
+    "hset",
+    "hdel",
+    "lpush",
+    "rpush",
+    "lpop",
+    "rpop",
+    "sadd",
+    "srem",
+    "zadd",
+    "zrem",
+    "expire",
+    "pexpire",
+    "expireat",
+    "pexpireat",
+    "persist",
+    "flushdb",
+    "flushall",
+];
+
+/// 把执行过的写命令传播到 AOF 和从节点。
+/// 命令在持有锁的时候执行和传播，保证传播的顺序和实际执行的顺序一致，事务执行时也不会插入其它命令
+#[derive(Debug, Clone, Default)]
+pub struct Propagator(Arc<Mutex<Sinks>>);
+
+#[derive(Debug, Default)]
+pub struct Sinks {
+    pub aof: Option<Aof>,
+    pub repl: Feed,
+}
+
+impl Propagator {
+    pub fn lock(&self) -> MutexGuard<'_, Sinks> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Sinks {
+    pub fn propagate(&mut self, frames: Vec<RespFrame>) {
+        if frames.is_empty() {
+            return;
+        }
+        let buf: Vec<u8> = frames.into_iter().flat_map(RespEncode::encode).collect();
+        if let Some(aof) = self.aof.as_mut() {
+            // 和 redis 一样，写 AOF 失败不影响已经执行的命令，只记录错误
+            if let Err(e) = aof.append(&buf) {
+                error!("failed to append AOF: {:?}", e);
+            }
+        }
+        self.repl.feed(&buf);
+    }
+}
+
+/// 命令名，小写
+pub fn command_name(frame: &RespFrame) -> Option<String> {
+    let RespFrame::Array(array) = frame else {
+        return None;
+    };
+    match array.0.first() {
+        Some(RespFrame::BulkString(name)) => Some(String::from_utf8_lossy(&name.0).to_lowercase()),
+        _ => None,
+    }
+}
+
+pub fn is_write_command(name: &str) -> bool {
+    WRITE_COMMANDS.contains(&name)
+}
+
+/// 根据执行的结果决定要传播的命令：失败或者没有修改数据的命令不传播；
+/// 相对的过期时间转换成绝对时间的 PEXPIREAT，重放时不会因为时间不同而得到不同的结果
+pub fn rewrite(
+    name: &str,
+    frame: RespFrame,
+    reply: &RespFrame,
+    backend: &Backend,
+) -> Vec<RespFrame> {
+    let RespFrame::Array(array) = frame else {
+        return vec![];
+    };
+    match (name, reply) {
+        (_, RespFrame::Error(_)) => vec![],
+        // NX/XX 的条件不满足
+        ("set", RespFrame::NullBulkString(_)) => vec![],
+        ("set", _) => {
+            let key = array.0[1].clone();
+            let set = RespArray::new(["SET".into(), key.clone(), array.0[2].clone()]);
+            let mut frames = vec![set.into()];
+            if let Some(frame) = expire_frame(key, backend).filter(|_| array.0.len() > 3) {
+                frames.push(frame);
+            }
+            frames
+        }
+        ("expire" | "pexpire" | "expireat" | "pexpireat" | "persist", RespFrame::Integer(0)) => {
+            vec![]
+        }
+        ("expire" | "pexpire" | "expireat" | "pexpireat", _) => {
+            expire_frame(array.0[1].clone(), backend)
+                .into_iter()
+                .collect()
+        }
+        _ => vec![array.into()],
+    }
+}
+
+/// key 当前的过期时间：还存在就是 PEXPIREAT，已经因为过期时间在过去被删除就是 DEL
+fn expire_frame(key: RespFrame, backend: &Backend) -> Option<RespFrame> {
+    let RespFrame::BulkString(name) = &key else {
+        return None;
+    };
+    let frame = match backend.expire_time(&String::from_utf8_lossy(&name.0)) {
+        Some(Some(at)) => {
+            RespArray::new(["PEXPIREAT".into(), key, (at.to_string().as_str()).into()])
+        }
+        Some(None) => return None,
+        None => RespArray::new(["DEL".into(), key]),
+    };
+    Some(frame.into())
+}
@@ -0,0 +1,157 @@
+use super::{BulkString, RespArray, RespFrame, RespNull, RespNullBulkString, SimpleError};
+
+/// 连接使用的协议版本，默认是 RESP2，客户端通过 `HELLO 3` 切换到 RESP3
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Protocol {
+    #[default]
+    Resp2,
+    Resp3,
+}
+
+impl Protocol {
+    pub fn version(self) -> i64 {
+        match self {
+            Protocol::Resp2 => 2,
+            Protocol::Resp3 => 3,
+        }
+    }
+}
+
+impl TryFrom<i64> for Protocol {
+    type Error = i64;
+
+    fn try_from(version: i64) -> Result<Self, Self::Error> {
+        match version {
+            2 => Ok(Protocol::Resp2),
+            3 => Ok(Protocol::Resp3),
+            v => Err(v),
+        }
+    }
+}
+
+impl RespFrame {
+    /// 命令按 RESP3 的类型构造回复，发送前再转换成客户端协商的协议：
+    /// RESP2 没有 map、set、double 这些类型，按 redis 的规则降级成 array 和 bulk string；
+    /// RESP3 里 `$-1` 和 `*-1` 都统一成 `_`
+    pub fn into_protocol(self, protocol: Protocol) -> RespFrame {
+        match protocol {
+            Protocol::Resp2 => self.into_resp2(),
+            Protocol::Resp3 => self.into_resp3(),
+        }
+    }
+
+    fn into_resp2(self) -> RespFrame {
+        let array = |frames: Vec<RespFrame>| -> RespFrame {
+            RespArray::new(
+                frames
+                    .into_iter()
+                    .map(RespFrame::into_resp2)
+                    .collect::<Vec<_>>(),
+            )
+            .into()
+        };
+        match self {
+            RespFrame::Null(_) => RespNullBulkString.into(),
+            RespFrame::Boolean(b) => RespFrame::Integer(b as i64),
+            RespFrame::Double(f) => format_double(f).into_bytes().into(),
+            RespFrame::BigNumber(n) => n.0.into_bytes().into(),
+            RespFrame::BulkError(e) => SimpleError::new(String::from_utf8_lossy(&e.0)).into(),
+            RespFrame::VerbatimString(s) => s.data.into(),
+            RespFrame::Array(a) => array(a.0),
+            RespFrame::Set(s) => array(s.0),
+            RespFrame::Push(p) => array(p.0),
+            RespFrame::Map(m) => array(
+                m.0.into_iter()
+                    .flat_map(|(k, v)| [BulkString::new(k).into(), v])
+                    .collect(),
+            ),
+            RespFrame::Attribute(a) => a.frame.into_resp2(),
+            frame => frame,
+        }
+    }
+
+    fn into_resp3(self) -> RespFrame {
+        let convert = |frames: Vec<RespFrame>| -> Vec<RespFrame> {
+            frames.into_iter().map(RespFrame::into_resp3).collect()
+        };
+        match self {
+            RespFrame::NullBulkString(_) | RespFrame::NullArray(_) => RespFrame::Null(RespNull),
+            RespFrame::Array(a) => RespArray::new(convert(a.0)).into(),
+            RespFrame::Set(mut s) => {
+                s.0 = convert(s.0);
+                s.into()
+            }
+            RespFrame::Push(mut p) => {
+                p.0 = convert(p.0);
+                p.into()
+            }
+            RespFrame::Map(mut m) => {
+                m.0.values_mut()
+                    .for_each(|v| *v = std::mem::replace(v, RespNull.into()).into_resp3());
+                m.into()
+            }
+            RespFrame::Attribute(mut a) => {
+                *a.frame = a.frame.into_resp3();
+                a.into()
+            }
+            frame => frame,
+        }
+    }
+}
+
+/// 和 redis 一样，RESP2 里 double 用 bulk string 表示
+pub fn format_double(f: f64) -> String {
+    match f {
+        f if f.is_nan() => "nan".into(),
+        f64::INFINITY => "inf".into(),
+        f64::NEG_INFINITY => "-inf".into(),
+        f => f.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::redis_core::{RespMap, RespSet};
+
+    #[test]
+    fn resp3_frames_should_downgrade_to_resp2() {
+        let map = RespMap::new(BTreeMap::from([
+            ("a".to_string(), RespFrame::Double(1.5)),
+            ("b".to_string(), RespFrame::Null(RespNull)),
+        ]));
+        assert_eq!(
+            RespFrame::from(map).into_protocol(Protocol::Resp2),
+            RespArray::new([
+                "a".into(),
+                "1.5".into(),
+                "b".into(),
+                RespNullBulkString.into()
+            ])
+            .into()
+        );
+        let set = RespSet::new([RespFrame::Boolean(true), RespFrame::Double(f64::INFINITY)]);
+        assert_eq!(
+            RespFrame::from(set).into_protocol(Protocol::Resp2),
+            RespArray::new([RespFrame::Integer(1), "inf".into()]).into()
+        );
+    }
+
+    #[test]
+    fn resp2_nulls_should_upgrade_to_resp3() {
+        let array = RespArray::new([
+            RespNullBulkString.into(),
+            crate::redis_core::RespNullArray.into(),
+        ]);
+        assert_eq!(
+            RespFrame::from(array).into_protocol(Protocol::Resp3),
+            RespArray::new([RespFrame::Null(RespNull), RespFrame::Null(RespNull)]).into()
+        );
+        assert_eq!(
+            RespFrame::Double(2.0).into_protocol(Protocol::Resp3),
+            RespFrame::Double(2.0)
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::warn;
+
+use super::pattern::glob_match;
+
+/// 每个订阅者最多缓存的消息个数，超过之后断开这个订阅者，不会阻塞 PUBLISH
+pub const SUBSCRIBER_BUFFER_SIZE: usize = 1024;
+
+/// 发给订阅者的消息，通过模式订阅收到的消息带着匹配的 pattern
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub pattern: Option<String>,
+    pub channel: String,
+    pub payload: Vec<u8>,
+}
+
+/// 频道和订阅者的对应关系，clone 之后共享同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct PubSub(Arc<Mutex<Broker>>);
+
+#[derive(Debug, Default)]
+struct Broker {
+    // 按客户端 id 保存发送消息的 channel
+    clients: HashMap<u64, mpsc::Sender<Arc<Message>>>,
+    channels: HashMap<String, HashSet<u64>>,
+    patterns: HashMap<String, HashSet<u64>>,
+}
+
+impl Broker {
+    /// 客户端断开或者缓冲区满了，去掉它的所有订阅
+    fn remove_client(&mut self, id: u64) {
+        self.clients.remove(&id);
+        for subscribers in [&mut self.channels, &mut self.patterns] {
+            subscribers.retain(|_, ids| {
+                ids.remove(&id);
+                !ids.is_empty()
+            });
+        }
+    }
+}
+
+fn remove_subscriber(subscribers: &mut HashMap<String, HashSet<u64>>, name: &str, id: u64) {
+    if let Some(ids) = subscribers.get_mut(name) {
+        ids.remove(&id);
+        if ids.is_empty() {
+            subscribers.remove(name);
+        }
+    }
+}
+
+impl PubSub {
+    fn lock(&self) -> MutexGuard<'_, Broker> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 客户端第一次订阅时注册，之后的消息从返回的 receiver 里读取；
+    /// receiver 返回 None 说明客户端的缓冲区满了，已经被去掉了所有订阅
+    pub fn register(&self, id: u64) -> mpsc::Receiver<Arc<Message>> {
+        let (tx, rx) = mpsc::channel(SUBSCRIBER_BUFFER_SIZE);
+        self.lock().clients.insert(id, tx);
+        rx
+    }
+
+    /// 不再订阅任何频道或者断开连接时调用
+    pub fn unregister(&self, id: u64) {
+        self.lock().remove_client(id);
+    }
+
+    pub fn subscribe(&self, id: u64, channel: &str) {
+        let mut broker = self.lock();
+        broker
+            .channels
+            .entry(channel.into())
+            .or_default()
+            .insert(id);
+    }
+
+    pub fn unsubscribe(&self, id: u64, channel: &str) {
+        remove_subscriber(&mut self.lock().channels, channel, id);
+    }
+
+    pub fn psubscribe(&self, id: u64, pattern: &str) {
+        let mut broker = self.lock();
+        broker
+            .patterns
+            .entry(pattern.into())
+            .or_default()
+            .insert(id);
+    }
+
+    pub fn punsubscribe(&self, id: u64, pattern: &str) {
+        remove_subscriber(&mut self.lock().patterns, pattern, id);
+    }
+
+    /// 发送给订阅了这个频道和匹配这个频道的模式的客户端，返回收到消息的个数。
+    /// 一个客户端通过多个订阅匹配时会收到多次
+    pub fn publish(&self, channel: &str, payload: Vec<u8>) -> i64 {
+        let mut broker = self.lock();
+        let mut deliveries = vec![];
+        if let Some(ids) = broker.channels.get(channel) {
+            let message = Arc::new(Message {
+                pattern: None,
+                channel: channel.into(),
+                payload: payload.clone(),
+            });
+            deliveries.extend(ids.iter().map(|id| (*id, message.clone())));
+        }
+        for (pattern, ids) in broker.patterns.iter() {
+            if !glob_match(pattern.as_bytes(), channel.as_bytes()) {
+                continue;
+            }
+            let message = Arc::new(Message {
+                pattern: Some(pattern.clone()),
+                channel: channel.into(),
+                payload: payload.clone(),
+            });
+            deliveries.extend(ids.iter().map(|id| (*id, message.clone())));
+        }
+
+        let mut received = 0;
+        let mut dropped = vec![];
+        for (id, message) in deliveries {
+            let Some(tx) = broker.clients.get(&id) else {
+                continue;
+            };
+            match tx.try_send(message) {
+                Ok(()) => received += 1,
+                Err(TrySendError::Full(_)) => {
+                    warn!("client {} pubsub buffer is full, disconnecting", id);
+                    dropped.push(id);
+                }
+                Err(TrySendError::Closed(_)) => dropped.push(id),
+            }
+        }
+        for id in dropped {
+            broker.remove_client(id);
+        }
+        received
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(pattern: Option<&str>, channel: &str, payload: &str) -> Arc<Message> {
+        Arc::new(Message {
+            pattern: pattern.map(Into::into),
+            channel: channel.into(),
+            payload: payload.into(),
+        })
+    }
+
+    #[test]
+    fn publish_should_reach_channel_and_pattern_subscribers() {
+        let pubsub = PubSub::default();
+        let mut rx1 = pubsub.register(1);
+        let mut rx2 = pubsub.register(2);
+        pubsub.subscribe(1, "news.tech");
+        pubsub.psubscribe(1, "news.*");
+        pubsub.psubscribe(2, "news.[st]*");
+
+        assert_eq!(pubsub.publish("news.tech", b"hi".to_vec()), 3);
+        assert_eq!(pubsub.publish("other", b"hi".to_vec()), 0);
+
+        let mut got = vec![rx1.try_recv().unwrap(), rx1.try_recv().unwrap()];
+        got.sort_by_key(|m| m.pattern.clone());
+        assert_eq!(
+            got,
+            [
+                message(None, "news.tech", "hi"),
+                message(Some("news.*"), "news.tech", "hi"),
+            ]
+        );
+        assert_eq!(
+            rx2.try_recv().unwrap(),
+            message(Some("news.[st]*"), "news.tech", "hi")
+        );
+
+        pubsub.unsubscribe(1, "news.tech");
+        pubsub.punsubscribe(2, "news.[st]*");
+        assert_eq!(pubsub.publish("news.tech", b"hi".to_vec()), 1);
+        pubsub.unregister(1);
+        assert_eq!(pubsub.publish("news.tech", b"hi".to_vec()), 0);
+    }
+
+    #[test]
+    fn slow_subscriber_should_be_dropped() {
+        let pubsub = PubSub::default();
+        let mut slow = pubsub.register(1);
+        let mut fast = pubsub.register(2);
+        pubsub.subscribe(1, "c");
+        pubsub.subscribe(2, "c");
+
+        for _ in 0..SUBSCRIBER_BUFFER_SIZE {
+            assert_eq!(pubsub.publish("c", b"m".to_vec()), 2);
+            fast.try_recv().unwrap();
+        }
+        // slow 的缓冲区满了，被去掉订阅，publish 不会阻塞
+        assert_eq!(pubsub.publish("c", b"m".to_vec()), 1);
+        assert_eq!(pubsub.publish("c", b"m".to_vec()), 1);
+
+        // 缓冲区里的消息读完之后收到 None
+        for _ in 0..SUBSCRIBER_BUFFER_SIZE {
+            slow.try_recv().unwrap();
+        }
+        assert!(matches!(
+            slow.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        ));
+    }
+}
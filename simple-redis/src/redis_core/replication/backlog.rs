@@ -0,0 +1,101 @@
+use std::collections::VecDeque;
+
+/// 默认和 redis 的 repl-backlog-size 一样是 1mb
+pub const DEFAULT_BACKLOG_SIZE: usize = 1024 * 1024;
+
+/// 最近传播的命令，只保留最后 capacity 个字节。
+/// 断线重连的从节点只要要求的 offset 还在这里，就可以接着同步，不需要重新全量同步
+#[derive(Debug)]
+pub struct Backlog {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    // 复制的偏移量，也就是一共传播过的字节数
+    offset: u64,
+}
+
+impl Default for Backlog {
+    fn default() -> Self {
+        Self::new(DEFAULT_BACKLOG_SIZE)
+    }
+}
+
+impl Backlog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buf: VecDeque::with_capacity(capacity),
+            capacity,
+            offset: 0,
+        }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.offset += data.len() as u64;
+        // 超过容量的部分本来就会被丢掉，不用先放进去
+        let data = &data[data.len().saturating_sub(self.capacity)..];
+        let overflow = (self.buf.len() + data.len()).saturating_sub(self.capacity);
+        self.buf.drain(..overflow);
+        self.buf.extend(data);
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// 保存的第一个字节的偏移量
+    pub fn first_offset(&self) -> u64 {
+        self.offset - self.buf.len() as u64
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 从 offset 开始到现在的数据，已经不在 backlog 里时返回 None
+    pub fn read_from(&self, offset: u64) -> Option<Vec<u8>> {
+        if offset < self.first_offset() || offset > self.offset {
+            return None;
+        }
+        let skip = (offset - self.first_offset()) as usize;
+        Some(self.buf.range(skip..).copied().collect())
+    }
+
+    /// 全量同步之后从主节点的 offset 重新开始
+    pub fn reset(&mut self, offset: u64) {
+        self.buf.clear();
+        self.offset = offset;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backlog_should_keep_latest_bytes() {
+        let mut backlog = Backlog::new(8);
+        assert_eq!(backlog.read_from(0), Some(vec![]));
+        backlog.push(b"abcde");
+        assert_eq!(backlog.read_from(2), Some(b"cde".to_vec()));
+        assert_eq!(backlog.read_from(6), None);
+
+        backlog.push(b"fghij");
+        assert_eq!(backlog.offset(), 10);
+        assert_eq!(backlog.first_offset(), 2);
+        assert_eq!(backlog.read_from(1), None);
+        assert_eq!(backlog.read_from(2), Some(b"cdefghij".to_vec()));
+        assert_eq!(backlog.read_from(10), Some(vec![]));
+
+        // 比容量还大的数据只保留最后的部分
+        backlog.push(b"0123456789");
+        assert_eq!(backlog.len(), 8);
+        assert_eq!(backlog.read_from(12), Some(b"23456789".to_vec()));
+
+        backlog.reset(100);
+        assert_eq!(backlog.first_offset(), 100);
+        assert_eq!(backlog.read_from(99), None);
+    }
+}
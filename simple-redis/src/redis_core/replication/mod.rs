@@ -0,0 +1,512 @@
+mod backlog;
+mod replica;
+
+use backlog::Backlog;
+
+use std::{
+    fmt::Write,
+    hash::{BuildHasher, Hasher, RandomState},
+    net::IpAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use futures::StreamExt;
+use tokio::{io::AsyncWriteExt, net::TcpStream, sync::mpsc, task::AbortHandle};
+use tokio_util::codec::Framed;
+use tracing::{info, warn};
+
+use super::{
+    backend::Backend, codec::RespFrameCodec, persistence, BulkString, RespFrame, SimpleString,
+};
+
+/// 每个从节点最多积压的数据块个数，超过之后断开，和 client-output-buffer-limit replica 的作用一样
+pub const REPLICA_BUFFER_SIZE: usize = 16 * 1024;
+
+/// 主节点这边的复制状态：replid、backlog 和连接着的从节点。
+/// 放在传播的锁里面，和 AOF 一起按执行的顺序接收写命令
+#[derive(Debug)]
+pub struct Feed {
+    replid: String,
+    backlog: Backlog,
+    replicas: Vec<ReplicaLink>,
+    stats: SyncStats,
+}
+
+/// 一个已经完成 PSYNC 的从节点
+#[derive(Debug)]
+struct ReplicaLink {
+    id: u64,
+    ip: Option<IpAddr>,
+    port: u16,
+    tx: mpsc::Sender<Bytes>,
+    ack_offset: u64,
+    last_ack: Instant,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct SyncStats {
+    full: u64,
+    partial_ok: u64,
+    partial_err: u64,
+}
+
+/// 发起 PSYNC 的从节点
+#[derive(Debug)]
+pub struct ReplicaInfo {
+    pub id: u64,
+    pub ip: Option<IpAddr>,
+    pub port: u16,
+}
+
+impl Default for Feed {
+    fn default() -> Self {
+        Self {
+            replid: new_replid(),
+            backlog: Backlog::default(),
+            replicas: vec![],
+            stats: SyncStats::default(),
+        }
+    }
+}
+
+impl Feed {
+    pub fn replid(&self) -> &str {
+        &self.replid
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.backlog.offset()
+    }
+
+    /// 写到 backlog 并发给所有从节点，积压太多的从节点直接断开，重连之后再同步
+    pub fn feed(&mut self, data: &[u8]) {
+        self.backlog.push(data);
+        if self.replicas.is_empty() {
+            return;
+        }
+        let data = Bytes::copy_from_slice(data);
+        self.replicas.retain(|replica| {
+            let ok = replica.tx.try_send(data.clone()).is_ok();
+            if !ok {
+                warn!(
+                    "replica {} output buffer overflow, disconnecting",
+                    replica.id
+                );
+            }
+            ok
+        });
+    }
+
+    /// PSYNC replid offset：replid 一样并且 offset 还在 backlog 里时只发送缺少的部分，否则发送快照全量同步。
+    /// 和 redis 一样，offset 是从节点已经处理的字节数加一
+    pub fn psync(
+        &mut self,
+        backend: &Backend,
+        replica: ReplicaInfo,
+        replid: &str,
+        offset: i64,
+    ) -> (Vec<RespFrame>, mpsc::Receiver<Bytes>) {
+        let (tx, rx) = mpsc::channel(REPLICA_BUFFER_SIZE);
+        let backlog = u64::try_from(offset - 1)
+            .ok()
+            .filter(|_| replid == self.replid)
+            .and_then(|offset| self.backlog.read_from(offset));
+        let (reply, ack_offset) = match backlog {
+            Some(data) => {
+                info!("partial resync with replica {}", replica.id);
+                self.stats.partial_ok += 1;
+                let ack_offset = self.offset() - data.len() as u64;
+                if !data.is_empty() {
+                    let _ = tx.try_send(data.into());
+                }
+                let reply = SimpleString::new(format!("CONTINUE {}", self.replid));
+                (vec![reply.into()], ack_offset)
+            }
+            None => {
+                info!("full resync with replica {}", replica.id);
+                if replid != "?" {
+                    self.stats.partial_err += 1;
+                }
+                self.stats.full += 1;
+                let reply =
+                    SimpleString::new(format!("FULLRESYNC {} {}", self.replid, self.offset()));
+                let snapshot = BulkString::new(persistence::dump_snapshot(backend));
+                (vec![reply.into(), snapshot.into()], self.offset())
+            }
+        };
+        self.replicas.push(ReplicaLink {
+            id: replica.id,
+            ip: replica.ip,
+            port: replica.port,
+            tx,
+            ack_offset,
+            last_ack: Instant::now(),
+        });
+        (reply, rx)
+    }
+
+    /// 从节点定期发来的 REPLCONF ACK offset
+    pub fn ack(&mut self, id: u64, offset: u64) {
+        if let Some(replica) = self.replicas.iter_mut().find(|r| r.id == id) {
+            replica.ack_offset = offset;
+            replica.last_ack = Instant::now();
+        }
+    }
+
+    pub fn detach(&mut self, id: u64) {
+        self.replicas.retain(|replica| replica.id != id);
+    }
+
+    /// 全量同步之后使用主节点的 replid 和 offset，自己的从节点需要重新同步
+    pub fn reset(&mut self, replid: String, offset: u64) {
+        self.replid = replid;
+        self.backlog.reset(offset);
+        self.replicas.clear();
+    }
+
+    /// 不再是从节点之后换一个 replid，数据已经和原来的主节点不一样了
+    pub fn shift_replid(&mut self) {
+        self.replid = new_replid();
+    }
+
+    #[cfg(test)]
+    pub fn disconnect_replicas(&mut self) {
+        self.replicas.clear();
+    }
+}
+
+/// 从节点这边的状态：主节点的地址和连接状态，clone 之后共享同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct Replication(Arc<Mutex<State>>);
+
+#[derive(Debug, Default)]
+struct State {
+    // 自己监听的端口，通过 REPLCONF listening-port 告诉主节点
+    listening_port: u16,
+    master: Option<Master>,
+}
+
+#[derive(Debug)]
+struct Master {
+    host: String,
+    port: u16,
+    status: LinkStatus,
+    last_io: Option<Instant>,
+    // 和这个主节点同步过之后，重连时才尝试部分同步
+    synced: bool,
+    task: AbortHandle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LinkStatus {
+    Connecting,
+    Sync,
+    Connected,
+}
+
+impl Replication {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_listening_port(&self, port: u16) {
+        self.lock().listening_port = port;
+    }
+
+    pub fn is_replica(&self) -> bool {
+        self.lock().master.is_some()
+    }
+
+    fn set_status(&self, status: LinkStatus) {
+        if let Some(master) = self.lock().master.as_mut() {
+            master.status = status;
+            if status == LinkStatus::Connected {
+                master.last_io = Some(Instant::now());
+                master.synced = true;
+            }
+        }
+    }
+
+    fn synced(&self) -> bool {
+        self.lock()
+            .master
+            .as_ref()
+            .is_some_and(|master| master.synced)
+    }
+
+    fn touch(&self) {
+        if let Some(master) = self.lock().master.as_mut() {
+            master.last_io = Some(Instant::now());
+        }
+    }
+}
+
+/// REPLICAOF host port 开始从新的主节点复制，REPLICAOF NO ONE 停止复制成为主节点
+pub fn replicaof(backend: &Backend, master: Option<(String, u16)>) {
+    let mut state = backend.replication().lock();
+    let old = state.master.take();
+    if let Some(old) = &old {
+        old.task.abort();
+    }
+    match master {
+        Some((host, port)) => {
+            info!("replicating from {}:{}", host, port);
+            let task = tokio::spawn(replica::run(backend.clone(), host.clone(), port));
+            state.master = Some(Master {
+                host,
+                port,
+                status: LinkStatus::Connecting,
+                last_io: None,
+                synced: false,
+                task: task.abort_handle(),
+            });
+        }
+        None if old.is_some() => {
+            info!("replication stopped, now acting as master");
+            drop(state);
+            backend.propagator().lock().repl.shift_replid();
+        }
+        None => {}
+    }
+}
+
+/// PSYNC 之后连接只用来发送复制的数据，同时接收从节点的 REPLCONF ACK
+pub async fn serve_replica(
+    mut framed: Framed<TcpStream, RespFrameCodec>,
+    backend: Backend,
+    id: u64,
+    mut rx: mpsc::Receiver<Bytes>,
+) -> Result<()> {
+    let result = async {
+        loop {
+            tokio::select! {
+                data = rx.recv() => {
+                    let Some(data) = data else {
+                        return Err(anyhow!("replica {} disconnected by master", id));
+                    };
+                    framed.get_mut().write_all(&data).await?;
+                }
+                frame = framed.next() => match frame {
+                    Some(frame) => {
+                        if let Some(offset) = parse_ack(&frame?) {
+                            backend.propagator().lock().repl.ack(id, offset);
+                        }
+                    }
+                    None => return Ok(()),
+                }
+            }
+        }
+    }
+    .await;
+    backend.propagator().lock().repl.detach(id);
+    result
+}
+
+fn parse_ack(frame: &RespFrame) -> Option<u64> {
+    let RespFrame::Array(array) = frame else {
+        return None;
+    };
+    let args: Vec<String> = array
+        .0
+        .iter()
+        .map(|frame| match frame {
+            RespFrame::BulkString(s) => String::from_utf8_lossy(&s.0).to_lowercase(),
+            _ => String::new(),
+        })
+        .collect();
+    match args.as_slice() {
+        [replconf, ack, offset] if replconf == "replconf" && ack == "ack" => offset.parse().ok(),
+        _ => None,
+    }
+}
+
+/// INFO replication
+pub fn info(backend: &Backend) -> String {
+    let mut info = String::new();
+    {
+        let state = backend.replication().lock();
+        match &state.master {
+            Some(master) => {
+                let last_io = master
+                    .last_io
+                    .map_or(-1, |at| at.elapsed().as_secs() as i64);
+                let link = match master.status {
+                    LinkStatus::Connected => "up",
+                    _ => "down",
+                };
+                let syncing = (master.status == LinkStatus::Sync) as u8;
+                let _ = write!(
+                    info,
+                    "role:slave\r\nmaster_host:{}\r\nmaster_port:{}\r\nmaster_link_status:{}\r\n\
+                     master_last_io_seconds_ago:{}\r\nmaster_sync_in_progress:{}\r\nslave_read_only:1\r\n",
+                    master.host, master.port, link, last_io, syncing
+                );
+            }
+            None => info.push_str("role:master\r\n"),
+        }
+    }
+
+    let is_replica = backend.replication().is_replica();
+    let sinks = backend.propagator().lock();
+    let feed = &sinks.repl;
+    if is_replica {
+        let _ = write!(info, "slave_repl_offset:{}\r\n", feed.offset());
+    }
+    let _ = write!(info, "connected_slaves:{}\r\n", feed.replicas.len());
+    for (i, replica) in feed.replicas.iter().enumerate() {
+        let ip = replica
+            .ip
+            .map_or_else(|| "?".to_string(), |ip| ip.to_string());
+        let _ = write!(
+            info,
+            "slave{}:ip={},port={},state=online,offset={},lag={}\r\n",
+            i,
+            ip,
+            replica.port,
+            replica.ack_offset,
+            replica.last_ack.elapsed().as_secs()
+        );
+    }
+    let _ = write!(
+        info,
+        "master_replid:{}\r\nmaster_repl_offset:{}\r\nrepl_backlog_active:1\r\n\
+         repl_backlog_size:{}\r\nrepl_backlog_first_byte_offset:{}\r\nrepl_backlog_histlen:{}\r\n",
+        feed.replid,
+        feed.offset(),
+        feed.backlog.capacity(),
+        feed.backlog.first_offset() + 1,
+        feed.backlog.len()
+    );
+    info
+}
+
+/// INFO stats 里和复制有关的部分
+pub fn info_stats(backend: &Backend) -> String {
+    let stats = backend.propagator().lock().repl.stats;
+    format!(
+        "sync_full:{}\r\nsync_partial_ok:{}\r\nsync_partial_err:{}\r\n",
+        stats.full, stats.partial_ok, stats.partial_err
+    )
+}
+
+/// 40 个十六进制字符的随机 id
+fn new_replid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let mut id = String::with_capacity(48);
+    for _ in 0..3 {
+        // 每次创建的 RandomState 的 key 都不一样
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        let _ = write!(id, "{:016x}", hasher.finish());
+    }
+    id.truncate(40);
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::SinkExt;
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::redis_core::{backend::SetValue, command::CommandError, server, RespArray};
+
+    async fn start() -> (Backend, u16) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let backend = Backend::new();
+        tokio::spawn(server::serve(listener, backend.clone()));
+        (backend, port)
+    }
+
+    async fn connect(port: u16) -> Framed<TcpStream, RespFrameCodec> {
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        Framed::new(stream, RespFrameCodec)
+    }
+
+    async fn call(client: &mut Framed<TcpStream, RespFrameCodec>, cmd: &str) -> RespFrame {
+        let args: Vec<RespFrame> = cmd.split_whitespace().map(|arg| arg.into()).collect();
+        client.send(RespArray::new(args).into()).await.unwrap();
+        client.next().await.unwrap().unwrap()
+    }
+
+    async fn wait_until(f: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if f() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("timed out");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn replica_should_follow_master() {
+        let (master, master_port) = start().await;
+        let (replica, replica_port) = start().await;
+        let mut m = connect(master_port).await;
+        let mut r = connect(replica_port).await;
+
+        call(&mut m, "set a 1").await;
+        call(&mut m, "rpush l x y").await;
+        let cmd = format!("replicaof 127.0.0.1 {}", master_port);
+        assert_eq!(call(&mut r, &cmd).await, SimpleString::new("OK").into());
+        wait_until(|| replica.get("a") == Ok(Some(b"1".to_vec()))).await;
+
+        // 全量同步之后继续接收写命令，事务也一起同步
+        call(&mut m, "incr a").await;
+        call(&mut m, "multi").await;
+        call(&mut m, "sadd s m").await;
+        call(&mut m, "exec").await;
+        wait_until(|| replica.read("s", |s: &SetValue| s.len()) == Ok(Some(1))).await;
+        assert_eq!(replica.get("a"), Ok(Some(b"2".to_vec())));
+        assert_eq!(call(&mut r, "set x 1").await, CommandError::ReadOnly.into());
+        wait_until(|| {
+            master.propagator().lock().repl.offset() == replica.propagator().lock().repl.offset()
+        })
+        .await;
+
+        let RespFrame::BulkString(info) = call(&mut r, "info replication").await else {
+            panic!("INFO should reply a bulk string");
+        };
+        let info = String::from_utf8(info.0).unwrap();
+        assert!(info.contains("role:slave\r\n"));
+        assert!(info.contains("master_link_status:up\r\n"));
+        let RespFrame::BulkString(info) = call(&mut m, "info replication").await else {
+            panic!("INFO should reply a bulk string");
+        };
+        let info = String::from_utf8(info.0).unwrap();
+        let slave = format!("slave0:ip=127.0.0.1,port={},state=online", replica_port);
+        assert!(info.contains(&slave), "{}", info);
+
+        // 短暂断开之后只同步缺少的部分
+        master.propagator().lock().repl.disconnect_replicas();
+        call(&mut m, "set b 2").await;
+        wait_until(|| replica.get("b") == Ok(Some(b"2".to_vec()))).await;
+        let stats = info_stats(&master);
+        assert!(stats.contains("sync_full:1\r\n"), "{}", stats);
+        assert!(stats.contains("sync_partial_ok:1\r\n"), "{}", stats);
+        assert!(stats.contains("sync_partial_err:0\r\n"), "{}", stats);
+
+        // 成为主节点之后可以写，也不再同步原来的主节点
+        assert_eq!(
+            call(&mut r, "replicaof no one").await,
+            SimpleString::new("OK").into()
+        );
+        assert_eq!(
+            call(&mut r, "set x 1").await,
+            SimpleString::new("OK").into()
+        );
+        call(&mut m, "set c 3").await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(replica.get("c"), Ok(None));
+    }
+}
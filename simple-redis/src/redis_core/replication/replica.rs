@@ -0,0 +1,165 @@
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use futures::{SinkExt, StreamExt};
+use tokio::{net::TcpStream, time};
+use tokio_util::codec::Framed;
+use tracing::{info, warn};
+
+use super::LinkStatus;
+use crate::redis_core::{
+    backend::Backend,
+    codec::RespFrameCodec,
+    command::{Command, CommandExecutor},
+    persistence,
+    propagate::{command_name, is_write_command},
+    protocol::Protocol,
+    RespArray, RespFrame,
+};
+
+/// 和主节点断开之后重连的间隔
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// 向主节点报告复制进度的间隔
+const ACK_PERIOD: Duration = Duration::from_secs(1);
+
+/// 从节点的复制任务：连接主节点并同步，断开之后不断重连，直到被 REPLICAOF 取消
+pub(super) async fn run(backend: Backend, host: String, port: u16) {
+    loop {
+        backend.replication().set_status(LinkStatus::Connecting);
+        if let Err(e) = sync(&backend, &host, port).await {
+            warn!("replication from {}:{} failed: {:?}", host, port, e);
+        }
+        time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn sync(backend: &Backend, host: &str, port: u16) -> Result<()> {
+    let stream = TcpStream::connect((host, port)).await?;
+    let mut framed = Framed::new(stream, RespFrameCodec);
+    let listening_port = backend.replication().lock().listening_port.to_string();
+    request(&mut framed, &["PING"]).await?;
+    request(
+        &mut framed,
+        &["REPLCONF", "listening-port", &listening_port],
+    )
+    .await?;
+    request(&mut framed, &["REPLCONF", "capa", "psync2"]).await?;
+
+    // 同步过之后用 replid 和 offset 尝试部分同步，第一次连接时直接要求全量同步
+    let (replid, offset) = match backend.replication().synced() {
+        true => {
+            let sinks = backend.propagator().lock();
+            (
+                sinks.repl.replid().to_string(),
+                (sinks.repl.offset() + 1).to_string(),
+            )
+        }
+        false => ("?".to_string(), "-1".to_string()),
+    };
+    backend.replication().set_status(LinkStatus::Sync);
+    let reply = match request(&mut framed, &["PSYNC", &replid, &offset]).await? {
+        RespFrame::SimpleString(reply) => reply.0,
+        reply => bail!("unexpected PSYNC reply {:?}", reply),
+    };
+    match reply.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["FULLRESYNC", replid, offset] => {
+            let snapshot = match framed.next().await {
+                Some(Ok(RespFrame::BulkString(snapshot))) => snapshot.0,
+                frame => bail!("expect snapshot from master, got {:?}", frame),
+            };
+            full_resync(backend, replid, offset.parse()?, &snapshot)?;
+        }
+        ["CONTINUE", ..] => info!("partial resync with master {}:{}", host, port),
+        _ => bail!("unexpected PSYNC reply {}", reply),
+    }
+
+    backend.replication().set_status(LinkStatus::Connected);
+    let mut ack = time::interval(ACK_PERIOD);
+    let mut transaction = None;
+    loop {
+        tokio::select! {
+            frame = framed.next() => {
+                let Some(frame) = frame else {
+                    bail!("connection closed by master");
+                };
+                backend.replication().touch();
+                apply(backend, &mut transaction, frame?);
+            }
+            _ = ack.tick() => {
+                let offset = backend.propagator().lock().repl.offset().to_string();
+                framed.send(command(&["REPLCONF", "ACK", &offset])).await?;
+            }
+        }
+    }
+}
+
+fn command(args: &[&str]) -> RespFrame {
+    let args: Vec<RespFrame> = args.iter().map(|&arg| arg.into()).collect();
+    RespArray::new(args).into()
+}
+
+async fn request(
+    framed: &mut Framed<TcpStream, RespFrameCodec>,
+    args: &[&str],
+) -> Result<RespFrame> {
+    framed.send(command(args)).await?;
+    match framed.next().await {
+        Some(Ok(RespFrame::Error(e))) => bail!("{} failed: {:?}", args[0], e),
+        Some(frame) => Ok(frame?),
+        None => bail!("connection closed by master"),
+    }
+}
+
+/// 用主节点的快照替换所有数据，之后从主节点的 offset 开始接收命令
+fn full_resync(backend: &Backend, replid: &str, offset: u64, snapshot: &[u8]) -> Result<()> {
+    info!("full resync with master, {} bytes snapshot", snapshot.len());
+    {
+        let mut sinks = backend.propagator().lock();
+        backend.flush();
+        persistence::load_snapshot(backend, snapshot)?;
+        sinks.repl.reset(replid.to_string(), offset);
+    }
+    // AOF 里还是原来的数据，保存一次快照，AOF 从这里重新开始
+    if backend.config().read(|c| c.persistence.appendonly) {
+        if let Err(e) = persistence::bgsave(backend) {
+            warn!("failed to save snapshot after full resync: {:?}", e);
+        }
+    }
+    Ok(())
+}
+
+/// 执行主节点传播的命令，再原样传播给自己的 AOF 和从节点，这样复制的 offset 和主节点一致。
+/// MULTI 和 EXEC 之间的命令攒到一起执行
+fn apply(backend: &Backend, transaction: &mut Option<Vec<RespFrame>>, frame: RespFrame) {
+    let name = command_name(&frame).unwrap_or_default();
+    let frames = match (name.as_str(), transaction.as_mut()) {
+        ("multi", _) => {
+            *transaction = Some(vec![frame]);
+            return;
+        }
+        ("exec", Some(frames)) => {
+            frames.push(frame);
+            transaction.take().unwrap_or_default()
+        }
+        (_, Some(frames)) => {
+            frames.push(frame);
+            return;
+        }
+        (_, None) => vec![frame],
+    };
+
+    let mut sinks = backend.propagator().lock();
+    for frame in &frames {
+        match command_name(frame) {
+            Some(name) if is_write_command(&name) => {}
+            _ => continue,
+        }
+        let reply = Command::try_from(frame.clone())
+            .map(|cmd| cmd.execute(backend, Protocol::Resp2))
+            .unwrap_or_else(RespFrame::from);
+        if let RespFrame::Error(e) = reply {
+            warn!("command from master failed: {:?}", e);
+        }
+    }
+    sinks.propagate(frames);
+}
@@ -0,0 +1,100 @@
+use std::net::SocketAddr;
+
+use anyhow::{anyhow, Result};
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_util::codec::Framed;
+use tracing::{error, info};
+
+use super::{
+    backend::Backend, codec::RespFrameCodec, command::CommandError, replication, session::Session,
+    RespFrame,
+};
+
+/// 超过 maxclients 时回复的错误，之后断开连接
+const MAX_CLIENTS_REACHED: &[u8] = b"-ERR max number of clients reached\r\n";
+
+/// 接受连接，每个连接一个任务；CONFIG SET port 之后换成新的 listener，已有的连接不受影响
+pub async fn serve(mut listener: TcpListener, backend: Backend) -> Result<()> {
+    let (tx, mut listeners) = mpsc::unbounded_channel();
+    backend.config().set_listener_channel(tx);
+    let local = listener.local_addr()?;
+    backend.replication().set_listening_port(local.port());
+    info!("listening on {:?}", local);
+    loop {
+        let (mut stream, addr) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            Some(std_listener) = listeners.recv() => {
+                listener = TcpListener::from_std(std_listener)?;
+                let local = listener.local_addr()?;
+                backend.replication().set_listening_port(local.port());
+                info!("listening on {:?}", local);
+                continue;
+            }
+        };
+        backend.stats().connection_received();
+        if backend.clients().len() >= backend.config().read(|c| c.maxclients) {
+            backend.stats().connection_rejected();
+            let _ = stream.write_all(MAX_CLIENTS_REACHED).await;
+            continue;
+        }
+        let backend = backend.clone();
+        tokio::spawn(async move {
+            info!("accepted connection from {:?}", addr);
+            if let Err(e) = process_redis_task(stream, addr, backend).await {
+                error!("[process_redis_task] error {:?}", e);
+            };
+            info!("connected over {:?}", addr);
+        });
+    }
+}
+
+async fn process_redis_task(stream: TcpStream, addr: SocketAddr, backend: Backend) -> Result<()> {
+    let mut framed = Framed::new(stream, RespFrameCodec);
+    let mut session = Session::new(backend.clone()).with_addr(addr);
+    let kill = session.kill_signal();
+    loop {
+        // 订阅之后，除了客户端发来的命令，还要把别的连接 PUBLISH 的消息推给客户端
+        let frame = tokio::select! {
+            frame = framed.next() => frame,
+            _ = kill.notified() => {
+                info!("client {:?} killed", addr);
+                return Ok(());
+            }
+            message = session.next_message() => {
+                let Some(message) = message else {
+                    return Err(anyhow!("pubsub output buffer overflow"));
+                };
+                framed.send(message).await?;
+                continue;
+            }
+        };
+        let frame = match frame {
+            Some(Ok(frame)) => frame,
+            Some(Err(e)) => {
+                // 协议错误之后无法再找到下一个 frame 的开始，回复错误后断开连接
+                let resp: RespFrame = CommandError::InvalidFrame(e.to_string()).into();
+                framed.send(resp).await?;
+                return Err(e.into());
+            }
+            None => return Ok(()),
+        };
+        info!("{:?}", frame);
+        for resp in session.handle(frame) {
+            info!("{:?}", resp);
+            framed.feed(resp).await?;
+        }
+        // pipeline 中还有没处理的命令时先不 flush，攒到一起发送
+        if framed.read_buffer().is_empty() {
+            framed.flush().await?;
+        }
+        if let Some((id, rx)) = session.take_replica() {
+            framed.flush().await?;
+            return replication::serve_replica(framed, backend, id, rx).await;
+        }
+    }
+}
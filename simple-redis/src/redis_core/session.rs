@@ -0,0 +1,906 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use bytes::Bytes;
+use tokio::sync::{mpsc, Notify};
+use tracing::info;
+
+use super::{
+    backend::{is_denyoom, Backend},
+    cluster,
+    command::{
+        Client, ClientKill, Command, CommandError, CommandExecutor, Hello, PSync, ReplConf,
+        Subscribe, Transaction, Unsubscribe, Watch,
+    },
+    propagate,
+    protocol::Protocol,
+    pubsub::Message,
+    replication::ReplicaInfo,
+    slowlog, RespArray, RespFrame, RespNull, RespNullArray, RespPush, SimpleString,
+};
+
+static NEXT_CLIENT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// RESP2 的连接订阅之后只能执行这些命令
+const SUBSCRIBED_COMMANDS: [&str; 7] = [
+    "subscribe",
+    "psubscribe",
+    "unsubscribe",
+    "punsubscribe",
+    "ping",
+    "quit",
+    "reset",
+];
+
+/// 一个客户端连接的状态：协商的协议版本、客户端名字、订阅的频道等，命令在这里解析、执行
+#[derive(Debug)]
+pub struct Session {
+    id: u64,
+    addr: Option<SocketAddr>,
+    name: Option<String>,
+    protocol: Protocol,
+    backend: Backend,
+    channels: BTreeSet<String>,
+    patterns: BTreeSet<String>,
+    // 订阅之后才有，从这里读取别的连接 PUBLISH 的消息
+    messages: Option<mpsc::Receiver<Arc<Message>>>,
+    // MULTI 之后才有
+    multi: Option<Multi>,
+    // WATCH 的 key 和当时的版本号
+    watched: BTreeMap<String, u64>,
+    // 从节点通过 REPLCONF listening-port 告诉的端口
+    listening_port: u16,
+    // PSYNC 之后才有，要发给从节点的数据
+    replica: Option<mpsc::Receiver<Bytes>>,
+    // CLIENT KILL 的通知
+    kill: Arc<Notify>,
+    // ASKING 之后的下一个命令可以访问正在迁移过来的 slot
+    asking: bool,
+}
+
+/// 解析好的命令，写命令保留原来的 frame
+#[derive(Debug)]
+struct Call {
+    name: String,
+    cmd: Command,
+    frame: Option<RespFrame>,
+}
+
+/// MULTI 之后排队的命令，排队时出错的话 EXEC 返回 EXECABORT
+#[derive(Debug, Default)]
+struct Multi {
+    queued: Vec<Call>,
+    aborted: bool,
+}
+
+impl Session {
+    pub fn new(backend: Backend) -> Self {
+        let id = NEXT_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+        let kill = backend.clients().register(id, None);
+        Self {
+            id,
+            addr: None,
+            name: None,
+            protocol: Protocol::default(),
+            backend,
+            channels: BTreeSet::new(),
+            patterns: BTreeSet::new(),
+            messages: None,
+            multi: None,
+            watched: BTreeMap::new(),
+            listening_port: 0,
+            replica: None,
+            kill,
+            asking: false,
+        }
+    }
+
+    /// 客户端的地址
+    pub fn with_addr(mut self, addr: SocketAddr) -> Self {
+        self.addr = Some(addr);
+        self.backend
+            .clients()
+            .update(self.id, |info| info.addr = Some(addr));
+        self
+    }
+
+    /// 被 CLIENT KILL 时收到通知，连接所在的任务应该断开连接
+    pub fn kill_signal(&self) -> Arc<Notify> {
+        self.kill.clone()
+    }
+
+    /// PSYNC 之后连接变成复制的连接，返回连接的 id 和要发给从节点的数据
+    pub fn take_replica(&mut self) -> Option<(u64, mpsc::Receiver<Bytes>)> {
+        self.replica.take().map(|rx| (self.id, rx))
+    }
+
+    /// 执行一个命令，返回按当前协议编码的回复；SUBSCRIBE 这类命令每个频道都有一个回复。
+    /// 执行时间超过 slowlog-log-slower-than 的命令记到 slowlog
+    pub fn handle(&mut self, frame: RespFrame) -> Vec<RespFrame> {
+        let (slower_than, max_len) = self
+            .backend
+            .config()
+            .read(|c| (c.slowlog_log_slower_than, c.slowlog_max_len));
+        let args = (slower_than >= 0).then(|| slowlog::command_args(&frame));
+        let name = propagate::command_name(&frame);
+        let started = Instant::now();
+        let resp = self.dispatch(name.clone(), frame);
+        let elapsed = started.elapsed();
+        // 和 redis 一样，事务里的命令都可以访问，直到 EXEC 或者 DISCARD
+        if name.as_deref() != Some("asking") && self.multi.is_none() {
+            self.asking = false;
+        }
+        if let Some(args) = args.filter(|_| elapsed.as_micros() >= slower_than as u128) {
+            self.backend
+                .slowlog()
+                .record(args, elapsed, self.addr, self.name.clone(), max_len);
+        }
+        self.backend.stats().command_processed();
+        self.update_client(name);
+        resp
+    }
+
+    fn dispatch(&mut self, name: Option<String>, frame: RespFrame) -> Vec<RespFrame> {
+        // 写命令保留原来的 frame，执行之后用来生成写到 AOF 的命令
+        let original = name
+            .as_deref()
+            .filter(|name| propagate::is_write_command(name))
+            .map(|_| frame.clone());
+        let cmd = self
+            .check_subscribed_context(name.as_deref())
+            .and_then(|_| self.check_cluster(name.as_deref(), &frame))
+            .and_then(|_| self.check_read_only(original.is_some()))
+            .and_then(|_| self.check_memory(name.as_deref()))
+            .and_then(|_| Command::try_from(frame))
+            .map(|cmd| Call {
+                name: name.unwrap_or_default(),
+                cmd,
+                frame: original,
+            });
+        let resp = match (cmd, self.multi.as_mut()) {
+            (Ok(call), Some(_)) => vec![self.queue(call)],
+            (Ok(call), None) => self.execute(call),
+            // 排队时出错，整个事务都不会执行
+            (Err(e), Some(multi)) => {
+                multi.aborted = true;
+                vec![e.into()]
+            }
+            (Err(e), None) => vec![e.into()],
+        };
+        resp.into_iter()
+            .map(|frame| frame.into_protocol(self.protocol))
+            .collect()
+    }
+
+    fn execute(&mut self, call: Call) -> Vec<RespFrame> {
+        let subscribed = self.protocol == Protocol::Resp2 && self.subscription_count() > 0;
+        match call.cmd {
+            Command::Hello(cmd) => vec![self.hello(cmd)],
+            Command::Subscribe(cmd) => self.subscribe(cmd),
+            Command::Unsubscribe(cmd) => self.unsubscribe(cmd),
+            Command::Ping(cmd) if subscribed => vec![cmd.into_pubsub_reply()],
+            Command::Transaction(Transaction::Multi) => {
+                self.multi = Some(Multi::default());
+                vec![ok()]
+            }
+            Command::Transaction(Transaction::Exec) => {
+                vec![CommandError::WithoutMulti("EXEC").into()]
+            }
+            Command::Transaction(Transaction::Discard) => {
+                vec![CommandError::WithoutMulti("DISCARD").into()]
+            }
+            Command::Transaction(Transaction::Unwatch) => {
+                self.unwatch();
+                vec![ok()]
+            }
+            Command::Watch(cmd) => {
+                self.watch(cmd);
+                vec![ok()]
+            }
+            Command::ReplConf(cmd) => self.replconf(cmd),
+            Command::PSync(cmd) => self.psync(cmd),
+            Command::Client(cmd) => vec![self.client(cmd)],
+            Command::Asking(_) => match self.backend.cluster().is_enabled() {
+                true => {
+                    self.asking = true;
+                    vec![ok()]
+                }
+                false => vec![CommandError::ClusterDisabled.into()],
+            },
+            cmd if locks_propagator(&cmd) => vec![cmd.execute(&self.backend, self.protocol)],
+            // 其它命令都在持有传播锁的时候执行，不会看到执行到一半的事务
+            _ => {
+                let mut sinks = self.backend.propagator().lock();
+                let (reply, frames) = self.run(call);
+                sinks.propagate(frames);
+                vec![reply]
+            }
+        }
+    }
+
+    /// 执行命令，返回回复和要传播的命令，调用时需要持有传播的锁
+    fn run(&self, call: Call) -> (RespFrame, Vec<RespFrame>) {
+        let reply = call.cmd.execute(&self.backend, self.protocol);
+        let frames = match call.frame {
+            Some(frame) => propagate::rewrite(&call.name, frame, &reply, &self.backend),
+            None => vec![],
+        };
+        (reply, frames)
+    }
+
+    /// MULTI 之后的命令：EXEC/DISCARD 结束事务，其它命令排队
+    fn queue(&mut self, call: Call) -> RespFrame {
+        let Some(multi) = self.multi.as_mut() else {
+            unreachable!()
+        };
+        match call.cmd {
+            Command::Transaction(Transaction::Exec) => self.exec(),
+            Command::Transaction(Transaction::Discard) => {
+                self.multi = None;
+                self.unwatch();
+                ok()
+            }
+            Command::Transaction(Transaction::Multi) => CommandError::NestedMulti.into(),
+            Command::Watch(_) => CommandError::WatchInMulti.into(),
+            // 会修改连接状态或者自己获取锁的命令不能放到事务里
+            Command::Hello(_)
+            | Command::Subscribe(_)
+            | Command::Unsubscribe(_)
+            | Command::ReplConf(_)
+            | Command::PSync(_)
+            | Command::Client(_)
+            | Command::Asking(_) => {
+                multi.aborted = true;
+                CommandError::NotAllowedInMulti.into()
+            }
+            ref cmd if locks_propagator(cmd) => {
+                multi.aborted = true;
+                CommandError::NotAllowedInMulti.into()
+            }
+            _ => {
+                multi.queued.push(call);
+                SimpleString::new("QUEUED").into()
+            }
+        }
+    }
+
+    /// 持有传播锁，检查 WATCH 的 key 之后依次执行排队的命令，中间不会插入其它连接的命令
+    fn exec(&mut self) -> RespFrame {
+        let multi = self.multi.take().unwrap_or_default();
+        // 不管事务有没有执行，EXEC 之后都取消 WATCH
+        let watched = std::mem::take(&mut self.watched);
+        let mut sinks = self.backend.propagator().lock();
+        let touched = watched
+            .iter()
+            .any(|(key, version)| self.backend.is_touched(key, *version));
+        for key in watched.keys() {
+            self.backend.unwatch(key);
+        }
+        if multi.aborted {
+            return CommandError::ExecAbort.into();
+        }
+        if touched {
+            return RespNullArray.into();
+        }
+
+        let mut replies = vec![];
+        let mut frames = vec![];
+        for call in multi.queued {
+            let (reply, propagated) = match call.cmd {
+                // WATCH 已经取消了
+                Command::Transaction(Transaction::Unwatch) => (ok(), vec![]),
+                _ => self.run(call),
+            };
+            replies.push(reply);
+            frames.extend(propagated);
+        }
+        // 和 redis 一样用 MULTI/EXEC 包起来，重放 AOF 时也是原子的
+        if !frames.is_empty() {
+            frames.insert(0, RespArray::new(["MULTI".into()]).into());
+            frames.push(RespArray::new(["EXEC".into()]).into());
+        }
+        sinks.propagate(frames);
+        RespArray::new(replies).into()
+    }
+
+    fn watch(&mut self, cmd: Watch) {
+        for key in cmd.keys {
+            if !self.watched.contains_key(&key) {
+                let version = self.backend.watch(&key);
+                self.watched.insert(key, version);
+            }
+        }
+    }
+
+    fn unwatch(&mut self) {
+        for key in std::mem::take(&mut self.watched).keys() {
+            self.backend.unwatch(key);
+        }
+    }
+
+    /// 等待订阅的消息，没有订阅时一直等待；返回 None 说明缓冲区满了，已经被取消了所有订阅
+    pub async fn next_message(&mut self) -> Option<RespFrame> {
+        let Some(messages) = self.messages.as_mut() else {
+            return std::future::pending().await;
+        };
+        let message = messages.recv().await?;
+        let frame: RespFrame = match &message.pattern {
+            Some(pattern) => RespPush::new([
+                "pmessage".into(),
+                pattern.as_str().into(),
+                message.channel.as_str().into(),
+                message.payload.clone().into(),
+            ]),
+            None => RespPush::new([
+                "message".into(),
+                message.channel.as_str().into(),
+                message.payload.clone().into(),
+            ]),
+        }
+        .into();
+        Some(frame.into_protocol(self.protocol))
+    }
+
+    fn subscription_count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+
+    /// 切换协议版本之后，HELLO 的回复就按新的协议编码
+    fn hello(&mut self, hello: Hello) -> RespFrame {
+        if let Some(protocol) = hello.protocol {
+            self.protocol = protocol;
+        }
+        if hello.name.is_some() {
+            self.name = hello.name;
+        }
+        info!(
+            "client {} (name: {:?}) uses {:?}",
+            self.id, self.name, self.protocol
+        );
+        Hello::reply(self.id, self.protocol)
+    }
+
+    fn subscribe(&mut self, cmd: Subscribe) -> Vec<RespFrame> {
+        let pubsub = self.backend.pubsub().clone();
+        if self.messages.is_none() {
+            self.messages = Some(pubsub.register(self.id));
+        }
+        let kind = if cmd.pattern {
+            "psubscribe"
+        } else {
+            "subscribe"
+        };
+        let mut replies = vec![];
+        for channel in cmd.channels {
+            match cmd.pattern {
+                true if self.patterns.insert(channel.clone()) => {
+                    pubsub.psubscribe(self.id, &channel)
+                }
+                false if self.channels.insert(channel.clone()) => {
+                    pubsub.subscribe(self.id, &channel)
+                }
+                _ => {}
+            }
+            replies.push(subscription_reply(
+                kind,
+                channel.into_bytes().into(),
+                self.subscription_count(),
+            ));
+        }
+        replies
+    }
+
+    fn unsubscribe(&mut self, cmd: Unsubscribe) -> Vec<RespFrame> {
+        let pubsub = self.backend.pubsub().clone();
+        let (kind, subscribed) = match cmd.pattern {
+            true => ("punsubscribe", &self.patterns),
+            false => ("unsubscribe", &self.channels),
+        };
+        // 不带参数时取消这一类的所有订阅
+        let channels: Vec<String> = match cmd.channels.is_empty() {
+            true => subscribed.iter().cloned().collect(),
+            false => cmd.channels,
+        };
+
+        let mut replies = vec![];
+        for channel in channels {
+            match cmd.pattern {
+                true if self.patterns.remove(&channel) => pubsub.punsubscribe(self.id, &channel),
+                false if self.channels.remove(&channel) => pubsub.unsubscribe(self.id, &channel),
+                _ => {}
+            }
+            replies.push(subscription_reply(
+                kind,
+                channel.into_bytes().into(),
+                self.subscription_count(),
+            ));
+        }
+        if replies.is_empty() {
+            replies.push(subscription_reply(
+                kind,
+                RespNull.into(),
+                self.subscription_count(),
+            ));
+        }
+        if self.subscription_count() == 0 && self.messages.take().is_some() {
+            pubsub.unregister(self.id);
+        }
+        replies
+    }
+
+    fn replconf(&mut self, cmd: ReplConf) -> Vec<RespFrame> {
+        for (name, value) in cmd.options {
+            match name.as_str() {
+                "listening-port" => match value.parse() {
+                    Ok(port) => self.listening_port = port,
+                    Err(_) => return vec![CommandError::NotInteger.into()],
+                },
+                // 复制进度的报告不需要回复
+                "ack" => return vec![],
+                _ => {}
+            }
+        }
+        vec![ok()]
+    }
+
+    /// 回复 FULLRESYNC 和快照或者 CONTINUE，之后的写命令都会发给这个连接
+    fn psync(&mut self, cmd: PSync) -> Vec<RespFrame> {
+        let replica = ReplicaInfo {
+            id: self.id,
+            ip: self.addr.map(|addr| addr.ip()),
+            port: self.listening_port,
+        };
+        let (replies, rx) = self.backend.propagator().lock().repl.psync(
+            &self.backend,
+            replica,
+            &cmd.replid,
+            cmd.offset,
+        );
+        self.replica = Some(rx);
+        replies
+    }
+
+    fn client(&mut self, cmd: Client) -> RespFrame {
+        match cmd {
+            Client::List => {
+                let list: String = self
+                    .backend
+                    .clients()
+                    .list()
+                    .iter()
+                    .map(|info| info.line() + "\n")
+                    .collect();
+                RespFrame::from(list.as_str())
+            }
+            Client::Id => (self.id as i64).into(),
+            Client::GetName => match &self.name {
+                Some(name) => name.as_str().into(),
+                None => RespNull.into(),
+            },
+            // 空的名字表示清除名字
+            Client::SetName(name) => {
+                self.name = Some(name).filter(|name| !name.is_empty());
+                ok()
+            }
+            Client::Kill(kill) => self.kill(kill),
+        }
+    }
+
+    fn kill(&self, kill: ClientKill) -> RespFrame {
+        let killed = self.backend.clients().kill(|info| {
+            kill.id.is_none_or(|id| info.id == id)
+                && kill
+                    .addr
+                    .as_ref()
+                    .is_none_or(|addr| info.addr.is_some_and(|a| a.to_string() == *addr))
+                && !(kill.skipme && info.id == self.id)
+        });
+        match (kill.legacy, killed) {
+            (true, 0) => CommandError::NoSuchClient.into(),
+            (true, _) => ok(),
+            (false, n) => (n as i64).into(),
+        }
+    }
+
+    /// 每个命令之后更新 CLIENT LIST 里的信息
+    fn update_client(&self, cmd: Option<String>) {
+        let multi = self.multi.as_ref().map(|multi| multi.queued.len());
+        let flags = if self.replica.is_some() {
+            "S"
+        } else if multi.is_some() {
+            "x"
+        } else if self.subscription_count() > 0 {
+            "P"
+        } else {
+            "N"
+        };
+        self.backend.clients().update(self.id, |info| {
+            info.name = self.name.clone();
+            info.last_interaction = Instant::now();
+            info.cmd = cmd.unwrap_or_default();
+            info.sub = self.channels.len();
+            info.psub = self.patterns.len();
+            info.multi = multi;
+            info.flags = flags;
+        });
+    }
+
+    /// 可能增加内存的命令执行之前先淘汰 key，淘汰不了时返回 OOM
+    fn check_memory(&self, name: Option<&str>) -> Result<(), CommandError> {
+        match name.is_some_and(is_denyoom) && !self.backend.free_memory() {
+            true => Err(CommandError::OutOfMemory),
+            false => Ok(()),
+        }
+    }
+
+    /// 集群里的 key 不在这个节点时返回 MOVED 或者 ASK
+    fn check_cluster(&self, name: Option<&str>, frame: &RespFrame) -> Result<(), CommandError> {
+        match name {
+            Some(name) => cluster::route(&self.backend, name, frame, self.asking),
+            None => Ok(()),
+        }
+    }
+
+    /// 从节点只接受主节点同步过来的写命令
+    fn check_read_only(&self, write: bool) -> Result<(), CommandError> {
+        match write && self.backend.replication().is_replica() {
+            true => Err(CommandError::ReadOnly),
+            false => Ok(()),
+        }
+    }
+
+    /// RESP2 的连接订阅之后，除了订阅相关的命令都返回错误
+    fn check_subscribed_context(&self, name: Option<&str>) -> Result<(), CommandError> {
+        if self.protocol != Protocol::Resp2 || self.subscription_count() == 0 {
+            return Ok(());
+        }
+        match name {
+            Some(name) if !SUBSCRIBED_COMMANDS.contains(&name) => {
+                Err(CommandError::SubscribedContext(name.to_string()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// SUBSCRIBE 这类命令的回复：[类型, 频道, 当前订阅的个数]
+fn subscription_reply(kind: &str, channel: RespFrame, count: usize) -> RespFrame {
+    RespPush::new([kind.into(), channel, (count as i64).into()]).into()
+}
+
+/// 执行时自己会获取传播锁的命令，比如保存快照，不能在持有锁的时候执行
+fn locks_propagator(cmd: &Command) -> bool {
+    matches!(
+        cmd,
+        Command::Save(_)
+            | Command::BgSave(_)
+            | Command::Info(_)
+            | Command::ReplicaOf(_)
+            | Command::Config(_)
+    )
+}
+
+fn ok() -> RespFrame {
+    SimpleString::new("OK").into()
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.unwatch();
+        self.backend.clients().unregister(self.id);
+        if self.messages.is_some() {
+            self.backend.pubsub().unregister(self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redis_core::RespNullBulkString;
+
+    fn cmd(cmd: &str) -> RespFrame {
+        let args: Vec<RespFrame> = cmd.split_whitespace().map(|arg| arg.into()).collect();
+        RespArray::new(args).into()
+    }
+
+    /// 只有一个回复的命令
+    fn one(session: &mut Session, c: &str) -> RespFrame {
+        let mut replies = session.handle(cmd(c));
+        assert_eq!(replies.len(), 1, "{}", c);
+        replies.remove(0)
+    }
+
+    fn array(frames: impl Into<Vec<RespFrame>>) -> RespFrame {
+        RespArray::new(frames).into()
+    }
+
+    #[test]
+    fn hello_should_switch_protocol() {
+        let mut session = Session::new(Backend::new());
+        one(&mut session, "hset h f v");
+        assert_eq!(one(&mut session, "get nokey"), RespNullBulkString.into());
+        assert_eq!(
+            one(&mut session, "hgetall h"),
+            array(["f".into(), "v".into()])
+        );
+
+        let RespFrame::Map(info) = one(&mut session, "hello 3 setname app") else {
+            panic!("HELLO 3 should reply a map");
+        };
+        assert_eq!(info.0.get("proto"), Some(&RespFrame::Integer(3)));
+        assert_eq!(
+            info.0.get("id"),
+            Some(&RespFrame::Integer(session.id as i64))
+        );
+        assert_eq!(session.name.as_deref(), Some("app"));
+        assert_eq!(one(&mut session, "get nokey"), RespNull.into());
+        assert!(matches!(one(&mut session, "hgetall h"), RespFrame::Map(_)));
+
+        // 不支持的版本不会改变当前协议
+        assert_eq!(
+            one(&mut session, "hello 4"),
+            RespFrame::from(CommandError::NoProto)
+        );
+        let RespFrame::Array(info) = one(&mut session, "hello 2") else {
+            panic!("HELLO 2 should reply an array");
+        };
+        assert!(info.0.contains(&"proto".into()));
+        assert!(matches!(
+            one(&mut session, "hgetall h"),
+            RespFrame::Array(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn subscriber_should_receive_messages() {
+        let backend = Backend::new();
+        let mut subscriber = Session::new(backend.clone());
+        let mut publisher = Session::new(backend);
+
+        assert_eq!(
+            subscriber.handle(cmd("subscribe a b")),
+            [
+                array(["subscribe".into(), "a".into(), 1.into()]),
+                array(["subscribe".into(), "b".into(), 2.into()]),
+            ]
+        );
+        assert_eq!(
+            subscriber.handle(cmd("psubscribe n*")),
+            [array(["psubscribe".into(), "n*".into(), 3.into()])]
+        );
+        assert_eq!(one(&mut publisher, "publish a hi"), RespFrame::Integer(1));
+        assert_eq!(
+            one(&mut publisher, "publish news hey"),
+            RespFrame::Integer(1)
+        );
+        assert_eq!(
+            subscriber.next_message().await,
+            Some(array(["message".into(), "a".into(), "hi".into()]))
+        );
+        assert_eq!(
+            subscriber.next_message().await,
+            Some(array([
+                "pmessage".into(),
+                "n*".into(),
+                "news".into(),
+                "hey".into()
+            ]))
+        );
+
+        // RESP2 订阅之后只能执行订阅相关的命令
+        assert_eq!(
+            one(&mut subscriber, "get a"),
+            CommandError::SubscribedContext("get".into()).into()
+        );
+        assert_eq!(
+            one(&mut subscriber, "ping"),
+            array(["pong".into(), "".into()])
+        );
+
+        assert_eq!(
+            subscriber.handle(cmd("unsubscribe")),
+            [
+                array(["unsubscribe".into(), "a".into(), 2.into()]),
+                array(["unsubscribe".into(), "b".into(), 1.into()]),
+            ]
+        );
+        assert_eq!(
+            subscriber.handle(cmd("punsubscribe")),
+            [array(["punsubscribe".into(), "n*".into(), 0.into()])]
+        );
+        assert_eq!(
+            subscriber.handle(cmd("unsubscribe")),
+            [array([
+                "unsubscribe".into(),
+                RespNullBulkString.into(),
+                0.into()
+            ])]
+        );
+        assert_eq!(one(&mut publisher, "publish a hi"), RespFrame::Integer(0));
+        assert_eq!(
+            one(&mut subscriber, "ping"),
+            SimpleString::new("PONG").into()
+        );
+    }
+
+    #[tokio::test]
+    async fn resp3_subscriber_should_receive_push_frames() {
+        let backend = Backend::new();
+        let mut subscriber = Session::new(backend.clone());
+        one(&mut subscriber, "hello 3");
+        assert_eq!(
+            subscriber.handle(cmd("subscribe a")),
+            [RespPush::new(["subscribe".into(), "a".into(), 1.into()]).into()]
+        );
+        // RESP3 订阅之后还可以执行其它命令
+        assert_eq!(
+            one(&mut subscriber, "set k v"),
+            SimpleString::new("OK").into()
+        );
+        backend.pubsub().publish("a", b"hi".to_vec());
+        assert_eq!(
+            subscriber.next_message().await,
+            Some(RespPush::new(["message".into(), "a".into(), "hi".into()]).into())
+        );
+
+        // 连接断开后取消所有订阅
+        drop(subscriber);
+        assert_eq!(backend.pubsub().publish("a", b"hi".to_vec()), 0);
+    }
+
+    #[test]
+    fn exec_should_run_queued_commands() {
+        let mut session = Session::new(Backend::new());
+        assert_eq!(
+            one(&mut session, "exec"),
+            CommandError::WithoutMulti("EXEC").into()
+        );
+        assert_eq!(one(&mut session, "multi"), ok());
+        assert_eq!(one(&mut session, "multi"), CommandError::NestedMulti.into());
+        assert_eq!(
+            one(&mut session, "set k 1"),
+            SimpleString::new("QUEUED").into()
+        );
+        assert_eq!(
+            one(&mut session, "incr k"),
+            SimpleString::new("QUEUED").into()
+        );
+        // 执行时出错不影响其它命令
+        assert_eq!(
+            one(&mut session, "hset k f v"),
+            SimpleString::new("QUEUED").into()
+        );
+        assert_eq!(
+            one(&mut session, "get k"),
+            SimpleString::new("QUEUED").into()
+        );
+        assert_eq!(
+            one(&mut session, "exec"),
+            array([ok(), 2.into(), CommandError::WrongType.into(), "2".into()])
+        );
+
+        one(&mut session, "multi");
+        one(&mut session, "incr k");
+        assert_eq!(one(&mut session, "discard"), ok());
+        assert_eq!(one(&mut session, "get k"), "2".into());
+
+        // 排队时出错，整个事务都不执行
+        one(&mut session, "multi");
+        one(&mut session, "incr k");
+        assert!(matches!(
+            one(&mut session, "nosuchcommand"),
+            RespFrame::Error(_)
+        ));
+        assert_eq!(
+            one(&mut session, "save"),
+            CommandError::NotAllowedInMulti.into()
+        );
+        assert_eq!(one(&mut session, "exec"), CommandError::ExecAbort.into());
+        assert_eq!(one(&mut session, "get k"), "2".into());
+    }
+
+    #[test]
+    fn exec_should_abort_when_watched_key_changed() {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        let mut other = Session::new(backend);
+
+        one(&mut session, "watch k");
+        one(&mut session, "multi");
+        assert_eq!(
+            one(&mut session, "watch k"),
+            CommandError::WatchInMulti.into()
+        );
+        one(&mut session, "set k 1");
+        one(&mut other, "set k 2");
+        assert_eq!(one(&mut session, "exec"), RespNullArray.into());
+        assert_eq!(one(&mut session, "get k"), "2".into());
+
+        // EXEC 之后 WATCH 被取消了
+        one(&mut session, "multi");
+        one(&mut session, "set k 1");
+        one(&mut other, "set k 3");
+        assert_eq!(one(&mut session, "exec"), array([ok()]));
+
+        // 自己的修改也算
+        one(&mut session, "watch k");
+        one(&mut session, "incr k");
+        one(&mut session, "multi");
+        one(&mut session, "incr k");
+        assert_eq!(one(&mut session, "exec"), RespNullArray.into());
+
+        one(&mut session, "watch k");
+        one(&mut other, "incr k");
+        assert_eq!(one(&mut session, "unwatch"), ok());
+        one(&mut session, "multi");
+        one(&mut session, "incr k");
+        assert_eq!(one(&mut session, "exec"), array([4.into()]));
+
+        // RESP3 的空回复是 null
+        one(&mut session, "hello 3");
+        one(&mut session, "watch k");
+        one(&mut other, "del k");
+        one(&mut session, "multi");
+        assert_eq!(one(&mut session, "exec"), RespNull.into());
+    }
+
+    #[test]
+    fn client_commands_should_use_registry() {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        let other = Session::new(backend.clone());
+        assert_eq!(
+            one(&mut session, "client getname"),
+            RespNullBulkString.into()
+        );
+        assert_eq!(one(&mut session, "client setname app"), ok());
+        assert_eq!(one(&mut session, "client getname"), "app".into());
+
+        let RespFrame::BulkString(list) = one(&mut session, "client list") else {
+            panic!("expect a bulk string");
+        };
+        let list = String::from_utf8_lossy(&list.0).to_string();
+        assert_eq!(list.lines().count(), 2);
+        assert!(list.contains(&format!("id={} addr= name=app", session.id)));
+
+        // 新的格式默认不会断开自己
+        let kill = format!("client kill id {}", session.id);
+        assert_eq!(one(&mut session, &kill), RespFrame::Integer(0));
+        let kill = format!("client kill id {}", other.id);
+        assert_eq!(one(&mut session, &kill), RespFrame::Integer(1));
+        assert_eq!(
+            one(&mut session, "client kill 10.0.0.1:1"),
+            CommandError::NoSuchClient.into()
+        );
+        drop(other);
+        assert_eq!(backend.clients().len(), 1);
+    }
+
+    #[test]
+    fn slow_commands_should_be_logged() {
+        let backend = Backend::new();
+        let mut session = Session::new(backend.clone());
+        one(
+            &mut session,
+            "config set slowlog-log-slower-than 0 slowlog-max-len 2",
+        );
+        one(&mut session, "set k v");
+        one(&mut session, "get k");
+        let entries = backend.slowlog().get(None);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].args, vec![b"get".to_vec(), b"k".to_vec()]);
+
+        one(&mut session, "config set slowlog-log-slower-than -1");
+        one(&mut session, "slowlog reset");
+        one(&mut session, "get k");
+        assert_eq!(backend.slowlog().len(), 0);
+    }
+}
@@ -0,0 +1,169 @@
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+use super::{RespArray, RespFrame};
+
+/// 和 redis 一样，每个命令最多记录 32 个参数，每个参数最多 128 个字节
+const MAX_ARGS: usize = 32;
+const MAX_ARG_LEN: usize = 128;
+
+/// 执行时间超过 slowlog-log-slower-than 的命令，最新的在前面，clone 之后共享同一份数据
+#[derive(Debug, Clone, Default)]
+pub struct SlowLog(Arc<Mutex<State>>);
+
+#[derive(Debug, Default)]
+struct State {
+    next_id: u64,
+    entries: VecDeque<SlowLogEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlowLogEntry {
+    pub id: u64,
+    /// unix 时间戳，秒
+    pub timestamp: u64,
+    pub duration: Duration,
+    pub args: Vec<Vec<u8>>,
+    pub addr: Option<SocketAddr>,
+    pub name: Option<String>,
+}
+
+impl SlowLogEntry {
+    /// [id, 时间戳, 微秒, [参数...], 地址, 名字]
+    pub fn to_frame(&self) -> RespFrame {
+        let args: Vec<RespFrame> = self.args.iter().map(|arg| arg.as_slice().into()).collect();
+        RespArray::new([
+            (self.id as i64).into(),
+            (self.timestamp as i64).into(),
+            (self.duration.as_micros() as i64).into(),
+            RespArray::new(args).into(),
+            self.addr
+                .map(|addr| addr.to_string())
+                .unwrap_or_default()
+                .as_str()
+                .into(),
+            self.name.as_deref().unwrap_or_default().into(),
+        ])
+        .into()
+    }
+}
+
+impl SlowLog {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 记录一个慢命令，超过 max_len 时丢掉最旧的
+    pub fn record(
+        &self,
+        args: Vec<Vec<u8>>,
+        duration: Duration,
+        addr: Option<SocketAddr>,
+        name: Option<String>,
+        max_len: usize,
+    ) {
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let mut state = self.lock();
+        let entry = SlowLogEntry {
+            id: state.next_id,
+            timestamp,
+            duration,
+            args: truncate_args(args),
+            addr,
+            name,
+        };
+        state.next_id += 1;
+        state.entries.push_front(entry);
+        state.entries.truncate(max_len);
+    }
+
+    /// 最新的 count 条，None 表示全部
+    pub fn get(&self, count: Option<usize>) -> Vec<SlowLogEntry> {
+        let state = self.lock();
+        let count = count.unwrap_or(state.entries.len());
+        state.entries.iter().take(count).cloned().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    pub fn reset(&self) {
+        self.lock().entries.clear();
+    }
+
+    /// CONFIG SET slowlog-max-len 改小之后丢掉多出来的
+    pub fn truncate(&self, max_len: usize) {
+        self.lock().entries.truncate(max_len);
+    }
+}
+
+/// 客户端发来的命令数组里的参数，包括命令名
+pub fn command_args(frame: &RespFrame) -> Vec<Vec<u8>> {
+    match frame {
+        RespFrame::Array(array) => array
+            .0
+            .iter()
+            .filter_map(|frame| match frame {
+                RespFrame::BulkString(s) => Some(s.0.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn truncate_args(mut args: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    if args.len() > MAX_ARGS {
+        let more = args.len() - (MAX_ARGS - 1);
+        args.truncate(MAX_ARGS - 1);
+        args.push(format!("... ({} more arguments)", more).into_bytes());
+    }
+    for arg in args.iter_mut() {
+        if arg.len() > MAX_ARG_LEN {
+            let more = arg.len() - MAX_ARG_LEN;
+            arg.truncate(MAX_ARG_LEN);
+            arg.extend_from_slice(format!("... ({} more bytes)", more).as_bytes());
+        }
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slowlog_should_keep_latest_entries() {
+        let slowlog = SlowLog::default();
+        for i in 0..3 {
+            let args = vec![b"set".to_vec(), format!("k{}", i).into_bytes()];
+            slowlog.record(args, Duration::from_millis(20), None, None, 2);
+        }
+        let entries = slowlog.get(None);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].id, 2);
+        assert_eq!(entries[1].args, vec![b"set".to_vec(), b"k1".to_vec()]);
+        assert_eq!(slowlog.get(Some(1)).len(), 1);
+
+        slowlog.reset();
+        assert_eq!(slowlog.len(), 0);
+    }
+
+    #[test]
+    fn long_args_should_be_truncated() {
+        let mut args = vec![vec![b'a'; 130]];
+        args.extend((0..40).map(|i| i.to_string().into_bytes()));
+        let args = truncate_args(args);
+        assert_eq!(args.len(), MAX_ARGS);
+        assert!(args[0].ends_with(b"... (2 more bytes)"));
+        assert_eq!(args[31], b"... (10 more arguments)".to_vec());
+    }
+}